@@ -0,0 +1,57 @@
+#ifndef TEXT_IMAGE_GENERATOR_H
+#define TEXT_IMAGE_GENERATOR_H
+
+/* Build with `cargo build --release --no-default-features --features ffi`. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TIG_OK 0
+#define TIG_INVALID_ARGUMENT -1
+#define TIG_BUFFER_TOO_SMALL -2
+#define TIG_PANIC -3
+
+typedef struct TigGenerator TigGenerator;
+
+/*
+ * Create a generator from a config file. NULL if the config or one of the files it names cannot
+ * be loaded, or a value of the config is invalid.
+ */
+TigGenerator *tig_generator_new(const char *config_path);
+
+void tig_generator_free(TigGenerator *generator);
+
+/*
+ * Generate a grayscale sample of min_len to max_len characters into buf (row-major, one byte
+ * per pixel). width and height receive the image size. Returns TIG_BUFFER_TOO_SMALL if buf
+ * cannot hold width * height bytes, the sample can then be fetched with tig_copy_last_image.
+ */
+int32_t tig_generate(TigGenerator *generator, uint32_t min_len, uint32_t max_len, uint8_t *buf,
+                     size_t buf_len, uint32_t *width, uint32_t *height);
+
+int32_t tig_copy_last_image(const TigGenerator *generator, uint8_t *buf, size_t buf_len,
+                            uint32_t *width, uint32_t *height);
+
+/* UTF-8 label of the last sample, normalized according to `label_normalization` and
+ * `fold_full_width` in the config file. Valid until the next tig_generate or tig_generator_free. */
+const char *tig_last_label(const TigGenerator *generator);
+
+/* Same as tig_last_label, but without normalization. */
+const char *tig_last_raw_label(const TigGenerator *generator);
+
+/*
+ * Serve batches over HTTP on addr (e.g. "0.0.0.0:8000"), blocking the calling thread; see
+ * src/server.rs for the protocol. The requests can only use config_path. Only available with
+ * the `server` feature. Returns only on failure.
+ */
+int32_t tig_serve(const char *config_path, const char *addr);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,147 @@
+//! Loading what a generator needs from the config file: the fonts and the characters they
+//! cover, the Chinese characters with their frequencies, and the rendering options. Shared by
+//! the Python `Generator` and the C `TigGenerator`, which add their own parts on top.
+
+use std::fs;
+
+use cosmic_text::FontSystem;
+use indexmap::IndexMap;
+
+use crate::{
+    coverage_cache::CoverageCache,
+    filter::CharFilter,
+    font_util::{font_weights_from_config, FontUtil},
+    image_glyph::ImageGlyphs,
+    image_process::LineStyling,
+    init::{count_corpus_freqs, init_ch_dict, init_ch_dict_and_freq, scale_freqs},
+    normalize::LabelNormalizer,
+    opentype::OpenTypeFeatures,
+    parse_config::Config,
+    utils::InternalAttrsOwned,
+};
+
+pub struct GeneratorBuilder {
+    pub font_system: FontSystem,
+    pub char_filter: CharFilter,
+    pub full_font_list: Vec<InternalAttrsOwned>,
+    pub chinese_ch_dict: IndexMap<String, Vec<InternalAttrsOwned>>,
+    /// in the order of `chinese_ch_dict`
+    pub chinese_ch_freqs: Vec<f64>,
+    pub main_font_list: Vec<String>,
+    pub opentype_features: OpenTypeFeatures,
+    pub label_normalizer: LabelNormalizer,
+    pub image_glyphs: ImageGlyphs,
+    pub line_styling: LineStyling,
+    // finds the characters covered by each font, with the coverage cache of the config
+    coverage: FontUtil,
+    coverage_cache_path: String,
+}
+
+impl GeneratorBuilder {
+    /// Load the fonts and the Chinese character file of `config`. Returns an error if a file
+    /// cannot be read or a value of the config is invalid.
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let char_filter = CharFilter::from_config(config)?;
+        if config.freq_smoothing.is_nan() || config.freq_smoothing < 0.0 {
+            return Err("`freq_smoothing` should be non-negative".to_string());
+        }
+        if config.sampling_temperature.is_nan() || config.sampling_temperature <= 0.0 {
+            return Err("`sampling_temperature` should be positive".to_string());
+        }
+
+        let mut font_system = FontSystem::new();
+        font_system.db_mut().load_fonts_dir(&config.font_dir);
+
+        let mut coverage = FontUtil::new(&font_system);
+        if !config.coverage_cache_path.is_empty() {
+            coverage = coverage.with_coverage_cache(CoverageCache::load(
+                &config.coverage_cache_path,
+                &config.font_dir,
+            ));
+        }
+        let full_font_list = coverage.get_full_font_list();
+        let filtered = char_filter.filter_ch_file(&read_file(&config.chinese_ch_file_path)?);
+        let chinesecharacter_file_data = if !config.freq_corpus_file_path.is_empty() {
+            let corpus = read_file(&config.freq_corpus_file_path)?;
+            count_corpus_freqs(&filtered, &corpus, config.freq_smoothing)
+        } else {
+            filtered
+        };
+        let (chinese_ch_dict, chinese_ch_freqs) =
+            init_ch_dict_and_freq(&mut coverage, &full_font_list, &chinesecharacter_file_data);
+        let chinese_ch_dict = chinese_ch_dict
+            .into_iter()
+            .map(|(ch, dic)| (ch.to_string(), dic))
+            .collect();
+
+        let main_font_list = if !config.main_font_list_file_path.is_empty() {
+            read_file(&config.main_font_list_file_path)?
+                .trim()
+                .split('\n')
+                .map(String::from)
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let mut label_normalizer =
+            LabelNormalizer::new(&config.label_normalization, config.fold_full_width)?;
+        label_normalizer.collapse_spaces = config.collapse_label_spaces;
+
+        Ok(Self {
+            font_system,
+            char_filter,
+            full_font_list,
+            chinese_ch_dict,
+            chinese_ch_freqs,
+            main_font_list,
+            opentype_features: OpenTypeFeatures::from_names(
+                &config.opentype_features,
+                &config.font_opentype_features,
+            )?,
+            label_normalizer,
+            image_glyphs: ImageGlyphs::load_dir(&config.image_glyph_dir, config.image_glyph_prob)?,
+            line_styling: LineStyling::from_config(config)?,
+            coverage,
+            coverage_cache_path: config.coverage_cache_path.clone(),
+        })
+    }
+
+    /// The fonts that cover each of `chars`.
+    pub fn init_ch_dict<'b, I: Iterator<Item = &'b S>, S: AsRef<str> + 'b + ?Sized>(
+        &mut self,
+        chars: I,
+    ) -> IndexMap<String, Vec<InternalAttrsOwned>> {
+        init_ch_dict(&mut self.coverage, &self.full_font_list, chars)
+            .into_iter()
+            .map(|(ch, dic)| (ch.to_string(), dic))
+            .collect()
+    }
+
+    /// The sampling weights of the Chinese characters, in the order of `chinese_ch_dict`.
+    pub fn chinese_ch_weights(&self, config: &Config) -> Vec<f64> {
+        scale_freqs(
+            &self.chinese_ch_freqs,
+            config.inverse_frequency,
+            config.sampling_temperature,
+        )
+    }
+
+    /// Write the coverage cache if the characters of a font have been looked up. The cache only
+    /// saves time, so the error may be ignored.
+    pub fn save_coverage_cache(&mut self) -> Result<(), String> {
+        match self.coverage.take_coverage_cache() {
+            Some(mut cache) if cache.is_dirty() => cache.save(&self.coverage_cache_path),
+            _ => Ok(()),
+        }
+    }
+
+    /// The font util to render with, weighting the fonts as in the config.
+    pub fn font_util(&self, config: &Config) -> Result<FontUtil, String> {
+        Ok(FontUtil::new(&self.font_system).with_font_weights(font_weights_from_config(config)?))
+    }
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("failed to read `{}`: {}", path, err))
+}
@@ -0,0 +1,270 @@
+//! Annotations of detection-style samples in the COCO JSON format, so that samples rendered with
+//! their cluster or line boxes can be fed to detection training frameworks. Each box becomes an
+//! annotation with its `bbox` (x, y, width, height), a rectangular `segmentation` polygon and the
+//! transcript in `text`.
+
+use std::{fs, path::Path};
+
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, pyclass, pymethods, PyResult};
+use serde::Serialize;
+
+use crate::image_process::BBox;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CocoImage {
+    pub id: u64,
+    pub file_name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CocoAnnotation {
+    pub id: u64,
+    pub image_id: u64,
+    pub category_id: u64,
+    /// [x, y, width, height]
+    pub bbox: [u32; 4],
+    /// one polygon [x0, y0, x1, y1, ...] going clockwise from the top left corner
+    pub segmentation: Vec<Vec<u32>>,
+    pub area: u32,
+    pub iscrowd: u8,
+    pub text: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CocoCategory {
+    pub id: u64,
+    pub name: String,
+}
+
+/// Accumulates the images and their annotations, the ids count from 1 as in COCO.
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Serialize, Debug, Default)]
+pub struct CocoExporter {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    categories: Vec<CocoCategory>,
+}
+
+impl CocoExporter {
+    pub fn new<S: AsRef<str>>(categories: &[S]) -> Result<Self, String> {
+        if categories.is_empty() {
+            return Err("there should be at least one category".to_string());
+        }
+
+        Ok(Self {
+            categories: categories
+                .iter()
+                .zip(1..)
+                .map(|(name, id)| CocoCategory {
+                    id,
+                    name: name.as_ref().to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the id of the image.
+    pub fn add_image(&mut self, file_name: &str, width: u32, height: u32) -> u64 {
+        let id = self.images.len() as u64 + 1;
+        self.images.push(CocoImage {
+            id,
+            file_name: file_name.to_string(),
+            width,
+            height,
+        });
+
+        id
+    }
+
+    /// Annotate the box (x0, y0, x1, y1), with the right and bottom edges exclusive as in the
+    /// cluster boxes, on an image added before. `category` defaults to the first category.
+    /// Returns the id of the annotation.
+    pub fn add_annotation(
+        &mut self,
+        image_id: u64,
+        bbox: BBox,
+        text: &str,
+        category: Option<&str>,
+    ) -> Result<u64, String> {
+        if image_id == 0 || image_id > self.images.len() as u64 {
+            return Err(format!("there is no image of id {}", image_id));
+        }
+        let category_id = match category {
+            Some(category) => {
+                self.categories
+                    .iter()
+                    .find(|each| each.name == category)
+                    .ok_or_else(|| format!("unknown category `{}`", category))?
+                    .id
+            }
+            None => 1,
+        };
+        let (x0, y0, x1, y1) = bbox;
+        if x1 < x0 || y1 < y0 {
+            return Err(format!("invalid box {:?}", bbox));
+        }
+
+        let id = self.annotations.len() as u64 + 1;
+        let (width, height) = (x1 - x0, y1 - y0);
+        self.annotations.push(CocoAnnotation {
+            id,
+            image_id,
+            category_id,
+            bbox: [x0, y0, width, height],
+            segmentation: vec![vec![x0, y0, x1, y0, x1, y1, x0, y1]],
+            area: width * height,
+            iscrowd: 0,
+            text: text.to_string(),
+        });
+
+        Ok(id)
+    }
+
+    /// `add_image` and `add_annotation` for each (text, box), returns the id of the image.
+    pub fn add_sample(
+        &mut self,
+        file_name: &str,
+        width: u32,
+        height: u32,
+        boxes: &[(String, BBox)],
+        category: Option<&str>,
+    ) -> Result<u64, String> {
+        let image_id = self.add_image(file_name, width, height);
+        for (text, bbox) in boxes {
+            if let Err(err) = self.add_annotation(image_id, *bbox, text, category) {
+                // do not leave a half annotated image behind
+                self.annotations.retain(|each| each.image_id != image_id);
+                self.images.pop();
+                return Err(err);
+            }
+        }
+
+        Ok(image_id)
+    }
+
+    pub fn images(&self) -> &[CocoImage] {
+        &self.images
+    }
+
+    pub fn annotations(&self) -> &[CocoAnnotation] {
+        &self.annotations
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("the annotations are always serializable")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        fs::write(&path, self.to_json()).map_err(|err| {
+            format!(
+                "fail to write the annotations `{}`: {}",
+                path.as_ref().display(),
+                err
+            )
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl CocoExporter {
+    #[new]
+    #[pyo3(signature = (categories=None))]
+    fn py_new(categories: Option<Vec<String>>) -> PyResult<Self> {
+        let categories = categories.unwrap_or_else(|| vec!["text".to_string()]);
+        Self::new(&categories).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "__len__")]
+    fn py_len(&self) -> usize {
+        self.images.len()
+    }
+
+    #[pyo3(name = "add_image")]
+    fn py_add_image(&mut self, file_name: &str, width: u32, height: u32) -> u64 {
+        self.add_image(file_name, width, height)
+    }
+
+    #[pyo3(name = "add_annotation", signature = (image_id, bbox, text="", category=None))]
+    fn py_add_annotation(
+        &mut self,
+        image_id: u64,
+        bbox: BBox,
+        text: &str,
+        category: Option<&str>,
+    ) -> PyResult<u64> {
+        self.add_annotation(image_id, bbox, text, category)
+            .map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "add_sample", signature = (file_name, width, height, boxes, category=None))]
+    fn py_add_sample(
+        &mut self,
+        file_name: &str,
+        width: u32,
+        height: u32,
+        boxes: Vec<(String, BBox)>,
+        category: Option<&str>,
+    ) -> PyResult<u64> {
+        self.add_sample(file_name, width, height, &boxes, category)
+            .map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "to_json")]
+    fn py_to_json(&self) -> String {
+        self.to_json()
+    }
+
+    #[pyo3(name = "save")]
+    fn py_save(&self, path: &str) -> PyResult<()> {
+        self.save(path).map_err(PyValueError::new_err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_coco_exporter() {
+        let mut exporter = CocoExporter::new(&["char", "line"]).unwrap();
+        let boxes = vec![
+            ("永".to_string(), (2, 3, 12, 20)),
+            ("和".to_string(), (12, 3, 22, 20)),
+        ];
+        let image_id = exporter
+            .add_sample("images/0.png", 32, 24, &boxes, Some("char"))
+            .unwrap();
+        assert_eq!(image_id, 1);
+        assert_eq!(
+            exporter.add_annotation(image_id, (2, 3, 22, 20), "永和", Some("line")),
+            Ok(3)
+        );
+
+        let annotation = &exporter.annotations()[0];
+        assert_eq!(annotation.bbox, [2, 3, 10, 17]);
+        assert_eq!(annotation.area, 170);
+        assert_eq!(
+            annotation.segmentation,
+            vec![vec![2, 3, 12, 3, 12, 20, 2, 20]]
+        );
+        assert_eq!(exporter.annotations()[2].category_id, 2);
+
+        // a failing sample leaves nothing behind
+        assert!(exporter
+            .add_sample("images/1.png", 32, 24, &boxes, Some("word"))
+            .is_err());
+        assert_eq!(exporter.images().len(), 1);
+        assert_eq!(exporter.annotations().len(), 3);
+        assert!(exporter.add_annotation(2, (0, 0, 1, 1), "", None).is_err());
+
+        let json: serde_json::Value = serde_json::from_str(&exporter.to_json()).unwrap();
+        assert_eq!(json["images"][0]["file_name"], "images/0.png");
+        assert_eq!(json["categories"][1]["name"], "line");
+        assert_eq!(json["annotations"][1]["text"], "和");
+    }
+}
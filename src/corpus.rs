@@ -1,10 +1,11 @@
-use std::{ops::RangeInclusive, str::from_utf8_unchecked};
+use std::{collections::HashMap, ops::RangeInclusive};
 
 use indexmap::IndexMap;
-use rand::{self, seq::SliceRandom, Rng};
-use rand_distr::{Distribution, WeightedAliasIndex};
+use rand::{seq::SliceRandom, Rng};
+use rand_distr::{Distribution, WeightedAliasIndex, WeightedIndex};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::utils::InternalAttrsOwned;
+use crate::{rng::sample_rng, segment::WordDict, utils::InternalAttrsOwned};
 
 pub fn get_random_french_text<'a, S1, S2, S3>(
     ch_dict: &'a IndexMap<S1, Vec<S2>>,
@@ -17,7 +18,7 @@ where
     S2: AsRef<str>,
     S3: AsRef<str>,
 {
-    let mut rng = rand::thread_rng();
+    let mut rng = sample_rng();
 
     let num = rng.gen_range(range);
 
@@ -43,37 +44,379 @@ where
     res
 }
 
-pub fn get_random_chinese_text_with_font_list<'a, S1, S2>(
+/// How symbols are inserted among the sampled characters.
+#[derive(Clone, Debug)]
+pub struct SymbolInsertion {
+    /// the number of symbols per text, (min, max); used when `prob` is 0
+    pub count: (u32, u32),
+    /// the probability of a symbol at each allowed position, replaces `count` if positive
+    pub prob: f64,
+    /// the weights of the symbols, uniform without
+    pub weights: Option<WeightedIndex<f64>>,
+    /// whether a symbol may come before the first character
+    pub at_start: bool,
+    /// whether a symbol may come after the last character
+    pub at_end: bool,
+}
+
+impl Default for SymbolInsertion {
+    /// One symbol between two characters.
+    fn default() -> Self {
+        Self {
+            count: (1, 1),
+            prob: 0.0,
+            weights: None,
+            at_start: false,
+            at_end: false,
+        }
+    }
+}
+
+impl SymbolInsertion {
+    /// The positions (the symbol goes before the character of the position, `num` is after the
+    /// last one) that get a symbol, in order.
+    fn positions<R: Rng>(&self, num: u32, rng: &mut R) -> Vec<u32> {
+        let first = if self.at_start { 0 } else { 1 };
+        let last = if self.at_end {
+            num
+        } else {
+            num.saturating_sub(1)
+        };
+        if first > last {
+            return vec![];
+        }
+
+        let candidates: Vec<u32> = (first..=last).collect();
+        if self.prob > 0.0 {
+            return candidates
+                .into_iter()
+                .filter(|_| rng.gen_bool(self.prob.min(1.0)))
+                .collect();
+        }
+
+        let count = rng.gen_range(self.count.0..=self.count.1) as usize;
+        let mut positions: Vec<u32> = candidates
+            .choose_multiple(rng, count.min(candidates.len()))
+            .copied()
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    fn choose<'a, S: AsRef<str>, R: Rng>(&self, symbols: &'a [S], rng: &mut R) -> &'a str {
+        match &self.weights {
+            Some(weights) => symbols[weights.sample(rng)].as_ref(),
+            None => symbols.choose(rng).unwrap().as_ref(),
+        }
+    }
+}
+
+/// Sample characters by `weights`, with symbols inserted as configured by the `SymbolInsertion`
+/// if `symbol` is given. The symbols have no font list.
+pub fn get_random_chinese_text_with_font_list<'a, S1, S2, W>(
     ch_dict: &'a IndexMap<S1, Vec<InternalAttrsOwned>>,
-    weights: &WeightedAliasIndex<f64>,
-    symbol: Option<&'a Vec<S2>>,
+    weights: &W,
+    symbol: Option<(&'a Vec<S2>, &SymbolInsertion)>,
     range: RangeInclusive<u32>,
 ) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>
 where
     S1: AsRef<str>,
     S2: AsRef<str>,
+    W: Distribution<usize>,
 {
-    let mut rng = rand::thread_rng();
+    let mut rng = sample_rng();
 
     let num = rng.gen_range(range);
 
     let mut res = Vec::with_capacity(15);
-    if let Some(symbol_content) = symbol {
-        let insert_idx = rng.gen_range(2..=num);
-        let symbol = symbol_content.choose(&mut rng).unwrap();
-        for i in 1..=num {
-            if i == insert_idx {
-                res.push((symbol.as_ref(), None));
+    let positions = match symbol {
+        Some((symbol_content, insertion)) if !symbol_content.is_empty() => {
+            insertion.positions(num, &mut rng)
+        }
+        _ => vec![],
+    };
+    let mut positions = positions.into_iter().peekable();
+    for i in 0..=num {
+        if positions.next_if_eq(&i).is_some() {
+            let (symbol_content, insertion) = symbol.unwrap();
+            res.push((insertion.choose(symbol_content, &mut rng), None));
+        }
+        if i == num {
+            break;
+        }
+
+        let (temp_ch, temp_font_list) = ch_dict.get_index(weights.sample(&mut rng)).unwrap();
+        res.push((temp_ch.as_ref(), Some(temp_font_list)));
+    }
+
+    res
+}
+
+/// Sample Chinese characters until their estimated width reaches `target_width`, instead of
+/// a number of characters. `advance` estimates the rendered width of a character from its font
+/// list. At least one character is returned.
+pub fn get_random_chinese_text_by_width<'a, S1, F, W>(
+    ch_dict: &'a IndexMap<S1, Vec<InternalAttrsOwned>>,
+    weights: &W,
+    target_width: f32,
+    mut advance: F,
+) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>
+where
+    S1: AsRef<str>,
+    F: FnMut(&str, &[InternalAttrsOwned]) -> f32,
+    W: Distribution<usize>,
+{
+    // guards against characters whose advance is (almost) zero
+    const MAX_LEN: usize = 1000;
+
+    let mut rng = sample_rng();
+
+    let mut res = Vec::with_capacity(15);
+    let mut width = 0.0;
+    while res.len() < MAX_LEN {
+        let (temp_ch, temp_font_list) = ch_dict.get_index(weights.sample(&mut rng)).unwrap();
+        let ch_width = advance(temp_ch.as_ref(), temp_font_list);
+        if width + ch_width > target_width && !res.is_empty() {
+            break;
+        }
+        res.push((temp_ch.as_ref(), Some(temp_font_list)));
+        width += ch_width;
+    }
+
+    res
+}
+
+/// A pool of items (Latin words or symbols) sampled uniformly, with the dict their clusters are
+/// looked up in.
+pub struct TextPool<'a, S> {
+    pub items: &'a [S],
+    pub ch_dict: &'a IndexMap<String, Vec<InternalAttrsOwned>>,
+}
+
+/// Sample each position from the Chinese characters, the Latin words or the symbols, with the
+/// probabilities of `pool_weights` (in that order). Adjacent Latin words are separated by a
+/// space.
+pub fn get_random_mixed_text_with_font_list<'a, S1, S2, W>(
+    ch_dict: &'a IndexMap<S1, Vec<InternalAttrsOwned>>,
+    weights: &W,
+    latin: Option<TextPool<'a, S2>>,
+    symbol: Option<TextPool<'a, S2>>,
+    pool_weights: &WeightedIndex<f64>,
+    range: RangeInclusive<u32>,
+) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>
+where
+    S1: AsRef<str>,
+    S2: AsRef<str>,
+    W: Distribution<usize>,
+{
+    let mut rng = sample_rng();
+
+    let num = rng.gen_range(range);
+
+    let mut res = Vec::with_capacity(num as usize * 2);
+    let mut prev_latin = false;
+    for _ in 0..num {
+        let pool_idx = pool_weights.sample(&mut rng);
+        let pool = match pool_idx {
+            1 => latin.as_ref(),
+            2 => symbol.as_ref(),
+            _ => None,
+        };
+        // Chinese, or an empty pool
+        let pool = match pool.filter(|pool| !pool.items.is_empty()) {
+            Some(pool) => pool,
+            None => {
+                let (temp_ch, temp_font_list) =
+                    ch_dict.get_index(weights.sample(&mut rng)).unwrap();
+                res.push((temp_ch.as_ref(), Some(temp_font_list)));
+                prev_latin = false;
+                continue;
             }
+        };
 
-            let (temp_ch, temp_font_list) = ch_dict.get_index(weights.sample(&mut rng)).unwrap();
-            res.push((temp_ch.as_ref(), Some(temp_font_list)));
+        let is_latin = pool_idx == 1;
+        if is_latin && prev_latin {
+            res.push((" ", pool.ch_dict.get(" ")));
         }
-    } else {
-        for _ in 1..=num {
-            let (temp_ch, temp_font_list) = ch_dict.get_index(weights.sample(&mut rng)).unwrap();
-            res.push((temp_ch.as_ref(), Some(temp_font_list)));
+        let item = pool.items.choose(&mut rng).unwrap().as_ref();
+        res.extend(wrap_text_with_dicts(item, &[pool.ch_dict]));
+        prev_latin = is_latin;
+    }
+
+    res
+}
+
+/// The digits a digit group of `get_random_mixed_line_with_font_list` is made of.
+pub const DIGITS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+/// The length of a digit group, in digits.
+const DIGIT_GROUP_LEN: RangeInclusive<usize> = 1..=6;
+
+/// Interleave Chinese characters, Latin words and groups of digits: each position is a Latin
+/// word with probability `latin_ratio`, a digit group with probability `digit_ratio` and a
+/// Chinese character otherwise. Adjacent words and digit groups are separated by a space, as
+/// they would be in real text.
+pub fn get_random_mixed_line_with_font_list<'a, S1, S2, W>(
+    ch_dict: &'a IndexMap<S1, Vec<InternalAttrsOwned>>,
+    weights: &W,
+    latin: Option<TextPool<'a, S2>>,
+    digit_dict: &'a IndexMap<String, Vec<InternalAttrsOwned>>,
+    latin_ratio: f64,
+    digit_ratio: f64,
+    range: RangeInclusive<u32>,
+) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>
+where
+    S1: AsRef<str>,
+    S2: AsRef<str>,
+    W: Distribution<usize>,
+{
+    let mut rng = sample_rng();
+
+    let num = rng.gen_range(range);
+
+    let mut res = Vec::with_capacity(num as usize * 4);
+    // whether the previous position is a word or a digit group
+    let mut prev_spaced = false;
+    for _ in 0..num {
+        let roll: f64 = rng.gen();
+        let latin = latin.as_ref().filter(|pool| !pool.items.is_empty());
+        prev_spaced = match latin {
+            Some(pool) if roll < latin_ratio => {
+                if prev_spaced {
+                    res.push((" ", pool.ch_dict.get(" ")));
+                }
+                let item = pool.items.choose(&mut rng).unwrap().as_ref();
+                res.extend(wrap_text_with_dicts(item, &[pool.ch_dict]));
+                true
+            }
+            _ if roll < latin_ratio + digit_ratio => {
+                if prev_spaced {
+                    res.push((" ", digit_dict.get(" ")));
+                }
+                for _ in 0..rng.gen_range(DIGIT_GROUP_LEN) {
+                    let (digit, font_list) = digit_dict
+                        .get_key_value(*DIGITS.choose(&mut rng).unwrap())
+                        .unwrap();
+                    res.push((digit.as_str(), Some(font_list)));
+                }
+                true
+            }
+            _ => {
+                let (temp_ch, temp_font_list) =
+                    ch_dict.get_index(weights.sample(&mut rng)).unwrap();
+                res.push((temp_ch.as_ref(), Some(temp_font_list)));
+                false
+            }
+        };
+    }
+
+    res
+}
+
+/// The distinct words of a Latin corpus with how often each appears, in order of first
+/// appearance. The punctuation around a word is stripped, e.g. `"Hello,` counts as `Hello`, and
+/// tokens without any letter or digit are dropped.
+pub fn count_words(corpus: &str) -> IndexMap<String, u32> {
+    count_words_with(corpus, char::is_alphanumeric)
+}
+
+/// `count_words` for right-to-left scripts, where the vowel points and other combining marks
+/// at the end of a word belong to the word.
+pub fn count_rtl_words(corpus: &str) -> IndexMap<String, u32> {
+    count_words_with(corpus, |ch| ch.is_alphanumeric() || is_combining_mark(ch))
+}
+
+fn count_words_with<F: Fn(char) -> bool>(corpus: &str, in_word: F) -> IndexMap<String, u32> {
+    let mut counts = IndexMap::new();
+    for token in corpus.split_whitespace() {
+        let word = token.trim_matches(|ch: char| !in_word(ch));
+        if !word.is_empty() {
+            *counts.entry(word.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    counts
+}
+
+/// Combining diacritics, and the points and marks of Hebrew and Arabic.
+fn is_combining_mark(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0300}'..='\u{036F}'
+            | '\u{0591}'..='\u{05BD}'
+            | '\u{05BF}'
+            | '\u{05C1}'..='\u{05C2}'
+            | '\u{05C4}'..='\u{05C5}'
+            | '\u{05C7}'
+            | '\u{0610}'..='\u{061A}'
+            | '\u{064B}'..='\u{065F}'
+            | '\u{0670}'
+            | '\u{06D6}'..='\u{06DC}'
+            | '\u{06DF}'..='\u{06E4}'
+            | '\u{06E7}'..='\u{06E8}'
+            | '\u{06EA}'..='\u{06ED}'
+    )
+}
+
+/// A line of whole words separated by spaces like `get_random_words_with_font_list`, but every
+/// word is a single unit with the fonts that cover all of it (see `init_word_dict`), so that the
+/// letters of a joining script such as Arabic are shaped together with one font.
+pub fn get_random_word_units_with_font_list<'a>(
+    word_dict: &'a IndexMap<String, Vec<InternalAttrsOwned>>,
+    weights: Option<&WeightedAliasIndex<f64>>,
+    space: Option<&'a Vec<InternalAttrsOwned>>,
+    range: RangeInclusive<u32>,
+) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)> {
+    let mut rng = sample_rng();
+    if word_dict.is_empty() {
+        return vec![];
+    }
+
+    let num = rng.gen_range(range);
+    let mut res = Vec::with_capacity(num as usize * 2);
+    for i in 0..num {
+        if i > 0 {
+            res.push((" ", space));
+        }
+        let index = match weights {
+            Some(weights) => weights.sample(&mut rng),
+            None => rng.gen_range(0..word_dict.len()),
+        };
+        let (word, font_list) = word_dict.get_index(index).unwrap();
+        res.push((word.as_str(), Some(font_list)));
+    }
+
+    res
+}
+
+/// A line of whole words separated by spaces, the number of words drawn from `range`. The words
+/// are drawn with `weights` (e.g. by their frequency in the corpus), or uniformly without.
+pub fn get_random_words_with_font_list<'a, S>(
+    words: &'a [S],
+    weights: Option<&WeightedAliasIndex<f64>>,
+    ch_dict: &'a IndexMap<String, Vec<InternalAttrsOwned>>,
+    range: RangeInclusive<u32>,
+) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>
+where
+    S: AsRef<str>,
+{
+    let mut rng = sample_rng();
+    if words.is_empty() {
+        return vec![];
+    }
+
+    let num = rng.gen_range(range);
+
+    let mut res = Vec::with_capacity(num as usize * 8);
+    for i in 0..num {
+        if i > 0 {
+            res.push((" ", ch_dict.get(" ")));
         }
+        let index = match weights {
+            Some(weights) => weights.sample(&mut rng),
+            None => rng.gen_range(0..words.len()),
+        };
+        res.extend(wrap_text_with_dicts(words[index].as_ref(), &[ch_dict]));
     }
 
     res
@@ -87,25 +430,240 @@ where
     S1: AsRef<str> + ?Sized,
     S2: std::hash::Hash + std::cmp::Eq + std::borrow::Borrow<str>,
 {
-    let bytes = text.as_ref().as_bytes();
+    wrap_text_with_dicts(text, &[ch_dict])
+}
+
+/// Look up every cluster in the dicts in order, the first dict that knows the cluster gives its
+/// font list.
+pub fn wrap_text_with_dicts<'a, 'b, S1, S2>(
+    text: &'a S1,
+    dicts: &[&'b IndexMap<S2, Vec<InternalAttrsOwned>>],
+) -> Vec<(&'a str, Option<&'b Vec<InternalAttrsOwned>>)>
+where
+    S1: AsRef<str> + ?Sized,
+    S2: std::hash::Hash + std::cmp::Eq + std::borrow::Borrow<str>,
+{
+    text.as_ref()
+        .graphemes(true)
+        .map(|cluster| {
+            (
+                cluster,
+                dicts.iter().find_map(|ch_dict| ch_dict.get(cluster)),
+            )
+        })
+        .collect()
+}
+
+/// Like `wrap_text_with_dicts`, but the text is split into words first, and each word
+/// gets the fonts that can render all of its characters, so that it is drawn in one font. A
+/// word that no single font covers is split back into clusters.
+pub fn wrap_words_with_font_list<'a, S1, S2>(
+    text: &'a S1,
+    dicts: &[&IndexMap<S2, Vec<InternalAttrsOwned>>],
+    word_dict: &WordDict,
+) -> Vec<(&'a str, Option<Vec<InternalAttrsOwned>>)>
+where
+    S1: AsRef<str> + ?Sized,
+    S2: std::hash::Hash + std::cmp::Eq + std::borrow::Borrow<str>,
+{
     let mut res = vec![];
+    for word in word_dict.segment(text.as_ref()) {
+        let clusters = wrap_text_with_dicts(word, dicts);
+        if clusters.len() == 1 {
+            res.extend(
+                clusters
+                    .into_iter()
+                    .map(|(cluster, font_list)| (cluster, font_list.cloned())),
+            );
+            continue;
+        }
 
-    let length = bytes.len();
-    let mut idx = 0;
-    while idx < length {
-        if !utf8_width::is_width_0(bytes[idx]) {
-            let ch_bytes_length = unsafe { utf8_width::get_width_assume_valid(bytes[idx]) };
-            let ch = unsafe { from_utf8_unchecked(&bytes[idx..idx + ch_bytes_length]) };
-            res.push((ch, ch_dict.get(ch)));
-            idx += ch_bytes_length;
-        } else {
-            idx += 1;
+        // fonts shared by all the clusters of the word
+        let mut common: Option<Vec<InternalAttrsOwned>> = None;
+        for (_, font_list) in clusters.iter() {
+            let font_list = match font_list {
+                Some(font_list) => *font_list,
+                None => {
+                    common = None;
+                    break;
+                }
+            };
+            common = Some(match common {
+                None => font_list.clone(),
+                Some(acc) => acc
+                    .into_iter()
+                    .filter(|font| font_list.contains(font))
+                    .collect(),
+            });
+        }
+        match common {
+            Some(common) if !common.is_empty() => res.push((word, Some(common))),
+            _ => res.extend(
+                clusters
+                    .into_iter()
+                    .map(|(cluster, font_list)| (cluster, font_list.cloned())),
+            ),
         }
     }
 
     res
 }
 
+/// Random runs of spaces between the clusters of a text, and an optional leading indentation,
+/// for tabular and poetry-style layouts.
+#[derive(Clone, Copy, Debug)]
+pub struct SpaceInsertion {
+    /// probability of a run of spaces after each cluster (except the last one)
+    pub prob: f64,
+    pub run_len: (u32, u32),
+    /// probability of an indentation before the first cluster
+    pub indent_prob: f64,
+    pub indent_len: (u32, u32),
+}
+
+impl SpaceInsertion {
+    pub fn insert<T: Clone>(&self, items: Vec<T>, space: T) -> Vec<T> {
+        let mut rng = sample_rng();
+
+        let mut res = Vec::with_capacity(items.len() * 2);
+        if rng.gen_bool(self.indent_prob) {
+            let len = rng.gen_range(self.indent_len.0..=self.indent_len.1);
+            res.extend((0..len).map(|_| space.clone()));
+        }
+
+        let count = items.len();
+        for (idx, item) in items.into_iter().enumerate() {
+            res.push(item);
+            if idx + 1 < count && rng.gen_bool(self.prob) {
+                let len = rng.gen_range(self.run_len.0..=self.run_len.1);
+                res.extend((0..len).map(|_| space.clone()));
+            }
+        }
+
+        res
+    }
+}
+
+/// Which script `ScriptConverter` emits Chinese text in: unchanged, traditional, simplified, or
+/// each character randomly in either form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Script {
+    Original,
+    Traditional,
+    Simplified,
+    Mixed,
+}
+
+impl Script {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "none" => Ok(Self::Original),
+            "traditional" => Ok(Self::Traditional),
+            "simplified" => Ok(Self::Simplified),
+            "mixed" => Ok(Self::Mixed),
+            _ => Err(format!(
+                "unknown script `{}`, expected none, traditional, simplified or mixed",
+                name
+            )),
+        }
+    }
+}
+
+/// Character-level conversion between traditional and simplified Chinese, read from an
+/// OpenCC-style table (`STCharacters.txt`): a simplified character, a tab, then its traditional
+/// forms separated by spaces, the first one being the default. The other direction maps every
+/// traditional form back to its simplified character.
+#[derive(Clone, Debug)]
+pub struct ScriptConverter {
+    to_traditional: HashMap<String, String>,
+    to_simplified: HashMap<String, String>,
+    pub script: Script,
+}
+
+impl ScriptConverter {
+    pub fn new(table: &str, script: Script) -> Result<Self, String> {
+        let mut to_traditional = HashMap::new();
+        let mut to_simplified = HashMap::new();
+        for (idx, line) in table.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (simplified, traditional) = line
+                .split_once('\t')
+                .ok_or_else(|| format!("line {} of the conversion table has no tab", idx + 1))?;
+            for (order, form) in traditional.split_whitespace().enumerate() {
+                if order == 0 {
+                    to_traditional.insert(simplified.to_string(), form.to_string());
+                }
+                if form != simplified {
+                    to_simplified
+                        .entry(form.to_string())
+                        .or_insert_with(|| simplified.to_string());
+                }
+            }
+        }
+
+        Ok(Self {
+            to_traditional,
+            to_simplified,
+            script,
+        })
+    }
+
+    /// Every character the conversion may produce.
+    pub fn targets(&self) -> impl Iterator<Item = &String> {
+        self.to_traditional
+            .values()
+            .chain(self.to_simplified.values())
+    }
+
+    pub fn convert<'a>(&'a self, cluster: &'a str) -> &'a str {
+        let table = match self.script {
+            Script::Original => return cluster,
+            Script::Traditional => &self.to_traditional,
+            Script::Simplified => &self.to_simplified,
+            Script::Mixed => {
+                if sample_rng().gen_bool(0.5) {
+                    &self.to_traditional
+                } else {
+                    &self.to_simplified
+                }
+            }
+        };
+
+        table.get(cluster).map_or(cluster, String::as_str)
+    }
+
+    /// Convert every cluster of a text with font lists, the font list of a converted cluster is
+    /// looked up in the dicts in order. A cluster whose converted form is not `allowed` or no
+    /// font covers is left as it is.
+    pub fn convert_with_font_list<'a, F>(
+        &'a self,
+        text: Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>,
+        dicts: &[&'a IndexMap<String, Vec<InternalAttrsOwned>>],
+        allowed: F,
+    ) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>
+    where
+        F: Fn(&str) -> bool,
+    {
+        text.into_iter()
+            .map(|(cluster, font_list)| {
+                let converted = self.convert(cluster);
+                if converted == cluster || !allowed(converted) {
+                    return (cluster, font_list);
+                }
+                match dicts.iter().find_map(|ch_dict| ch_dict.get(converted)) {
+                    Some(converted_font_list) if !converted_font_list.is_empty() => {
+                        (converted, Some(converted_font_list))
+                    }
+                    _ => (cluster, font_list),
+                }
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::fs;
@@ -128,4 +686,304 @@ mod test {
 
         println!("{:?}", wrap_text_with_font_list("這是一個測試", &ch_dict));
     }
+
+    #[test]
+    fn test_symbol_insertion() {
+        let chinese: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([("測".to_string(), vec![])]);
+        let weights = WeightedAliasIndex::new(vec![1.0]).unwrap();
+        let symbols = vec!["，".to_string(), "。".to_string()];
+        let text = |insertion: &SymbolInsertion, num| {
+            get_random_chinese_text_with_font_list(
+                &chinese,
+                &weights,
+                Some((&symbols, insertion)),
+                num..=num,
+            )
+            .into_iter()
+            .map(|(ch, _)| ch)
+            .collect::<String>()
+        };
+
+        // one symbol between two characters, never at the ends
+        let insertion = SymbolInsertion::default();
+        for _ in 0..20 {
+            let res = text(&insertion, 3);
+            assert_eq!(res.chars().count(), 4);
+            assert!(res.starts_with('測') && res.ends_with('測'));
+        }
+        assert_eq!(text(&insertion, 1), "測");
+
+        let insertion = SymbolInsertion {
+            count: (3, 3),
+            weights: Some(WeightedIndex::new([0.0, 1.0]).unwrap()),
+            at_start: true,
+            at_end: true,
+            ..Default::default()
+        };
+        assert_eq!(text(&insertion, 2), "。測。測。");
+
+        let insertion = SymbolInsertion {
+            prob: 1.0,
+            at_end: true,
+            ..Default::default()
+        };
+        assert_eq!(text(&insertion, 3).chars().count(), 6);
+    }
+
+    #[test]
+    fn test_random_mixed_text() {
+        let chinese: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([("測".to_string(), vec![])]);
+        let weights = WeightedAliasIndex::new(vec![1.0]).unwrap();
+        let latin_dict: IndexMap<String, Vec<InternalAttrsOwned>> = IndexMap::new();
+        let words = ["ab".to_string()];
+        let latin = TextPool {
+            items: &words[..],
+            ch_dict: &latin_dict,
+        };
+
+        let pool_weights = WeightedIndex::new([0.0, 1.0, 0.0]).unwrap();
+        let res = get_random_mixed_text_with_font_list(
+            &chinese,
+            &weights,
+            Some(latin),
+            None,
+            &pool_weights,
+            2..=2,
+        );
+        let text: String = res.iter().map(|(ch, _)| *ch).collect();
+        assert_eq!(text, "ab ab");
+
+        // the symbol pool is missing, Chinese characters are used instead
+        let pool_weights = WeightedIndex::new([0.0, 0.0, 1.0]).unwrap();
+        let res = get_random_mixed_text_with_font_list::<_, String, _>(
+            &chinese,
+            &weights,
+            None,
+            None,
+            &pool_weights,
+            3..=3,
+        );
+        let text: String = res.iter().map(|(ch, _)| *ch).collect();
+        assert_eq!(text, "測測測");
+    }
+
+    #[test]
+    fn test_random_mixed_line() {
+        let chinese: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([("測".to_string(), vec![])]);
+        let weights = WeightedAliasIndex::new(vec![1.0]).unwrap();
+        let digit_dict: IndexMap<String, Vec<InternalAttrsOwned>> = DIGITS
+            .iter()
+            .chain([&" "])
+            .map(|digit| (digit.to_string(), vec![]))
+            .collect();
+        let latin_dict: IndexMap<String, Vec<InternalAttrsOwned>> = IndexMap::new();
+        let words = ["ab".to_string()];
+
+        // digit groups only, separated by spaces
+        let res = get_random_mixed_line_with_font_list::<_, String, _>(
+            &chinese,
+            &weights,
+            None,
+            &digit_dict,
+            0.0,
+            1.0,
+            3..=3,
+        );
+        let text: String = res.iter().map(|(ch, _)| *ch).collect();
+        let groups: Vec<_> = text.split(' ').collect();
+        assert_eq!(groups.len(), 3);
+        for group in groups {
+            assert!(DIGIT_GROUP_LEN.contains(&group.len()));
+            assert!(group.chars().all(|ch| ch.is_ascii_digit()));
+        }
+
+        let latin = TextPool {
+            items: &words[..],
+            ch_dict: &latin_dict,
+        };
+        let res = get_random_mixed_line_with_font_list(
+            &chinese,
+            &weights,
+            Some(latin),
+            &digit_dict,
+            1.0,
+            0.0,
+            2..=2,
+        );
+        let text: String = res.iter().map(|(ch, _)| *ch).collect();
+        assert_eq!(text, "ab ab");
+
+        let res = get_random_mixed_line_with_font_list::<_, String, _>(
+            &chinese,
+            &weights,
+            None,
+            &digit_dict,
+            0.0,
+            0.0,
+            3..=3,
+        );
+        let text: String = res.iter().map(|(ch, _)| *ch).collect();
+        assert_eq!(text, "測測測");
+    }
+
+    #[test]
+    fn test_random_words() {
+        let counts = count_words("\"Hello, world!\" -- hello world world... l'été");
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("Hello".to_string(), 1),
+                ("world".to_string(), 3),
+                ("hello".to_string(), 1),
+                ("l'été".to_string(), 1),
+            ]
+        );
+
+        let ch_dict: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([(" ".to_string(), vec![])]);
+        let words = ["ab", "cd"];
+        let weights = WeightedAliasIndex::new(vec![0.0, 1.0]).unwrap();
+        let res = get_random_words_with_font_list(&words, Some(&weights), &ch_dict, 3..=3);
+        let text: String = res.iter().map(|(ch, _)| *ch).collect();
+        assert_eq!(text, "cd cd cd");
+        assert!(res[2].1.is_some());
+
+        let res = get_random_words_with_font_list(&words, None, &ch_dict, 2..=4);
+        let text: String = res.iter().map(|(ch, _)| *ch).collect();
+        assert!((2..=4).contains(&text.split(' ').count()));
+    }
+
+    #[test]
+    fn test_random_rtl_words() {
+        // the trailing vowel point stays, the Arabic comma goes
+        let counts = count_rtl_words("«كتبَ، كتبَ» שָׁלוֹם");
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            vec![("كتبَ".to_string(), 2), ("שָׁלוֹם".to_string(), 1)]
+        );
+
+        let word_dict: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([("سلام".to_string(), vec![]), ("كتاب".to_string(), vec![])]);
+        let space = vec![];
+        let weights = WeightedAliasIndex::new(vec![1.0, 0.0]).unwrap();
+        let res =
+            get_random_word_units_with_font_list(&word_dict, Some(&weights), Some(&space), 3..=3);
+        let units: Vec<&str> = res.iter().map(|(unit, _)| *unit).collect();
+        assert_eq!(units, vec!["سلام", " ", "سلام", " ", "سلام"]);
+        assert!(res.iter().all(|(_, font_list)| font_list.is_some()));
+    }
+
+    #[test]
+    fn test_wrap_text_with_dicts() {
+        let chinese: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([("測".to_string(), vec![])]);
+        let latin: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([("A".to_string(), vec![]), ("測".to_string(), vec![])]);
+        let res = wrap_text_with_dicts("測A,", &[&chinese, &latin]);
+
+        assert!(std::ptr::eq(res[0].1.unwrap(), &chinese["測"]));
+        assert!(std::ptr::eq(res[1].1.unwrap(), &latin["A"]));
+        assert!(res[2].1.is_none());
+    }
+
+    #[test]
+    fn test_wrap_text_by_grapheme_cluster() {
+        let ch_dict: IndexMap<String, Vec<InternalAttrsOwned>> = IndexMap::new();
+        // "e" + combining circumflex + combining dot below, and a ZWJ emoji sequence
+        let text = "Vie\u{302}\u{323}t 👩\u{200d}💻";
+        let clusters: Vec<_> = wrap_text_with_font_list(text, &ch_dict)
+            .into_iter()
+            .map(|(cluster, _)| cluster)
+            .collect();
+
+        assert_eq!(
+            clusters,
+            vec!["V", "i", "e\u{302}\u{323}", "t", " ", "👩\u{200d}💻"]
+        );
+    }
+
+    #[test]
+    fn test_random_chinese_text_by_width() {
+        let chinese: IndexMap<String, Vec<InternalAttrsOwned>> =
+            IndexMap::from([("測".to_string(), vec![])]);
+        let weights = WeightedAliasIndex::new(vec![1.0]).unwrap();
+
+        let res = get_random_chinese_text_by_width(&chinese, &weights, 105.0, |_, _| 10.0);
+        assert_eq!(res.len(), 10);
+        let res = get_random_chinese_text_by_width(&chinese, &weights, 5.0, |_, _| 10.0);
+        assert_eq!(res.len(), 1);
+    }
+
+    #[test]
+    fn test_space_insertion() {
+        let text = vec!["天", "地", "玄", "黃"];
+
+        let never = SpaceInsertion {
+            prob: 0.0,
+            run_len: (1, 3),
+            indent_prob: 0.0,
+            indent_len: (2, 4),
+        };
+        assert_eq!(never.insert(text.clone(), " "), text);
+
+        let always = SpaceInsertion {
+            prob: 1.0,
+            run_len: (2, 2),
+            indent_prob: 1.0,
+            indent_len: (4, 4),
+        };
+        assert_eq!(always.insert(text, " ").concat(), "    天  地  玄  黃");
+    }
+
+    #[test]
+    fn test_script_converter() {
+        let table = "发\t發 髮\n国\t國\n# comment\n\n后\t後 后\n";
+        assert!(Script::from_name("cantonese").is_err());
+        assert!(ScriptConverter::new("发發", Script::Traditional).is_err());
+
+        let mut converter = ScriptConverter::new(table, Script::Traditional).unwrap();
+        assert_eq!(converter.convert("发"), "發");
+        assert_eq!(converter.convert("國"), "國");
+        assert_eq!(converter.convert("后"), "後");
+        converter.script = Script::Simplified;
+        assert_eq!(converter.convert("髮"), "发");
+        assert_eq!(converter.convert("國"), "国");
+        converter.script = Script::Original;
+        assert_eq!(converter.convert("國"), "國");
+        // 后 stays 后 in simplified text
+        converter.script = Script::Simplified;
+        assert_eq!(converter.convert("后"), "后");
+
+        // the font list follows the converted character, no font covers 發 so 发 stays
+        let font = InternalAttrsOwned::new(cosmic_text::AttrsOwned::new(cosmic_text::Attrs::new()));
+        let ch_dict: IndexMap<String, Vec<InternalAttrsOwned>> = [
+            ("发", vec![font.clone()]),
+            ("國", vec![font.clone()]),
+            ("国", vec![font.clone()]),
+        ]
+        .into_iter()
+        .map(|(ch, fonts)| (ch.to_string(), fonts))
+        .collect();
+        let converted_dict: IndexMap<String, Vec<InternalAttrsOwned>> =
+            [("發".to_string(), vec![]), ("髮".to_string(), vec![font])]
+                .into_iter()
+                .collect();
+        converter.script = Script::Traditional;
+        let text = wrap_text_with_dicts("发国", &[&ch_dict]);
+        let converted =
+            converter.convert_with_font_list(text, &[&ch_dict, &converted_dict], |_| true);
+        assert_eq!(converted[0].0, "发");
+        assert_eq!(converted[1], ("國", ch_dict.get("國")));
+        let text = wrap_text_with_dicts("国", &[&ch_dict]);
+        let converted = converter.convert_with_font_list(text, &[&ch_dict], |ch: &str| ch != "國");
+        assert_eq!(converted[0].0, "国");
+
+        converter.script = Script::Mixed;
+        let forms: std::collections::HashSet<_> =
+            (0..50).map(|_| converter.convert("国")).collect();
+        assert_eq!(forms.len(), 2);
+    }
 }
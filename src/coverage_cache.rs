@@ -0,0 +1,247 @@
+//! On-disk cache of which fonts cover which characters.
+//!
+//! Checking every character of the character files against every font takes minutes with a few
+//! hundred fonts, so the answers are saved to a JSON file and reused by the next run. The cache
+//! is keyed by a fingerprint of the font directory (the path, size and modification time of each
+//! file), and starts over as soon as a font is added, removed or replaced.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use cosmic_text::Attrs;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct FontCoverageEntry {
+    // the clusters known to be covered and known to be missing, separated by newlines
+    covered: String,
+    missing: String,
+}
+
+#[derive(Default, Debug)]
+struct FontCoverageSets {
+    covered: HashSet<String>,
+    missing: HashSet<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CoverageCacheFile {
+    fingerprint: String,
+    fonts: HashMap<String, FontCoverageEntry>,
+}
+
+#[derive(Default, Debug)]
+pub struct CoverageCache {
+    fingerprint: String,
+    // keyed by `font_key`
+    fonts: HashMap<String, FontCoverageSets>,
+    // whether there are answers that are not saved yet
+    dirty: bool,
+}
+
+impl CoverageCache {
+    /// An empty cache for the fonts in `font_dir`.
+    pub fn new<P: AsRef<Path>>(font_dir: P) -> Self {
+        Self {
+            fingerprint: font_dir_fingerprint(font_dir),
+            ..Default::default()
+        }
+    }
+
+    /// The cache saved at `path`, or an empty one if there is none or the fonts in `font_dir`
+    /// have changed since it was saved.
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(path: P, font_dir: Q) -> Self {
+        let mut res = Self::new(font_dir);
+        let file: CoverageCacheFile = match fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+        {
+            Some(file) => file,
+            None => return res,
+        };
+        if file.fingerprint != res.fingerprint {
+            return res;
+        }
+
+        let split = |clusters: &str| -> HashSet<String> {
+            clusters
+                .split('\n')
+                .filter(|each| !each.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        res.fonts = file
+            .fonts
+            .into_iter()
+            .map(|(font, entry)| {
+                let sets = FontCoverageSets {
+                    covered: split(&entry.covered),
+                    missing: split(&entry.missing),
+                };
+                (font, sets)
+            })
+            .collect();
+
+        res
+    }
+
+    /// Write the cache to `path` as JSON.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let join = |clusters: &HashSet<String>| {
+            let mut clusters: Vec<_> = clusters.iter().map(String::as_str).collect();
+            clusters.sort_unstable();
+            clusters.join("\n")
+        };
+        let file = CoverageCacheFile {
+            fingerprint: self.fingerprint.clone(),
+            fonts: self
+                .fonts
+                .iter()
+                .map(|(font, sets)| {
+                    let entry = FontCoverageEntry {
+                        covered: join(&sets.covered),
+                        missing: join(&sets.missing),
+                    };
+                    (font.clone(), entry)
+                })
+                .collect(),
+        };
+        let data = serde_json::to_string(&file).expect("the cache is always serializable");
+        fs::write(&path, data).map_err(|err| {
+            format!(
+                "fail to write the coverage cache `{}`: {}",
+                path.as_ref().display(),
+                err
+            )
+        })?;
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    /// Whether some answers were added since the cache was loaded or saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Whether the font covers the cluster, `None` if it is not known yet.
+    pub fn get(&self, font_attrs: Attrs, cluster: &str) -> Option<bool> {
+        let sets = self.fonts.get(&font_key(font_attrs))?;
+        if sets.covered.contains(cluster) {
+            Some(true)
+        } else if sets.missing.contains(cluster) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, font_attrs: Attrs, cluster: &str, covered: bool) {
+        let sets = self.fonts.entry(font_key(font_attrs)).or_default();
+        let set = if covered {
+            &mut sets.covered
+        } else {
+            &mut sets.missing
+        };
+        // a newline would break the saved format, such clusters are simply not cached
+        if !cluster.contains('\n') && set.insert(cluster.to_string()) {
+            self.dirty = true;
+        }
+    }
+}
+
+/// The face is identified in the same way as it is queried.
+fn font_key(font_attrs: Attrs) -> String {
+    format!(
+        "{:?}/{:?}/{}/{:?}",
+        font_attrs.family, font_attrs.style, font_attrs.weight.0, font_attrs.stretch
+    )
+}
+
+/// FNV-1a hash of the path, size and modification time of every file under `dir`, in the order
+/// of the paths. A missing directory gives the hash of nothing.
+pub fn font_dir_fingerprint<P: AsRef<Path>>(dir: P) -> String {
+    let mut files = vec![];
+    collect_files(dir.as_ref(), &mut files);
+    files.sort();
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    for path in files {
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        feed(path.to_string_lossy().as_bytes());
+        feed(&metadata.len().to_le_bytes());
+        feed(&modified.to_le_bytes());
+    }
+
+    format!("{:016x}", hash)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cosmic_text::Family;
+
+    use super::*;
+
+    #[test]
+    fn test_coverage_cache() {
+        let dir = std::env::temp_dir().join("tig_coverage_cache_test");
+        fs::create_dir_all(dir.join("fonts")).unwrap();
+        fs::write(dir.join("fonts/a.ttf"), b"a").unwrap();
+        let cache_path = dir.join("cache.json");
+        let attrs = Attrs::new().family(Family::Name("Test"));
+
+        let mut cache = CoverageCache::new(dir.join("fonts"));
+        assert_eq!(cache.get(attrs, "永"), None);
+        cache.insert(attrs, "永", true);
+        cache.insert(attrs, "𠀀", false);
+        assert!(cache.is_dirty());
+        cache.save(&cache_path).unwrap();
+        assert!(!cache.is_dirty());
+
+        let cache = CoverageCache::load(&cache_path, dir.join("fonts"));
+        assert_eq!(cache.get(attrs, "永"), Some(true));
+        assert_eq!(cache.get(attrs, "𠀀"), Some(false));
+        assert_eq!(cache.get(attrs, "a"), None);
+
+        // a new font invalidates the cache
+        fs::write(dir.join("fonts/b.ttf"), b"b").unwrap();
+        let cache = CoverageCache::load(&cache_path, dir.join("fonts"));
+        assert_eq!(cache.get(attrs, "永"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
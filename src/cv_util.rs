@@ -1,19 +1,28 @@
-use image::{imageops::FilterType, GenericImage, GrayImage, Luma};
+use image::{
+    imageops::FilterType, GenericImage, GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage,
+};
 use imageproc::rect::Rect;
 use nalgebra::{Matrix3, Matrix4, Matrix4x2, Matrix4x3};
-use numpy::{PyArray, PyArray2, PyReadonlyArray2};
+#[cfg(feature = "python")]
+use numpy::{PyArray, PyArray2, PyArray3, PyReadonlyArray2, PyReadonlyArray3};
 use once_cell::sync::Lazy;
+#[cfg(feature = "python")]
 use pyo3::{pyclass, pymethods, types::PyType, Python};
 use rand::{
     distributions::{Distribution, Uniform},
     seq::SliceRandom,
     Rng,
 };
-
-use super::effect_helper::{
-    cv::{self, rectangle},
-    gaussian_blur::GaussBlur,
-    math::Random,
+use rand_distr::Normal;
+
+use super::{
+    effect_helper::{
+        cv::{self, rectangle},
+        gaussian_blur::GaussBlur,
+        math::Random,
+    },
+    parse_config::Config,
+    rng::effect_rng,
 };
 
 #[inline]
@@ -163,13 +172,157 @@ fn get_warp_matrix(
 const SHARP_KERNEL: [i32; 9] = [-1, -1, -1, -1, 9, -1, -1, -1, -1]; // 3x3
 const EMBOSS_KERNEL: [i32; 9] = [-2, -1, 0, -1, 1, 1, 0, 1, 2]; // 3x3
 
-const UNIFORM_1_2: Lazy<Uniform<f64>> = Lazy::new(|| Uniform::new_inclusive(1.0, 2.0));
-const COLOR_50_255: Lazy<Uniform<u8>> = Lazy::new(|| Uniform::new_inclusive(50, 255));
+static UNIFORM_0_1: Lazy<Uniform<f64>> = Lazy::new(|| Uniform::new_inclusive(0.0, 1.0));
+static UNIFORM_1_2: Lazy<Uniform<f64>> = Lazy::new(|| Uniform::new_inclusive(1.0, 2.0));
+static COLOR_50_255: Lazy<Uniform<u8>> = Lazy::new(|| Uniform::new_inclusive(50, 255));
 const THICKNESS: [u32; 2] = [1, 2];
 
+/// An effect that fired on a sample and the parameters it was drawn with, for the metadata of
+/// the sample.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppliedEffect {
+    pub name: &'static str,
+    pub params: Vec<(&'static str, f64)>,
+}
+
+impl AppliedEffect {
+    fn new(name: &'static str, params: Vec<(&'static str, f64)>) -> Self {
+        Self { name, params }
+    }
+}
+
+impl std::fmt::Display for AppliedEffect {
+    /// e.g. `blur(sigma=1.25)`, or just the name without parameters.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if self.params.is_empty() {
+            return Ok(());
+        }
+        let params: Vec<_> = self
+            .params
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        write!(f, "({})", params.join(", "))
+    }
+}
+
+/// A mark `occlude` draws over the text, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Occluder {
+    /// a segment between two points, of a thickness, as a scratch or a strikeout
+    Line((f64, f64), (f64, f64), f64),
+    /// an ellipse with its center and its two radii, as a blot
+    Blob((f64, f64), (f64, f64)),
+    /// a rectangle (x0, y0, x1, y1), as a sticker or a stamp
+    Rect(f64, f64, f64, f64),
+}
+
+impl Occluder {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        match *self {
+            Self::Line((x0, y0), (x1, y1), thickness) => {
+                // the distance to the closest point of the segment
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let len = dx * dx + dy * dy;
+                let t = if len == 0.0 {
+                    0.0
+                } else {
+                    (((x - x0) * dx + (y - y0) * dy) / len).clamp(0.0, 1.0)
+                };
+                let (px, py) = (x0 + t * dx - x, y0 + t * dy - y);
+                (px * px + py * py).sqrt() <= thickness / 2.0
+            }
+            Self::Blob((cx, cy), (rx, ry)) => {
+                rx > 0.0 && ry > 0.0 && ((x - cx) / rx).powi(2) + ((y - cy) / ry).powi(2) <= 1.0
+            }
+            Self::Rect(x0, y0, x1, y1) => (x0..x1).contains(&x) && (y0..y1).contains(&y),
+        }
+    }
+}
+
+/// A geometric step of the effects, replayed on a mask of the text (255 where there is ink) so
+/// that it stays aligned with the image, see `replay_transforms`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transform {
+    /// resize to (width, height)
+    Resize(u32, u32),
+    /// put at (left, top) of a black canvas of (width, height)
+    Pad {
+        left: u32,
+        top: u32,
+        width: u32,
+        height: u32,
+    },
+    /// `warp_perspective_transform` with the rotation angles (x, y, z)
+    Perspective(f32, f32, f32),
+    /// `cylinder_warp` with the strength and whether the spine is on the left, without shading
+    Cylinder(f64, bool),
+    /// `wave_warp` with the amplitude, the wavelength, the phase and whether the rows shift
+    Wave(f64, f64, f64, bool),
+}
+
+impl Transform {
+    pub fn apply(&self, mask: &GrayImage) -> GrayImage {
+        match *self {
+            Self::Resize(width, height) => {
+                image::imageops::resize(mask, width, height, FilterType::Triangle)
+            }
+            Self::Pad {
+                left,
+                top,
+                width,
+                height,
+            } => {
+                let mut padded = GrayImage::new(width, height);
+                image::imageops::replace(&mut padded, mask, left as i64, top as i64);
+                padded
+            }
+            Self::Perspective(x, y, z) => CvUtil::warp_perspective_transform(mask, (x, y, z)),
+            Self::Cylinder(strength, spine_on_left) => {
+                CvUtil::cylinder_warp_with(mask, strength, spine_on_left, false)
+            }
+            Self::Wave(amplitude, wavelength, phase, shift_rows) => {
+                CvUtil::wave_warp(mask, amplitude, wavelength, phase, shift_rows)
+            }
+        }
+    }
+}
+
+/// Replay the steps on the mask in order.
+pub fn replay_transforms(mask: GrayImage, transforms: &[Transform]) -> GrayImage {
+    transforms
+        .iter()
+        .fold(mask, |mask, transform| transform.apply(&mask))
+}
+
 #[derive(Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct CvUtil {
+    // morphology of the strokes: erosion thins and breaks them, dilation spreads the ink; the
+    // structuring element is a rectangle of random width and height in the kernel size range
+    pub erode_prob: f64,
+    pub erode_kernel: Random,
+    pub dilate_prob: f64,
+    pub dilate_kernel: Random,
+    // ink bleed around the strokes: the spread of the ink in pixels, the sigma of the blur and
+    // the standard deviation of the threshold noise in gray levels
+    pub ink_bleed_prob: f64,
+    pub ink_bleed_spread: Random,
+    pub ink_bleed_sigma: Random,
+    pub ink_bleed_noise: Random,
+    // occlusion: short lines, blobs and rectangles over the glyphs, each filled with a gray level
+    // of the intensity range; the thickness of the lines is in pixels
+    pub occlusion_prob: f64,
+    pub occlusion_count: Random,
+    pub occlusion_thickness: Random,
+    pub occlusion_intensity: Random,
+    // ruled lines and cell borders of a table around the text: the spacing of the vertical
+    // lines is relative to the height, the thickness is in pixels
+    pub table_line_prob: f64,
+    pub table_line_spacing: Random,
+    pub table_line_thickness: Random,
+    pub table_line_intensity: Random,
     // draw box
     pub box_prob: f64,
     // perspective transform
@@ -184,41 +337,215 @@ pub struct CvUtil {
     pub filter_prob: f64,
     pub emboss_prob: f64,
     pub sharp_prob: f64,
+    // color jitter of RGB images: hue rotation in degrees, saturation/value scaling
+    pub color_jitter_prob: f64,
+    pub hue_shift: Random,
+    pub saturation_scale: Random,
+    pub value_scale: Random,
+    // book-spine curvature of the merged image
+    pub curvature_prob: f64,
+    pub curvature_strength: Random,
+    // sine wave warp of the merged image: the amplitude is relative to the height (the width
+    // when the rows shift), the wavelength to the width (the height), the phase in degrees
+    pub wave_prob: f64,
+    pub wave_amplitude: Random,
+    pub wave_wavelength: Random,
+    pub wave_phase: Random,
+    pub wave_rows_prob: f64,
+    // sensor noise of the merged image, each type on its own
+    pub gaussian_noise_prob: f64,
+    pub gaussian_noise_sigma: Random,
+    pub salt_pepper_prob: f64,
+    pub salt_pepper_amount: Random,
+    pub speckle_noise_prob: f64,
+    pub speckle_noise_sigma: Random,
 }
 
 impl CvUtil {
-    const UNIFORM_0_1: Lazy<Uniform<f64>> = Lazy::new(|| Uniform::new_inclusive(0.0, 1.0));
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            erode_prob: config.erode_prob,
+            erode_kernel: config.erode_kernel,
+            dilate_prob: config.dilate_prob,
+            dilate_kernel: config.dilate_kernel,
+            ink_bleed_prob: config.ink_bleed_prob,
+            ink_bleed_spread: config.ink_bleed_spread,
+            ink_bleed_sigma: config.ink_bleed_sigma,
+            ink_bleed_noise: config.ink_bleed_noise,
+            occlusion_prob: config.occlusion_prob,
+            occlusion_count: config.occlusion_count,
+            occlusion_thickness: config.occlusion_thickness,
+            occlusion_intensity: config.occlusion_intensity,
+            table_line_prob: config.table_line_prob,
+            table_line_spacing: config.table_line_spacing,
+            table_line_thickness: config.table_line_thickness,
+            table_line_intensity: config.table_line_intensity,
+            box_prob: config.box_prob,
+            perspective_prob: config.perspective_prob,
+            perspective_x: config.perspective_x,
+            perspective_y: config.perspective_y,
+            perspective_z: config.perspective_z,
+            blur_prob: config.blur_prob,
+            blur_sigma: config.blur_sigma,
+            filter_prob: config.filter_prob,
+            emboss_prob: config.emboss_prob,
+            sharp_prob: config.sharp_prob,
+            color_jitter_prob: config.color_jitter_prob,
+            hue_shift: config.hue_shift,
+            saturation_scale: config.saturation_scale,
+            value_scale: config.value_scale,
+            curvature_prob: config.curvature_prob,
+            curvature_strength: config.curvature_strength,
+            wave_prob: config.wave_prob,
+            wave_amplitude: config.wave_amplitude,
+            wave_wavelength: config.wave_wavelength,
+            wave_phase: config.wave_phase,
+            wave_rows_prob: config.wave_rows_prob,
+            gaussian_noise_prob: config.gaussian_noise_prob,
+            gaussian_noise_sigma: config.gaussian_noise_sigma,
+            salt_pepper_prob: config.salt_pepper_prob,
+            salt_pepper_amount: config.salt_pepper_amount,
+            speckle_noise_prob: config.speckle_noise_prob,
+            speckle_noise_sigma: config.speckle_noise_sigma,
+        }
+    }
 
     pub fn apply_effect(&self, img: GrayImage) -> GrayImage {
+        self.apply_effect_recorded(img, &mut vec![])
+    }
+
+    /// `apply_effect`, pushing the effects that fire to `applied`.
+    pub fn apply_effect_recorded(
+        &self,
+        img: GrayImage,
+        applied: &mut Vec<AppliedEffect>,
+    ) -> GrayImage {
+        self.apply_effect_traced(img, applied, &mut vec![])
+    }
+
+    /// `apply_effect_recorded`, pushing the geometric steps to `transforms` as well.
+    pub fn apply_effect_traced(
+        &self,
+        img: GrayImage,
+        applied: &mut Vec<AppliedEffect>,
+        transforms: &mut Vec<Transform>,
+    ) -> GrayImage {
         assert!(
             self.emboss_prob + self.sharp_prob == 1.0,
             "emboss probability plus sharp probability should be equal to 1.0"
         );
 
-        let img = if Self::UNIFORM_0_1.sample(&mut rand::thread_rng()) < self.box_prob {
-            Self::draw_box(&img, 1.3)
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.erode_prob {
+            let (width, height) = Self::sample_kernel(&self.erode_kernel);
+            applied.push(AppliedEffect::new(
+                "erode",
+                vec![("width", width as f64), ("height", height as f64)],
+            ));
+            Self::erode(&img, width, height)
+        } else {
+            img
+        };
+
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.dilate_prob {
+            let (width, height) = Self::sample_kernel(&self.dilate_kernel);
+            applied.push(AppliedEffect::new(
+                "dilate",
+                vec![("width", width as f64), ("height", height as f64)],
+            ));
+            Self::dilate(&img, width, height)
+        } else {
+            img
+        };
+
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.ink_bleed_prob {
+            let spread = self.ink_bleed_spread.sample().round().max(0.0) as u32;
+            let sigma = self.ink_bleed_sigma.sample().max(0.0);
+            let noise = self.ink_bleed_noise.sample().max(0.0);
+            applied.push(AppliedEffect::new(
+                "ink_bleed",
+                vec![
+                    ("spread", spread as f64),
+                    ("sigma", sigma),
+                    ("noise", noise),
+                ],
+            ));
+            Self::ink_bleed(&img, spread, sigma as f32, noise)
+        } else {
+            img
+        };
+
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.occlusion_prob {
+            let count = self.occlusion_count.sample().round().max(1.0) as usize;
+            applied.push(AppliedEffect::new(
+                "occlusion",
+                vec![("count", count as f64)],
+            ));
+            let occluders: Vec<_> = (0..count)
+                .map(|_| {
+                    let intensity = self.occlusion_intensity.sample().round().clamp(0.0, 255.0);
+                    (
+                        self.random_occluder(img.width(), img.height()),
+                        intensity as u8,
+                    )
+                })
+                .collect();
+            Self::occlude(&img, &occluders)
         } else {
             img
         };
 
-        let img = if Self::UNIFORM_0_1.sample(&mut rand::thread_rng()) < self.perspective_prob {
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.table_line_prob {
+            let lines = self.random_table_lines(img.width(), img.height());
+            applied.push(AppliedEffect::new(
+                "table_lines",
+                vec![("count", lines.len() as f64)],
+            ));
+            Self::occlude(&img, &lines)
+        } else {
+            img
+        };
+
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.box_prob {
+            applied.push(AppliedEffect::new("box", vec![]));
+            Self::draw_box_traced(&img, 1.3, transforms)
+        } else {
+            img
+        };
+
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.perspective_prob {
             let rotate_angle = (
                 self.perspective_x.sample() as f32,
                 self.perspective_y.sample() as f32,
                 self.perspective_z.sample() as f32,
             );
+            applied.push(AppliedEffect::new(
+                "perspective",
+                vec![
+                    ("x", rotate_angle.0 as f64),
+                    ("y", rotate_angle.1 as f64),
+                    ("z", rotate_angle.2 as f64),
+                ],
+            ));
+            transforms.push(Transform::Perspective(
+                rotate_angle.0,
+                rotate_angle.1,
+                rotate_angle.2,
+            ));
             Self::warp_perspective_transform(&img, rotate_angle)
         } else {
             img
         };
 
-        if Self::UNIFORM_0_1.sample(&mut rand::thread_rng()) < self.blur_prob {
+        if UNIFORM_0_1.sample(&mut effect_rng()) < self.blur_prob {
             let sigma = self.blur_sigma.sample() as f32;
+            applied.push(AppliedEffect::new("blur", vec![("sigma", sigma as f64)]));
             let img = Self::gauss_blur(img, sigma);
-            if Self::UNIFORM_0_1.sample(&mut rand::thread_rng()) < self.filter_prob {
-                if Self::UNIFORM_0_1.sample(&mut rand::thread_rng()) < self.emboss_prob {
+            if UNIFORM_0_1.sample(&mut effect_rng()) < self.filter_prob {
+                if UNIFORM_0_1.sample(&mut effect_rng()) < self.emboss_prob {
+                    applied.push(AppliedEffect::new("emboss", vec![]));
                     Self::apply_emboss(&img)
                 } else {
+                    applied.push(AppliedEffect::new("sharp", vec![]));
                     Self::apply_sharp(&img)
                 }
             } else {
@@ -229,19 +556,222 @@ impl CvUtil {
         }
     }
 
+    /// The width and the height of a structuring element, each drawn from `kernel` and rounded,
+    /// at least 1.
+    fn sample_kernel(kernel: &Random) -> (u32, u32) {
+        let size = || kernel.sample().round().max(1.0) as u32;
+        (size(), size())
+    }
+
+    /// Thin the dark strokes of a text image: every pixel takes the brightest value of the
+    /// `width` x `height` rectangle around it, so that thin strokes break up.
+    pub fn erode(img: &GrayImage, width: u32, height: u32) -> GrayImage {
+        Self::rank_filter(img, width, height, u8::max)
+    }
+
+    /// Spread the dark strokes of a text image: every pixel takes the darkest value of the
+    /// `width` x `height` rectangle around it, as ink bleeding into the paper.
+    pub fn dilate(img: &GrayImage, width: u32, height: u32) -> GrayImage {
+        Self::rank_filter(img, width, height, u8::min)
+    }
+
+    /// Reduce the rectangle around each pixel with `pick`, one row pass and one column pass.
+    /// The rectangle is centered on the pixel (one more pixel after it for an even size) and
+    /// clipped to the image.
+    fn rank_filter(img: &GrayImage, width: u32, height: u32, pick: fn(u8, u8) -> u8) -> GrayImage {
+        let pass = |img: &GrayImage, size: u32, horizontal: bool| {
+            let (before, after) = ((size.max(1) - 1) / 2, size.max(1) / 2);
+            GrayImage::from_fn(img.width(), img.height(), |x, y| {
+                let (position, len) = if horizontal {
+                    (x, img.width())
+                } else {
+                    (y, img.height())
+                };
+                let range = position.saturating_sub(before)..(position + after + 1).min(len);
+                let value = range
+                    .map(|each| {
+                        if horizontal {
+                            img.get_pixel(each, y).0[0]
+                        } else {
+                            img.get_pixel(x, each).0[0]
+                        }
+                    })
+                    .reduce(pick)
+                    .unwrap();
+                Luma([value])
+            })
+        };
+
+        pass(&pass(img, width, true), height, false)
+    }
+
+    /// Let the ink of a text image bleed, as with cheap printing or a rubber stamp: the strokes
+    /// spread by `spread` pixels on each side and are blurred with `sigma`, then a pixel takes
+    /// the blurred value where it plus gaussian noise of `noise` gray levels is still below the
+    /// middle gray, so that the edges come out ragged. Only the pixels within reach of the ink
+    /// (darker than the middle gray) change, the paper elsewhere stays clean.
+    pub fn ink_bleed(img: &GrayImage, spread: u32, sigma: f32, noise: f64) -> GrayImage {
+        let reach = 2 * (spread + sigma.ceil() as u32) + 1;
+        // 0 on the ink and around it, 255 elsewhere
+        let mut region = img.clone();
+        binarize(&mut region);
+        let region = Self::dilate(&region, reach, reach);
+        let bled = GaussBlur::gaussian_blur(
+            Self::dilate(img, 2 * spread + 1, 2 * spread + 1),
+            sigma,
+            sigma,
+        );
+
+        let normal = match Normal::new(0.0, noise) {
+            Ok(normal) => normal,
+            Err(_) => return img.clone(),
+        };
+        let mut rng = effect_rng();
+        let mut res = img.clone();
+        for (x, y, pixel) in res.enumerate_pixels_mut() {
+            if region.get_pixel(x, y).0[0] != 0 {
+                continue;
+            }
+            let value = bled.get_pixel(x, y).0[0];
+            if value as f64 + normal.sample(&mut rng) < 128.0 {
+                pixel.0[0] = pixel.0[0].min(value);
+            }
+        }
+
+        res
+    }
+
+    /// A line, a blob or a rectangle somewhere on an image of `width` x `height`, sized after
+    /// the height, i.e. the size of the glyphs of a text line.
+    fn random_occluder(&self, width: u32, height: u32) -> Occluder {
+        let mut rng = effect_rng();
+        let (width, height) = (width as f64, height as f64);
+        let (x, y) = (rng.gen_range(0.0..=width), rng.gen_range(0.0..=height));
+        match rng.gen_range(0..3) {
+            0 => {
+                let len = rng.gen_range(0.3..=1.5) * height;
+                let angle = rng.gen_range(0.0..std::f64::consts::PI);
+                let (dx, dy) = (len / 2.0 * angle.cos(), len / 2.0 * angle.sin());
+                let thickness = self.occlusion_thickness.sample().max(1.0);
+                Occluder::Line((x - dx, y - dy), (x + dx, y + dy), thickness)
+            }
+            1 => {
+                let radii = (
+                    rng.gen_range(0.05..=0.25) * height,
+                    rng.gen_range(0.05..=0.25) * height,
+                );
+                Occluder::Blob((x, y), radii)
+            }
+            _ => {
+                let (w, h) = (
+                    rng.gen_range(0.1..=0.5) * height,
+                    rng.gen_range(0.1..=0.5) * height,
+                );
+                Occluder::Rect(x - w / 2.0, y - h / 2.0, x + w / 2.0, y + h / 2.0)
+            }
+        }
+    }
+
+    /// The borders of the table cell the text sits in: a ruled line near the top and one near
+    /// the bottom, each with a probability of one half but at least one of them, and with a
+    /// probability of one half vertical cell borders every `table_line_spacing` heights. Some
+    /// lines stop partway, as the borders of merged cells. All the lines share one thickness and
+    /// one gray level.
+    fn random_table_lines(&self, width: u32, height: u32) -> Vec<(Occluder, u8)> {
+        let (width, height) = (width as f64, height as f64);
+        let thickness = self.table_line_thickness.sample().max(1.0);
+        let intensity = self.table_line_intensity.sample().round().clamp(0.0, 255.0) as u8;
+        // a line from `start` to `end`, partial with a probability of 0.3
+        let span = |start: f64, end: f64| {
+            let mut rng = effect_rng();
+            if rng.gen_bool(0.3) {
+                let len = (end - start) * rng.gen_range(0.5..=1.0);
+                let from = rng.gen_range(start..=end - len);
+                (from, from + len)
+            } else {
+                (start, end)
+            }
+        };
+
+        let mut rows = vec![];
+        let top = effect_rng().gen_bool(0.5);
+        if top {
+            rows.push(effect_rng().gen_range(0.0..=0.1) * height);
+        }
+        if !top || effect_rng().gen_bool(0.5) {
+            rows.push(effect_rng().gen_range(0.9..=1.0) * height);
+        }
+        let mut lines: Vec<_> = rows
+            .into_iter()
+            .map(|y| {
+                let (x0, x1) = span(0.0, width);
+                Occluder::Line((x0, y), (x1, y), thickness)
+            })
+            .collect();
+
+        if effect_rng().gen_bool(0.5) {
+            let spacing = (self.table_line_spacing.sample() * height).max(1.0);
+            let mut x = effect_rng().gen_range(0.0..spacing);
+            while x < width {
+                let (y0, y1) = span(0.0, height);
+                lines.push(Occluder::Line((x, y0), (x, y1), thickness));
+                x += spacing;
+            }
+        }
+
+        lines.into_iter().map(|line| (line, intensity)).collect()
+    }
+
+    /// Fill the pixels whose centers are inside each occluder with its gray level, in order.
+    pub fn occlude(img: &GrayImage, occluders: &[(Occluder, u8)]) -> GrayImage {
+        let mut res = img.clone();
+        for (x, y, pixel) in res.enumerate_pixels_mut() {
+            let center = (x as f64 + 0.5, y as f64 + 0.5);
+            for (occluder, intensity) in occluders {
+                if occluder.contains(center.0, center.1) {
+                    *pixel = Luma([*intensity]);
+                }
+            }
+        }
+
+        res
+    }
+
     /// Perform a perspective transform and crop the transformed text area.
     pub fn warp_perspective_transform(img: &GrayImage, rotate_angle: (f32, f32, f32)) -> GrayImage {
-        let (raw_height, raw_width) = (img.height(), img.width());
+        let (raw_height, raw_width) = (img.height() as f32, img.width() as f32);
+        let (crop_img, _) = Self::warp_perspective_quad(img, rotate_angle);
+
+        let (new_height, new_width) = (crop_img.height() as f32, crop_img.width() as f32);
+        let (resize_width, resize_height) = (
+            (new_width * raw_height / new_height).ceil() as u32,
+            raw_height as u32,
+        );
+        if resize_width <= raw_width as u32 && resize_height <= raw_height as u32 {
+            image::imageops::resize(&crop_img, resize_width, resize_height, FilterType::Triangle)
+        } else {
+            let (resize_width, resize_height) = (
+                raw_width as u32,
+                (new_height * raw_width / new_width).ceil() as u32,
+            );
+            image::imageops::resize(&crop_img, resize_width, resize_height, FilterType::Triangle)
+        }
+    }
 
+    /// Perform a perspective transform and crop the transformed image, without resizing it back.
+    /// Returns the crop and where the corners of the image went in it: top left, top right,
+    /// bottom right and bottom left. The area outside the image is black.
+    pub fn warp_perspective_quad(
+        img: &GrayImage,
+        rotate_angle: (f32, f32, f32),
+    ) -> (GrayImage, [(f32, f32); 4]) {
         let (transform_mat, side_length, _, points_out) = get_warp_matrix(
-            raw_width as usize,
-            raw_height as usize,
+            img.width() as usize,
+            img.height() as usize,
             rotate_angle,
             1.0,
             50.,
         );
-
-        let (raw_height, raw_width) = (raw_height as f32, raw_width as f32);
         let side_length = side_length.ceil() as u32;
 
         let mut warp_img = cv::warp_perspective(img, &transform_mat, side_length, Luma([0]));
@@ -262,22 +792,26 @@ impl CvUtil {
             .sub_image(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
             .to_image();
 
-        let (new_height, new_width) = (crop_img.height() as f32, crop_img.width() as f32);
-        let (resize_width, resize_height) = (
-            (new_width * raw_height / new_height).ceil() as u32,
-            raw_height as u32,
-        );
-        let resize_img = if resize_width <= raw_width as u32 && resize_height <= raw_height as u32 {
-            image::imageops::resize(&crop_img, resize_width, resize_height, FilterType::Triangle)
-        } else {
-            let (resize_width, resize_height) = (
-                raw_width as u32,
-                (new_height * raw_width / new_width).ceil() as u32,
-            );
-            image::imageops::resize(&crop_img, resize_width, resize_height, FilterType::Triangle)
+        // the points go bottom left, bottom right, top right, top left in image coordinates
+        let corner = |row: usize| {
+            (
+                points_out[(row, 0)] - min_x as f32,
+                points_out[(row, 1)] - min_y as f32,
+            )
         };
 
-        resize_img
+        (crop_img, [corner(3), corner(2), corner(1), corner(0)])
+    }
+
+    /// `warp_perspective_quad` with the rotation angles drawn from the perspective settings.
+    pub fn random_perspective_quad(&self, img: &GrayImage) -> (GrayImage, [(f32, f32); 4]) {
+        let rotate_angle = (
+            self.perspective_x.sample() as f32,
+            self.perspective_y.sample() as f32,
+            self.perspective_z.sample() as f32,
+        );
+
+        Self::warp_perspective_quad(img, rotate_angle)
     }
 
     pub fn apply_emboss(img: &GrayImage) -> GrayImage {
@@ -292,7 +826,7 @@ impl CvUtil {
 
     /// Blur the image to simulate the effect of enlarging the small image
     pub fn apply_down_up(img: &GrayImage) -> GrayImage {
-        let scale = UNIFORM_1_2.sample(&mut rand::thread_rng());
+        let scale = UNIFORM_1_2.sample(&mut effect_rng());
         let height = img.height();
         let width = img.width();
 
@@ -310,6 +844,15 @@ impl CvUtil {
     }
 
     pub fn draw_box(img: &GrayImage, alpha: f64) -> GrayImage {
+        Self::draw_box_traced(img, alpha, &mut vec![])
+    }
+
+    /// `draw_box`, pushing the padding and the resizing back to `transforms`.
+    pub fn draw_box_traced(
+        img: &GrayImage,
+        alpha: f64,
+        transforms: &mut Vec<Transform>,
+    ) -> GrayImage {
         assert!(alpha >= 1.0, "alpha should be greater than 1.0");
 
         let (height, width) = (img.height(), img.width());
@@ -317,33 +860,503 @@ impl CvUtil {
             (height as f64 * alpha).ceil() as u32,
             (width as f64 * alpha).ceil() as u32,
         );
-        let top = rand::thread_rng().gen_range(1..=(pad_height - height));
-        let left = rand::thread_rng().gen_range(1..=(pad_width - width));
+        let top = effect_rng().gen_range(1..=(pad_height - height));
+        let left = effect_rng().gen_range(1..=(pad_width - width));
 
         let mut img_pad = GrayImage::from_pixel(pad_width, pad_height, Luma([0]));
         img_pad
             .copy_from(img, left, top)
             .expect("origin image is smaller than padded image");
 
-        let box_left = rand::thread_rng().gen_range(1..=(left as i32));
-        let box_top = rand::thread_rng().gen_range(1..=(top as i32));
-        let box_width = rand::thread_rng()
+        let box_left = effect_rng().gen_range(1..=(left as i32));
+        let box_top = effect_rng().gen_range(1..=(top as i32));
+        let box_width = effect_rng()
             .gen_range((width + left - box_left as u32)..=(pad_width - box_left as u32));
-        let box_height = rand::thread_rng()
-            .gen_range((height + top - box_top as u32)..=(pad_height - box_top as u32));
+        let box_height =
+            effect_rng().gen_range((height + top - box_top as u32)..=(pad_height - box_top as u32));
 
         let rect = Rect::at(box_left, box_top).of_size(box_width, box_height);
-        let color = Luma([COLOR_50_255.sample(&mut rand::thread_rng())]);
-        let thickness = THICKNESS.choose(&mut rand::thread_rng()).unwrap().clone();
+        let color = Luma([COLOR_50_255.sample(&mut effect_rng())]);
+        let thickness = *THICKNESS.choose(&mut effect_rng()).unwrap();
 
         rectangle(&mut img_pad, rect, color, thickness);
+        transforms.extend([
+            Transform::Pad {
+                left,
+                top,
+                width: pad_width,
+                height: pad_height,
+            },
+            Transform::Resize(width, height),
+        ]);
 
         image::imageops::resize(&img_pad, width, height, FilterType::Triangle)
     }
+
+    /// Randomly jitter the hue, saturation and value of an RGB image according to the config,
+    /// to model the color cast of lighting.
+    pub fn apply_color_jitter(&self, img: RgbImage) -> RgbImage {
+        if UNIFORM_0_1.sample(&mut effect_rng()) >= self.color_jitter_prob {
+            return img;
+        }
+
+        Self::hsv_jitter(
+            &img,
+            self.hue_shift.sample(),
+            self.saturation_scale.sample(),
+            self.value_scale.sample(),
+        )
+    }
+
+    /// Rotate the hue by `hue_shift` degrees and scale the saturation and the value.
+    pub fn hsv_jitter(
+        img: &RgbImage,
+        hue_shift: f64,
+        saturation_scale: f64,
+        value_scale: f64,
+    ) -> RgbImage {
+        let mut res = img.clone();
+        for pixel in res.pixels_mut() {
+            let (h, s, v) = rgb_to_hsv(*pixel);
+            let h = (h + hue_shift).rem_euclid(360.0);
+            let s = (s * saturation_scale).clamp(0.0, 1.0);
+            let v = (v * value_scale).clamp(0.0, 1.0);
+            *pixel = hsv_to_rgb(h, s, v);
+        }
+
+        res
+    }
+
+    /// Randomly curve the merged image like a book page near the spine according to the config,
+    /// the spine is on the left or on the right with equal probability.
+    pub fn apply_curvature<P>(&self, img: ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        self.apply_curvature_recorded(img, &mut vec![])
+    }
+
+    /// `apply_curvature`, pushing the curvature to `applied` if it fires.
+    pub fn apply_curvature_recorded<P>(
+        &self,
+        img: ImageBuffer<P, Vec<u8>>,
+        applied: &mut Vec<AppliedEffect>,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        self.apply_curvature_traced(img, applied, &mut vec![])
+    }
+
+    /// `apply_curvature_recorded`, pushing the warp to `transforms` as well.
+    pub fn apply_curvature_traced<P>(
+        &self,
+        img: ImageBuffer<P, Vec<u8>>,
+        applied: &mut Vec<AppliedEffect>,
+        transforms: &mut Vec<Transform>,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        if UNIFORM_0_1.sample(&mut effect_rng()) >= self.curvature_prob {
+            return img;
+        }
+
+        let strength = self.curvature_strength.sample().clamp(0.0, 1.0);
+        let spine_on_left = effect_rng().gen_bool(0.5);
+        applied.push(AppliedEffect::new(
+            "curvature",
+            vec![
+                ("strength", strength),
+                ("spine_on_left", spine_on_left as u8 as f64),
+            ],
+        ));
+        transforms.push(Transform::Cylinder(strength, spine_on_left));
+        Self::cylinder_warp(&img, strength, spine_on_left)
+    }
+
+    /// Randomly bend the merged image along a sine wave according to the config, as paper that
+    /// is not flat. Usually the columns shift up and down, with `wave_rows_prob` the rows shift
+    /// left and right instead.
+    pub fn apply_wave<P>(&self, img: ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        self.apply_wave_traced(img, &mut vec![], &mut vec![])
+    }
+
+    /// `apply_wave`, pushing the warp to `applied` and `transforms` if it fires.
+    pub fn apply_wave_traced<P>(
+        &self,
+        img: ImageBuffer<P, Vec<u8>>,
+        applied: &mut Vec<AppliedEffect>,
+        transforms: &mut Vec<Transform>,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        if UNIFORM_0_1.sample(&mut effect_rng()) >= self.wave_prob {
+            return img;
+        }
+
+        let shift_rows = UNIFORM_0_1.sample(&mut effect_rng()) < self.wave_rows_prob;
+        let (across, along) = if shift_rows {
+            (img.width() as f64, img.height() as f64)
+        } else {
+            (img.height() as f64, img.width() as f64)
+        };
+        let amplitude = self.wave_amplitude.sample().max(0.0) * across;
+        let wavelength = self.wave_wavelength.sample().max(0.0) * along;
+        let phase = self.wave_phase.sample();
+        applied.push(AppliedEffect::new(
+            "wave_warp",
+            vec![
+                ("amplitude", amplitude),
+                ("wavelength", wavelength),
+                ("phase", phase),
+                ("rows", shift_rows as u8 as f64),
+            ],
+        ));
+        transforms.push(Transform::Wave(amplitude, wavelength, phase, shift_rows));
+        Self::wave_warp(&img, amplitude, wavelength, phase, shift_rows)
+    }
+
+    /// Shift every column up or down by `amplitude * sin(2 * pi * x / wavelength + phase)`
+    /// pixels, or every row left or right by the same function of y with `shift_rows`. The
+    /// phase is in degrees, the pixels shifted in from outside repeat the border.
+    pub fn wave_warp<P>(
+        img: &ImageBuffer<P, Vec<u8>>,
+        amplitude: f64,
+        wavelength: f64,
+        phase: f64,
+        shift_rows: bool,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let (width, height) = (img.width(), img.height());
+        if amplitude == 0.0 || wavelength <= 0.0 || width == 0 || height == 0 {
+            return img.clone();
+        }
+
+        let phase = phase.to_radians();
+        let offset = |position: u32| {
+            amplitude * (std::f64::consts::TAU * position as f64 / wavelength + phase).sin()
+        };
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let (src_x, src_y) = if shift_rows {
+                (x as f64 - offset(y), y as f64)
+            } else {
+                (x as f64, y as f64 - offset(x))
+            };
+
+            let mut pixel = *img.get_pixel(0, 0);
+            for (channel, value) in pixel.channels_mut().iter_mut().enumerate() {
+                let sampled = sample_bilinear(img, channel, src_x, src_y);
+                *value = sampled.round().clamp(0.0, 255.0) as u8;
+            }
+            pixel
+        })
+    }
+
+    /// Randomly add speckle, gaussian and salt-and-pepper noise to the merged image according to
+    /// the config, each with its own probability, to model the noise of a camera sensor.
+    pub fn apply_noise<P>(&self, img: ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        self.apply_noise_recorded(img, &mut vec![])
+    }
+
+    /// `apply_noise`, pushing the noise that fires to `applied`. The noise never moves a pixel,
+    /// so there is no transform to record.
+    pub fn apply_noise_recorded<P>(
+        &self,
+        img: ImageBuffer<P, Vec<u8>>,
+        applied: &mut Vec<AppliedEffect>,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.speckle_noise_prob {
+            let sigma = self.speckle_noise_sigma.sample().max(0.0);
+            applied.push(AppliedEffect::new("speckle_noise", vec![("sigma", sigma)]));
+            Self::speckle_noise(img, sigma)
+        } else {
+            img
+        };
+
+        let img = if UNIFORM_0_1.sample(&mut effect_rng()) < self.gaussian_noise_prob {
+            let sigma = self.gaussian_noise_sigma.sample().max(0.0);
+            applied.push(AppliedEffect::new("gaussian_noise", vec![("sigma", sigma)]));
+            Self::gaussian_noise(img, sigma)
+        } else {
+            img
+        };
+
+        if UNIFORM_0_1.sample(&mut effect_rng()) < self.salt_pepper_prob {
+            let amount = self.salt_pepper_amount.sample().clamp(0.0, 1.0);
+            applied.push(AppliedEffect::new(
+                "salt_pepper_noise",
+                vec![("amount", amount)],
+            ));
+            Self::salt_pepper_noise(img, amount)
+        } else {
+            img
+        }
+    }
+
+    /// Add zero-mean gaussian noise of standard deviation `sigma` (in gray levels) to every
+    /// channel of every pixel independently.
+    pub fn gaussian_noise<P>(
+        mut img: ImageBuffer<P, Vec<u8>>,
+        sigma: f64,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let normal = match Normal::new(0.0, sigma) {
+            Ok(normal) => normal,
+            Err(_) => return img,
+        };
+        let mut rng = effect_rng();
+        for value in img.iter_mut() {
+            *value = (*value as f64 + normal.sample(&mut rng))
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+
+        img
+    }
+
+    /// Multiply every channel of every pixel by 1 plus zero-mean gaussian noise of standard
+    /// deviation `sigma`, so that the noise grows with the brightness.
+    pub fn speckle_noise<P>(mut img: ImageBuffer<P, Vec<u8>>, sigma: f64) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let normal = match Normal::new(0.0, sigma) {
+            Ok(normal) => normal,
+            Err(_) => return img,
+        };
+        let mut rng = effect_rng();
+        for value in img.iter_mut() {
+            *value = (*value as f64 * (1.0 + normal.sample(&mut rng)))
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+
+        img
+    }
+
+    /// Turn the fraction `amount` of the pixels black or white, half of them each on average.
+    pub fn salt_pepper_noise<P>(
+        mut img: ImageBuffer<P, Vec<u8>>,
+        amount: f64,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let amount = amount.clamp(0.0, 1.0);
+        let mut rng = effect_rng();
+        for pixel in img.pixels_mut() {
+            if rng.gen_bool(amount) {
+                let value = if rng.gen_bool(0.5) { 255 } else { 0 };
+                pixel.channels_mut().fill(value);
+            }
+        }
+
+        img
+    }
+
+    /// Project the image onto a cylinder, as a page bending towards the spine of a book.
+    ///
+    /// `strength` is the fraction of the width (0 to 1) that is curved. The curved part is
+    /// compressed more and more towards the spine edge, squeezed vertically and shaded darker.
+    pub fn cylinder_warp<P>(
+        img: &ImageBuffer<P, Vec<u8>>,
+        strength: f64,
+        spine_on_left: bool,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        Self::cylinder_warp_with(img, strength, spine_on_left, true)
+    }
+
+    /// `cylinder_warp`, shading the curved part only with `shaded`.
+    fn cylinder_warp_with<P>(
+        img: &ImageBuffer<P, Vec<u8>>,
+        strength: f64,
+        spine_on_left: bool,
+        shaded: bool,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let (width, height) = (img.width(), img.height());
+        let strength = strength.clamp(0.0, 1.0);
+        if strength == 0.0 || width == 0 || height == 0 {
+            return img.clone();
+        }
+
+        let (width_f, height_f) = (width as f64, height as f64);
+        // the curved part covers `radius` pixels of the output and a quarter of the cylinder,
+        // i.e. `radius * pi / 2` pixels of the flat page
+        let radius = strength * width_f;
+        let flat_width = width_f - radius;
+        let page_width = flat_width + radius * std::f64::consts::FRAC_PI_2;
+        let page_scale = width_f / page_width;
+        let center_y = (height_f - 1.0) * 0.5;
+
+        ImageBuffer::from_fn(width, height, |x, y| {
+            // distance to the edge opposite to the spine
+            let dist = if spine_on_left {
+                width_f - 1.0 - x as f64
+            } else {
+                x as f64
+            };
+
+            let (page_x, theta) = if dist <= flat_width {
+                (dist, 0.0)
+            } else {
+                let theta = ((dist - flat_width) / radius).min(1.0).asin();
+                (flat_width + radius * theta, theta)
+            };
+            let cos = theta.cos();
+
+            let page_x = page_x * page_scale;
+            let src_x = if spine_on_left {
+                width_f - 1.0 - page_x
+            } else {
+                page_x
+            };
+            // the receding page looks shorter
+            let squeeze = 1.0 - 0.2 * strength * (1.0 - cos);
+            let src_y = center_y + (y as f64 - center_y) / squeeze;
+
+            let shade = if shaded { 1.0 - 0.6 * (1.0 - cos) } else { 1.0 };
+            let mut pixel = *img.get_pixel(0, 0);
+            for (channel, value) in pixel.channels_mut().iter_mut().enumerate() {
+                let sampled = sample_bilinear(img, channel, src_x, src_y) * shade;
+                *value = sampled.round().clamp(0.0, 255.0) as u8;
+            }
+            pixel
+        })
+    }
+}
+
+/// 0 where the pixel is darker than the middle gray, 255 elsewhere.
+fn binarize(img: &mut GrayImage) {
+    for pixel in img.pixels_mut() {
+        pixel.0[0] = if pixel.0[0] < 128 { 0 } else { 255 };
+    }
 }
 
+/// Bilinear interpolation of one channel, coordinates outside of the image are clamped to the
+/// border.
+fn sample_bilinear<P>(img: &ImageBuffer<P, Vec<u8>>, channel: usize, x: f64, y: f64) -> f64
+where
+    P: Pixel<Subpixel = u8>,
+{
+    let max_x = (img.width() - 1) as f64;
+    let max_y = (img.height() - 1) as f64;
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    let (x0, y0) = (x.floor(), y.floor());
+    let (x1, y1) = ((x0 + 1.0).min(max_x), (y0 + 1.0).min(max_y));
+    let (dx, dy) = (x - x0, y - y0);
+
+    let pixel = |px: f64, py: f64| img.get_pixel(px as u32, py as u32).channels()[channel] as f64;
+    let top = pixel(x0, y0) * (1.0 - dx) + pixel(x1, y0) * dx;
+    let bottom = pixel(x0, y1) * (1.0 - dx) + pixel(x1, y1) * dx;
+
+    top * (1.0 - dy) + bottom * dy
+}
+
+/// Hue in degrees, saturation and value in [0, 1].
+fn rgb_to_hsv(pixel: Rgb<u8>) -> (f64, f64, f64) {
+    let [r, g, b] = pixel.0.map(|each| each as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+
+    (h, s, max)
+}
+
+pub(crate) fn hsv_to_rgb(h: f64, s: f64, v: f64) -> Rgb<u8> {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    Rgb([r, g, b].map(|each| ((each + m) * 255.0).round().clamp(0.0, 255.0) as u8))
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl CvUtil {
+    #[pyo3(name = "apply_color_jitter")]
+    pub fn apply_color_jitter_py<'py>(
+        &self,
+        img: PyReadonlyArray3<'py, u8>,
+        _py: Python<'py>,
+    ) -> &'py PyArray3<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = RgbImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to RgbImage");
+
+        let res = self.apply_color_jitter(img);
+        let [height_after, width_after] = [res.height() as usize, res.width() as usize];
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([height_after, width_after, 3]).unwrap();
+
+        reshape_py
+    }
+
+    #[classmethod]
+    #[pyo3(name = "hsv_jitter")]
+    pub fn hsv_jitter_py<'py>(
+        _cls: &PyType,
+        img: PyReadonlyArray3<'py, u8>,
+        hue_shift: f64,
+        saturation_scale: f64,
+        value_scale: f64,
+        _py: Python<'py>,
+    ) -> &'py PyArray3<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = RgbImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to RgbImage");
+
+        let res = Self::hsv_jitter(&img, hue_shift, saturation_scale, value_scale);
+        let [height_after, width_after] = [res.height() as usize, res.width() as usize];
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([height_after, width_after, 3]).unwrap();
+
+        reshape_py
+    }
+
     #[pyo3(name = "apply_effect")]
     pub fn apply_effect_py<'py>(
         &self,
@@ -386,6 +1399,50 @@ impl CvUtil {
         reshape_py
     }
 
+    #[classmethod]
+    #[pyo3(name = "erode")]
+    pub fn erode_py<'py>(
+        _cls: &PyType,
+        img: PyReadonlyArray2<'py, u8>,
+        width: u32,
+        height: u32,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to GrayImage");
+
+        let res = Self::erode(&img, width, height);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape[0], shape[1]]).unwrap();
+
+        reshape_py
+    }
+
+    #[classmethod]
+    #[pyo3(name = "dilate")]
+    pub fn dilate_py<'py>(
+        _cls: &PyType,
+        img: PyReadonlyArray2<'py, u8>,
+        width: u32,
+        height: u32,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to GrayImage");
+
+        let res = Self::dilate(&img, width, height);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape[0], shape[1]]).unwrap();
+
+        reshape_py
+    }
+
     #[classmethod]
     #[pyo3(name = "apply_emboss")]
     pub fn apply_emboss_py<'py>(
@@ -467,6 +1524,109 @@ impl CvUtil {
         reshape_py
     }
 
+    #[pyo3(name = "apply_curvature")]
+    pub fn apply_curvature_py<'py>(
+        &self,
+        img: PyReadonlyArray2<'py, u8>,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to GrayImage");
+
+        let res = self.apply_curvature(img);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape[0], shape[1]]).unwrap();
+
+        reshape_py
+    }
+
+    #[pyo3(name = "apply_wave")]
+    pub fn apply_wave_py<'py>(
+        &self,
+        img: PyReadonlyArray2<'py, u8>,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to GrayImage");
+
+        let res = self.apply_wave(img);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape[0], shape[1]]).unwrap();
+
+        reshape_py
+    }
+
+    #[classmethod]
+    #[pyo3(name = "wave_warp", signature = (img, amplitude, wavelength, phase=0.0, shift_rows=false))]
+    pub fn wave_warp_py<'py>(
+        _cls: &PyType,
+        img: PyReadonlyArray2<'py, u8>,
+        amplitude: f64,
+        wavelength: f64,
+        phase: f64,
+        shift_rows: bool,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to GrayImage");
+
+        let res = Self::wave_warp(&img, amplitude, wavelength, phase, shift_rows);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape[0], shape[1]]).unwrap();
+
+        reshape_py
+    }
+
+    #[pyo3(name = "apply_noise")]
+    pub fn apply_noise_py<'py>(
+        &self,
+        img: PyReadonlyArray2<'py, u8>,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to GrayImage");
+
+        let res = self.apply_noise(img);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape[0], shape[1]]).unwrap();
+
+        reshape_py
+    }
+
+    #[classmethod]
+    #[pyo3(name = "cylinder_warp", signature = (img, strength, spine_on_left=true))]
+    pub fn cylinder_warp_py<'py>(
+        _cls: &PyType,
+        img: PyReadonlyArray2<'py, u8>,
+        strength: f64,
+        spine_on_left: bool,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let shape = img.shape();
+        let img = img.as_slice().expect("fail to read input `img`");
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, img.to_vec())
+            .expect("fail to cast input img to GrayImage");
+
+        let res = Self::cylinder_warp(&img, strength, spine_on_left);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape[0], shape[1]]).unwrap();
+
+        reshape_py
+    }
+
     #[classmethod]
     #[pyo3(name = "draw_box")]
     pub fn draw_box_py<'py>(
@@ -497,6 +1657,22 @@ mod test {
 
     fn create_cv_util() -> CvUtil {
         CvUtil {
+            erode_prob: 0.0,
+            erode_kernel: Random::new_uniform(2.0, 3.0),
+            dilate_prob: 0.0,
+            dilate_kernel: Random::new_uniform(2.0, 3.0),
+            ink_bleed_prob: 0.0,
+            ink_bleed_spread: Random::new_uniform(0.0, 1.0),
+            ink_bleed_sigma: Random::new_uniform(0.5, 1.0),
+            ink_bleed_noise: Random::new_uniform(10.0, 40.0),
+            occlusion_prob: 0.0,
+            occlusion_count: Random::new_uniform(1.0, 3.0),
+            occlusion_thickness: Random::new_uniform(1.0, 3.0),
+            occlusion_intensity: Random::new_uniform(0.0, 255.0),
+            table_line_prob: 0.0,
+            table_line_spacing: Random::new_uniform(2.0, 8.0),
+            table_line_thickness: Random::new_uniform(1.0, 2.0),
+            table_line_intensity: Random::new_uniform(0.0, 150.0),
             box_prob: 0.1,
             perspective_prob: 0.2,
             perspective_x: Random::new_gaussian(-15.0, 15.0),
@@ -507,6 +1683,23 @@ mod test {
             filter_prob: 0.01,
             emboss_prob: 0.4,
             sharp_prob: 0.6,
+            color_jitter_prob: 0.0,
+            hue_shift: Random::new_uniform(-10.0, 10.0),
+            saturation_scale: Random::new_uniform(0.8, 1.2),
+            value_scale: Random::new_uniform(0.8, 1.2),
+            curvature_prob: 0.0,
+            curvature_strength: Random::new_uniform(0.1, 0.4),
+            wave_prob: 0.0,
+            wave_amplitude: Random::new_uniform(0.02, 0.08),
+            wave_wavelength: Random::new_uniform(0.5, 2.0),
+            wave_phase: Random::new_uniform(0.0, 360.0),
+            wave_rows_prob: 0.0,
+            gaussian_noise_prob: 0.0,
+            gaussian_noise_sigma: Random::new_uniform(2.0, 10.0),
+            salt_pepper_prob: 0.0,
+            salt_pepper_amount: Random::new_uniform(0.001, 0.01),
+            speckle_noise_prob: 0.0,
+            speckle_noise_sigma: Random::new_uniform(0.02, 0.1),
         }
     }
 
@@ -535,6 +1728,26 @@ mod test {
         println!("warp elapsed: {}", start.elapsed().as_secs_f64());
     }
 
+    #[test]
+    fn test_warp_perspective_quad() {
+        let img = GrayImage::from_pixel(40, 10, Luma([255]));
+
+        // without rotation the image stays an upright rectangle
+        let (res, [tl, tr, br, bl]) = CvUtil::warp_perspective_quad(&img, (0., 0., 0.));
+        assert!((tl.0 - bl.0).abs() < 0.5 && (tr.0 - br.0).abs() < 0.5);
+        assert!((tl.1 - tr.1).abs() < 0.5 && (bl.1 - br.1).abs() < 0.5);
+        assert!(tl.0 < tr.0 && tl.1 < bl.1);
+        let center = ((tl.0 + br.0) / 2.0) as u32;
+        assert_eq!(
+            res.get_pixel(center, ((tl.1 + br.1) / 2.0) as u32).0[0],
+            255
+        );
+
+        // rotating around z turns the top edge
+        let (_, [tl, tr, _, _]) = CvUtil::warp_perspective_quad(&img, (0., 0., 10.));
+        assert!((tl.1 - tr.1).abs() > 1.0);
+    }
+
     #[test]
     fn test_sharp() {
         let start = Instant::now();
@@ -594,4 +1807,221 @@ mod test {
         res.save("./test-img/box.png").unwrap();
         println!("draw box elapsed: {}", start.elapsed().as_secs_f64());
     }
+
+    #[test]
+    fn test_hsv_jitter() {
+        let img = RgbImage::from_fn(4, 1, |x, _| {
+            [
+                Rgb([255, 0, 0]),
+                Rgb([0, 255, 0]),
+                Rgb([30, 60, 90]),
+                Rgb([128, 128, 128]),
+            ][x as usize]
+        });
+
+        assert_eq!(CvUtil::hsv_jitter(&img, 0.0, 1.0, 1.0), img);
+
+        let res = CvUtil::hsv_jitter(&img, 120.0, 1.0, 1.0);
+        assert_eq!(res.get_pixel(0, 0), &Rgb([0, 255, 0]));
+        assert_eq!(res.get_pixel(1, 0), &Rgb([0, 0, 255]));
+        // gray has no hue
+        assert_eq!(res.get_pixel(3, 0), &Rgb([128, 128, 128]));
+
+        let res = CvUtil::hsv_jitter(&img, 0.0, 0.0, 1.0);
+        assert_eq!(res.get_pixel(0, 0), &Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn test_cylinder_warp() {
+        let img = GrayImage::from_pixel(100, 20, Luma([200]));
+
+        assert_eq!(CvUtil::cylinder_warp(&img, 0.0, true), img);
+
+        let res = CvUtil::cylinder_warp(&img, 0.5, true);
+        assert_eq!(res.dimensions(), img.dimensions());
+        // the flat side is untouched, the spine side is shaded
+        assert_eq!(res.get_pixel(99, 10), &Luma([200]));
+        assert!(res.get_pixel(0, 10).0[0] < 150);
+
+        let res = CvUtil::cylinder_warp(&img, 0.5, false);
+        assert_eq!(res.get_pixel(0, 10), &Luma([200]));
+        assert!(res.get_pixel(99, 10).0[0] < 150);
+    }
+
+    #[test]
+    fn test_applied_effects() {
+        let mut cv_util = create_cv_util();
+        cv_util.box_prob = 0.0;
+        cv_util.perspective_prob = 0.0;
+        cv_util.blur_prob = 1.0;
+        cv_util.blur_sigma = Random::new_uniform(1.0, 1.0);
+        cv_util.filter_prob = 1.0;
+        cv_util.emboss_prob = 1.0;
+        cv_util.sharp_prob = 0.0;
+
+        let mut applied = vec![];
+        cv_util.apply_effect_recorded(GrayImage::from_pixel(20, 10, Luma([255])), &mut applied);
+        assert_eq!(
+            applied,
+            vec![
+                AppliedEffect::new("blur", vec![("sigma", 1.0)]),
+                AppliedEffect::new("emboss", vec![]),
+            ]
+        );
+        assert_eq!(applied[0].to_string(), "blur(sigma=1)");
+        assert_eq!(applied[1].to_string(), "emboss");
+    }
+    #[test]
+    fn test_morphology() {
+        // a dark vertical stroke 3 pixels wide on white
+        let img = GrayImage::from_fn(9, 5, |x, _| {
+            Luma([if (3..6).contains(&x) { 0 } else { 255 }])
+        });
+        let dark_columns =
+            |img: &GrayImage| (0..9).filter(|&x| img.get_pixel(x, 2).0[0] == 0).count();
+
+        assert_eq!(CvUtil::erode(&img, 1, 1), img);
+        assert_eq!(dark_columns(&CvUtil::erode(&img, 3, 1)), 1);
+        assert_eq!(dark_columns(&CvUtil::dilate(&img, 3, 1)), 5);
+        // an even rectangle reaches one more pixel to the right, the stroke grows to the left
+        let res = CvUtil::dilate(&img, 2, 1);
+        assert_eq!(res.get_pixel(2, 2).0[0], 0);
+        assert_eq!(res.get_pixel(6, 2).0[0], 255);
+        // a vertical element leaves a vertical stroke as is
+        assert_eq!(CvUtil::erode(&img, 1, 3), img);
+    }
+
+    #[test]
+    fn test_ink_bleed() {
+        // a dark vertical stroke 2 pixels wide on white, and a light gray dot far from it
+        let mut img = GrayImage::from_fn(30, 10, |x, _| {
+            Luma([if (10..12).contains(&x) { 0 } else { 255 }])
+        });
+        img.put_pixel(27, 5, Luma([200]));
+
+        let res = CvUtil::ink_bleed(&img, 1, 0.5, 0.0);
+        // the stroke spreads by a pixel on each side, the rest is untouched
+        assert!((0..10).all(|y| res.get_pixel(9, y).0[0] < 128 && res.get_pixel(12, y).0[0] < 128));
+        assert!((0..10).all(|y| res.get_pixel(10, y).0[0] == 0));
+        assert_eq!(res.get_pixel(27, 5).0[0], 200);
+        assert!((0..10).all(|y| res.get_pixel(3, y).0[0] == 255));
+
+        // without ink there is nothing to bleed
+        let blank = GrayImage::from_pixel(30, 10, Luma([255]));
+        assert_eq!(CvUtil::ink_bleed(&blank, 2, 1.0, 50.0), blank);
+    }
+
+    #[test]
+    fn test_occlusion() {
+        let img = GrayImage::from_pixel(20, 10, Luma([255]));
+        let occluders = [
+            (Occluder::Line((0.0, 5.0), (20.0, 5.0), 2.0), 0),
+            (Occluder::Rect(2.0, 0.0, 4.0, 2.0), 100),
+            (Occluder::Blob((15.0, 2.0), (2.0, 1.0)), 50),
+        ];
+        let res = CvUtil::occlude(&img, &occluders);
+        // the line covers the rows 4 and 5
+        assert!((0..20).all(|x| res.get_pixel(x, 4).0[0] == 0 && res.get_pixel(x, 5).0[0] == 0));
+        assert_eq!(res.get_pixel(10, 3).0[0], 255);
+        assert_eq!(res.get_pixel(10, 6).0[0], 255);
+        assert_eq!(res.get_pixel(3, 1).0[0], 100);
+        assert_eq!(res.get_pixel(4, 1).0[0], 255);
+        assert_eq!(res.get_pixel(14, 1).0[0], 50);
+        assert_eq!(res.get_pixel(14, 3).0[0], 255);
+
+        let mut cv_util = create_cv_util();
+        cv_util.box_prob = 0.0;
+        cv_util.perspective_prob = 0.0;
+        cv_util.blur_prob = 0.0;
+        cv_util.occlusion_prob = 1.0;
+        cv_util.occlusion_count = Random::new_uniform(2.0, 2.0);
+        let mut applied = vec![];
+        let res = cv_util.apply_effect_recorded(img.clone(), &mut applied);
+        assert_eq!(
+            applied,
+            vec![AppliedEffect::new("occlusion", vec![("count", 2.0)])]
+        );
+        assert_eq!(res.dimensions(), img.dimensions());
+    }
+
+    #[test]
+    fn test_table_lines() {
+        let mut cv_util = create_cv_util();
+        cv_util.table_line_thickness = Random::new_uniform(1.0, 1.0);
+        cv_util.table_line_intensity = Random::new_uniform(0.0, 0.0);
+        let img = GrayImage::from_pixel(100, 20, Luma([255]));
+        for _ in 0..20 {
+            let lines = cv_util.random_table_lines(100, 20);
+            let res = CvUtil::occlude(&img, &lines);
+            let dark = |x: u32, y: u32| res.get_pixel(x, y).0[0] == 0;
+            // a ruled line, at least half as long as the image, close to the top or the bottom
+            let ruled: Vec<_> = (0..20)
+                .filter(|&y| (0..100).filter(|&x| dark(x, y)).count() >= 50)
+                .collect();
+            assert!(!ruled.is_empty());
+            assert!(ruled.iter().all(|&y| y <= 2 || y >= 17));
+        }
+    }
+
+    #[test]
+    fn test_wave_warp() {
+        let mut img = GrayImage::new(40, 20);
+        for x in 0..40 {
+            img.put_pixel(x, 10, Luma([255]));
+        }
+
+        assert_eq!(CvUtil::wave_warp(&img, 0.0, 20.0, 0.0, false), img);
+
+        // the line follows the wave: up by 3 at a quarter of the wavelength, down at 3 quarters
+        let res = CvUtil::wave_warp(&img, 3.0, 20.0, 0.0, false);
+        assert_eq!(res.get_pixel(0, 10).0[0], 255);
+        assert_eq!(res.get_pixel(5, 13).0[0], 255);
+        assert_eq!(res.get_pixel(5, 10).0[0], 0);
+        assert_eq!(res.get_pixel(15, 7).0[0], 255);
+
+        // shifting rows leaves a horizontal line in place
+        let res = CvUtil::wave_warp(&img, 3.0, 20.0, 90.0, true);
+        assert_eq!(res.get_pixel(20, 10).0[0], 255);
+        assert_eq!(
+            Transform::Wave(3.0, 20.0, 0.0, false).apply(&img),
+            CvUtil::wave_warp(&img, 3.0, 20.0, 0.0, false)
+        );
+    }
+
+    #[test]
+    fn test_noise() {
+        let img = GrayImage::from_pixel(100, 100, Luma([128]));
+        assert_eq!(CvUtil::gaussian_noise(img.clone(), 0.0), img);
+        assert_eq!(CvUtil::salt_pepper_noise(img.clone(), 0.0), img);
+
+        let res = CvUtil::gaussian_noise(img.clone(), 10.0);
+        let mean = res.iter().map(|&each| each as f64).sum::<f64>() / 10000.0;
+        assert!((mean - 128.0).abs() < 1.0);
+        assert!(res.iter().any(|&each| each != 128));
+
+        // the speckle is multiplicative, black stays black
+        let black = GrayImage::new(10, 10);
+        assert_eq!(CvUtil::speckle_noise(black.clone(), 0.5), black);
+
+        let rgb = RgbImage::from_pixel(100, 100, Rgb([128, 128, 128]));
+        let res = CvUtil::salt_pepper_noise(rgb, 1.0);
+        assert!(res
+            .pixels()
+            .all(|pixel| *pixel == Rgb([0, 0, 0]) || *pixel == Rgb([255, 255, 255])));
+
+        let mut cv_util = create_cv_util();
+        cv_util.salt_pepper_prob = 1.0;
+        cv_util.salt_pepper_amount = Random::new_uniform(0.5, 0.5);
+        let mut applied = vec![];
+        let res = cv_util.apply_noise_recorded(img, &mut applied);
+        assert_eq!(
+            applied,
+            vec![AppliedEffect::new(
+                "salt_pepper_noise",
+                vec![("amount", 0.5)]
+            )]
+        );
+        let noisy = res.iter().filter(|&&each| each != 128).count();
+        assert!((4000..6000).contains(&noisy));
+    }
 }
@@ -0,0 +1,281 @@
+//! Writing samples straight into a text recognition dataset, in the label format of PaddleOCR
+//! and of the ICDAR recognition tasks: the images go into `<dir>/images`, and each sample
+//! appends a `images/<name>\t<label>` line to `<dir>/train.txt`, or to `<dir>/val.txt` for the
+//! validation split.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use image::DynamicImage;
+#[cfg(feature = "python")]
+use numpy::PyReadonlyArrayDyn;
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, pyclass, pymethods, PyResult};
+
+use crate::metadata::{encode_jpeg, encode_png, ImageMetadata};
+#[cfg(feature = "python")]
+use crate::utils::array_to_image;
+
+pub const IMAGE_DIR: &str = "images";
+pub const TRAIN_LABEL_FILE: &str = "train.txt";
+pub const VAL_LABEL_FILE: &str = "val.txt";
+
+/// A destination of generated samples. Encoding does not touch the destination, so the samples
+/// can be encoded on several threads and then appended one by one.
+pub trait SampleWriter: Sync {
+    /// Encode the image in the format of the destination, with the metadata embedded if the
+    /// writer is created with `embed_metadata`.
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        metadata: Option<&ImageMetadata>,
+    ) -> Result<Vec<u8>, String>;
+
+    /// Store an encoded image with its label, returns where it is stored.
+    fn append(&mut self, encoded: &[u8], label: &str) -> Result<String, String>;
+
+    fn flush(&mut self) -> Result<(), String>;
+
+    /// `encode` and `append`.
+    fn write(
+        &mut self,
+        img: &DynamicImage,
+        label: &str,
+        metadata: Option<&ImageMetadata>,
+    ) -> Result<String, String> {
+        let encoded = self.encode(img, metadata)?;
+        self.append(&encoded, label)
+    }
+}
+
+/// The normalized image extension, `png` or `jpg`.
+pub(crate) fn parse_extension(extension: &str) -> Result<&'static str, String> {
+    match extension.to_lowercase().as_str() {
+        "png" => Ok("png"),
+        "jpg" | "jpeg" => Ok("jpg"),
+        _ => Err("`extension` should be `png` or `jpg`".to_string()),
+    }
+}
+
+/// Encode as PNG, or as JPEG of quality 95, according to the extension from `parse_extension`.
+pub(crate) fn encode_image(
+    img: &DynamicImage,
+    extension: &str,
+    metadata: Option<&ImageMetadata>,
+) -> Result<Vec<u8>, String> {
+    match extension {
+        "png" => encode_png(img, metadata),
+        _ => encode_jpeg(img, 95, metadata),
+    }
+}
+
+#[cfg_attr(feature = "python", pyclass)]
+pub struct DatasetWriter {
+    dir: PathBuf,
+    // "png" or "jpg"
+    extension: String,
+    // fraction of the samples that go to the validation split
+    val_ratio: f64,
+    embed_metadata: bool,
+    train: BufWriter<File>,
+    val: Option<BufWriter<File>>,
+    // samples in the dataset, including the ones written by earlier runs
+    count: usize,
+}
+
+impl DatasetWriter {
+    /// Open the dataset in `dir`, creating it if needed. Samples are appended after the ones
+    /// already in the label files, so that a dataset can be extended over several runs.
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        extension: &str,
+        val_ratio: f64,
+        embed_metadata: bool,
+    ) -> Result<Self, String> {
+        let extension = parse_extension(extension)?;
+        if !(0.0..=1.0).contains(&val_ratio) {
+            return Err("`val_ratio` should be between 0 and 1".to_string());
+        }
+
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join(IMAGE_DIR))
+            .map_err(|err| format!("fail to create `{}`: {}", dir.display(), err))?;
+
+        let count = [TRAIN_LABEL_FILE, VAL_LABEL_FILE]
+            .iter()
+            .map(|name| {
+                fs::read_to_string(dir.join(name))
+                    .map(|data| data.lines().filter(|line| !line.is_empty()).count())
+                    .unwrap_or(0)
+            })
+            .sum();
+        let open = |name: &str| {
+            let path = dir.join(name);
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map(BufWriter::new)
+                .map_err(|err| format!("fail to open `{}`: {}", path.display(), err))
+        };
+        let train = open(TRAIN_LABEL_FILE)?;
+        let val = if val_ratio > 0.0 {
+            Some(open(VAL_LABEL_FILE)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            dir,
+            extension: extension.to_string(),
+            val_ratio,
+            embed_metadata,
+            train,
+            val,
+            count,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The samples are split evenly: sample `index` goes to the validation split whenever
+    /// `index * val_ratio` crosses an integer.
+    fn is_val(&self, index: usize) -> bool {
+        ((index + 1) as f64 * self.val_ratio).floor() > (index as f64 * self.val_ratio).floor()
+    }
+}
+
+impl SampleWriter for DatasetWriter {
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        metadata: Option<&ImageMetadata>,
+    ) -> Result<Vec<u8>, String> {
+        encode_image(
+            img,
+            &self.extension,
+            metadata.filter(|_| self.embed_metadata),
+        )
+    }
+
+    /// Save the image and append its label line, returns the path of the image relative to the
+    /// dataset directory as written in the label file.
+    fn append(&mut self, encoded: &[u8], label: &str) -> Result<String, String> {
+        let name = format!("{}/{:09}.{}", IMAGE_DIR, self.count, self.extension);
+        let path = self.dir.join(&name);
+        fs::write(&path, encoded)
+            .map_err(|err| format!("fail to write `{}`: {}", path.display(), err))?;
+
+        let is_val = self.is_val(self.count);
+        let writer = match &mut self.val {
+            Some(val) if is_val => val,
+            _ => &mut self.train,
+        };
+        writeln!(writer, "{}\t{}", name, escape_label(label))
+            .map_err(|err| format!("fail to write the label of `{}`: {}", name, err))?;
+        self.count += 1;
+
+        Ok(name)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.train
+            .flush()
+            .and_then(|_| self.val.as_mut().map_or(Ok(()), Write::flush))
+            .map_err(|err| format!("fail to write the label files: {}", err))
+    }
+}
+
+/// Tabs and line breaks would break the label file, they are written as spaces.
+fn escape_label(label: &str) -> String {
+    label.replace(['\t', '\r', '\n'], " ")
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl DatasetWriter {
+    #[new]
+    #[pyo3(signature = (dir, extension="png", val_ratio=0.0, embed_metadata=false))]
+    fn py_new(dir: &str, extension: &str, val_ratio: f64, embed_metadata: bool) -> PyResult<Self> {
+        Self::new(dir, extension, val_ratio, embed_metadata).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "__len__")]
+    fn py_len(&self) -> usize {
+        self.len()
+    }
+
+    #[pyo3(name = "write", signature = (image, label, seed=None))]
+    fn py_write(
+        &mut self,
+        image: PyReadonlyArrayDyn<'_, u8>,
+        label: &str,
+        seed: Option<u64>,
+    ) -> PyResult<String> {
+        let img = array_to_image(&image)?;
+        let metadata = ImageMetadata {
+            label: label.to_string(),
+            seed,
+            ..Default::default()
+        };
+
+        self.write(&img, label, Some(&metadata))
+            .map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "flush")]
+    fn py_flush(&mut self) -> PyResult<()> {
+        self.flush().map_err(PyValueError::new_err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::GrayImage;
+
+    use super::*;
+
+    #[test]
+    fn test_dataset_writer() {
+        let dir = std::env::temp_dir().join("tig_dataset_writer_test");
+        let _ = fs::remove_dir_all(&dir);
+        let img = DynamicImage::ImageLuma8(GrayImage::new(4, 2));
+
+        let mut writer = DatasetWriter::new(&dir, "png", 0.5, false).unwrap();
+        for label in ["一", "二\t三", "四"] {
+            writer.write(&img, label, None).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let train = fs::read_to_string(dir.join(TRAIN_LABEL_FILE)).unwrap();
+        let val = fs::read_to_string(dir.join(VAL_LABEL_FILE)).unwrap();
+        assert_eq!(
+            train,
+            "images/000000000.png\t一\nimages/000000002.png\t四\n"
+        );
+        assert_eq!(val, "images/000000001.png\t二 三\n");
+        assert!(dir.join("images/000000002.png").exists());
+
+        // a second run continues after the existing samples
+        let mut writer = DatasetWriter::new(&dir, "jpg", 0.0, false).unwrap();
+        assert_eq!(writer.len(), 3);
+        let name = writer.write(&img, "五", None).unwrap();
+        assert_eq!(name, "images/000000003.jpg");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 use rand::distributions::Distribution;
 
+use crate::rng::effect_rng;
+
 #[derive(Clone, Copy, Debug)]
 pub enum Random {
     Uniform(rand::distributions::Uniform<f64>),
@@ -26,9 +28,9 @@ impl Random {
 
     pub fn sample(&self) -> f64 {
         match self {
-            Random::Uniform(s) => s.sample(&mut rand::thread_rng()),
+            Random::Uniform(s) => s.sample(&mut effect_rng()),
             Random::Gaussian((min_val, max_val, s)) => {
-                let mut val = s.sample(&mut rand::thread_rng());
+                let mut val = s.sample(&mut effect_rng());
                 if val < *min_val {
                     val = *min_val
                 }
@@ -0,0 +1,331 @@
+//! C-compatible interface, so that the generator can be embedded without the Python runtime.
+//! See `include/text_image_generator.h` for the declarations.
+//!
+//! Every sample is a random line from the Chinese character file, rendered with the cv effects
+//! and merged onto a random background, like `apply_effect=True` on the Python side.
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+    sync::Arc,
+};
+
+use cosmic_text::{Attrs, Buffer, BufferLine, Color, Family, FontSystem, Metrics, SwashCache};
+use image::GrayImage;
+use indexmap::IndexMap;
+use rand_distr::WeightedAliasIndex;
+
+use crate::{
+    builder::GeneratorBuilder,
+    corpus::get_random_chinese_text_with_font_list,
+    cv_util::CvUtil,
+    font_util::FontUtil,
+    image_process::{
+        build_line, generate_image, CharColors, FauxStyle, GlyphJitter, InkTexture, Kerning,
+        LineMetrics, LineStyling, LineWidth, Padding, RenderOptions, Spacing, VerticalLayout,
+    },
+    merge_util::{BgFactory, MergeUtil},
+    normalize::LabelNormalizer,
+    parse_config::Config,
+    utils::InternalAttrsOwned,
+};
+
+pub const TIG_OK: i32 = 0;
+pub const TIG_INVALID_ARGUMENT: i32 = -1;
+pub const TIG_BUFFER_TOO_SMALL: i32 = -2;
+pub const TIG_PANIC: i32 = -3;
+
+pub struct TigGenerator {
+    font_system: FontSystem,
+    font_util: FontUtil,
+    editor_buffer: Buffer,
+    swash_cache: SwashCache,
+    chinese_ch_dict: IndexMap<String, Vec<InternalAttrsOwned>>,
+    chinese_ch_weights: WeightedAliasIndex<f64>,
+    main_font_list: Vec<String>,
+    options: RenderOptions,
+    line_metrics: LineMetrics,
+    line_width: LineWidth,
+    line_styling: LineStyling,
+    ligatures: bool,
+    label_normalizer: LabelNormalizer,
+    pub(crate) cv_util: CvUtil,
+    pub(crate) merge_util: MergeUtil,
+    bg_factory: BgFactory,
+    pub(crate) last_image: Option<GrayImage>,
+    pub(crate) last_label: CString,
+    pub(crate) last_raw_label: CString,
+}
+
+impl TigGenerator {
+    /// Returns an error if the config or one of the files it names cannot be loaded.
+    pub(crate) fn new(config_path: &str) -> Result<Self, String> {
+        let config = Config::from_yaml_with(config_path, None, &[])?;
+        let mut builder = GeneratorBuilder::new(&config)?;
+        // the cache only saves time, failing to write it is not an error
+        let _ = builder.save_coverage_cache();
+        let font_util = builder.font_util(&config)?;
+        let chinese_ch_weights = WeightedAliasIndex::new(builder.chinese_ch_weights(&config))
+            .map_err(|err| format!("invalid character frequencies: {}", err))?;
+
+        let mut font_system = builder.font_system;
+        let mut buffer = Buffer::new(
+            &mut font_system,
+            Metrics::new(config.font_size as f32, config.line_height as f32),
+        );
+        buffer.set_size(
+            &mut font_system,
+            config.font_img_width as f32,
+            config.font_img_height as f32,
+        );
+        let image_glyphs = builder.image_glyphs;
+
+        Ok(Self {
+            font_util,
+            font_system,
+            editor_buffer: buffer,
+            swash_cache: SwashCache::new(),
+            chinese_ch_dict: builder.chinese_ch_dict,
+            chinese_ch_weights,
+            main_font_list: builder.main_font_list,
+            options: RenderOptions {
+                features: builder.opentype_features,
+                kerning: Kerning {
+                    enabled: config.kerning,
+                    jitter: config.kerning_jitter,
+                },
+                ink_texture: InkTexture {
+                    strength: config.ink_texture_strength,
+                    scale: config.ink_texture_scale,
+                },
+                padding: Padding::default(),
+                glyph_size_jitter: config.glyph_size_jitter,
+                image_glyphs: (!image_glyphs.is_empty()).then(|| Arc::new(image_glyphs)),
+                char_colors: CharColors::from_config(&config),
+                vertical: VerticalLayout::from_config(&config),
+                faux_style: FauxStyle::default(),
+                spacing: Spacing::default(),
+                glyph_jitter: GlyphJitter::from_config(&config),
+                text_path: None,
+                drop_shadow: None,
+                decoration: None,
+            },
+            line_metrics: LineMetrics::from_config(&config),
+            line_width: LineWidth::from_config(&config),
+            line_styling: builder.line_styling,
+            ligatures: config.ligatures,
+            label_normalizer: builder.label_normalizer,
+            cv_util: CvUtil::from_config(&config),
+            merge_util: MergeUtil::from_config(&config),
+            bg_factory: BgFactory::new(&config.bg_dir, config.bg_height, config.bg_width),
+            last_image: None,
+            last_label: CString::default(),
+            last_raw_label: CString::default(),
+        })
+    }
+
+    pub(crate) fn generate(&mut self, min: u32, max: u32) {
+        let text_with_font_list = get_random_chinese_text_with_font_list::<_, String, _>(
+            &self.chinese_ch_dict,
+            &self.chinese_ch_weights,
+            None,
+            min..=max,
+        );
+        let label: String = text_with_font_list.iter().map(|(ch, _)| *ch).collect();
+
+        let attrs = Attrs::new().family(Family::Name("Gandhari Unicode"));
+        let res = self
+            .font_util
+            .map_chinese_corpus_with_attrs(&text_with_font_list, &self.main_font_list);
+        self.options.padding = self
+            .line_metrics
+            .sample(&mut self.editor_buffer, &mut self.font_system);
+        let font_size = self.editor_buffer.metrics().font_size;
+        self.line_styling.sample(&mut self.options, font_size);
+        self.line_width
+            .prepare(&mut self.editor_buffer, &mut self.font_system, None);
+        if let Some(vertical) = &self.options.vertical {
+            vertical.set_lines(
+                &mut self.editor_buffer,
+                &mut self.font_system,
+                res,
+                attrs,
+                self.ligatures,
+            );
+        } else {
+            let (line_text, attrs_list) = build_line(res, attrs, self.ligatures);
+            self.editor_buffer.lines.clear();
+            self.editor_buffer.lines.push(BufferLine::new(
+                &line_text,
+                attrs_list,
+                cosmic_text::Shaping::Advanced,
+            ));
+            self.editor_buffer
+                .shape_until_scroll(&mut self.font_system, false);
+            self.line_width
+                .fit(&mut self.editor_buffer, &mut self.font_system, None);
+        }
+
+        let (img, _) = generate_image(
+            &mut self.editor_buffer,
+            &mut self.font_system,
+            &mut self.swash_cache,
+            Color::rgb(0, 0, 0),
+            image::Rgb([255, 255, 255]),
+            &self.options,
+        );
+
+        let gray = image::imageops::grayscale(&img);
+        let font_img = self.cv_util.apply_effect(gray);
+        let bg_img = self.merge_util.random_background(&self.bg_factory);
+
+        let merged = self.merge_util.poisson_edit(&font_img, &bg_img);
+        let merged = self.cv_util.apply_curvature(merged);
+        let merged = self.cv_util.apply_wave(merged);
+        self.last_image = Some(self.cv_util.apply_noise(merged));
+        // the label comes from the character file, which never contains NUL
+        self.last_label = CString::new(self.label_normalizer.normalize(&label)).unwrap_or_default();
+        self.last_raw_label = CString::new(label).unwrap_or_default();
+    }
+
+    /// Copy the last image into the buffer.
+    unsafe fn copy_last_image(
+        &self,
+        buf: *mut u8,
+        buf_len: usize,
+        width: *mut u32,
+        height: *mut u32,
+    ) -> i32 {
+        let img = match &self.last_image {
+            Some(img) => img,
+            None => return TIG_INVALID_ARGUMENT,
+        };
+        if !width.is_null() {
+            *width = img.width();
+        }
+        if !height.is_null() {
+            *height = img.height();
+        }
+
+        let raw = img.as_raw();
+        if buf.is_null() || buf_len < raw.len() {
+            return TIG_BUFFER_TOO_SMALL;
+        }
+        slice::from_raw_parts_mut(buf, raw.len()).copy_from_slice(raw);
+
+        TIG_OK
+    }
+}
+
+/// Create a generator from a config file. Returns NULL if the config or one of the files it
+/// names cannot be loaded, or a value of the config is invalid.
+///
+/// # Safety
+///
+/// `config_path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tig_generator_new(config_path: *const c_char) -> *mut TigGenerator {
+    if config_path.is_null() {
+        return ptr::null_mut();
+    }
+    let config_path = match CStr::from_ptr(config_path).to_str() {
+        Ok(path) => path,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    match catch_unwind(|| TigGenerator::new(config_path)) {
+        Ok(Ok(generator)) => Box::into_raw(Box::new(generator)),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `generator` must come from `tig_generator_new` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tig_generator_free(generator: *mut TigGenerator) {
+    if !generator.is_null() {
+        drop(Box::from_raw(generator));
+    }
+}
+
+/// Generate a grayscale sample of `min_len` to `max_len` characters and copy it into `buf`
+/// row by row. The size of the image is written to `width` and `height`. If the buffer is too
+/// small, `TIG_BUFFER_TOO_SMALL` is returned and the sample is kept, so that it can be fetched
+/// with `tig_copy_last_image`.
+///
+/// # Safety
+///
+/// `generator` must come from `tig_generator_new`, `buf` must be valid for `buf_len` bytes,
+/// `width` and `height` must be valid or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn tig_generate(
+    generator: *mut TigGenerator,
+    min_len: u32,
+    max_len: u32,
+    buf: *mut u8,
+    buf_len: usize,
+    width: *mut u32,
+    height: *mut u32,
+) -> i32 {
+    let generator = match generator.as_mut() {
+        Some(generator) => generator,
+        None => return TIG_INVALID_ARGUMENT,
+    };
+    if min_len == 0 || min_len > max_len {
+        return TIG_INVALID_ARGUMENT;
+    }
+
+    match catch_unwind(AssertUnwindSafe(|| generator.generate(min_len, max_len))) {
+        Ok(()) => generator.copy_last_image(buf, buf_len, width, height),
+        Err(_) => TIG_PANIC,
+    }
+}
+
+/// Copy the last generated sample into `buf`, see `tig_generate`.
+///
+/// # Safety
+///
+/// Same as `tig_generate`.
+#[no_mangle]
+pub unsafe extern "C" fn tig_copy_last_image(
+    generator: *const TigGenerator,
+    buf: *mut u8,
+    buf_len: usize,
+    width: *mut u32,
+    height: *mut u32,
+) -> i32 {
+    match generator.as_ref() {
+        Some(generator) => generator.copy_last_image(buf, buf_len, width, height),
+        None => TIG_INVALID_ARGUMENT,
+    }
+}
+
+/// The UTF-8 label of the last generated sample, normalized according to the config. The
+/// string is owned by the generator and stays valid until the next call to `tig_generate` or
+/// `tig_generator_free`.
+///
+/// # Safety
+///
+/// `generator` must come from `tig_generator_new`.
+#[no_mangle]
+pub unsafe extern "C" fn tig_last_label(generator: *const TigGenerator) -> *const c_char {
+    match generator.as_ref() {
+        Some(generator) => generator.last_label.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Same as `tig_last_label`, but without normalization.
+///
+/// # Safety
+///
+/// `generator` must come from `tig_generator_new`.
+#[no_mangle]
+pub unsafe extern "C" fn tig_last_raw_label(generator: *const TigGenerator) -> *const c_char {
+    match generator.as_ref() {
+        Some(generator) => generator.last_raw_label.as_ptr(),
+        None => ptr::null(),
+    }
+}
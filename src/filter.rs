@@ -0,0 +1,191 @@
+//! Unicode-range and regex filters applied to the character file and the Latin corpus when
+//! they are loaded.
+
+use std::ops::RangeInclusive;
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::parse_config::Config;
+
+#[derive(Clone, Debug, Default)]
+pub struct CharFilter {
+    include_ranges: Vec<RangeInclusive<u32>>,
+    exclude_ranges: Vec<RangeInclusive<u32>>,
+    include_regex: Option<Regex>,
+    exclude_regex: Option<Regex>,
+}
+
+/// Parse a range of code points like `4E00-9FFF`, `U+3000` or `U+E000-U+F8FF`.
+pub fn parse_range(range: &str) -> Result<RangeInclusive<u32>, String> {
+    let parse = |value: &str| {
+        let value = value.trim();
+        let value = value
+            .strip_prefix("U+")
+            .or_else(|| value.strip_prefix("u+"))
+            .unwrap_or(value);
+        u32::from_str_radix(value, 16).map_err(|_| format!("invalid code point range: {}", range))
+    };
+
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => {
+            let value = parse(range)?;
+            (value, value)
+        }
+    };
+    if start > end {
+        return Err(format!("invalid code point range: {}", range));
+    }
+
+    Ok(start..=end)
+}
+
+impl CharFilter {
+    pub fn new(
+        include_ranges: &[String],
+        exclude_ranges: &[String],
+        include_regex: &str,
+        exclude_regex: &str,
+    ) -> Result<Self, String> {
+        let parse_regex = |pattern: &str| {
+            if pattern.is_empty() {
+                Ok(None)
+            } else {
+                Regex::new(pattern)
+                    .map(Some)
+                    .map_err(|err| format!("invalid regex `{}`: {}", pattern, err))
+            }
+        };
+
+        Ok(Self {
+            include_ranges: include_ranges
+                .iter()
+                .map(|range| parse_range(range))
+                .collect::<Result<_, _>>()?,
+            exclude_ranges: exclude_ranges
+                .iter()
+                .map(|range| parse_range(range))
+                .collect::<Result<_, _>>()?,
+            include_regex: parse_regex(include_regex)?,
+            exclude_regex: parse_regex(exclude_regex)?,
+        })
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        Self::new(
+            &config.include_ranges,
+            &config.exclude_ranges,
+            &config.include_regex,
+            &config.exclude_regex,
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include_ranges.is_empty()
+            && self.exclude_ranges.is_empty()
+            && self.include_regex.is_none()
+            && self.exclude_regex.is_none()
+    }
+
+    fn accepts_ch(&self, ch: char) -> bool {
+        // whitespace is never filtered out, it separates the words of the Latin corpus
+        if ch.is_whitespace() {
+            return true;
+        }
+
+        let codepoint = ch as u32;
+        let included = self.include_ranges.is_empty()
+            || self
+                .include_ranges
+                .iter()
+                .any(|range| range.contains(&codepoint));
+        let excluded = self
+            .exclude_ranges
+            .iter()
+            .any(|range| range.contains(&codepoint));
+
+        included && !excluded
+    }
+
+    /// Whether a character (or a grapheme cluster) is kept. Every code point has to be in an
+    /// included range and outside the excluded ones, and the regexes are matched against the
+    /// whole cluster.
+    pub fn accepts(&self, cluster: &str) -> bool {
+        cluster.chars().all(|ch| self.accepts_ch(ch))
+            && self
+                .include_regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(cluster))
+            && !self
+                .exclude_regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(cluster))
+    }
+
+    /// Drop the lines of a character file whose character (the first column) is rejected.
+    pub fn filter_ch_file(&self, data: &str) -> String {
+        if self.is_empty() {
+            return data.to_string();
+        }
+        data.lines()
+            .filter(|line| {
+                let ch = line.trim().split('\t').next().unwrap_or_default();
+                !ch.is_empty() && self.accepts(ch)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Drop the words of a corpus that contain a rejected cluster.
+    pub fn filter_corpus(&self, data: &str) -> String {
+        if self.is_empty() {
+            return data.to_string();
+        }
+        data.lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .filter(|word| word.graphemes(true).all(|cluster| self.accepts(cluster)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("4E00-9FFF"), Ok(0x4E00..=0x9FFF));
+        assert_eq!(parse_range("U+3000"), Ok(0x3000..=0x3000));
+        assert_eq!(parse_range("U+E000-U+F8FF"), Ok(0xE000..=0xF8FF));
+        assert!(parse_range("9FFF-4E00").is_err());
+        assert!(parse_range("xyz").is_err());
+    }
+
+    #[test]
+    fn test_char_filter() {
+        let filter = CharFilter::new(
+            &["4E00-9FFF".to_string()],
+            &["4E01".to_string()],
+            "",
+            "^乙$",
+        )
+        .unwrap();
+
+        assert_eq!(
+            filter.filter_ch_file("一\t10\n丁\t5\n乙\t3\n\u{E000}\t1"),
+            "一\t10"
+        );
+
+        let filter = CharFilter::new(&[], &["E000-F8FF".to_string()], "", "").unwrap();
+        assert_eq!(
+            filter.filter_corpus("hello w\u{E000}rld\nfoo"),
+            "hello\nfoo"
+        );
+    }
+}
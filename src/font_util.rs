@@ -1,15 +1,104 @@
-use std::fs;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs,
+    sync::{Mutex, PoisonError},
+};
 
-use cosmic_text::{Attrs, AttrsOwned, Family, FontSystem};
+use cosmic_text::{fontdb, rustybuzz, Attrs, AttrsOwned, Family, FontSystem};
 use once_cell::sync::Lazy;
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, pyclass, pymethods, PyResult};
 use rand::seq::{IteratorRandom, SliceRandom};
 use rand_distr::WeightedAliasIndex;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "python")]
+use crate::utils::FontAttrs;
 use crate::utils::InternalAttrsOwned;
+use crate::{coverage_cache::CoverageCache, parse_config::Config, rng::sample_rng};
 
+#[cfg_attr(feature = "python", pyclass)]
 pub struct FontUtil {
     font_system: FontSystem,
+    // answers of `is_font_contain_cluster` kept across runs
+    coverage_cache: Option<CoverageCache>,
+    coverages: HashMap<fontdb::ID, FontCoverage>,
+    // sampling weight of each font family, the families not listed weigh 1
+    font_weights: HashMap<String, f64>,
+}
+
+/// The codepoints a face can render, extracted once from its `cmap` table and stored as sorted
+/// inclusive ranges, so that a lookup is a binary search.
+///
+/// Only Unicode subtables count: non-Unicode ones (e.g. Mac Roman) would map a codepoint to a
+/// wrong glyph, and characters above U+FFFF are only found in format 12/13 Unicode subtables. A
+/// codepoint is decided by the first subtable mapping it, and needs a glyph with an outline.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontCoverage {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl FontCoverage {
+    pub fn from_face(face: &rustybuzz::Face) -> Self {
+        let cmap = match face.tables().cmap {
+            Some(cmap) => cmap,
+            None => return Self::default(),
+        };
+
+        let mut decided = HashSet::new();
+        let mut covered = vec![];
+        for subtable in cmap.subtables.into_iter() {
+            if !subtable.is_unicode() {
+                continue;
+            }
+            subtable.codepoints(|codepoint| {
+                let glyph_id = match subtable.glyph_index(codepoint) {
+                    Some(glyph_id) => glyph_id,
+                    None => return,
+                };
+                if decided.insert(codepoint) && face.glyph_bounding_box(glyph_id).is_some() {
+                    covered.push(codepoint);
+                }
+            });
+        }
+
+        Self::from_codepoints(covered)
+    }
+
+    pub fn from_codepoints(mut codepoints: Vec<u32>) -> Self {
+        codepoints.sort_unstable();
+        codepoints.dedup();
+
+        let mut ranges: Vec<(u32, u32)> = vec![];
+        for codepoint in codepoints {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == codepoint => *end = codepoint,
+                _ => ranges.push((codepoint, codepoint)),
+            }
+        }
+
+        Self { ranges }
+    }
+
+    pub fn contains(&self, character: char) -> bool {
+        let codepoint = character as u32;
+        let index = self.ranges.partition_point(|&(_, end)| end < codepoint);
+
+        matches!(self.ranges.get(index), Some(&(start, _)) if start <= codepoint)
+    }
+
+    /// The number of codepoints covered.
+    pub fn len(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(|(start, end)| (end - start + 1) as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
 }
 
 impl FontUtil {
@@ -19,7 +108,67 @@ impl FontUtil {
                 font_system.locale().to_string(),
                 font_system.db().clone(),
             ),
+            coverage_cache: None,
+            coverages: HashMap::new(),
+            font_weights: HashMap::new(),
+        }
+    }
+
+    /// Choose the font of a cluster from its font list by the weights of the families.
+    pub fn with_font_weights(mut self, font_weights: HashMap<String, f64>) -> Self {
+        self.font_weights = font_weights;
+        self
+    }
+
+    pub fn set_font_weights(&mut self, font_weights: HashMap<String, f64>) {
+        self.font_weights = font_weights;
+    }
+
+    fn font_weight(&self, font_name: &str) -> f64 {
+        self.font_weights.get(font_name).copied().unwrap_or(1.0)
+    }
+
+    /// A font of the list, by the weights of the families if any are set. A list whose weights
+    /// are all zero is chosen from uniformly.
+    fn choose_font<'a>(&self, fonts: &'a [InternalAttrsOwned]) -> Option<&'a InternalAttrsOwned> {
+        let mut rng = sample_rng();
+        if self.font_weights.is_empty() {
+            return fonts.choose(&mut rng);
         }
+
+        fonts
+            .choose_weighted(&mut rng, |font| match font.as_attrs().family {
+                Family::Name(name) => self.font_weight(name),
+                _ => 1.0,
+            })
+            .ok()
+            .or_else(|| fonts.choose(&mut rng))
+    }
+
+    /// Answer `is_font_contain_cluster` from the cache where possible, and record the new answers
+    /// in it.
+    pub fn with_coverage_cache(mut self, cache: CoverageCache) -> Self {
+        self.coverage_cache = Some(cache);
+        self
+    }
+
+    /// The cache given to `with_coverage_cache`, with the answers added since.
+    pub fn take_coverage_cache(&mut self) -> Option<CoverageCache> {
+        self.coverage_cache.take()
+    }
+
+    /// Load the fonts of a directory, the coverages of the faces already loaded are kept.
+    pub fn load_fonts_dir(&mut self, path: &str) {
+        self.font_system.db_mut().load_fonts_dir(path);
+    }
+
+    /// Load a font file from memory, returns whether any face was found in it.
+    pub fn load_font_data(&mut self, data: Vec<u8>) -> bool {
+        let db = self.font_system.db_mut();
+        let before = db.len();
+        db.load_font_data(data);
+
+        db.len() > before
     }
 
     pub fn get_full_font_list(&self) -> Vec<InternalAttrsOwned> {
@@ -42,6 +191,52 @@ impl FontUtil {
     }
 
     pub fn is_font_contain_ch(&mut self, font_attrs: Attrs, character: char) -> bool {
+        self.coverage(font_attrs).contains(character)
+    }
+
+    /// The codepoints the face can render, extracted on the first query of each face.
+    pub fn coverage(&mut self, font_attrs: Attrs) -> &FontCoverage {
+        let query = cosmic_text::fontdb::Query {
+            families: &[font_attrs.family],
+            weight: font_attrs.weight,
+            stretch: font_attrs.stretch,
+            style: font_attrs.style,
+        };
+        let id = self.font_system.db().query(&query).unwrap();
+        if !self.coverages.contains_key(&id) {
+            let font = self.font_system.get_font(id).unwrap();
+            self.coverages
+                .insert(id, FontCoverage::from_face(font.rustybuzz()));
+        }
+
+        &self.coverages[&id]
+    }
+
+    /// Check whether the font can render a whole grapheme cluster. Default-ignorable code
+    /// points (ZWJ, variation selectors, ...) have no outline and are not required.
+    pub fn is_font_contain_cluster(&mut self, font_attrs: Attrs, cluster: &str) -> bool {
+        if let Some(cached) = self
+            .coverage_cache
+            .as_ref()
+            .and_then(|cache| cache.get(font_attrs, cluster))
+        {
+            return cached;
+        }
+
+        let res = cluster
+            .chars()
+            .filter(|&each_ch| !is_default_ignorable(each_ch))
+            .all(|each_ch| self.is_font_contain_ch(font_attrs, each_ch));
+        if let Some(cache) = self.coverage_cache.as_mut() {
+            cache.insert(font_attrs, cluster, res);
+        }
+
+        res
+    }
+
+    /// Nominal advance width of the grapheme cluster in em, from the `hmtx` table of the font
+    /// (no shaping, so kerning and ligatures are not taken into account).
+    pub fn cluster_advance(&mut self, font_attrs: Attrs, cluster: &str) -> f32 {
         let query = cosmic_text::fontdb::Query {
             families: &[font_attrs.family],
             weight: font_attrs.weight,
@@ -51,24 +246,36 @@ impl FontUtil {
         let db = self.font_system.db();
         let id = db.query(&query).unwrap();
         let font = self.font_system.get_font(id).unwrap();
-        let codepoint = character as u32;
 
         let rustybuzz_face = font.rustybuzz();
-        let cmap = rustybuzz_face.tables().cmap.unwrap();
-        for subtable in cmap.subtables.into_iter() {
-            let glyph_id = match subtable.glyph_index(codepoint) {
-                Some(content) => content,
-                None => continue,
-            };
+        let units_per_em = rustybuzz_face.units_per_em() as f32;
+        let advance: u32 = cluster
+            .chars()
+            .filter_map(|each_ch| rustybuzz_face.glyph_index(each_ch))
+            .filter_map(|glyph_id| rustybuzz_face.glyph_hor_advance(glyph_id))
+            .map(u32::from)
+            .sum();
 
-            return matches!(rustybuzz_face.glyph_bounding_box(glyph_id), Some(_));
-        }
+        advance as f32 / units_per_em
+    }
+
+    /// All faces that can render the grapheme cluster.
+    pub fn fonts_containing(&mut self, cluster: &str) -> Vec<InternalAttrsOwned> {
+        self.get_full_font_list()
+            .into_iter()
+            .filter(|each| self.is_font_contain_cluster(each.as_attrs(), cluster))
+            .collect()
+    }
 
-        return false;
+    pub fn contains_font_name<S: AsRef<str>>(&self, font_name: S) -> bool {
+        self.font_system
+            .db()
+            .faces()
+            .any(|each| each.families.first().unwrap().0 == font_name.as_ref())
     }
 
     pub fn map_chinese_corpus_with_attrs<'a, S1, S2, V>(
-        &mut self,
+        &self,
         ch_list_with_font_name_list: &'a Vec<(S1, Option<&Vec<InternalAttrsOwned>>)>,
         main_font_list: &'a V,
     ) -> Vec<(&'a S1, Attrs<'a>)>
@@ -77,20 +284,23 @@ impl FontUtil {
         S2: AsRef<str> + 'a,
         V: AsRef<[S2]>,
     {
-        let main_font = main_font_list
-            .as_ref()
-            .choose(&mut rand::thread_rng())
-            .unwrap();
+        let main_font_list = main_font_list.as_ref();
+        let main_font = if self.font_weights.is_empty() {
+            main_font_list.choose(&mut sample_rng())
+        } else {
+            main_font_list
+                .choose_weighted(&mut sample_rng(), |name| self.font_weight(name.as_ref()))
+                .ok()
+                .or_else(|| main_font_list.choose(&mut sample_rng()))
+        }
+        .unwrap();
 
         let mut res = vec![];
 
         for (text, font_name_list) in ch_list_with_font_name_list {
             if let Some(content) = font_name_list {
                 if content.len() != 0 {
-                    res.push((
-                        text,
-                        content.choose(&mut rand::thread_rng()).unwrap().as_attrs(),
-                    ));
+                    res.push((text, self.choose_font(content).unwrap().as_attrs()));
                 } else {
                     // todo: use more elegant way to use main font
                     res.push((text, self.font_name_to_attrs(main_font)));
@@ -109,7 +319,7 @@ impl FontUtil {
             .db()
             .faces()
             .filter(|each| each.families.iter().next().unwrap().0 == font_name.as_ref())
-            .choose(&mut rand::thread_rng())
+            .choose(&mut sample_rng())
             .unwrap();
 
         Attrs::new()
@@ -119,6 +329,54 @@ impl FontUtil {
     }
 }
 
+#[cfg(feature = "python")]
+#[pymethods]
+impl FontUtil {
+    #[new]
+    fn py_new(font_dir: &str) -> Self {
+        let mut font_system = FontSystem::new();
+        font_system.db_mut().load_fonts_dir(font_dir);
+
+        FontUtil::new(&font_system)
+    }
+
+    #[pyo3(name = "get_full_font_list")]
+    fn get_full_font_list_py(&self) -> Vec<FontAttrs> {
+        self.get_full_font_list()
+            .iter()
+            .map(FontAttrs::from)
+            .collect()
+    }
+
+    #[pyo3(name = "fonts_containing")]
+    fn fonts_containing_py(&mut self, ch: &str) -> Vec<FontAttrs> {
+        self.fonts_containing(ch)
+            .iter()
+            .map(FontAttrs::from)
+            .collect()
+    }
+
+    #[pyo3(name = "font_name_to_attrs")]
+    fn font_name_to_attrs_py(&self, name: String) -> PyResult<FontAttrs> {
+        if !self.contains_font_name(&name) {
+            return Err(PyValueError::new_err(format!("font not found: {}", name)));
+        }
+        let attrs = self.font_name_to_attrs(&name);
+
+        Ok(FontAttrs::from(&InternalAttrsOwned::new(AttrsOwned::new(
+            attrs,
+        ))))
+    }
+}
+
+/// Code points that fonts usually map to an empty glyph, but that are part of a cluster.
+fn is_default_ignorable(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x034F | 0x200B..=0x200F | 0x2060..=0x2064 | 0xFE00..=0xFE0F | 0xFEFF | 0xE0100..=0xE01EF
+    )
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct FontConfig {
@@ -126,6 +384,44 @@ struct FontConfig {
     weight: f64,
 }
 
+/// The weight of each font family from a JSON file of `{"fontList": [...], "weight": ...}`
+/// groups, as in `config.json`.
+pub fn read_font_weights(path: &str) -> Result<HashMap<String, f64>, String> {
+    let data =
+        fs::read_to_string(path).map_err(|err| format!("failed to read `{}`: {}", path, err))?;
+    let groups: Vec<FontConfig> = serde_json::from_str(&data)
+        .map_err(|err| format!("failed to parse `{}`: {}", path, err))?;
+
+    Ok(groups
+        .into_iter()
+        .flat_map(|group| {
+            let weight = group.weight;
+            group.font_list.into_iter().map(move |name| (name, weight))
+        })
+        .collect())
+}
+
+/// The font weights of the config, `font_weights` over the ones of `font_weights_file_path`.
+pub fn font_weights_from_config(config: &Config) -> Result<HashMap<String, f64>, String> {
+    let mut font_weights = if !config.font_weights_file_path.is_empty() {
+        read_font_weights(&config.font_weights_file_path)?
+    } else {
+        HashMap::new()
+    };
+    font_weights.extend(config.font_weights.clone());
+    if let Some((name, _)) = font_weights
+        .iter()
+        .find(|(_, weight)| !(**weight >= 0.0 && weight.is_finite()))
+    {
+        return Err(format!(
+            "the weight of font `{}` should be non-negative",
+            name
+        ));
+    }
+
+    Ok(font_weights)
+}
+
 const FONT_CONFIG: Lazy<Vec<FontConfig>> = Lazy::new(|| {
     let data = fs::read_to_string("./config.json").unwrap();
     let font_weight: Vec<FontConfig> = serde_json::from_str(&data).unwrap();
@@ -133,6 +429,100 @@ const FONT_CONFIG: Lazy<Vec<FontConfig>> = Lazy::new(|| {
     font_weight
 });
 
+/// The attributes and the file of a loaded face, the file is empty for fonts loaded from memory.
+pub fn describe_face(
+    font_system: &FontSystem,
+    font_id: fontdb::ID,
+) -> Option<(InternalAttrsOwned, String)> {
+    let face = font_system.db().face(font_id)?;
+    let font_name = &face.families.first()?.0;
+    let attrs = Attrs::new()
+        .family(Family::Name(font_name))
+        .style(face.style)
+        .weight(face.weight)
+        .stretch(face.stretch);
+    let file = match &face.source {
+        fontdb::Source::File(path) | fontdb::Source::SharedFile(path, _) => {
+            path.display().to_string()
+        }
+        fontdb::Source::Binary(_) => String::new(),
+    };
+
+    Some((InternalAttrsOwned::new(AttrsOwned::new(attrs)), file))
+}
+
+/// For each font family, how many of the characters (with their font lists and frequencies)
+/// one of its faces covers, and which of the `common` most frequent characters none covers.
+/// The families covering the most characters come first.
+pub fn coverage_report<'a>(
+    chars: &[(&'a str, &[InternalAttrsOwned], f64)],
+    fonts: &[InternalAttrsOwned],
+    common: usize,
+) -> Vec<(String, usize, Vec<&'a str>)> {
+    let mut by_frequency: Vec<_> = chars.iter().collect();
+    by_frequency.sort_by(|a, b| b.2.total_cmp(&a.2));
+    by_frequency.truncate(common);
+
+    let mut families: Vec<String> = fonts.iter().map(|font| font.to_tuple().0).collect();
+    families.sort_unstable();
+    families.dedup();
+
+    let covers = |font_list: &[InternalAttrsOwned], family: &str| {
+        font_list.iter().any(|font| font.to_tuple().0 == family)
+    };
+    let mut res: Vec<_> = families
+        .into_iter()
+        .map(|family| {
+            let covered = chars
+                .iter()
+                .filter(|(_, font_list, _)| covers(font_list, &family))
+                .count();
+            let missing = by_frequency
+                .iter()
+                .filter(|(_, font_list, _)| !covers(font_list, &family))
+                .map(|(ch, _, _)| *ch)
+                .collect();
+            (family, covered, missing)
+        })
+        .collect();
+    res.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    res
+}
+
+/// How many clusters each face has rendered, after the random choice of the fonts and the
+/// fallback to `main_font_list`. A generator shared by several threads records into one counter.
+#[derive(Debug, Default)]
+pub struct FontUsage {
+    counts: Mutex<HashMap<fontdb::ID, usize>>,
+}
+
+impl FontUsage {
+    /// Count the face of every cluster.
+    pub fn record<I: IntoIterator<Item = Option<fontdb::ID>>>(&self, font_ids: I) {
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        for font_id in font_ids.into_iter().flatten() {
+            *counts.entry(font_id).or_insert(0) += 1;
+        }
+    }
+
+    /// The count of every face of the database, the unused ones included, most used first.
+    /// With `reset`, counting starts over.
+    pub fn stats(&self, db: &fontdb::Database, reset: bool) -> Vec<(fontdb::ID, usize)> {
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut res: Vec<_> = db
+            .faces()
+            .map(|face| (face.id, counts.get(&face.id).copied().unwrap_or(0)))
+            .collect();
+        res.sort_by_key(|&(_, count)| Reverse(count));
+        if reset {
+            counts.clear();
+        }
+
+        res
+    }
+}
+
 pub static TOTAL_FONT_NAME_LIST: Lazy<Vec<String>> = Lazy::new(|| {
     FONT_CONFIG
         .iter()
@@ -155,7 +545,10 @@ pub static TOTAL_FONT_WEIGHT_DIST: Lazy<WeightedAliasIndex<f64>> = Lazy::new(||
 
 #[cfg(test)]
 mod test {
-    use crate::{corpus::get_random_chinese_text_with_font_list, init::init_ch_dict_and_weight};
+    use crate::{
+        corpus::{get_random_chinese_text_with_font_list, SymbolInsertion},
+        init::init_ch_dict_and_weight,
+    };
 
     use super::*;
 
@@ -180,7 +573,7 @@ mod test {
         let ch_list_with_font_name_list = get_random_chinese_text_with_font_list(
             &ch_list,
             &ch_list_weights,
-            Some(&symbol),
+            Some((&symbol, &SymbolInsertion::default())),
             50..=60,
         );
         // let corpus_info = CorpusInfo::new("這是一……個——測 (試");
@@ -194,4 +587,124 @@ mod test {
 
         println!("{a:#?}")
     }
+
+    #[test]
+    fn test_font_weights() {
+        let font_system =
+            FontSystem::new_with_locale_and_db("en-US".to_string(), fontdb::Database::new());
+        let font = |name| {
+            InternalAttrsOwned::new(AttrsOwned::new(Attrs::new().family(Family::Name(name))))
+        };
+        let fonts = vec![font("SimSun"), font("Decorative")];
+
+        let mut fu = FontUtil::new(&font_system);
+        fu.set_font_weights(HashMap::from([("Decorative".to_string(), 0.0)]));
+        for _ in 0..20 {
+            assert_eq!(fu.choose_font(&fonts), Some(&fonts[0]));
+        }
+        // only zero weights, chosen uniformly
+        assert!(fu.choose_font(&fonts[1..]).is_some());
+
+        let path = std::env::temp_dir().join("tig_font_weights_test.json");
+        fs::write(
+            &path,
+            r#"[{"fontList": ["SimSun", "KaiTi"], "weight": 10}, {"fontList": ["Decorative"], "weight": 0.5}]"#,
+        )
+        .unwrap();
+        let weights = read_font_weights(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(weights.len(), 3);
+        assert_eq!(weights["KaiTi"], 10.0);
+        assert_eq!(weights["Decorative"], 0.5);
+        assert!(read_font_weights("./no_such_font_weights.json").is_err());
+    }
+
+    #[test]
+    fn test_coverage_report() {
+        let font = |name, weight| {
+            InternalAttrsOwned::new(AttrsOwned::new(
+                Attrs::new()
+                    .family(Family::Name(name))
+                    .weight(cosmic_text::Weight(weight)),
+            ))
+        };
+        let (song, song_bold, kai) = (font("Song", 400), font("Song", 700), font("Kai", 400));
+        let fonts = [song.clone(), song_bold.clone(), kai.clone()];
+        let (both, song_only, bold_only) = (
+            vec![song.clone(), kai.clone()],
+            vec![song.clone()],
+            vec![song_bold],
+        );
+        let chars: Vec<(&str, &[InternalAttrsOwned], f64)> = vec![
+            ("的", both.as_slice(), 100.0),
+            ("一", song_only.as_slice(), 50.0),
+            ("是", bold_only.as_slice(), 10.0),
+            ("龘", &[][..], 1.0),
+        ];
+
+        let report = coverage_report(&chars, &fonts, 3);
+        assert_eq!(
+            report,
+            vec![
+                ("Song".to_string(), 3, vec![]),
+                ("Kai".to_string(), 1, vec!["一", "是"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_font_data() {
+        let font_system =
+            FontSystem::new_with_locale_and_db("en-US".to_string(), fontdb::Database::new());
+        let mut fu = FontUtil::new(&font_system);
+        assert!(!fu.load_font_data(b"not a font".to_vec()));
+        assert!(fu.get_full_font_list().is_empty());
+
+        let mut db = fontdb::Database::new();
+        db.load_fonts_dir("./font");
+        let file = db.faces().find_map(|face| match &face.source {
+            fontdb::Source::File(path) => Some(path.clone()),
+            _ => None,
+        });
+        if let Some(file) = file {
+            assert!(fu.load_font_data(fs::read(file).unwrap()));
+            assert!(!fu.get_full_font_list().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_font_usage() {
+        let mut db = fontdb::Database::new();
+        db.load_fonts_dir("./font");
+        let ids: Vec<_> = db.faces().map(|face| face.id).take(2).collect();
+        if ids.len() < 2 {
+            return;
+        }
+
+        let usage = FontUsage::default();
+        usage.record([Some(ids[1]), None, Some(ids[1]), Some(ids[0])]);
+        let stats = usage.stats(&db, true);
+        assert_eq!(stats.len(), db.len());
+        assert_eq!(stats[0], (ids[1], 2));
+        assert_eq!(stats[1], (ids[0], 1));
+
+        assert!(usage.stats(&db, false).iter().all(|(_, count)| *count == 0));
+    }
+
+    #[test]
+    fn test_font_coverage() {
+        let coverage = FontCoverage::from_codepoints(vec![0x4E01, 0x41, 0x42, 0x43, 0x4E00, 0x41]);
+        assert_eq!(coverage.ranges, vec![(0x41, 0x43), (0x4E00, 0x4E01)]);
+        assert_eq!(coverage.len(), 5);
+        assert!(!coverage.is_empty());
+        assert!(FontCoverage::from_codepoints(vec![]).is_empty());
+
+        assert!(coverage.contains('A'));
+        assert!(coverage.contains('C'));
+        assert!(coverage.contains('丁'));
+        assert!(!coverage.contains('@'));
+        assert!(!coverage.contains('D'));
+        assert!(!coverage.contains('𠀀'));
+        assert!(!FontCoverage::default().contains('A'));
+    }
 }
@@ -0,0 +1,314 @@
+//! Composite batches of rasterized lines on the GPU with wgpu.
+//!
+//! Glyphs are still rasterized by swash on the CPU (and cached by `SwashCache`), the tiles of a
+//! whole batch are then uploaded as one atlas and blended onto the backgrounds by a compute
+//! shader, one invocation per output pixel.
+
+use std::borrow::Cow;
+
+use bytemuck::{Pod, Zeroable};
+use image::ImageBuffer;
+use wgpu::util::DeviceExt;
+
+use crate::image_process::GlyphTile;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+const SHADER: &str = r#"
+struct Glyph {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    offset: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+struct Image {
+    width: u32,
+    height: u32,
+    glyph_start: u32,
+    glyph_end: u32,
+    out_offset: u32,
+    background: u32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var<storage, read> glyphs: array<Glyph>;
+@group(0) @binding(1) var<storage, read> atlas: array<u32>;
+@group(0) @binding(2) var<storage, read> images: array<Image>;
+@group(0) @binding(3) var<storage, read_write> output: array<u32>;
+
+fn channel(color: u32, shift: u32) -> u32 {
+    return (color >> shift) & 0xffu;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let image = images[id.z];
+    if (id.x >= image.width || id.y >= image.height) {
+        return;
+    }
+
+    var r = channel(image.background, 0u);
+    var g = channel(image.background, 8u);
+    var b = channel(image.background, 16u);
+    let x = i32(id.x);
+    let y = i32(id.y);
+    // the CPU path never draws the top left pixel either
+    if (x != 0 || y != 0) {
+        for (var i = image.glyph_start; i < image.glyph_end; i++) {
+            let glyph = glyphs[i];
+            let local_x = x - glyph.x;
+            let local_y = y - glyph.y;
+            if (local_x < 0 || local_y < 0 || local_x >= i32(glyph.width) || local_y >= i32(glyph.height)) {
+                continue;
+            }
+            let px = atlas[glyph.offset + u32(local_y) * glyph.width + u32(local_x)];
+            let a = channel(px, 24u);
+            r = channel(px, 0u) * a / 255u + r * (255u - a) / 255u;
+            g = channel(px, 8u) * a / 255u + g * (255u - a) / 255u;
+            b = channel(px, 16u) * a / 255u + b * (255u - a) / 255u;
+        }
+    }
+
+    output[image.out_offset + id.y * image.width + id.x] = r | (g << 8u) | (b << 16u);
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuGlyph {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    offset: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuImage {
+    width: u32,
+    height: u32,
+    glyph_start: u32,
+    glyph_end: u32,
+    out_offset: u32,
+    background: u32,
+    _pad: [u32; 2],
+}
+
+/// One image of a batch: its size, background and glyph tiles.
+pub struct CompositeJob<'a> {
+    pub tiles: &'a [GlyphTile],
+    pub background_color: image::Rgb<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+pub struct GpuCompositor {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    max_buffer_size: u64,
+}
+
+fn pack_rgba(px: &[u8]) -> u32 {
+    u32::from_le_bytes([px[0], px[1], px[2], px[3]])
+}
+
+impl GpuCompositor {
+    /// Returns `None` if no GPU adapter is available.
+    pub fn new() -> Option<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                required_limits:
+                    wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            },
+            None,
+        ))
+        .ok()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("composite"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("composite"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+        let max_buffer_size = device.limits().max_storage_buffer_binding_size as u64;
+
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+            max_buffer_size,
+        })
+    }
+
+    fn storage_buffer<T: Pod + Zeroable>(&self, data: &[T], label: &str) -> wgpu::Buffer {
+        // empty bindings are not allowed
+        let zero = [T::zeroed()];
+        let data = if data.is_empty() { &zero[..] } else { data };
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(data),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+    }
+
+    /// Composite a batch of images. Returns `None` if the batch does not fit in the GPU
+    /// buffers or the GPU fails, the caller should then fall back to the CPU.
+    pub fn composite(
+        &self,
+        jobs: &[CompositeJob],
+    ) -> Option<Vec<ImageBuffer<image::Rgb<u8>, Vec<u8>>>> {
+        if jobs.is_empty() {
+            return Some(vec![]);
+        }
+        if jobs.len() > self.device.limits().max_compute_workgroups_per_dimension as usize {
+            return None;
+        }
+
+        let mut glyphs = vec![];
+        let mut atlas: Vec<u32> = vec![];
+        let mut images = Vec::with_capacity(jobs.len());
+        let mut out_len = 0;
+        let (mut max_width, mut max_height) = (0, 0);
+        for job in jobs {
+            let glyph_start = glyphs.len() as u32;
+            for tile in job.tiles {
+                glyphs.push(GpuGlyph {
+                    x: tile.x,
+                    y: tile.y,
+                    width: tile.width,
+                    height: tile.height,
+                    offset: atlas.len() as u32,
+                    _pad: [0; 3],
+                });
+                atlas.extend(tile.rgba.chunks_exact(4).map(pack_rgba));
+            }
+            let [r, g, b] = job.background_color.0;
+            images.push(GpuImage {
+                width: job.width as u32,
+                height: job.height as u32,
+                glyph_start,
+                glyph_end: glyphs.len() as u32,
+                out_offset: out_len as u32,
+                background: pack_rgba(&[r, g, b, 255]),
+                _pad: [0; 2],
+            });
+            out_len += job.width * job.height;
+            max_width = max_width.max(job.width as u32);
+            max_height = max_height.max(job.height as u32);
+        }
+
+        let out_size = (out_len.max(1) * 4) as u64;
+        if out_size > self.max_buffer_size || (atlas.len() * 4) as u64 > self.max_buffer_size {
+            return None;
+        }
+
+        let glyph_buffer = self.storage_buffer(&glyphs, "glyphs");
+        let atlas_buffer = self.storage_buffer(&atlas, "atlas");
+        let image_buffer = self.storage_buffer(&images, "images");
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("output"),
+            size: out_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("read"),
+            size: out_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: glyph_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: atlas_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: image_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: output_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                max_width.div_ceil(WORKGROUP_SIZE),
+                max_height.div_ceil(WORKGROUP_SIZE),
+                jobs.len() as u32,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&output_buffer, 0, &read_buffer, 0, out_size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = read_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            let _ = sender.send(res);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+
+        let res = {
+            let data = slice.get_mapped_range();
+            let pixels: &[u32] = bytemuck::cast_slice(&data);
+            jobs.iter()
+                .zip(images.iter())
+                .map(|(job, image)| {
+                    let start = image.out_offset as usize;
+                    let raw = pixels[start..start + job.width * job.height]
+                        .iter()
+                        .flat_map(|px| {
+                            let [r, g, b, _] = px.to_le_bytes();
+                            [r, g, b]
+                        })
+                        .collect();
+                    ImageBuffer::from_raw(job.width as u32, job.height as u32, raw).unwrap()
+                })
+                .collect()
+        };
+        read_buffer.unmap();
+
+        Some(res)
+    }
+}
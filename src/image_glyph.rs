@@ -0,0 +1,129 @@
+//! Images drawn instead of the font for some characters, e.g. cropped handwriting samples, so
+//! that printed and handwritten characters can be mixed in one line. A character keeps the
+//! advance its font gives it, the image is scaled into that cell.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use image::GrayImage;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::rng::sample_rng;
+
+/// The images of each character, dark ink on a light background.
+#[derive(Clone, Debug, Default)]
+pub struct ImageGlyphs {
+    glyphs: HashMap<String, Vec<GrayImage>>,
+    /// probability that a character with images is drawn with one of them
+    pub prob: f64,
+}
+
+impl ImageGlyphs {
+    pub fn new(prob: f64) -> Self {
+        Self {
+            glyphs: HashMap::new(),
+            prob,
+        }
+    }
+
+    /// Load the images of a directory. The character is the file name up to the first `_`
+    /// (`字.png`, `字_03.png`), or the name of the sub-directory the image is in (`字/03.png`).
+    /// An empty path loads nothing.
+    pub fn load_dir<P: AsRef<Path>>(dir: P, prob: f64) -> Result<Self, String> {
+        let mut res = Self::new(prob);
+        if dir.as_ref().as_os_str().is_empty() {
+            return Ok(res);
+        }
+
+        let read_dir = |dir: &Path| {
+            fs::read_dir(dir).map_err(|err| {
+                format!(
+                    "fail to read the image glyph directory `{}`: {}",
+                    dir.display(),
+                    err
+                )
+            })
+        };
+        for entry in read_dir(dir.as_ref())? {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            if path.is_dir() {
+                let ch = match path.file_name().and_then(|name| name.to_str()) {
+                    Some(ch) => ch.to_string(),
+                    None => continue,
+                };
+                for entry in read_dir(&path)? {
+                    let path = entry.map_err(|err| err.to_string())?.path();
+                    if let Some(img) = open_image(&path) {
+                        res.insert(ch.clone(), img);
+                    }
+                }
+            } else if let Some(img) = open_image(&path) {
+                let stem = path.file_stem().and_then(|stem| stem.to_str());
+                if let Some(ch) = stem.and_then(|stem| stem.split('_').next()) {
+                    res.insert(ch.to_string(), img);
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    pub fn insert(&mut self, ch: String, img: GrayImage) {
+        if img.width() > 0 && img.height() > 0 {
+            self.glyphs.entry(ch).or_default().push(img);
+        }
+    }
+
+    /// Remove every image of a character.
+    pub fn remove(&mut self, ch: &str) {
+        self.glyphs.remove(ch);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    /// Draw an image for a cluster, `None` if it has none or the font is kept this time.
+    pub fn sample(&self, cluster: &str) -> Option<&GrayImage> {
+        let images = self.glyphs.get(cluster)?;
+        let mut rng = sample_rng();
+        if self.prob < 1.0 && rng.gen_range(0.0..1.0) >= self.prob {
+            return None;
+        }
+
+        images.choose(&mut rng)
+    }
+}
+
+fn open_image(path: &Path) -> Option<GrayImage> {
+    let extension = path.extension()?;
+    if extension != "png" && extension != "jpg" && extension != "jpeg" {
+        return None;
+    }
+
+    image::open(path).ok().map(|img| img.to_luma8())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_image_glyphs() {
+        let mut glyphs = ImageGlyphs::new(1.0);
+        assert!(glyphs.is_empty());
+        glyphs.insert("字".to_string(), GrayImage::new(0, 4));
+        assert!(glyphs.is_empty());
+
+        glyphs.insert("字".to_string(), GrayImage::new(4, 4));
+        glyphs.insert("字".to_string(), GrayImage::new(6, 4));
+        assert!(glyphs.sample("字").is_some());
+        assert!(glyphs.sample("文").is_none());
+
+        glyphs.prob = 0.0;
+        assert!(glyphs.sample("字").is_none());
+
+        glyphs.remove("字");
+        assert!(glyphs.is_empty());
+        assert!(ImageGlyphs::load_dir("", 1.0).unwrap().is_empty());
+    }
+}
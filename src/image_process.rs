@@ -1,36 +1,2148 @@
-use cosmic_text::{Buffer, FontSystem, SwashCache};
-use image::{GenericImage, GenericImageView, ImageBuffer};
+use std::{ops::Range, sync::Arc};
 
-pub fn generate_image(
+use cosmic_text::{
+    fontdb, rustybuzz::ttf_parser::GlyphId, Align, Attrs, AttrsList, Buffer, BufferLine,
+    FontSystem, LayoutGlyph, Metrics, SwashCache,
+};
+use image::{imageops::FilterType, GenericImage, GenericImageView, GrayImage, ImageBuffer, Pixel};
+use indexmap::IndexMap;
+use rand::{seq::SliceRandom, Rng};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    cv_util::hsv_to_rgb, effect_helper::math::Random, image_glyph::ImageGlyphs,
+    opentype::OpenTypeFeatures, parse_config::Config, rng::sample_rng,
+};
+
+/// (x0, y0, x1, y1) of a box in an image, the right and bottom edges are exclusive.
+pub type BBox = (u32, u32, u32, u32);
+
+/// A shaping cluster and the pixels its glyphs cover in the rendered image.
+///
+/// Glyphs are grouped by the text range cosmic-text assigns to them, so a Devanagari syllable
+/// or a Thai consonant with its marks is labelled as one unit, in visual order.
+#[derive(Clone, Debug)]
+pub struct ClusterBox {
+    pub text: String,
+    /// (x0, y0, x1, y1), the right and bottom edges are exclusive
+    pub bbox: (u32, u32, u32, u32),
+    /// the face that rendered the first glyph of the cluster, after fallback
+    pub font_id: Option<fontdb::ID>,
+    /// the layout run the cluster is in, i.e. the visual line of a wrapped buffer
+    pub line: usize,
+}
+
+struct ClusterState {
+    text: String,
+    font_id: fontdb::ID,
+    /// the layout run the cluster is in
+    line: usize,
+    ink: Option<(i32, i32, i32, i32)>,
+    advance: (f32, f32, f32, f32),
+}
+
+impl ClusterState {
+    fn new(
+        text: &str,
+        font_id: fontdb::ID,
+        line: usize,
+        x: f32,
+        w: f32,
+        top: f32,
+        height: f32,
+    ) -> Self {
+        Self {
+            // drop the ZWNJ inserted by `build_line`
+            text: text.chars().filter(|&ch| ch != ZWNJ).collect(),
+            font_id,
+            line,
+            ink: None,
+            advance: (x, top, x + w, top + height),
+        }
+    }
+
+    fn extend_advance(&mut self, x: f32, w: f32) {
+        self.advance.0 = self.advance.0.min(x);
+        self.advance.2 = self.advance.2.max(x + w);
+    }
+
+    /// Move the advance box by `(dx, dy)`, then scale it by `factor` around `origin`.
+    fn transform(&mut self, (dx, dy): (f32, f32), factor: f32, (origin_x, origin_y): (f32, f32)) {
+        let (x0, y0, x1, y1) = self.advance;
+        let scale_x = |x: f32| origin_x + (x + dx - origin_x) * factor;
+        let scale_y = |y: f32| origin_y + (y + dy - origin_y) * factor;
+        self.advance = (scale_x(x0), scale_y(y0), scale_x(x1), scale_y(y1));
+    }
+
+    fn extend_ink(&mut self, x: i32, y: i32) {
+        self.ink = Some(match self.ink {
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1)),
+            None => (x, y, x + 1, y + 1),
+        });
+    }
+
+    /// Move the ink and the advance box by `(dx, dy)`.
+    fn translate(&mut self, dx: i32, dy: i32) {
+        self.transform((dx as f32, dy as f32), 1.0, (0.0, 0.0));
+        self.ink = self
+            .ink
+            .map(|(x0, y0, x1, y1)| (x0 + dx, y0 + dy, x1 + dx, y1 + dy));
+    }
+
+    /// Clusters without ink (e.g. spaces) fall back to their advance box.
+    fn finish(self, width: u32, height: u32) -> ClusterBox {
+        let (x0, y0, x1, y1) = match self.ink {
+            Some(ink) => ink,
+            None => (
+                self.advance.0.floor() as i32,
+                self.advance.1.floor() as i32,
+                self.advance.2.ceil() as i32,
+                self.advance.3.ceil() as i32,
+            ),
+        };
+        let clamp_x = |val: i32| val.clamp(0, width as i32) as u32;
+        let clamp_y = |val: i32| val.clamp(0, height as i32) as u32;
+
+        ClusterBox {
+            text: self.text,
+            bbox: (clamp_x(x0), clamp_y(y0), clamp_x(x1), clamp_y(y1)),
+            font_id: Some(self.font_id),
+            line: self.line,
+        }
+    }
+}
+
+/// Zero-width non-joiner, which stops ligatures from forming across it.
+const ZWNJ: char = '\u{200C}';
+
+fn is_latin_cluster(cluster: &str) -> bool {
+    match cluster.chars().next() {
+        Some(ch) => {
+            ch.is_alphanumeric() && (ch < '\u{0250}' || ('\u{1E00}'..='\u{1EFF}').contains(&ch))
+        }
+        None => false,
+    }
+}
+
+/// Join the text spans and their attrs into one line. When `ligatures` is false, a ZWNJ is put
+/// between adjacent Latin clusters, which keeps standard ligatures from forming while kerning
+/// and complex scripts are shaped as usual.
+pub fn build_line<T: AsRef<str>>(
+    spans: Vec<(T, Attrs)>,
+    default_attrs: Attrs,
+    ligatures: bool,
+) -> (String, AttrsList) {
+    let mut line_text = String::new();
+    let mut attrs_list = AttrsList::new(default_attrs);
+    let mut prev_latin = false;
+    for (text, attrs) in spans {
+        let start = line_text.len();
+        if ligatures {
+            line_text.push_str(text.as_ref());
+        } else {
+            for cluster in text.as_ref().graphemes(true) {
+                let is_latin = is_latin_cluster(cluster);
+                if prev_latin && is_latin {
+                    line_text.push(ZWNJ);
+                }
+                line_text.push_str(cluster);
+                prev_latin = is_latin;
+            }
+        }
+        let end = line_text.len();
+        attrs_list.add_span(start..end, attrs);
+    }
+
+    (line_text, attrs_list)
+}
+
+/// A single line built by `build_line`, shaped with bidi reordering and aligned to the left
+/// whatever its direction: a right-to-left line would otherwise end at the right border of the
+/// buffer, and the crop to the drawn text keeps everything from the left border.
+pub fn single_line(line_text: &str, attrs_list: AttrsList) -> BufferLine {
+    let mut line = BufferLine::new(line_text, attrs_list, cosmic_text::Shaping::Advanced);
+    line.set_align(Some(Align::Left));
+
+    line
+}
+
+/// How the kerning found by shaping is applied.
+#[derive(Clone, Copy, Debug)]
+pub struct Kerning {
+    pub enabled: bool,
+    /// Each kerning value is scaled by a random factor in `[1 - jitter, 1 + jitter]`.
+    pub jitter: f64,
+}
+
+impl Default for Kerning {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            jitter: 0.0,
+        }
+    }
+}
+
+impl Kerning {
+    fn is_default(&self) -> bool {
+        self.enabled && self.jitter == 0.0
+    }
+
+    fn factor(&self) -> f32 {
+        if !self.enabled {
+            0.0
+        } else if self.jitter > 0.0 {
+            sample_rng().gen_range(1.0 - self.jitter..=1.0 + self.jitter) as f32
+        } else {
+            1.0
+        }
+    }
+}
+
+/// The advance of a glyph without any positioning from the font, in pixels.
+fn nominal_advance(font_system: &mut FontSystem, glyph: &LayoutGlyph) -> Option<f32> {
+    let font = font_system.get_font(glyph.font_id)?;
+    let face = font.rustybuzz();
+    let advance = face.glyph_hor_advance(GlyphId(glyph.glyph_id))?;
+
+    Some(advance as f32 * glyph.font_size / face.units_per_em() as f32)
+}
+
+/// A noise texture that modulates the ink inside the strokes, for pencil, chalk or worn print.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InkTexture {
+    /// 0 keeps the ink flat, 1 lets the noise erase the ink completely
+    pub strength: f32,
+    /// size of the noise features in pixels
+    pub scale: f32,
+}
+
+impl InkTexture {
+    fn is_enabled(&self) -> bool {
+        self.strength > 0.0 && self.scale > 0.0
+    }
+}
+
+/// Smooth value noise in [0, 1] over a lattice of random values.
+struct ValueNoise {
+    cell: f32,
+    cols: usize,
+    rows: usize,
+    values: Vec<f32>,
+}
+
+impl ValueNoise {
+    fn new(width: usize, height: usize, cell: f32) -> Self {
+        let cols = (width as f32 / cell).ceil() as usize + 2;
+        let rows = (height as f32 / cell).ceil() as usize + 2;
+        let mut rng = sample_rng();
+
+        Self {
+            cell,
+            cols,
+            rows,
+            values: (0..cols * rows).map(|_| rng.gen::<f32>()).collect(),
+        }
+    }
+
+    fn get(&self, x: f32, y: f32) -> f32 {
+        let fx = (x / self.cell).clamp(0.0, (self.cols - 2) as f32);
+        let fy = (y / self.cell).clamp(0.0, (self.rows - 2) as f32);
+        let (col, row) = (fx as usize, fy as usize);
+        // smoothstep, so that the lattice does not show
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, ty) = (smooth(fx - col as f32), smooth(fy - row as f32));
+
+        let value = |col: usize, row: usize| self.values[row * self.cols + col];
+        let top = value(col, row) * (1.0 - tx) + value(col + 1, row) * tx;
+        let bottom = value(col, row + 1) * (1.0 - tx) + value(col + 1, row + 1) * tx;
+
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+/// Fade the coverage of the glyph tiles with two octaves of value noise.
+fn apply_ink_texture(tiles: &mut [GlyphTile], width: usize, height: usize, texture: &InkTexture) {
+    let coarse = ValueNoise::new(width, height, texture.scale);
+    let fine = ValueNoise::new(width, height, (texture.scale / 4.0).max(1.0));
+
+    for tile in tiles.iter_mut() {
+        for (idx, pixel) in tile.rgba.chunks_exact_mut(4).enumerate() {
+            if pixel[3] == 0 {
+                continue;
+            }
+            let x = (tile.x + (idx as u32 % tile.width) as i32) as f32;
+            let y = (tile.y + (idx as u32 / tile.width) as i32) as f32;
+            let noise = 0.65 * coarse.get(x, y) + 0.35 * fine.get(x, y);
+            let keep = 1.0 - texture.strength.clamp(0.0, 1.0) * noise;
+            pixel[3] = (pixel[3] as f32 * keep).round() as u8;
+        }
+    }
+}
+
+/// Options for drawing the shaped glyphs.
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    pub features: OpenTypeFeatures,
+    pub kerning: Kerning,
+    pub ink_texture: InkTexture,
+    /// empty pixels added around the line
+    pub padding: Padding,
+    /// Each cluster is scaled around its baseline by a random factor in
+    /// `[1 - glyph_size_jitter, 1 + glyph_size_jitter]`.
+    pub glyph_size_jitter: f64,
+    /// images drawn instead of the font for some characters
+    pub image_glyphs: Option<Arc<ImageGlyphs>>,
+    /// `None` draws every character in the text color
+    pub char_colors: Option<CharColors>,
+    /// `None` lays the text out horizontally
+    pub vertical: Option<VerticalLayout>,
+    /// synthetic bold and italic of this line
+    pub faux_style: FauxStyle,
+    /// extra space between the clusters of this line, horizontal text only
+    pub spacing: Spacing,
+    /// random vertical offset and rotation of each cluster
+    pub glyph_jitter: GlyphJitter,
+    /// `None` keeps the baseline straight, horizontal text only
+    pub text_path: Option<TextPath>,
+    /// `None` draws no shadow
+    pub drop_shadow: Option<DropShadow>,
+    /// `None` draws no line along the text, horizontal text on a straight baseline only
+    pub decoration: Option<Decoration>,
+}
+
+impl RenderOptions {
+    /// The names of the styles of this line, for the metadata of a sample.
+    pub fn styles(&self) -> Vec<String> {
+        let mut res = self.faux_style.names();
+        if let Some(text_path) = &self.text_path {
+            res.push(text_path.name().to_string());
+        }
+        if self.drop_shadow.is_some() {
+            res.push("drop_shadow".to_string());
+        }
+        if let Some(decoration) = &self.decoration {
+            res.push(decoration.name());
+        }
+
+        res
+    }
+}
+
+/// Uneven text as from cheap printing: every cluster is moved up or down by a random number of
+/// pixels, and rotated by a random angle in degrees around the middle of its em box.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlyphJitter {
+    pub baseline: Option<Random>,
+    pub rotation: Option<Random>,
+}
+
+impl GlyphJitter {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            baseline: config.baseline_jitter,
+            rotation: config.glyph_rotation,
+        }
+    }
+
+    /// The vertical offset in pixels and the angle in radians of a cluster.
+    fn sample(&self) -> (i32, f32) {
+        (
+            self.baseline
+                .map_or(0, |baseline| baseline.sample().round() as i32),
+            self.rotation
+                .map_or(0.0, |rotation| rotation.sample().to_radians() as f32),
+        )
+    }
+
+    fn apply(&self, tile: &mut GlyphTile, (offset, angle): (i32, f32), center: (f32, f32)) {
+        tile.rotate(angle, center.0, center.1);
+        tile.y += offset;
+    }
+}
+
+/// A curved baseline for logos, seals and signs. The clusters are moved onto the curve and
+/// rotated along it, each around the point where its center meets the baseline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextPath {
+    /// A circular arc as high as `bend` times the length of the line; positive bends bow
+    /// upwards like the top of a seal, negative ones downwards.
+    Arc { bend: f32 },
+    /// A sine wave, in pixels.
+    Wave {
+        amplitude: f32,
+        period: f32,
+        phase: f32,
+    },
+}
+
+impl TextPath {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextPath::Arc { .. } => "arc_text",
+            TextPath::Wave { .. } => "wave_text",
+        }
+    }
+
+    /// The displacement (dx, dy) of the point `t` pixels from the middle of a straight baseline
+    /// `length` long, and the angle of the curve there in radians, clockwise.
+    fn place(&self, t: f32, length: f32) -> (f32, f32, f32) {
+        match *self {
+            TextPath::Arc { bend } => {
+                let sagitta = bend.abs() * length;
+                if sagitta <= f32::EPSILON {
+                    return (0.0, 0.0, 0.0);
+                }
+                // the arc keeps the length of the line, so the ends move inwards
+                let radius = (length * length / 4.0 + sagitta * sagitta) / (2.0 * sagitta);
+                let angle = t / radius;
+                let sign = bend.signum();
+                (
+                    radius * angle.sin() - t,
+                    sign * radius * (1.0 - angle.cos()),
+                    sign * angle,
+                )
+            }
+            TextPath::Wave {
+                amplitude,
+                period,
+                phase,
+            } => {
+                let k = std::f32::consts::TAU / period.max(1.0);
+                let theta = k * t + phase;
+                (
+                    0.0,
+                    amplitude * theta.sin(),
+                    (amplitude * k * theta.cos()).atan(),
+                )
+            }
+        }
+    }
+
+    /// Move the tiles and the clusters of every layout run onto the curve, `baselines` holds the
+    /// baseline of each run.
+    fn bend(&self, tiles: &mut [GlyphTile], clusters: &mut [ClusterState], baselines: &[f32]) {
+        let mut extents = vec![(f32::MAX, f32::MIN); baselines.len()];
+        for cluster in clusters.iter() {
+            let extent = &mut extents[cluster.line];
+            *extent = (
+                extent.0.min(cluster.advance.0),
+                extent.1.max(cluster.advance.2),
+            );
+        }
+
+        // (dx, dy, angle, center) of each cluster
+        let places: Vec<_> = clusters
+            .iter_mut()
+            .map(|cluster| {
+                let (start, end) = extents[cluster.line];
+                let center = (cluster.advance.0 + cluster.advance.2) / 2.0;
+                let (dx, dy, angle) = self.place(center - (start + end) / 2.0, end - start);
+                cluster.transform((dx, dy), 1.0, (0.0, 0.0));
+                (dx, dy, angle, center)
+            })
+            .collect();
+        for tile in tiles.iter_mut() {
+            let (dx, dy, angle, center) = places[tile.cluster];
+            tile.rotate(angle, center, baselines[clusters[tile.cluster].line]);
+            tile.x += dx.round() as i32;
+            tile.y += dy.round() as i32;
+        }
+    }
+}
+
+/// How often a line is set on a `TextPath`, drawn for every sample.
+#[derive(Clone, Copy, Debug)]
+pub struct CurvedText {
+    pub arc_prob: f64,
+    pub arc_bend: Random,
+    pub wave_prob: f64,
+    /// in em
+    pub wave_amplitude: Random,
+    /// in em
+    pub wave_period: Random,
+}
+
+impl CurvedText {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            arc_prob: config.arc_text_prob,
+            arc_bend: config.arc_text_bend,
+            wave_prob: config.wave_text_prob,
+            wave_amplitude: config.wave_text_amplitude,
+            wave_period: config.wave_text_period,
+        }
+    }
+
+    /// An arc with `arc_prob`, otherwise a wave with `wave_prob`.
+    pub fn sample(&self, font_size: f32) -> Option<TextPath> {
+        let mut rng = sample_rng();
+        if self.arc_prob > 0.0 && rng.gen_bool(self.arc_prob.min(1.0)) {
+            Some(TextPath::Arc {
+                bend: self.arc_bend.sample() as f32,
+            })
+        } else if self.wave_prob > 0.0 && rng.gen_bool(self.wave_prob.min(1.0)) {
+            Some(TextPath::Wave {
+                amplitude: self.wave_amplitude.sample() as f32 * font_size,
+                period: self.wave_period.sample() as f32 * font_size,
+                phase: rng.gen_range(0.0..std::f32::consts::TAU),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// A soft shadow beneath the glyphs, as under overlaid captions and the text of UI screenshots.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DropShadow {
+    /// (x, y) in pixels, positive to the right and down
+    pub offset: (i32, i32),
+    /// sigma of the gaussian blur in pixels
+    pub sigma: f32,
+    /// 0 to 1
+    pub opacity: f32,
+}
+
+impl DropShadow {
+    /// One black tile of the shadow of all the tiles, clipped to the canvas. `None` if there
+    /// is no ink or the shadow falls outside the canvas.
+    fn tile(&self, tiles: &[GlyphTile], (width, height): (usize, usize)) -> Option<GlyphTile> {
+        let (x0, y0, x1, y1) = tiles
+            .iter()
+            .filter(|tile| tile.width > 0 && tile.height > 0)
+            .fold(None, |bounds, tile| {
+                let (x0, y0) = (tile.x, tile.y);
+                let (x1, y1) = (x0 + tile.width as i32, y0 + tile.height as i32);
+                Some(match bounds {
+                    Some((bx0, by0, bx1, by1)) => {
+                        (x0.min(bx0), y0.min(by0), x1.max(bx1), y1.max(by1))
+                    }
+                    None => (x0, y0, x1, y1),
+                })
+            })?;
+        // the blur spreads the shadow by about three sigmas
+        let margin = (self.sigma.max(0.0) * 3.0).ceil() as i32;
+        let (dx, dy) = self.offset;
+        let (x0, y0) = ((x0 + dx - margin).max(0), (y0 + dy - margin).max(0));
+        let (x1, y1) = (
+            (x1 + dx + margin).min(width as i32),
+            (y1 + dy + margin).min(height as i32),
+        );
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+
+        let (shadow_width, shadow_height) = ((x1 - x0) as u32, (y1 - y0) as u32);
+        let mut coverage = GrayImage::new(shadow_width, shadow_height);
+        for tile in tiles {
+            for (x, y, px) in tile.pixels() {
+                let (x, y) = (x + dx - x0, y + dy - y0);
+                if px[3] == 0 || !(0..x1 - x0).contains(&x) || !(0..y1 - y0).contains(&y) {
+                    continue;
+                }
+                // the union of the glyphs, overlapping ink does not darken the shadow
+                let target = &mut coverage.get_pixel_mut(x as u32, y as u32).0[0];
+                let alpha = px[3] as u32;
+                *target = (alpha + *target as u32 * (255 - alpha) / 255) as u8;
+            }
+        }
+        if self.sigma > 0.0 {
+            coverage = image::imageops::blur(&coverage, self.sigma);
+        }
+
+        let opacity = self.opacity.clamp(0.0, 1.0);
+        Some(GlyphTile {
+            x: x0,
+            y: y0,
+            width: shadow_width,
+            height: shadow_height,
+            rgba: coverage
+                .pixels()
+                .flat_map(|px| [0, 0, 0, (px.0[0] as f32 * opacity).round() as u8])
+                .collect(),
+            cluster: GlyphTile::NO_CLUSTER,
+        })
+    }
+}
+
+/// How often a line casts a `DropShadow`, drawn for every sample.
+#[derive(Clone, Copy, Debug)]
+pub struct DropShadowing {
+    pub prob: f64,
+    /// in em, for x and y each
+    pub offset: Random,
+    /// in em
+    pub sigma: Random,
+    pub opacity: Random,
+}
+
+impl DropShadowing {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            prob: config.drop_shadow_prob,
+            offset: config.drop_shadow_offset,
+            sigma: config.drop_shadow_sigma,
+            opacity: config.drop_shadow_opacity,
+        }
+    }
+
+    pub fn sample(&self, font_size: f32) -> Option<DropShadow> {
+        if self.prob <= 0.0 || !sample_rng().gen_bool(self.prob.min(1.0)) {
+            return None;
+        }
+
+        let offset = || (self.offset.sample() as f32 * font_size).round() as i32;
+        Some(DropShadow {
+            offset: (offset(), offset()),
+            sigma: (self.sigma.sample() as f32 * font_size).max(0.0),
+            opacity: self.opacity.sample().clamp(0.0, 1.0) as f32,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecorationLine {
+    Underline,
+    Strikethrough,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecorationStyle {
+    Solid,
+    Dashed,
+    Wavy,
+}
+
+impl DecorationStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "solid" => Some(Self::Solid),
+            "dashed" => Some(Self::Dashed),
+            "wavy" => Some(Self::Wavy),
+            _ => None,
+        }
+    }
+}
+
+/// A line drawn along each line of text in the text color, at the underline or strikeout
+/// position and thickness of the font, as in underlined form fields and struck-out amounts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoration {
+    pub line: DecorationLine,
+    pub style: DecorationStyle,
+    /// factor of the thickness given by the font
+    pub thickness: f32,
+}
+
+impl Decoration {
+    /// `underline`, `dashed_underline`, `wavy_strikethrough`, ...
+    pub fn name(&self) -> String {
+        let line = match self.line {
+            DecorationLine::Underline => "underline",
+            DecorationLine::Strikethrough => "strikethrough",
+        };
+        match self.style {
+            DecorationStyle::Solid => line.to_string(),
+            DecorationStyle::Dashed => format!("dashed_{}", line),
+            DecorationStyle::Wavy => format!("wavy_{}", line),
+        }
+    }
+
+    /// Position above the baseline and thickness in em, from the `post` or `OS/2` table of the
+    /// font, or typical values if the font has none.
+    fn metrics(&self, font_system: &mut FontSystem, font_id: fontdb::ID) -> (f32, f32) {
+        let fallback = match self.line {
+            DecorationLine::Underline => (-0.1, 0.05),
+            DecorationLine::Strikethrough => (0.3, 0.05),
+        };
+        let font = match font_system.get_font(font_id) {
+            Some(font) => font,
+            None => return fallback,
+        };
+        let face = font.rustybuzz();
+        let units_per_em = face.units_per_em() as f32;
+        let metrics = match self.line {
+            DecorationLine::Underline => face.underline_metrics(),
+            DecorationLine::Strikethrough => face.strikeout_metrics(),
+        };
+
+        match metrics.filter(|metrics| metrics.thickness > 0) {
+            Some(metrics) => (
+                metrics.position as f32 / units_per_em,
+                metrics.thickness as f32 / units_per_em,
+            ),
+            None => fallback,
+        }
+    }
+
+    /// A tile of the decoration from `x0` to `x1` along the baseline at `baseline`.
+    fn tile(
+        &self,
+        (x0, x1): (f32, f32),
+        baseline: f32,
+        (position, thickness): (f32, f32),
+        font_size: f32,
+        color: cosmic_text::Color,
+    ) -> Option<GlyphTile> {
+        if x1 <= x0 {
+            return None;
+        }
+
+        let thickness = (thickness * self.thickness * font_size).max(1.0);
+        let center = baseline - position * font_size;
+        let (amplitude, period) = match self.style {
+            DecorationStyle::Wavy => ((0.06 * font_size).max(thickness), 0.3 * font_size),
+            _ => (0.0, 1.0),
+        };
+        // dashes of a quarter em with gaps of a sixth
+        let (dash, gap) = (0.25 * font_size, font_size / 6.0);
+        let reach = thickness * 0.5 + amplitude + 1.0;
+        let (left, top) = (x0.floor() as i32, (center - reach).floor() as i32);
+        let (width, height) = (
+            (x1.ceil() as i32 - left).max(1) as u32,
+            ((center + reach).ceil() as i32 - top).max(1) as u32,
+        );
+
+        let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+        for row in 0..height {
+            for col in 0..width {
+                let (x, y) = (
+                    (left + col as i32) as f32 + 0.5,
+                    (top + row as i32) as f32 + 0.5,
+                );
+                let along = x - x0;
+                let line_y = center + amplitude * (along / period * std::f32::consts::TAU).sin();
+                let mut coverage = (thickness * 0.5 + 0.5 - (y - line_y).abs()).clamp(0.0, 1.0);
+                if self.style == DecorationStyle::Dashed && along.rem_euclid(dash + gap) >= dash {
+                    coverage = 0.0;
+                }
+                if !(x0..x1).contains(&x) {
+                    coverage = 0.0;
+                }
+                rgba.extend([
+                    color.r(),
+                    color.g(),
+                    color.b(),
+                    (coverage * color.a() as f32).round() as u8,
+                ]);
+            }
+        }
+
+        Some(GlyphTile {
+            x: left,
+            y: top,
+            width,
+            height,
+            rgba,
+            cluster: GlyphTile::NO_CLUSTER,
+        })
+    }
+}
+
+/// How often a line is decorated, drawn for every sample.
+#[derive(Clone, Debug)]
+pub struct Decorating {
+    pub underline_prob: f64,
+    pub strikethrough_prob: f64,
+    /// chosen from uniformly
+    pub styles: Vec<DecorationStyle>,
+    pub thickness: Random,
+}
+
+impl Decorating {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let styles = config
+            .decoration_styles
+            .iter()
+            .map(|name| {
+                DecorationStyle::from_name(name)
+                    .ok_or_else(|| format!("unknown decoration style `{}`", name))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            underline_prob: config.underline_prob,
+            strikethrough_prob: config.strikethrough_prob,
+            styles,
+            thickness: config.decoration_thickness,
+        })
+    }
+
+    /// An underline with `underline_prob`, otherwise a strikethrough with `strikethrough_prob`.
+    pub fn sample(&self) -> Option<Decoration> {
+        let mut rng = sample_rng();
+        let line = if self.underline_prob > 0.0 && rng.gen_bool(self.underline_prob.min(1.0)) {
+            DecorationLine::Underline
+        } else if self.strikethrough_prob > 0.0 && rng.gen_bool(self.strikethrough_prob.min(1.0)) {
+            DecorationLine::Strikethrough
+        } else {
+            return None;
+        };
+
+        Some(Decoration {
+            line,
+            style: *self.styles.choose(&mut rng)?,
+            thickness: self.thickness.sample().max(0.0) as f32,
+        })
+    }
+}
+
+/// Move the tiles and the clusters so that the ink keeps the padding from the top and the left
+/// border, and grow the size of the canvas for the ink and the padding at the bottom and the
+/// right.
+fn fit_canvas(
+    tiles: &mut [GlyphTile],
+    clusters: &mut [ClusterState],
+    (width, height): (usize, usize),
+    padding: Padding,
+) -> (usize, usize) {
+    let bounds = tiles
+        .iter()
+        .filter(|tile| tile.width > 0 && tile.height > 0)
+        .fold(None, |bounds, tile| {
+            let (x0, y0) = (tile.x, tile.y);
+            let (x1, y1) = (x0 + tile.width as i32, y0 + tile.height as i32);
+            Some(match bounds {
+                Some((bx0, by0, bx1, by1)) => (x0.min(bx0), y0.min(by0), x1.max(bx1), y1.max(by1)),
+                None => (x0, y0, x1, y1),
+            })
+        });
+    let (x0, y0, x1, y1) = match bounds {
+        Some(bounds) => bounds,
+        None => return (width, height),
+    };
+
+    let shift_x = (padding.left as i32 - x0).max(0);
+    let shift_y = (padding.top as i32 - y0).max(0);
+    if shift_x > 0 || shift_y > 0 {
+        for tile in tiles.iter_mut() {
+            tile.x += shift_x;
+            tile.y += shift_y;
+        }
+        for cluster in clusters.iter_mut() {
+            cluster.transform((shift_x as f32, shift_y as f32), 1.0, (0.0, 0.0));
+        }
+    }
+
+    (
+        width.max((x1 + shift_x) as usize + padding.right as usize),
+        height.max((y1 + shift_y) as usize + padding.bottom as usize),
+    )
+}
+
+/// Random colors for runs of characters, for multi-colored headlines and highlighted words.
+#[derive(Clone, Debug)]
+pub struct CharColors {
+    /// probability that a line is drawn with these colors
+    pub prob: f64,
+    /// number of clusters sharing a color
+    pub run_len: (u32, u32),
+    /// colors to choose from, empty for a random hue
+    pub palette: Vec<(u8, u8, u8)>,
+    /// hue in degrees, the saturation and the value are fixed so that the text stays legible
+    /// on light backgrounds
+    pub hue: Random,
+}
+
+impl CharColors {
+    /// `None` if per-character colors are disabled by the config.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (config.char_color_prob > 0.0).then(|| Self {
+            prob: config.char_color_prob,
+            run_len: config.char_color_run_len,
+            palette: config.char_color_palette.clone(),
+            hue: config.char_color_hue,
+        })
+    }
+
+    fn sample_color(&self) -> cosmic_text::Color {
+        let (r, g, b) = match self.palette.choose(&mut sample_rng()) {
+            Some(color) => *color,
+            None => {
+                let [r, g, b] = hsv_to_rgb(self.hue.sample().rem_euclid(360.0), 0.8, 0.6).0;
+                (r, g, b)
+            }
+        };
+
+        cosmic_text::Color::rgb(r, g, b)
+    }
+
+    fn sample_run_len(&self) -> u32 {
+        let (min, max) = self.run_len;
+        sample_rng().gen_range(min.max(1)..=max.max(min).max(1))
+    }
+}
+
+/// Random text and background colors of a sample, from palettes or HSV ranges, redrawn until
+/// their contrast is high enough to read.
+#[derive(Clone, Debug)]
+pub struct ColorScheme {
+    /// probability that a sample is drawn in random colors instead of black on white
+    pub prob: f64,
+    /// colors to choose from, empty for the HSV ranges
+    pub text_palette: Vec<(u8, u8, u8)>,
+    pub background_palette: Vec<(u8, u8, u8)>,
+    /// hue in degrees, saturation and value in 0..=1
+    pub text_hsv: (Random, Random, Random),
+    pub background_hsv: (Random, Random, Random),
+    /// the minimum WCAG contrast ratio of the two colors, from 1 (none) to 21 (black on white)
+    pub min_contrast: f64,
+}
+
+impl ColorScheme {
+    const MAX_TRIES: usize = 32;
+
+    /// `None` if random colors are disabled by the config.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (config.color_prob > 0.0).then(|| Self {
+            prob: config.color_prob,
+            text_palette: config.text_color_palette.clone(),
+            background_palette: config.background_color_palette.clone(),
+            text_hsv: (
+                config.text_color_hue,
+                config.text_color_saturation,
+                config.text_color_value,
+            ),
+            background_hsv: (
+                config.background_color_hue,
+                config.background_color_saturation,
+                config.background_color_value,
+            ),
+            min_contrast: config.min_contrast,
+        })
+    }
+
+    /// The text and background colors of a sample. The colors given are kept and only the
+    /// others are drawn; if no draw reaches `min_contrast`, the text falls back to black or
+    /// white, whichever stands out more.
+    pub fn sample(
+        &self,
+        text: Option<image::Rgb<u8>>,
+        background: Option<image::Rgb<u8>>,
+    ) -> (image::Rgb<u8>, image::Rgb<u8>) {
+        let (black, white) = (image::Rgb([0, 0, 0]), image::Rgb([255, 255, 255]));
+        if !sample_rng().gen_bool(self.prob.clamp(0.0, 1.0)) {
+            return (text.unwrap_or(black), background.unwrap_or(white));
+        }
+
+        let mut best: Option<(f64, image::Rgb<u8>, image::Rgb<u8>)> = None;
+        for _ in 0..Self::MAX_TRIES {
+            let text = text.unwrap_or_else(|| Self::draw(&self.text_palette, self.text_hsv));
+            let background = background
+                .unwrap_or_else(|| Self::draw(&self.background_palette, self.background_hsv));
+            let contrast = contrast_ratio(text, background);
+            if contrast >= self.min_contrast {
+                return (text, background);
+            }
+            if best.is_none_or(|(best, _, _)| contrast > best) {
+                best = Some((contrast, text, background));
+            }
+        }
+
+        let (_, best_text, background) = best.unwrap();
+        let text = text.unwrap_or_else(|| {
+            if contrast_ratio(black, background) >= contrast_ratio(white, background) {
+                black
+            } else {
+                white
+            }
+        });
+        if contrast_ratio(text, background) > contrast_ratio(best_text, background) {
+            (text, background)
+        } else {
+            (best_text, background)
+        }
+    }
+
+    fn draw(
+        palette: &[(u8, u8, u8)],
+        (hue, saturation, value): (Random, Random, Random),
+    ) -> image::Rgb<u8> {
+        match palette.choose(&mut sample_rng()) {
+            Some(&(r, g, b)) => image::Rgb([r, g, b]),
+            None => hsv_to_rgb(
+                hue.sample().rem_euclid(360.0),
+                saturation.sample().clamp(0.0, 1.0),
+                value.sample().clamp(0.0, 1.0),
+            ),
+        }
+    }
+}
+
+/// The WCAG contrast ratio of two colors, from 1 to 21.
+pub fn contrast_ratio(a: image::Rgb<u8>, b: image::Rgb<u8>) -> f64 {
+    let luminance = |color: image::Rgb<u8>| {
+        let [r, g, b] = color.0.map(|c| {
+            let c = c as f64 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    };
+    let (a, b) = (luminance(a), luminance(b));
+
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Empty pixels around the line. The top and bottom rows are added to the height of the
+/// buffer, the left columns before the first glyph and the right ones after the right border of
+/// the drawn pixels. With `tight`, the image is cropped to the ink on every side first, so that
+/// the padding is the exact whitespace around the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Padding {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+    pub tight: bool,
+}
+
+/// The font size, the line height and the padding, drawn for every sample.
+#[derive(Clone, Copy, Debug)]
+pub struct LineMetrics {
+    /// `None` keeps `base.0`
+    pub font_size: Option<Random>,
+    /// `None` keeps `base.1` and the height of the buffer
+    pub line_height: Option<Random>,
+    /// the font size and the line height of the config
+    pub base: (f32, f32),
+    pub padding_top: Random,
+    pub padding_bottom: Random,
+    pub padding_left: Random,
+    pub padding_right: Random,
+    /// measure the padding from the ink instead of the line box
+    pub tight: bool,
+}
+
+impl LineMetrics {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            font_size: config.font_size_range,
+            line_height: config.line_height_range,
+            base: (config.font_size as f32, config.line_height as f32),
+            padding_top: config.padding_top,
+            padding_bottom: config.padding_bottom,
+            padding_left: config.padding_left,
+            padding_right: config.padding_right,
+            tight: config.tight_crop,
+        }
+    }
+
+    /// Set a random font size on the buffer, and a random line height with which the buffer is
+    /// exactly one line tall, and draw the padding for `RenderOptions`.
+    pub fn sample(&self, editor: &mut Buffer, font_system: &mut FontSystem) -> Padding {
+        let font_size = self.font_size.map_or(self.base.0, |font_size| {
+            (font_size.sample() as f32).max(1.0).round()
+        });
+        match &self.line_height {
+            Some(line_height) => {
+                let line_height = (line_height.sample() as f32).max(1.0).round();
+                let width = editor.size().0;
+                editor.set_metrics(font_system, Metrics::new(font_size, line_height));
+                editor.set_size(font_system, width, line_height);
+            }
+            None => {
+                let metrics = editor.metrics();
+                if metrics.font_size != font_size || metrics.line_height != self.base.1 {
+                    editor.set_metrics(font_system, Metrics::new(font_size, self.base.1));
+                }
+            }
+        }
+
+        let sample = |random: &Random| random.sample().max(0.0).round() as u32;
+        Padding {
+            top: sample(&self.padding_top),
+            bottom: sample(&self.padding_bottom),
+            left: sample(&self.padding_left),
+            right: sample(&self.padding_right),
+            tight: self.tight,
+        }
+    }
+}
+
+/// Synthetic bold and italic for fonts without such faces, applied to the rasterized glyphs:
+/// the strokes are thickened by dilating the ink, and the glyphs are slanted by shearing them
+/// around the baseline.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FauxStyle {
+    /// how far the ink is dilated in pixels, 0 for none
+    pub bold: u32,
+    /// horizontal shift per pixel above the baseline (the tangent of the slant), 0 for none
+    pub italic: f32,
+}
+
+impl FauxStyle {
+    /// The names of the styles applied, for the metadata of a sample.
+    pub fn names(&self) -> Vec<String> {
+        let mut res = vec![];
+        if self.bold > 0 {
+            res.push("faux_bold".to_string());
+        }
+        if self.italic != 0.0 {
+            res.push("faux_italic".to_string());
+        }
+
+        res
+    }
+
+    fn apply(&self, tile: &mut GlyphTile, baseline: f32) {
+        tile.embolden(self.bold);
+        tile.shear(self.italic, baseline);
+    }
+}
+
+/// How often a line gets a `FauxStyle`, drawn for every sample.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FauxStyling {
+    pub bold_prob: f64,
+    /// the dilation in em
+    pub bold_width: f32,
+    pub italic_prob: f64,
+    /// the tangent of the slant angle, e.g. 0.2 for about 11°
+    pub italic_slant: f32,
+}
+
+impl FauxStyling {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            bold_prob: config.faux_bold_prob,
+            bold_width: config.faux_bold_width,
+            italic_prob: config.faux_italic_prob,
+            italic_slant: config.faux_italic_slant,
+        }
+    }
+
+    pub fn sample(&self, font_size: f32) -> FauxStyle {
+        let mut rng = sample_rng();
+        FauxStyle {
+            bold: if self.bold_prob > 0.0 && rng.gen_bool(self.bold_prob.min(1.0)) {
+                (self.bold_width * font_size).round().max(1.0) as u32
+            } else {
+                0
+            },
+            italic: if self.italic_prob > 0.0 && rng.gen_bool(self.italic_prob.min(1.0)) {
+                self.italic_slant
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Extra space added between the clusters of a line when it is rasterized, in pixels: `letter`
+/// between every two clusters (tracking, negative to tighten), and `word` on top of it after
+/// every space.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Spacing {
+    pub letter: f32,
+    pub word: f32,
+}
+
+impl Spacing {
+    /// The extra space before a cluster following a cluster that is a space or not.
+    fn after(&self, space: bool) -> f32 {
+        if space {
+            self.letter + self.word
+        } else {
+            self.letter
+        }
+    }
+}
+
+/// The letter and word spacing in em, drawn for every line.
+#[derive(Clone, Copy, Debug)]
+pub struct SpacingJitter {
+    pub letter: Random,
+    pub word: Random,
+}
+
+impl SpacingJitter {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            letter: config.letter_spacing,
+            word: config.word_spacing,
+        }
+    }
+
+    pub fn sample(&self, font_size: f32) -> Spacing {
+        Spacing {
+            letter: self.letter.sample() as f32 * font_size,
+            word: self.word.sample() as f32 * font_size,
+        }
+    }
+}
+
+/// The styles drawn anew for every line: faux bold and italic, the spacing, a curved baseline, a
+/// drop shadow and a decoration.
+#[derive(Clone, Debug)]
+pub struct LineStyling {
+    pub faux: FauxStyling,
+    pub spacing: SpacingJitter,
+    pub curve: CurvedText,
+    pub drop_shadow: DropShadowing,
+    pub decoration: Decorating,
+}
+
+impl LineStyling {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        Ok(Self {
+            faux: FauxStyling::from_config(config),
+            spacing: SpacingJitter::from_config(config),
+            curve: CurvedText::from_config(config),
+            drop_shadow: DropShadowing::from_config(config),
+            decoration: Decorating::from_config(config)?,
+        })
+    }
+
+    /// Draw the styles of the next line into `options`.
+    pub fn sample(&self, options: &mut RenderOptions, font_size: f32) {
+        options.faux_style = self.faux.sample(font_size);
+        options.spacing = self.spacing.sample(font_size);
+        options.text_path = self.curve.sample(font_size);
+        options.drop_shadow = self.drop_shadow.sample(font_size);
+        options.decoration = self.decoration.sample();
+    }
+}
+
+/// The width of the buffer: `font_img_width`, or with `auto_width` the width of the shaped line
+/// up to `max_font_img_width`.
+#[derive(Clone, Copy, Debug)]
+pub struct LineWidth {
+    pub width: f32,
+    /// `Some` when the buffer grows with the line
+    pub max_width: Option<f32>,
+}
+
+impl LineWidth {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            width: config.font_img_width as f32,
+            max_width: config
+                .auto_width
+                .then_some(config.max_font_img_width as f32),
+        }
+    }
+
+    /// Set the width of the buffer before a line is shaped. `width` overrides the config for one
+    /// line.
+    pub fn prepare(&self, editor: &mut Buffer, font_system: &mut FontSystem, width: Option<f32>) {
+        let width = width.or(self.max_width).unwrap_or(self.width);
+        let (current_width, height) = editor.size();
+        if current_width != width {
+            editor.set_size(font_system, width, height);
+        }
+    }
+
+    /// Shrink a growing buffer to the shaped line, with one em to spare for kerning and size
+    /// jitter, so that the line is not composited at `max_font_img_width`.
+    pub fn fit(&self, editor: &mut Buffer, font_system: &mut FontSystem, width: Option<f32>) {
+        if width.is_some() || self.max_width.is_none() {
+            return;
+        }
+
+        let line_width = editor
+            .layout_runs()
+            .fold(0.0f32, |acc, run| acc.max(run.line_w));
+        let fitted = (line_width + editor.metrics().font_size).ceil();
+        let (current_width, height) = editor.size();
+        if fitted < current_width {
+            editor.set_size(font_system, fitted, height);
+        }
+    }
+}
+
+/// Split units of the given widths into lines no wider than `max_width`. A line breaks after
+/// the last unit marked in `breaks` (e.g. a space) that fits or is the first not to fit, if
+/// there is one, otherwise before the unit that does not fit, and the break units at both ends of a line are dropped. A unit wider than
+/// `max_width` gets a line of its own.
+pub fn split_by_width(widths: &[f32], breaks: &[bool], max_width: f32) -> Vec<Range<usize>> {
+    let len = widths.len();
+    let mut res = vec![];
+    let mut start = 0;
+    loop {
+        while start < len && breaks[start] {
+            start += 1;
+        }
+        if start == len {
+            break;
+        }
+
+        let mut end = start;
+        let mut width = 0.0;
+        let mut last_break = None;
+        while end < len && (end == start || width + widths[end] <= max_width) {
+            width += widths[end];
+            if breaks[end] {
+                last_break = Some(end);
+            }
+            end += 1;
+        }
+
+        // the unit that does not fit may be a break itself
+        if end < len && breaks[end] {
+            last_break = Some(end);
+        }
+        let (mut line_end, next) = match last_break {
+            Some(idx) if end < len => (idx, idx + 1),
+            _ => (end, end),
+        };
+        while line_end > start && breaks[line_end - 1] {
+            line_end -= 1;
+        }
+        res.push(start..line_end);
+        start = next;
+    }
+
+    res
+}
+
+/// A unit of a vertical column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerticalUnit<'a> {
+    /// A single cluster set upright in the column.
+    Upright(&'a str),
+    /// A short run of Latin letters or digits set horizontally within the column (tate-chū-yoko).
+    Horizontal(&'a str),
+}
+
+fn is_tcy_cluster(cluster: &str) -> bool {
+    cluster.chars().all(|ch| ch.is_ascii_alphanumeric())
+}
+
+fn push_vertical_run<'a>(
+    res: &mut Vec<VerticalUnit<'a>>,
+    run: &'a str,
+    count: usize,
+    tcy_max_len: usize,
+) {
+    if count <= tcy_max_len {
+        res.push(VerticalUnit::Horizontal(run));
+    } else {
+        res.extend(run.graphemes(true).map(VerticalUnit::Upright));
+    }
+}
+
+/// Split text into vertical units. Runs of Latin letters or digits no longer than `tcy_max_len`
+/// are kept together as tate-chū-yoko, longer runs are set upright cluster by cluster.
+pub fn split_vertical_units(text: &str, tcy_max_len: usize) -> Vec<VerticalUnit<'_>> {
+    let mut res = vec![];
+    let mut run_start: Option<(usize, usize)> = None; // (byte index, cluster count)
+
+    for (idx, cluster) in text.grapheme_indices(true) {
+        if is_tcy_cluster(cluster) {
+            run_start = match run_start {
+                Some((start, count)) => Some((start, count + 1)),
+                None => Some((idx, 1)),
+            };
+            continue;
+        }
+        if let Some((start, count)) = run_start.take() {
+            push_vertical_run(&mut res, &text[start..idx], count, tcy_max_len);
+        }
+        res.push(VerticalUnit::Upright(cluster));
+    }
+    if let Some((start, count)) = run_start {
+        push_vertical_run(&mut res, &text[start..], count, tcy_max_len);
+    }
+
+    res
+}
+
+/// Vertical writing mode: every vertical unit is laid out as a line of its own, then the units
+/// are moved into columns one em apart, running from right to left. The width of the buffer is
+/// the length of a column, and the line height the width of a column.
+#[derive(Clone, Copy, Debug)]
+pub struct VerticalLayout {
+    pub tcy_max_len: usize,
+    /// empty pixels between two columns
+    pub column_spacing: u32,
+    /// rotate the finished image 90° counterclockwise, so that it reads from left to right
+    pub rotate: bool,
+}
+
+impl VerticalLayout {
+    /// `None` if the config is in horizontal mode.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.vertical.then_some(Self {
+            tcy_max_len: config.tcy_max_len,
+            column_spacing: config.column_spacing as u32,
+            rotate: config.vertical_rotate,
+        })
+    }
+
+    /// Put the spans into the buffer, one line per vertical unit built by `build_line`, and
+    /// shape them. The buffer is made as tall as the lines.
+    pub fn set_lines<T: AsRef<str>>(
+        &self,
+        editor: &mut Buffer,
+        font_system: &mut FontSystem,
+        spans: Vec<(T, Attrs)>,
+        default_attrs: Attrs,
+        ligatures: bool,
+    ) {
+        let text: String = spans.iter().map(|(text, _)| text.as_ref()).collect();
+        let mut span_ranges = Vec::with_capacity(spans.len());
+        let mut start = 0;
+        for (span, attrs) in spans.iter() {
+            let end = start + span.as_ref().len();
+            span_ranges.push((start..end, *attrs));
+            start = end;
+        }
+
+        editor.lines.clear();
+        let mut unit_start = 0;
+        for unit in split_vertical_units(&text, self.tcy_max_len) {
+            let (VerticalUnit::Upright(unit) | VerticalUnit::Horizontal(unit)) = unit;
+            let unit_range = unit_start..unit_start + unit.len();
+            unit_start = unit_range.end;
+
+            let unit_spans = span_ranges
+                .iter()
+                .filter_map(|(range, attrs)| {
+                    let start = range.start.max(unit_range.start);
+                    let end = range.end.min(unit_range.end);
+                    (start < end).then(|| (&text[start..end], *attrs))
+                })
+                .collect();
+            let (line_text, attrs_list) = build_line(unit_spans, default_attrs, ligatures);
+            editor.lines.push(BufferLine::new(
+                &line_text,
+                attrs_list,
+                cosmic_text::Shaping::Advanced,
+            ));
+        }
+
+        let line_height = editor.metrics().line_height;
+        let width = editor.size().0;
+        editor.set_size(
+            font_system,
+            width,
+            editor.lines.len().max(1) as f32 * line_height,
+        );
+        editor.shape_until_scroll(font_system, false);
+    }
+
+    /// Move the units laid out by `set_lines` into columns and shrink tate-chū-yoko runs wider
+    /// than one em. `runs` holds the top and the width of each unit. Returns the size of the
+    /// image with the padding.
+    fn arrange(
+        &self,
+        tiles: &mut [GlyphTile],
+        clusters: &mut [ClusterState],
+        runs: &[(f32, f32)],
+        (em, line_height): (f32, f32),
+        column_len: f32,
+        padding: Padding,
+    ) -> (usize, usize) {
+        let per_column = ((column_len / em).floor() as usize).max(1);
+        let columns = runs.len().div_ceil(per_column).max(1);
+        let step = line_height + self.column_spacing as f32;
+
+        // (shift, scale factor, center) of each unit
+        let placements: Vec<_> = runs
+            .iter()
+            .enumerate()
+            .map(|(idx, &(top, width))| {
+                let (column, row) = (idx / per_column, idx % per_column);
+                let center = (
+                    padding.left as f32 + (columns - 1 - column) as f32 * step + line_height * 0.5,
+                    padding.top as f32 + (row as f32 + 0.5) * em,
+                );
+                let shift = (
+                    center.0 - (padding.left as f32 + width * 0.5),
+                    center.1 - (padding.top as f32 + top + line_height * 0.5),
+                );
+                let factor = if width > em { em / width } else { 1.0 };
+                (shift, factor, center)
+            })
+            .collect();
+
+        for tile in tiles.iter_mut() {
+            let (shift, factor, center) = placements[clusters[tile.cluster].line];
+            tile.x += shift.0.round() as i32;
+            tile.y += shift.1.round() as i32;
+            if factor != 1.0 {
+                tile.scale(factor, center.0, center.1);
+            }
+        }
+        for cluster in clusters.iter_mut() {
+            let (shift, factor, center) = placements[cluster.line];
+            cluster.transform(shift, factor, center);
+        }
+
+        let rows = runs.len().clamp(1, per_column);
+        let width = columns as f32 * step - self.column_spacing as f32;
+        (
+            width.ceil() as usize + (padding.left + padding.right) as usize,
+            (rows as f32 * em).ceil() as usize + (padding.top + padding.bottom) as usize,
+        )
+    }
+}
+
+/// A glyph rasterized into an RGBA tile, with the top left corner at (x, y) of the image.
+#[derive(Clone, Debug)]
+pub struct GlyphTile {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+    cluster: usize,
+}
+
+impl GlyphTile {
+    /// The cluster of a tile that belongs to no cluster, e.g. the drop shadow.
+    const NO_CLUSTER: usize = usize::MAX;
+
+    /// Pixels of the tile in image coordinates.
+    fn pixels(&self) -> impl Iterator<Item = (i32, i32, &[u8])> {
+        self.rgba.chunks_exact(4).enumerate().map(|(idx, px)| {
+            let idx = idx as u32;
+            (
+                self.x + (idx % self.width) as i32,
+                self.y + (idx / self.width) as i32,
+                px,
+            )
+        })
+    }
+
+    /// Scale the tile by `factor` around `(origin_x, origin_y)` in image coordinates. The
+    /// pixels are resampled premultiplied, so that the transparent pixels around a color glyph
+    /// (emoji) do not darken its edges.
+    fn scale(&mut self, factor: f32, origin_x: f32, origin_y: f32) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let width = ((self.width as f32 * factor).round() as u32).max(1);
+        let height = ((self.height as f32 * factor).round() as u32).max(1);
+        // float pixels are resampled in 0..=1
+        const UNIT: [f32; 4] = [255.0 * 255.0, 255.0 * 255.0, 255.0 * 255.0, 255.0];
+        let premultiplied: Vec<f32> = (0..self.height as i32)
+            .flat_map(|row| (0..self.width as i32).map(move |col| (col, row)))
+            .flat_map(|(col, row)| {
+                let px = self.premultiplied(col, row);
+                [0, 1, 2, 3].map(|c| px[c] / UNIT[c])
+            })
+            .collect();
+        let img: image::Rgba32FImage =
+            ImageBuffer::from_raw(self.width, self.height, premultiplied)
+                .expect("tile size should match its pixels");
+        self.rgba = image::imageops::resize(&img, width, height, FilterType::Triangle)
+            .pixels()
+            .flat_map(|px| {
+                let px = [0, 1, 2, 3].map(|c| px.0[c] * UNIT[c]);
+                if px[3] > 0.0 {
+                    unpremultiply(px)
+                } else {
+                    [0; 4]
+                }
+            })
+            .collect();
+        self.x = (origin_x + (self.x as f32 - origin_x) * factor).round() as i32;
+        self.y = (origin_y + (self.y as f32 - origin_y) * factor).round() as i32;
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Thicken the ink by `radius` pixels in every direction, each pixel takes the most opaque
+    /// pixel within the radius.
+    fn embolden(&mut self, radius: u32) {
+        if radius == 0 || self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let r = radius as i32;
+        let (width, height) = (self.width + 2 * radius, self.height + 2 * radius);
+        let mut rgba = vec![0; (width * height * 4) as usize];
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let mut best: Option<&[u8]> = None;
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        let (src_x, src_y) = (x - r + dx, y - r + dy);
+                        if dx * dx + dy * dy > r * r
+                            || !(0..self.width as i32).contains(&src_x)
+                            || !(0..self.height as i32).contains(&src_y)
+                        {
+                            continue;
+                        }
+                        let idx = (src_y as usize * self.width as usize + src_x as usize) * 4;
+                        let px = &self.rgba[idx..idx + 4];
+                        if best.is_none_or(|best| px[3] > best[3]) {
+                            best = Some(px);
+                        }
+                    }
+                }
+                if let Some(px) = best {
+                    let idx = (y as usize * width as usize + x as usize) * 4;
+                    rgba[idx..idx + 4].copy_from_slice(px);
+                }
+            }
+        }
+
+        self.x -= r;
+        self.y -= r;
+        self.width = width;
+        self.height = height;
+        self.rgba = rgba;
+    }
+
+    /// Slant the tile: every row is shifted right by `slant` pixels per pixel above `baseline`
+    /// in image coordinates, and resampled linearly.
+    fn shear(&mut self, slant: f32, baseline: f32) {
+        if slant == 0.0 || self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let offset = |row: u32| slant * (baseline - (self.y as f32 + row as f32 + 0.5));
+        let (first, last) = (offset(0), offset(self.height - 1));
+        let left = first.min(last).floor() as i32;
+        let right = first.max(last).ceil() as i32;
+        let width = self.width + (right - left) as u32 + 1;
+
+        let mut rgba = vec![0; (width * self.height * 4) as usize];
+        for row in 0..self.height {
+            let shift = offset(row) - left as f32;
+            for col in 0..width {
+                let src = col as f32 - shift;
+                let src_col = src.floor();
+                let t = src - src_col;
+                let a = self.premultiplied(src_col as i32, row as i32);
+                let b = self.premultiplied(src_col as i32 + 1, row as i32);
+                let px: [f32; 4] = std::array::from_fn(|c| a[c] * (1.0 - t) + b[c] * t);
+                if px[3] > 0.0 {
+                    let idx = ((row * width + col) * 4) as usize;
+                    rgba[idx..idx + 4].copy_from_slice(&unpremultiply(px));
+                }
+            }
+        }
+
+        self.x += left;
+        self.width = width;
+        self.rgba = rgba;
+    }
+
+    /// Rotate the tile by `angle` radians (clockwise, as the y axis points down) around
+    /// `(origin_x, origin_y)` in image coordinates, and resample it bilinearly.
+    fn rotate(&mut self, angle: f32, origin_x: f32, origin_y: f32) {
+        if angle == 0.0 || self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let (sin, cos) = angle.sin_cos();
+        let (x0, y0) = (self.x as f32, self.y as f32);
+        let (x1, y1) = (x0 + self.width as f32, y0 + self.height as f32);
+        let corners = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)].map(|(x, y)| {
+            let (dx, dy) = (x - origin_x, y - origin_y);
+            (
+                origin_x + dx * cos - dy * sin,
+                origin_y + dx * sin + dy * cos,
+            )
+        });
+        let bound = |values: [f32; 4]| {
+            let min = values.iter().copied().fold(f32::MAX, f32::min);
+            let max = values.iter().copied().fold(f32::MIN, f32::max);
+            (min.floor() as i32, max.ceil() as i32)
+        };
+        let (left, right) = bound(corners.map(|corner| corner.0));
+        let (top, bottom) = bound(corners.map(|corner| corner.1));
+        let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+
+        let mut rgba = vec![0; (width * height * 4) as usize];
+        for row in 0..height {
+            for col in 0..width {
+                // the center of the pixel rotated back into the tile
+                let dx = left as f32 + col as f32 + 0.5 - origin_x;
+                let dy = top as f32 + row as f32 + 0.5 - origin_y;
+                let src_x = origin_x + dx * cos + dy * sin - x0 - 0.5;
+                let src_y = origin_y - dx * sin + dy * cos - y0 - 0.5;
+                let (src_col, src_row) = (src_x.floor(), src_y.floor());
+                let (tx, ty) = (src_x - src_col, src_y - src_row);
+                let (src_col, src_row) = (src_col as i32, src_row as i32);
+                let (a, b) = (
+                    self.premultiplied(src_col, src_row),
+                    self.premultiplied(src_col + 1, src_row),
+                );
+                let (c, d) = (
+                    self.premultiplied(src_col, src_row + 1),
+                    self.premultiplied(src_col + 1, src_row + 1),
+                );
+                let px: [f32; 4] = std::array::from_fn(|i| {
+                    (a[i] * (1.0 - tx) + b[i] * tx) * (1.0 - ty)
+                        + (c[i] * (1.0 - tx) + d[i] * tx) * ty
+                });
+                if px[3] > 0.0 {
+                    let idx = ((row * width + col) * 4) as usize;
+                    rgba[idx..idx + 4].copy_from_slice(&unpremultiply(px));
+                }
+            }
+        }
+
+        self.x = left;
+        self.y = top;
+        self.width = width;
+        self.height = height;
+        self.rgba = rgba;
+    }
+
+    /// The pixel at `(col, row)` of the tile with its color premultiplied by the alpha, so that
+    /// the color of transparent pixels does not bleed when resampling; transparent outside.
+    fn premultiplied(&self, col: i32, row: i32) -> [f32; 4] {
+        if !(0..self.width as i32).contains(&col) || !(0..self.height as i32).contains(&row) {
+            return [0.0; 4];
+        }
+        let idx = (row as usize * self.width as usize + col as usize) * 4;
+        let px = &self.rgba[idx..idx + 4];
+        let alpha = px[3] as f32;
+
+        [
+            px[0] as f32 * alpha,
+            px[1] as f32 * alpha,
+            px[2] as f32 * alpha,
+            alpha,
+        ]
+    }
+}
+
+fn unpremultiply(px: [f32; 4]) -> [u8; 4] {
+    [
+        (px[0] / px[3]).round() as u8,
+        (px[1] / px[3]).round() as u8,
+        (px[2] / px[3]).round() as u8,
+        px[3].round() as u8,
+    ]
+}
+
+/// A tile drawing `img` (dark ink on light) in `color`, fitted into the cell of a glyph: the
+/// advance `(x, w)` wide and one em tall, sitting on the baseline like an ideograph.
+fn image_glyph_tile(
+    img: &GrayImage,
+    color: cosmic_text::Color,
+    (x, w): (f32, f32),
+    baseline: f32,
+    font_size: f32,
+    cluster: usize,
+) -> GlyphTile {
+    let cell_width = if w > 0.0 { w } else { font_size };
+    let scale = (cell_width / img.width() as f32).min(font_size / img.height() as f32);
+    let width = ((img.width() as f32 * scale).round() as u32).max(1);
+    let height = ((img.height() as f32 * scale).round() as u32).max(1);
+    let resized = image::imageops::resize(img, width, height, FilterType::Triangle);
+    let rgba = resized
+        .pixels()
+        .flat_map(|px| {
+            let alpha = (255 - px[0]) as u32 * color.a() as u32 / 255;
+            [color.r(), color.g(), color.b(), alpha as u8]
+        })
+        .collect();
+    // the ideographic em box reaches about 0.88 em above the baseline
+    let top = baseline - font_size * 0.88;
+
+    GlyphTile {
+        x: (x + (cell_width - width as f32) / 2.0).round() as i32,
+        y: (top + (font_size - height as f32) / 2.0).round() as i32,
+        width,
+        height,
+        rgba,
+        cluster,
+    }
+}
+
+/// The shaped and rasterized glyphs of a buffer, ready to be composited.
+pub struct RasterizedText {
+    pub width: usize,
+    pub height: usize,
+    padding: Padding,
+    vertical: Option<VerticalLayout>,
+    pub tiles: Vec<GlyphTile>,
+    clusters: Vec<ClusterState>,
+}
+
+impl RasterizedText {
+    /// The top left corner and the size of the cropped image, and the cluster boxes in it. The
+    /// image is cut after the drawn pixels plus the padding, at the right border or in vertical
+    /// mode at the bottom one. With a tight padding, it is cut to the ink on every side and the
+    /// padding is put around the ink, so the corner may lie outside the canvas.
+    fn crop(self) -> ((i32, i32), (u32, u32), Vec<ClusterBox>) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let mut clusters = self.clusters;
+        let (mut right_border, mut bottom_border) = (0, 0);
+        let mut ink: Option<(i32, i32, i32, i32)> = None;
+        for tile in self.tiles.iter() {
+            for (x, y, px) in tile.pixels() {
+                if x < 0 || x >= width || y < 0 || y >= height || (x == 0 && y == 0) {
+                    continue;
+                }
+                right_border = right_border.max(x);
+                bottom_border = bottom_border.max(y);
+                if px[3] > 0 {
+                    ink = Some(match ink {
+                        Some((x0, y0, x1, y1)) => {
+                            (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1))
+                        }
+                        None => (x, y, x + 1, y + 1),
+                    });
+                    if tile.cluster != GlyphTile::NO_CLUSTER {
+                        clusters[tile.cluster].extend_ink(x, y);
+                    }
+                }
+            }
+        }
+
+        let padding = self.padding;
+        let (origin, crop_size) = match ink.filter(|_| padding.tight) {
+            Some((x0, y0, x1, y1)) => (
+                (x0 - padding.left as i32, y0 - padding.top as i32),
+                (
+                    (x1 - x0) as u32 + padding.left + padding.right,
+                    (y1 - y0) as u32 + padding.top + padding.bottom,
+                ),
+            ),
+            None => (
+                (0, 0),
+                match self.vertical {
+                    Some(_) => (
+                        self.width as u32,
+                        ((bottom_border + 1) as u32 + padding.bottom).min(self.height as u32),
+                    ),
+                    None => (
+                        ((right_border + 1) as u32 + padding.right).min(self.width as u32),
+                        self.height as u32,
+                    ),
+                },
+            ),
+        };
+        let clusters = clusters
+            .into_iter()
+            .filter(|each| !each.text.is_empty())
+            .map(|mut each| {
+                each.translate(-origin.0, -origin.1);
+                each.finish(crop_size.0, crop_size.1)
+            })
+            .collect();
+
+        (origin, crop_size, clusters)
+    }
+}
+
+/// Shape and rasterize every glyph of the buffer into its own tile, with OpenType features
+/// and kerning applied. A cluster drawn with an image glyph gets one tile from the image instead
+/// of the tiles of its glyphs. The image is `height` plus the vertical padding of the options tall, and
+/// `width` plus the horizontal padding wide, in vertical mode as large as the columns.
+pub fn rasterize_text(
     editor: &mut Buffer,
     font_system: &mut FontSystem,
     swash_cache: &mut SwashCache,
     foreground_color: cosmic_text::Color,
+    width: usize,
+    height: usize,
+    options: &RenderOptions,
+) -> RasterizedText {
+    let mut tiles = vec![];
+    let mut clusters: IndexMap<(usize, usize, usize), ClusterState> = IndexMap::new();
+    // size factor of each cluster, so that a base and its marks are scaled together
+    let mut cluster_scales: Vec<f32> = vec![];
+    // vertical offset and angle of each cluster, shared by a base and its marks as well, and
+    // the horizontal center of the cluster it is rotated around
+    let mut cluster_jitters: Vec<((i32, f32), f32)> = vec![];
+    // whether each cluster is drawn with an image glyph
+    let mut image_clusters: Vec<bool> = vec![];
+    // color of each cluster, and the clusters left in the current color run
+    let mut cluster_colors: Vec<cosmic_text::Color> = vec![];
+    let char_colors = options
+        .char_colors
+        .as_ref()
+        .filter(|each| sample_rng().gen_range(0.0..1.0) < each.prob);
+    let (mut run_color, mut run_left) = (foreground_color, 0);
+    // top and width of each layout run
+    let mut runs: Vec<(f32, f32)> = vec![];
+    // baseline of each layout run
+    let mut baselines: Vec<f32> = vec![];
+    let metrics = editor.metrics();
+    let line_height = metrics.line_height;
+    let padding = options.padding;
+    let padding_top = padding.top;
+    let column_len = width as f32;
+    let mut height = height + (padding.top + padding.bottom) as usize;
+    let mut width = width + (padding.left + padding.right) as usize;
+    // the spacing is laid along the line, the columns of vertical text are left alone
+    let spacing = match options.vertical {
+        Some(_) => Spacing::default(),
+        None => options.spacing,
+    };
+    // how much wider the spacing makes the widest line
+    let mut spaced_width = 0.0f32;
+
+    for run in editor.layout_runs() {
+        let line = runs.len();
+        runs.push((run.line_top, run.line_w));
+        baselines.push(run.line_y + padding_top as f32);
+        // shift of the following glyphs by the left padding, after the kerning has been
+        // rescaled and by the spacing
+        let mut x_shift = padding.left as f32;
+        let mut spaced = 0.0f32;
+        // whether the previous cluster of the run is a space
+        let mut prev_space = None;
+        for glyph in run.glyphs.iter() {
+            if spacing != Spacing::default()
+                && !clusters.contains_key(&(run.line_i, glyph.start, glyph.end))
+            {
+                if let Some(prev_space) = prev_space {
+                    let extra = spacing.after(prev_space);
+                    x_shift += extra;
+                    spaced += extra;
+                }
+                let text = run.text[glyph.start..glyph.end].trim_matches(ZWNJ);
+                prev_space = Some(!text.is_empty() && text.chars().all(char::is_whitespace));
+            }
+            let features = &options.features;
+            let physical_glyph = if features.is_empty() {
+                glyph.physical((x_shift, 0.), 1.0)
+            } else {
+                let mut substituted = glyph.clone();
+                substituted.glyph_id =
+                    features.substitute(font_system, glyph.font_id, glyph.glyph_id);
+                substituted.physical((x_shift, 0.), 1.0)
+            };
+            let cluster = clusters.entry((run.line_i, glyph.start, glyph.end));
+            let cluster_idx = cluster.index();
+            cluster
+                .or_insert_with(|| {
+                    ClusterState::new(
+                        &run.text[glyph.start..glyph.end],
+                        glyph.font_id,
+                        line,
+                        glyph.x + x_shift,
+                        glyph.w,
+                        run.line_top + padding_top as f32,
+                        line_height,
+                    )
+                })
+                .extend_advance(glyph.x + x_shift, glyph.w);
+            if cluster_idx == cluster_scales.len() {
+                cluster_scales.push(if options.glyph_size_jitter > 0.0 {
+                    let jitter = options.glyph_size_jitter;
+                    sample_rng().gen_range(1.0 - jitter..=1.0 + jitter) as f32
+                } else {
+                    1.0
+                });
+                cluster_jitters.push((
+                    options.glyph_jitter.sample(),
+                    glyph.x + x_shift + glyph.w * 0.5,
+                ));
+                if let Some(char_colors) = char_colors {
+                    if run_left == 0 {
+                        run_color = char_colors.sample_color();
+                        run_left = char_colors.sample_run_len();
+                    }
+                    run_left -= 1;
+                }
+                cluster_colors.push(run_color);
+            }
+            let glyph_color = match glyph.color_opt {
+                Some(some) => some,
+                None => cluster_colors[cluster_idx],
+            };
+
+            // the first glyph of a new cluster
+            if cluster_idx == image_clusters.len() {
+                let image = options.image_glyphs.as_ref().and_then(|glyphs| {
+                    glyphs.sample(run.text[glyph.start..glyph.end].trim_matches(ZWNJ))
+                });
+                image_clusters.push(image.is_some());
+                if let Some(img) = image {
+                    let mut tile = image_glyph_tile(
+                        img,
+                        glyph_color,
+                        (glyph.x + x_shift, glyph.w),
+                        run.line_y + padding_top as f32,
+                        glyph.font_size,
+                        cluster_idx,
+                    );
+                    let scale = cluster_scales[cluster_idx];
+                    if scale != 1.0 {
+                        tile.scale(
+                            scale,
+                            glyph.x + x_shift + glyph.w * 0.5,
+                            run.line_y + padding_top as f32,
+                        );
+                    }
+                    options
+                        .faux_style
+                        .apply(&mut tile, run.line_y + padding_top as f32);
+                    let (jitter, center_x) = cluster_jitters[cluster_idx];
+                    options.glyph_jitter.apply(
+                        &mut tile,
+                        jitter,
+                        (
+                            center_x,
+                            run.line_y + padding_top as f32 - glyph.font_size * 0.44,
+                        ),
+                    );
+                    tiles.push(tile);
+                }
+            }
+
+            let placement = if image_clusters[cluster_idx] {
+                None
+            } else {
+                swash_cache
+                    .get_image(font_system, physical_glyph.cache_key)
+                    .as_ref()
+                    .map(|image| image.placement)
+            };
+            if let Some(placement) = placement {
+                let mut tile = GlyphTile {
+                    x: physical_glyph.x + placement.left,
+                    y: run.line_y as i32 + padding_top as i32 + physical_glyph.y - placement.top,
+                    width: placement.width,
+                    height: placement.height,
+                    rgba: vec![0; (placement.width * placement.height * 4) as usize],
+                    cluster: cluster_idx,
+                };
+                swash_cache.with_pixels(
+                    font_system,
+                    physical_glyph.cache_key,
+                    glyph_color,
+                    |x, y, color| {
+                        let idx = ((y + placement.top) as u32 * placement.width
+                            + (x - placement.left) as u32)
+                            as usize
+                            * 4;
+                        tile.rgba[idx..idx + 4].copy_from_slice(&[
+                            color.r(),
+                            color.g(),
+                            color.b(),
+                            color.a(),
+                        ]);
+                    },
+                );
+                let scale = cluster_scales[cluster_idx];
+                if scale != 1.0 {
+                    tile.scale(
+                        scale,
+                        glyph.x + x_shift + glyph.w * 0.5,
+                        run.line_y + padding_top as f32,
+                    );
+                }
+                options
+                    .faux_style
+                    .apply(&mut tile, run.line_y + padding_top as f32);
+                let (jitter, center_x) = cluster_jitters[cluster_idx];
+                options.glyph_jitter.apply(
+                    &mut tile,
+                    jitter,
+                    (
+                        center_x,
+                        run.line_y + padding_top as f32 - glyph.font_size * 0.44,
+                    ),
+                );
+                tiles.push(tile);
+            }
+
+            // Kerning is folded into the advance of the first glyph of a pair, only Latin
+            // clusters are adjusted so that mark and cursive positioning are left alone
+            if !options.kerning.is_default()
+                && glyph.w > 0.0
+                && is_latin_cluster(&run.text[glyph.start..glyph.end])
+            {
+                if let Some(nominal) = nominal_advance(font_system, glyph) {
+                    let kern = glyph.w - nominal;
+                    if kern != 0.0 {
+                        x_shift += kern * (options.kerning.factor() - 1.0);
+                    }
+                }
+            }
+        }
+        spaced_width = spaced_width.max(spaced);
+    }
+    // the canvas grows with loose spacing, so that the end of the line is not cut off
+    width += spaced_width.ceil() as usize;
+
+    let mut clusters: Vec<_> = clusters.into_values().collect();
+    if let Some(vertical) = &options.vertical {
+        (width, height) = vertical.arrange(
+            &mut tiles,
+            &mut clusters,
+            &runs,
+            (metrics.font_size, line_height),
+            column_len,
+            padding,
+        );
+    } else if let Some(text_path) = &options.text_path {
+        text_path.bend(&mut tiles, &mut clusters, &baselines);
+        (width, height) = fit_canvas(&mut tiles, &mut clusters, (width, height), padding);
+    }
+
+    if let Some(decoration) = options
+        .decoration
+        .filter(|_| options.vertical.is_none() && options.text_path.is_none())
+    {
+        // along the clusters of each line, without the spaces at its ends
+        let mut lines: Vec<Option<(f32, f32, fontdb::ID)>> = vec![None; baselines.len()];
+        for cluster in clusters.iter().filter(|each| !each.text.trim().is_empty()) {
+            let (x0, _, x1, _) = cluster.advance;
+            let line = &mut lines[cluster.line];
+            *line = Some(match *line {
+                Some((lx0, lx1, font_id)) => (lx0.min(x0), lx1.max(x1), font_id),
+                None => (x0, x1, cluster.font_id),
+            });
+        }
+        for (line, baseline) in lines.into_iter().zip(baselines.iter()) {
+            if let Some((x0, x1, font_id)) = line {
+                let line_metrics = decoration.metrics(font_system, font_id);
+                tiles.extend(decoration.tile(
+                    (x0, x1),
+                    *baseline,
+                    line_metrics,
+                    metrics.font_size,
+                    foreground_color,
+                ));
+            }
+        }
+    }
+    if options.ink_texture.is_enabled() {
+        apply_ink_texture(&mut tiles, width, height, &options.ink_texture);
+    }
+    // beneath all the glyphs
+    if let Some(shadow) = options
+        .drop_shadow
+        .and_then(|shadow| shadow.tile(&tiles, (width, height)))
+    {
+        tiles.insert(0, shadow);
+    }
+
+    RasterizedText {
+        width,
+        height,
+        padding,
+        vertical: options.vertical,
+        tiles,
+        clusters,
+    }
+}
+
+/// Alpha blend the glyph tiles onto the background on the CPU.
+pub fn composite(
+    tiles: &[GlyphTile],
     background_color: image::Rgb<u8>,
     width: usize,
     height: usize,
 ) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
     let mut raw_image = ImageBuffer::from_pixel(width as u32, height as u32, background_color);
-    let mut right_border = 0;
-    // Draw the buffer (for performance, instead use SwashCache directly)
-    editor.draw(
-        font_system,
-        swash_cache,
-        foreground_color,
-        |x, y, _, _, color| {
+
+    for tile in tiles {
+        for (x, y, px) in tile.pixels() {
             if x < 0 || x >= width as i32 || y < 0 || y >= height as i32 || (x == 0 && y == 0) {
-                return;
-            }
-            if x > right_border {
-                right_border = x
+                continue;
             }
 
-            let (r, g, b, a) = (
-                color.r() as u32,
-                color.g() as u32,
-                color.b() as u32,
-                color.a() as u32,
-            );
+            let (r, g, b, a) = (px[0] as u32, px[1] as u32, px[2] as u32, px[3] as u32);
             let (raw_image_r, raw_image_g, raw_image_b) = unsafe {
                 let tmp = raw_image.unsafe_get_pixel(x as u32, y as u32).0;
                 (tmp[0] as u32, tmp[1] as u32, tmp[2] as u32)
@@ -43,10 +2155,914 @@ pub fn generate_image(
             unsafe {
                 raw_image.unsafe_put_pixel(x as u32, y as u32, rgb);
             }
-        },
-    );
+        }
+    }
 
     raw_image
-        .sub_image(0, 0, (right_border + 1) as u32, height as u32)
-        .to_image()
+}
+
+/// Crop the composited image to the drawn text and collect the cluster boxes. Vertical text
+/// is rotated here if its layout asks for it.
+pub fn finish_image(
+    raw_image: ImageBuffer<image::Rgb<u8>, Vec<u8>>,
+    text: RasterizedText,
+) -> (ImageBuffer<image::Rgb<u8>, Vec<u8>>, Vec<ClusterBox>) {
+    let rotate = text.vertical.is_some_and(|vertical| vertical.rotate);
+    let ((x0, y0), (crop_width, crop_height), clusters) = text.crop();
+    let (width, height) = raw_image.dimensions();
+    let img = if x0 >= 0
+        && y0 >= 0
+        && x0 as u32 + crop_width <= width
+        && y0 as u32 + crop_height <= height
+    {
+        raw_image
+            .view(x0 as u32, y0 as u32, crop_width, crop_height)
+            .to_image()
+    } else {
+        // the padding reaches past the canvas; its top left pixel is never drawn on and
+        // holds the background color
+        let background = *raw_image.get_pixel(0, 0);
+        ImageBuffer::from_fn(crop_width, crop_height, |x, y| {
+            let (x, y) = (x as i32 + x0, y as i32 + y0);
+            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                *raw_image.get_pixel(x as u32, y as u32)
+            } else {
+                background
+            }
+        })
+    };
+
+    if rotate {
+        let clusters = clusters
+            .iter()
+            .map(|each| each.rotated_ccw(crop_width))
+            .collect();
+        (image::imageops::rotate270(&img), clusters)
+    } else {
+        (img, clusters)
+    }
+}
+
+pub fn generate_image(
+    editor: &mut Buffer,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+    foreground_color: cosmic_text::Color,
+    background_color: image::Rgb<u8>,
+    options: &RenderOptions,
+) -> (ImageBuffer<image::Rgb<u8>, Vec<u8>>, Vec<ClusterBox>) {
+    let (width, height) = editor.size();
+    let text = rasterize_text(
+        editor,
+        font_system,
+        swash_cache,
+        foreground_color,
+        width as usize,
+        height as usize,
+        options,
+    );
+    let raw_image = composite(&text.tiles, background_color, text.width, text.height);
+
+    finish_image(raw_image, text)
+}
+
+/// Resize the image to each of `heights`, scaling the width proportionally.
+pub fn resize_to_heights<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    heights: &[u32],
+) -> Vec<ImageBuffer<P, Vec<u8>>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    heights
+        .iter()
+        .map(|&height| {
+            if height == img.height() {
+                return img.clone();
+            }
+            let width = scaled_len(img.width(), height as f64 / img.height() as f64);
+            image::imageops::resize(img, width, height, FilterType::Triangle)
+        })
+        .collect()
+}
+
+fn scaled_len(len: u32, scale: f64) -> u32 {
+    ((len as f64 * scale).round() as u32).max(1)
+}
+
+/// Join the cluster boxes of each line of a wrapped buffer into the text and the box of the
+/// line. Whitespace only counts for the text, and lines without ink are dropped.
+pub fn line_boxes(clusters: &[ClusterBox]) -> Vec<(String, BBox)> {
+    let mut lines: Vec<(String, Option<BBox>)> = vec![];
+    for cluster in clusters {
+        if lines.len() <= cluster.line {
+            lines.resize(cluster.line + 1, (String::new(), None));
+        }
+        let (text, bbox) = &mut lines[cluster.line];
+        text.push_str(&cluster.text);
+        if cluster.text.trim().is_empty() {
+            continue;
+        }
+
+        let (x0, y0, x1, y1) = cluster.bbox;
+        *bbox = Some(match *bbox {
+            Some(line) => (
+                line.0.min(x0),
+                line.1.min(y0),
+                line.2.max(x1),
+                line.3.max(y1),
+            ),
+            None => cluster.bbox,
+        });
+    }
+
+    lines
+        .into_iter()
+        .filter_map(|(text, bbox)| Some((text.trim().to_string(), bbox?)))
+        .collect()
+}
+
+impl ClusterBox {
+    /// The box after an image `width` wide is rotated 90° counterclockwise.
+    pub fn rotated_ccw(&self, width: u32) -> Self {
+        let (x0, y0, x1, y1) = self.bbox;
+
+        Self {
+            text: self.text.clone(),
+            bbox: (y0, width - x1, y1, width - x0),
+            font_id: self.font_id,
+            line: self.line,
+        }
+    }
+
+    /// The box after the image is resized from `from` to `to` (width, height).
+    pub fn resized(&self, from: (u32, u32), to: (u32, u32)) -> Self {
+        let scale_x = to.0 as f64 / from.0 as f64;
+        let scale_y = to.1 as f64 / from.1 as f64;
+        let (x0, y0, x1, y1) = self.bbox;
+
+        Self {
+            text: self.text.clone(),
+            bbox: (
+                (x0 as f64 * scale_x).floor() as u32,
+                (y0 as f64 * scale_y).floor() as u32,
+                ((x1 as f64 * scale_x).ceil() as u32).min(to.0),
+                ((y1 as f64 * scale_y).ceil() as u32).min(to.1),
+            ),
+            font_id: self.font_id,
+            line: self.line,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_line_without_ligatures() {
+        let attrs = Attrs::new();
+        let spans = vec![
+            ("f", attrs),
+            ("i", attrs),
+            ("字", attrs),
+            ("f", attrs),
+            ("l", attrs),
+        ];
+
+        let (line_text, _) = build_line(spans.clone(), attrs, true);
+        assert_eq!(line_text, "fi字fl");
+
+        let (line_text, _) = build_line(spans, attrs, false);
+        assert_eq!(line_text, "f\u{200C}i字f\u{200C}l");
+    }
+
+    #[test]
+    fn test_kerning_factor() {
+        assert!(Kerning::default().is_default());
+        let disabled = Kerning {
+            enabled: false,
+            jitter: 0.5,
+        };
+        assert_eq!(disabled.factor(), 0.0);
+        let jittered = Kerning {
+            enabled: true,
+            jitter: 0.2,
+        };
+        for _ in 0..100 {
+            let factor = jittered.factor();
+            assert!((0.8..=1.2).contains(&factor));
+        }
+    }
+
+    #[test]
+    fn test_char_colors() {
+        let char_colors = CharColors {
+            prob: 1.0,
+            run_len: (2, 2),
+            palette: vec![(200, 30, 30), (20, 60, 160)],
+            hue: Random::new_uniform(0.0, 360.0),
+        };
+        for _ in 0..20 {
+            let color = char_colors.sample_color();
+            assert!([(200, 30, 30), (20, 60, 160)].contains(&(color.r(), color.g(), color.b())));
+            assert_eq!(char_colors.sample_run_len(), 2);
+        }
+
+        let char_colors = CharColors {
+            palette: vec![],
+            run_len: (3, 1),
+            ..char_colors
+        };
+        assert!((1..=3).contains(&char_colors.sample_run_len()));
+        // value 0.6 keeps the strongest channel at 153
+        let color = char_colors.sample_color();
+        assert_eq!(color.r().max(color.g()).max(color.b()), 153);
+    }
+
+    #[test]
+    fn test_color_scheme() {
+        let (black, white) = (image::Rgb([0, 0, 0]), image::Rgb([255, 255, 255]));
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 1e-6);
+        assert_eq!(contrast_ratio(white, white), 1.0);
+
+        let scheme = ColorScheme {
+            prob: 1.0,
+            text_palette: vec![],
+            background_palette: vec![],
+            text_hsv: (
+                Random::new_uniform(0.0, 360.0),
+                Random::new_uniform(0.0, 1.0),
+                Random::new_uniform(0.0, 1.0),
+            ),
+            background_hsv: (
+                Random::new_uniform(0.0, 360.0),
+                Random::new_uniform(0.0, 1.0),
+                Random::new_uniform(0.0, 1.0),
+            ),
+            min_contrast: 4.5,
+        };
+        for _ in 0..50 {
+            let (text, background) = scheme.sample(None, None);
+            assert!(contrast_ratio(text, background) >= 4.5);
+        }
+
+        // the given background is kept, a text color that cannot stand out falls back to black
+        let scheme = ColorScheme {
+            text_palette: vec![(250, 250, 250)],
+            ..scheme
+        };
+        assert_eq!(scheme.sample(None, Some(white)), (black, white));
+        let scheme = ColorScheme {
+            prob: 0.0,
+            ..scheme
+        };
+        assert_eq!(scheme.sample(None, None), (black, white));
+    }
+
+    #[test]
+    fn test_ink_texture() {
+        let mut tiles = vec![GlyphTile {
+            x: -2,
+            y: 3,
+            width: 4,
+            height: 4,
+            rgba: [0, 0, 0, 255].repeat(16),
+            cluster: 0,
+        }];
+        let texture = InkTexture {
+            strength: 0.5,
+            scale: 3.0,
+        };
+        apply_ink_texture(&mut tiles, 10, 10, &texture);
+
+        for (_, _, pixel) in tiles[0].pixels() {
+            assert!((127..=255).contains(&pixel[3]));
+        }
+    }
+
+    #[test]
+    fn test_split_vertical_units() {
+        let units = split_vertical_units("令和2年12月31日ABCD", 2);
+
+        assert_eq!(
+            units,
+            vec![
+                VerticalUnit::Upright("令"),
+                VerticalUnit::Upright("和"),
+                VerticalUnit::Horizontal("2"),
+                VerticalUnit::Upright("年"),
+                VerticalUnit::Horizontal("12"),
+                VerticalUnit::Upright("月"),
+                VerticalUnit::Horizontal("31"),
+                VerticalUnit::Upright("日"),
+                VerticalUnit::Upright("A"),
+                VerticalUnit::Upright("B"),
+                VerticalUnit::Upright("C"),
+                VerticalUnit::Upright("D"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resize_to_heights() {
+        let img = image::GrayImage::from_pixel(300, 64, image::Luma([255]));
+        let res = resize_to_heights(&img, &[32, 48, 64]);
+
+        let sizes: Vec<_> = res.iter().map(|each| each.dimensions()).collect();
+        assert_eq!(sizes, vec![(150, 32), (225, 48), (300, 64)]);
+
+        let cluster = ClusterBox {
+            text: "字".to_string(),
+            bbox: (10, 3, 75, 61),
+            font_id: None,
+            line: 0,
+        };
+        assert_eq!(cluster.resized((300, 64), (150, 32)).bbox, (5, 1, 38, 31));
+    }
+
+    #[test]
+    fn test_line_boxes() {
+        let cluster = |text: &str, bbox, line| ClusterBox {
+            text: text.to_string(),
+            bbox,
+            font_id: None,
+            line,
+        };
+        let clusters = [
+            cluster("一", (2, 4, 20, 22), 0),
+            cluster(" ", (20, 0, 30, 32), 0),
+            cluster("二", (30, 6, 48, 20), 0),
+            cluster(" ", (48, 0, 58, 32), 0),
+            cluster(" ", (0, 32, 10, 64), 1),
+            cluster("三", (3, 36, 21, 54), 2),
+        ];
+
+        assert_eq!(
+            line_boxes(&clusters),
+            vec![
+                ("一 二".to_string(), (2, 4, 48, 22)),
+                ("三".to_string(), (3, 36, 21, 54)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_width() {
+        let mut font_system = FontSystem::new();
+        font_system.db_mut().load_fonts_dir("./font");
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(50.0, 64.0));
+        buffer.set_size(&mut font_system, 200.0, 64.0);
+
+        let line_width = LineWidth {
+            width: 200.0,
+            max_width: Some(8192.0),
+        };
+        line_width.prepare(&mut buffer, &mut font_system, None);
+        buffer.lines.clear();
+        buffer.lines.push(cosmic_text::BufferLine::new(
+            "天地玄黃宇宙洪荒日月盈昃辰宿列張",
+            AttrsList::new(Attrs::new()),
+            cosmic_text::Shaping::Advanced,
+        ));
+        buffer.shape_until_scroll(&mut font_system, false);
+        line_width.fit(&mut buffer, &mut font_system, None);
+
+        let width = buffer.size().0;
+        assert!(width > 200.0 && width < 8192.0);
+        assert_eq!(buffer.layout_runs().count(), 1);
+
+        line_width.prepare(&mut buffer, &mut font_system, Some(300.0));
+        line_width.fit(&mut buffer, &mut font_system, Some(300.0));
+        assert_eq!(buffer.size().0, 300.0);
+    }
+
+    #[test]
+    fn test_spacing() {
+        let mut font_system = FontSystem::new();
+        font_system.db_mut().load_fonts_dir("./font");
+        let mut swash_cache = SwashCache::new();
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(50.0, 64.0));
+        buffer.set_size(&mut font_system, 400.0, 64.0);
+        buffer.lines.clear();
+        buffer.lines.push(cosmic_text::BufferLine::new(
+            "天 地玄",
+            AttrsList::new(Attrs::new()),
+            cosmic_text::Shaping::Advanced,
+        ));
+        buffer.shape_until_scroll(&mut font_system, false);
+
+        let mut rasterize = |spacing| {
+            let options = RenderOptions {
+                spacing,
+                ..Default::default()
+            };
+            let text = rasterize_text(
+                &mut buffer,
+                &mut font_system,
+                &mut swash_cache,
+                cosmic_text::Color::rgb(0, 0, 0),
+                400,
+                64,
+                &options,
+            );
+            let width = text.width;
+            let (_, _, clusters) = text.crop();
+            (width, clusters)
+        };
+        let (width, plain) = rasterize(Spacing::default());
+        let (spaced_width, spaced) = rasterize(Spacing {
+            letter: 10.0,
+            word: 20.0,
+        });
+
+        assert_eq!(spaced_width, width + 50);
+        let shifts: Vec<i64> = plain
+            .iter()
+            .zip(spaced.iter())
+            .map(|(plain, spaced)| spaced.bbox.0 as i64 - plain.bbox.0 as i64)
+            .collect();
+        // the word spacing comes after the space only
+        assert_eq!(shifts, vec![0, 10, 40, 50]);
+    }
+
+    #[test]
+    fn test_reseeded_lines() {
+        // `gen_confusable_images` reseeds before each member, so that only the member differs
+        let mut font_system = FontSystem::new();
+        let mut swash_cache = SwashCache::new();
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(32.0, 48.0));
+        buffer.set_size(&mut font_system, 200.0, 48.0);
+        let options = RenderOptions {
+            kerning: Kerning {
+                enabled: true,
+                jitter: 0.3,
+            },
+            glyph_size_jitter: 0.2,
+            glyph_jitter: GlyphJitter {
+                baseline: Some(Random::new_uniform(-3.0, 3.0)),
+                rotation: Some(Random::new_uniform(-10.0, 10.0)),
+            },
+            ..Default::default()
+        };
+
+        let mut render = |text: &str| {
+            buffer.lines.clear();
+            buffer.lines.push(BufferLine::new(
+                text,
+                AttrsList::new(Attrs::new().family(cosmic_text::Family::Monospace)),
+                cosmic_text::Shaping::Advanced,
+            ));
+            buffer.shape_until_scroll(&mut font_system, false);
+            crate::rng::reseed(7);
+            generate_image(
+                &mut buffer,
+                &mut font_system,
+                &mut swash_cache,
+                cosmic_text::Color::rgb(0, 0, 0),
+                image::Rgb([255, 255, 255]),
+                &options,
+            )
+        };
+        let (letter, letter_clusters) = render("abOcd");
+        let (digit, digit_clusters) = render("ab0cd");
+
+        assert_eq!(letter.dimensions(), digit.dimensions());
+        assert_ne!(letter, digit);
+        let member_x0 = letter_clusters[2].bbox.0.min(digit_clusters[2].bbox.0);
+        let member_x1 = letter_clusters[2].bbox.2.max(digit_clusters[2].bbox.2);
+        for (x, y, px) in letter.enumerate_pixels() {
+            if x < member_x0 || x >= member_x1 {
+                assert_eq!(px, digit.get_pixel(x, y), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scale_glyph_tile() {
+        let mut tile = GlyphTile {
+            x: 10,
+            y: 20,
+            width: 4,
+            height: 10,
+            rgba: vec![255; 4 * 10 * 4],
+            cluster: 0,
+        };
+        // the baseline is at y = 30
+        tile.scale(1.5, 12.0, 30.0);
+
+        assert_eq!((tile.x, tile.y, tile.width, tile.height), (9, 15, 6, 15));
+        assert_eq!(tile.rgba.len(), 6 * 15 * 4);
+
+        // a color glyph keeps its color at the edges next to transparent black
+        let mut tile = GlyphTile {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 1,
+            rgba: vec![255, 200, 0, 255, 0, 0, 0, 0],
+            cluster: 0,
+        };
+        tile.scale(2.0, 0.0, 0.0);
+        for px in tile.rgba.chunks_exact(4).filter(|px| px[3] > 0) {
+            assert_eq!(px[..3], [255, 200, 0]);
+        }
+    }
+
+    #[test]
+    fn test_rotate_glyph_tile() {
+        let tile = GlyphTile {
+            x: 10,
+            y: 20,
+            width: 8,
+            height: 2,
+            rgba: [0, 0, 0, 255].repeat(16),
+            cluster: 0,
+        };
+        let mut rotated = tile.clone();
+        rotated.rotate(0.0, 14.0, 21.0);
+        assert_eq!(rotated.rgba, tile.rgba);
+
+        // a quarter turn around the center stands the bar upright
+        rotated.rotate(std::f32::consts::FRAC_PI_2, 14.0, 21.0);
+        assert!(rotated.width <= 4 && rotated.height >= 8);
+        assert!((rotated.x..rotated.x + rotated.width as i32).contains(&13));
+        assert!((rotated.y..rotated.y + rotated.height as i32).contains(&17));
+        let ink: u32 = rotated.rgba.chunks_exact(4).map(|px| px[3] as u32).sum();
+        assert!(ink.abs_diff(16 * 255) < 16 * 255 / 20);
+    }
+
+    #[test]
+    fn test_drop_shadow() {
+        let tiles = vec![GlyphTile {
+            x: 4,
+            y: 4,
+            width: 2,
+            height: 2,
+            rgba: [0, 0, 0, 255].repeat(4),
+            cluster: 0,
+        }];
+        let shadow = DropShadow {
+            offset: (1, 2),
+            sigma: 0.0,
+            opacity: 0.5,
+        };
+        let tile = shadow.tile(&tiles, (20, 20)).unwrap();
+        assert_eq!((tile.x, tile.y, tile.width, tile.height), (5, 6, 2, 2));
+        assert_eq!(tile.rgba, [0, 0, 0, 128].repeat(4));
+        assert_eq!(tile.cluster, GlyphTile::NO_CLUSTER);
+
+        // the blur spreads the shadow, the canvas clips it
+        let shadow = DropShadow {
+            sigma: 1.0,
+            ..shadow
+        };
+        let tile = shadow.tile(&tiles, (8, 20)).unwrap();
+        assert_eq!((tile.x, tile.y), (2, 3));
+        assert_eq!(tile.x + tile.width as i32, 8);
+        assert!(shadow.tile(&tiles, (3, 3)).is_none());
+        assert!(shadow.tile(&[], (20, 20)).is_none());
+    }
+
+    #[test]
+    fn test_decoration() {
+        let color = cosmic_text::Color::rgb(0, 0, 0);
+        let underline = Decoration {
+            line: DecorationLine::Underline,
+            style: DecorationStyle::Solid,
+            thickness: 1.0,
+        };
+        assert_eq!(underline.name(), "underline");
+        // 2 pixels thick, centered 4 pixels below the baseline
+        let tile = underline
+            .tile((10.0, 50.0), 20.0, (-0.1, 0.05), 40.0, color)
+            .unwrap();
+        assert_eq!((tile.x, tile.width), (10, 40));
+        let alpha = |tile: &GlyphTile, x: i32, y: i32| {
+            let (col, row) = (x - tile.x, y - tile.y);
+            if !(0..tile.width as i32).contains(&col) || !(0..tile.height as i32).contains(&row) {
+                return 0;
+            }
+            tile.rgba[(row as usize * tile.width as usize + col as usize) * 4 + 3]
+        };
+        assert_eq!(alpha(&tile, 30, 23), 255);
+        assert_eq!(alpha(&tile, 30, 24), 255);
+        assert_eq!(alpha(&tile, 30, 21), 0);
+        assert_eq!(alpha(&tile, 30, 26), 0);
+
+        // a dash of 10 pixels, then a gap
+        let dashed = Decoration {
+            style: DecorationStyle::Dashed,
+            ..underline
+        };
+        assert_eq!(dashed.name(), "dashed_underline");
+        let tile = dashed
+            .tile((10.0, 50.0), 20.0, (-0.1, 0.05), 40.0, color)
+            .unwrap();
+        assert_eq!(alpha(&tile, 15, 23), 255);
+        assert_eq!(alpha(&tile, 22, 23), 0);
+
+        let wavy = Decoration {
+            line: DecorationLine::Strikethrough,
+            style: DecorationStyle::Wavy,
+            ..underline
+        };
+        assert_eq!(wavy.name(), "wavy_strikethrough");
+        let tile = wavy
+            .tile((10.0, 50.0), 20.0, (0.3, 0.05), 40.0, color)
+            .unwrap();
+        assert!(tile.y + (tile.height as i32) < 20);
+        let rows: Vec<i32> = (10..50)
+            .filter_map(|x| (0..40).find(|&y| alpha(&tile, x, y) == 255))
+            .collect();
+        assert!(rows.iter().max().unwrap() - rows.iter().min().unwrap() >= 3);
+        assert!(wavy
+            .tile((10.0, 10.0), 20.0, (0.3, 0.05), 40.0, color)
+            .is_none());
+    }
+
+    #[test]
+    fn test_text_path() {
+        let arc = TextPath::Arc { bend: 0.25 };
+        assert_eq!(arc.place(0.0, 400.0), (0.0, 0.0, 0.0));
+        let (left, right) = (arc.place(-200.0, 400.0), arc.place(200.0, 400.0));
+        // the ends bow down and inwards symmetrically, and turn towards the middle
+        assert!(right.1 > 50.0 && right.1 <= 100.0);
+        assert!((left.1 - right.1).abs() < 1e-3);
+        assert!(right.0 < 0.0 && (left.0 + right.0).abs() < 1e-3);
+        assert!(right.2 > 0.0 && (left.2 + right.2).abs() < 1e-5);
+        let (_, dy, _) = TextPath::Arc { bend: -0.25 }.place(200.0, 400.0);
+        assert!((dy + right.1).abs() < 1e-3);
+
+        let wave = TextPath::Wave {
+            amplitude: 10.0,
+            period: 100.0,
+            phase: 0.0,
+        };
+        for t in [-80.0, -25.0, 0.0, 25.0, 60.0] {
+            let (dx, dy, _) = wave.place(t, 400.0);
+            assert_eq!(dx, 0.0);
+            assert!(dy.abs() <= 10.0);
+        }
+        assert!((wave.place(25.0, 400.0).1 - 10.0).abs() < 1e-3);
+
+        // the ink pushed above the top border is moved back below the padding
+        let mut tiles = vec![GlyphTile {
+            x: 4,
+            y: -6,
+            width: 4,
+            height: 4,
+            rgba: [0, 0, 0, 255].repeat(16),
+            cluster: 0,
+        }];
+        let padding = Padding {
+            top: 2,
+            bottom: 3,
+            ..Default::default()
+        };
+        let size = fit_canvas(&mut tiles, &mut [], (20, 10), padding);
+        assert_eq!((tiles[0].x, tiles[0].y), (4, 2));
+        assert_eq!(size, (20, 10));
+        tiles[0].y = 8;
+        assert_eq!(fit_canvas(&mut tiles, &mut [], (20, 10), padding), (20, 15));
+    }
+
+    #[test]
+    fn test_faux_style() {
+        let mut tile = GlyphTile {
+            x: 10,
+            y: 20,
+            width: 1,
+            height: 1,
+            rgba: vec![0, 0, 0, 255],
+            cluster: 0,
+        };
+        tile.embolden(1);
+        assert_eq!((tile.x, tile.y, tile.width, tile.height), (9, 19, 3, 3));
+        let alpha: Vec<u8> = tile.rgba.chunks_exact(4).map(|px| px[3]).collect();
+        assert_eq!(alpha, vec![0, 255, 0, 255, 255, 255, 0, 255, 0]);
+
+        // a vertical bar standing on the baseline at y = 23 leans to the right
+        let mut tile = GlyphTile {
+            x: 10,
+            y: 20,
+            width: 1,
+            height: 3,
+            rgba: vec![255; 3 * 4],
+            cluster: 0,
+        };
+        tile.shear(1.0, 23.0);
+        assert_eq!((tile.x, tile.width, tile.height), (10, 5, 3));
+        let center = |row: usize| {
+            let alpha: Vec<f32> = (0..5)
+                .map(|col| tile.rgba[(row * 5 + col) * 4 + 3] as f32)
+                .collect();
+            let sum: f32 = alpha.iter().sum();
+            alpha
+                .iter()
+                .enumerate()
+                .map(|(col, a)| col as f32 * a)
+                .sum::<f32>()
+                / sum
+        };
+        assert!((center(0) - center(2) - 2.0).abs() < 0.01);
+        assert!(tile
+            .rgba
+            .chunks_exact(4)
+            .all(|px| px[3] == 0 || px[0] == 255));
+
+        let styling = FauxStyling {
+            bold_prob: 1.0,
+            bold_width: 0.04,
+            italic_prob: 0.0,
+            italic_slant: 0.2,
+        };
+        let style = styling.sample(50.0);
+        assert_eq!(
+            style,
+            FauxStyle {
+                bold: 2,
+                italic: 0.0
+            }
+        );
+        assert_eq!(style.names(), vec!["faux_bold"]);
+        assert!(FauxStyle::default().names().is_empty());
+    }
+
+    #[test]
+    fn test_split_by_width() {
+        // "ab cd ef" with one unit per character
+        let widths = [10.0, 10.0, 5.0, 10.0, 10.0, 5.0, 10.0, 10.0];
+        let breaks = [false, false, true, false, false, true, false, false];
+        assert_eq!(split_by_width(&widths, &breaks, 48.0), vec![0..5, 6..8]);
+        assert_eq!(
+            split_by_width(&widths, &breaks, 30.0),
+            vec![0..2, 3..5, 6..8]
+        );
+
+        // no break opportunity, and a unit wider than the line
+        let widths = [10.0, 40.0, 10.0, 10.0];
+        let breaks = [false; 4];
+        assert_eq!(
+            split_by_width(&widths, &breaks, 25.0),
+            vec![0..1, 1..2, 2..4]
+        );
+        assert!(split_by_width(&[5.0], &[true], 25.0).is_empty());
+    }
+
+    #[test]
+    fn test_cluster_boxes_after_crop() {
+        let text = RasterizedText {
+            width: 20,
+            height: 10,
+            padding: Padding::default(),
+            vertical: None,
+            tiles: vec![GlyphTile {
+                x: 2,
+                y: 2,
+                width: 4,
+                height: 4,
+                rgba: [0, 0, 0, 255].repeat(16),
+                cluster: 0,
+            }],
+            clusters: vec![
+                ClusterState::new("字", fontdb::ID::dummy(), 0, 1.0, 6.0, 0.0, 10.0),
+                // a trailing space has no ink, its advance box reaches past the crop
+                ClusterState::new(" ", fontdb::ID::dummy(), 0, 7.0, 5.0, 0.0, 10.0),
+            ],
+        };
+
+        let (_, (crop_width, _), clusters) = text.crop();
+        assert_eq!(crop_width, 6);
+        assert_eq!(clusters[0].text, "字");
+        assert_eq!(clusters[0].bbox, (2, 2, 6, 6));
+        assert_eq!(clusters[1].bbox, (6, 0, 6, 10));
+    }
+
+    #[test]
+    fn test_measure_right_padding() {
+        let text = |right| RasterizedText {
+            width: 20,
+            height: 10,
+            padding: Padding {
+                right,
+                ..Default::default()
+            },
+            vertical: None,
+            tiles: vec![GlyphTile {
+                x: 2,
+                y: 2,
+                width: 4,
+                height: 4,
+                rgba: [0, 0, 0, 255].repeat(16),
+                cluster: GlyphTile::NO_CLUSTER,
+            }],
+            clusters: vec![],
+        };
+
+        assert_eq!(text(0).crop().1, (6, 10));
+        assert_eq!(text(5).crop().1, (11, 10));
+        assert_eq!(text(30).crop().1, (20, 10));
+    }
+
+    #[test]
+    fn test_tight_crop() {
+        let text = RasterizedText {
+            width: 20,
+            height: 10,
+            padding: Padding {
+                top: 1,
+                bottom: 2,
+                left: 3,
+                right: 4,
+                tight: true,
+            },
+            vertical: None,
+            tiles: vec![GlyphTile {
+                x: 2,
+                y: 2,
+                width: 4,
+                height: 4,
+                rgba: [0, 0, 0, 255].repeat(16),
+                cluster: 0,
+            }],
+            clusters: vec![ClusterState::new(
+                "字",
+                fontdb::ID::dummy(),
+                0,
+                1.0,
+                6.0,
+                0.0,
+                10.0,
+            )],
+        };
+
+        // the left padding reaches past the canvas
+        let raw_image = ImageBuffer::from_fn(20, 10, |x, y| {
+            if (2..6).contains(&x) && (2..6).contains(&y) {
+                image::Rgb([0, 0, 0])
+            } else {
+                image::Rgb([255, 255, 255])
+            }
+        });
+        let (img, clusters) = finish_image(raw_image, text);
+        assert_eq!(img.dimensions(), (4 + 3 + 4, 4 + 1 + 2));
+        assert_eq!(clusters[0].bbox, (3, 1, 7, 5));
+        assert_eq!(img.get_pixel(0, 0), &image::Rgb([255, 255, 255]));
+        assert_eq!(img.get_pixel(3, 1), &image::Rgb([0, 0, 0]));
+        assert_eq!(img.get_pixel(7, 5), &image::Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn test_arrange_vertical_columns() {
+        let vertical = VerticalLayout {
+            tcy_max_len: 2,
+            column_spacing: 4,
+            rotate: false,
+        };
+        let tile = |cluster| GlyphTile {
+            x: 0,
+            y: 4,
+            width: 10,
+            height: 10,
+            rgba: [0, 0, 0, 255].repeat(100),
+            cluster,
+        };
+        let mut tiles: Vec<_> = (0..3).map(tile).collect();
+        // three units 10 px wide on lines 16 px tall, the last one a tate-chū-yoko run
+        let mut clusters: Vec<_> = (0..3)
+            .map(|line| {
+                let top = line as f32 * 16.0;
+                ClusterState::new("字", fontdb::ID::dummy(), line, 0.0, 10.0, top, 16.0)
+            })
+            .collect();
+        tiles[1].y += 16;
+        tiles[2].y += 32;
+        let runs = [(0.0, 10.0), (16.0, 10.0), (32.0, 20.0)];
+
+        // two units per column of 25 px at an em of 12 px
+        let size = vertical.arrange(
+            &mut tiles,
+            &mut clusters,
+            &runs,
+            (12.0, 16.0),
+            25.0,
+            Padding::default(),
+        );
+        assert_eq!(size, (36, 24));
+        // the first column is on the right
+        assert_eq!((tiles[0].x, tiles[0].y), (23, 2));
+        assert_eq!((tiles[1].x, tiles[1].y), (23, 14));
+        // the run wider than an em is shrunk to one em around its center
+        assert_eq!((tiles[2].x, tiles[2].y), (2, 4));
+        assert_eq!((tiles[2].width, tiles[2].height), (6, 6));
+
+        let cluster = ClusterBox {
+            text: "字".to_string(),
+            bbox: (23, 1, 33, 11),
+            font_id: None,
+            line: 0,
+        };
+        assert_eq!(cluster.rotated_ccw(36).bbox, (1, 3, 11, 13));
+    }
 }
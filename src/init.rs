@@ -1,5 +1,11 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
 use indexmap::IndexMap;
 use rand_distr::WeightedAliasIndex;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{font_util::FontUtil, utils::InternalAttrsOwned};
 
@@ -12,10 +18,7 @@ pub fn init_ch_dict<'a, 'b, I: Iterator<Item = &'b S>, S: AsRef<str> + 'b + ?Siz
 
     for (ch_str, ch_font_list) in ch_list.iter_mut() {
         for font_attrs in full_font_list.iter() {
-            if ch_str
-                .as_ref()
-                .chars()
-                .all(|each_ch| font_util.is_font_contain_ch(font_attrs.as_attrs(), each_ch))
+            if font_util.is_font_contain_cluster(font_attrs.as_attrs(), ch_str.as_ref())
                 && !ch_font_list.contains(font_attrs)
             {
                 ch_font_list.push(font_attrs.clone());
@@ -44,6 +47,19 @@ pub fn init_ch_dict_and_weight<'a, 'b>(
     IndexMap<&'b str, Vec<InternalAttrsOwned>>,
     WeightedAliasIndex<f64>,
 ) {
+    let (ch_list, ch_list_freqs) =
+        init_ch_dict_and_freq(font_util, full_font_list, character_file_data);
+
+    (ch_list, WeightedAliasIndex::new(ch_list_freqs).unwrap())
+}
+
+/// Same as `init_ch_dict_and_weight`, but returns the frequency of each character (in the order
+/// of the dict) instead of the weight table, so that the table can be rebuilt later.
+pub fn init_ch_dict_and_freq<'b>(
+    font_util: &mut FontUtil,
+    full_font_list: &[InternalAttrsOwned],
+    character_file_data: &'b str,
+) -> (IndexMap<&'b str, Vec<InternalAttrsOwned>>, Vec<f64>) {
     let mut is_all_freq_empty = true;
     let mut ch_list_and_weight: Vec<_> = character_file_data
         .trim()
@@ -70,9 +86,7 @@ pub fn init_ch_dict_and_weight<'a, 'b>(
 
     for (ch_str, _, ch_font_list) in ch_list_and_weight.iter_mut() {
         for font_attrs in full_font_list.iter() {
-            if ch_str
-                .chars()
-                .all(|each_ch| font_util.is_font_contain_ch(font_attrs.as_attrs(), each_ch))
+            if font_util.is_font_contain_cluster(font_attrs.as_attrs(), ch_str)
                 && !ch_font_list.contains(font_attrs)
             {
                 ch_font_list.push(font_attrs.clone());
@@ -80,26 +94,205 @@ pub fn init_ch_dict_and_weight<'a, 'b>(
         }
     }
 
-    let ch_list_weights = WeightedAliasIndex::new(
-        ch_list_and_weight
-            .iter()
-            .map(|(_, weight, _)| match weight {
-                Frequence::NUM(value) => *value,
-                Frequence::MIN => {
-                    if is_all_freq_empty {
-                        1.0
-                    } else {
-                        0.0
-                    }
+    let ch_list_freqs = ch_list_and_weight
+        .iter()
+        .map(|(_, weight, _)| match weight {
+            Frequence::NUM(value) => *value,
+            Frequence::MIN => {
+                if is_all_freq_empty {
+                    1.0
+                } else {
+                    0.0
                 }
-            })
-            .collect::<Vec<_>>(),
-    )
-    .unwrap();
+            }
+        })
+        .collect::<Vec<_>>();
     let ch_list: IndexMap<&str, Vec<InternalAttrsOwned>> = ch_list_and_weight
         .into_iter()
         .map(|(ch, _, font_list)| (ch, font_list))
         .collect();
 
-    (ch_list, ch_list_weights)
+    (ch_list, ch_list_freqs)
+}
+
+/// Replace the frequency column of the character file with the number of times each character
+/// occurs in a raw corpus, plus `smoothing` (additive smoothing, 1 is Laplace) so that the
+/// characters missing from the corpus can still be sampled; with 0 they are never sampled. The
+/// result can be passed to `init_ch_dict_and_weight`.
+pub fn count_corpus_freqs(character_file_data: &str, corpus: &str, smoothing: f64) -> String {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for cluster in corpus.graphemes(true) {
+        *counts.entry(cluster).or_default() += 1;
+    }
+
+    character_file_data
+        .trim()
+        .split("\n")
+        .map(|each| {
+            let ch = each.trim().split("\t").next().unwrap();
+            let count = counts.get(ch).copied().unwrap_or(0) as f64;
+            format!("{}\t{}", ch, count + smoothing)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The characters (grapheme clusters) of a charset file, e.g. one per line as in the label
+/// dicts of recognizers; whitespace is skipped and repeated characters are kept once.
+pub fn read_char_file(path: &str) -> Result<Vec<String>, String> {
+    let data =
+        fs::read_to_string(path).map_err(|err| format!("fail to read `{}`: {}", path, err))?;
+    let mut seen = HashSet::new();
+
+    Ok(data
+        .graphemes(true)
+        .filter(|cluster| !cluster.trim().is_empty() && seen.insert(*cluster))
+        .map(String::from)
+        .collect())
+}
+
+/// Turn the frequencies from the character file into sampling weights: `1 / freq` if
+/// `inverse`, then raised to `1 / temperature`. Characters with a zero frequency stay
+/// unsampled.
+pub fn scale_freqs(freqs: &[f64], inverse: bool, temperature: f64) -> Vec<f64> {
+    freqs
+        .iter()
+        .map(|&freq| {
+            if freq <= 0.0 {
+                return 0.0;
+            }
+            let base = if inverse { freq.recip() } else { freq };
+            base.powf(temperature.recip())
+        })
+        .collect()
+}
+
+/// The fonts that can render every cluster of each word, from the font lists of `ch_dict`, so
+/// that a word of a joining script (e.g. Arabic) can be shaped with a single font. A word with a
+/// cluster missing from `ch_dict` gets an empty list.
+pub fn init_word_dict<'a, I: Iterator<Item = &'a str>>(
+    words: I,
+    ch_dict: &IndexMap<String, Vec<InternalAttrsOwned>>,
+) -> IndexMap<String, Vec<InternalAttrsOwned>> {
+    words
+        .map(|word| {
+            let mut clusters = word.graphemes(true);
+            let mut font_list = clusters
+                .next()
+                .and_then(|cluster| ch_dict.get(cluster))
+                .cloned()
+                .unwrap_or_default();
+            for cluster in clusters {
+                let covering = ch_dict.get(cluster);
+                font_list.retain(|font| covering.is_some_and(|each| each.contains(font)));
+            }
+
+            (word.to_string(), font_list)
+        })
+        .collect()
+}
+
+/// Characters that no loaded font can render, they are drawn with a font of `main_font_list`
+/// and most likely come out as tofu.
+pub fn uncovered_chars<S: AsRef<str>>(ch_dict: &IndexMap<S, Vec<InternalAttrsOwned>>) -> Vec<&str> {
+    ch_dict
+        .iter()
+        .filter(|(_, font_list)| font_list.is_empty())
+        .map(|(ch, _)| ch.as_ref())
+        .collect()
+}
+
+/// Characters outside the BMP (e.g. CJK Ext B–G) that no loaded font can render.
+pub fn uncovered_supplementary_chars<S: AsRef<str>>(
+    ch_dict: &IndexMap<S, Vec<InternalAttrsOwned>>,
+) -> Vec<&str> {
+    uncovered_chars(ch_dict)
+        .into_iter()
+        .filter(|ch| ch.chars().any(|each_ch| each_ch as u32 > 0xFFFF))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use cosmic_text::{Attrs, AttrsOwned};
+
+    use super::*;
+
+    #[test]
+    fn test_scale_freqs() {
+        let freqs = [4.0, 1.0, 0.0];
+
+        assert_eq!(scale_freqs(&freqs, false, 1.0), vec![4.0, 1.0, 0.0]);
+        assert_eq!(scale_freqs(&freqs, true, 1.0), vec![0.25, 1.0, 0.0]);
+        assert_eq!(scale_freqs(&freqs, false, 2.0), vec![2.0, 1.0, 0.0]);
+        assert_eq!(scale_freqs(&freqs, true, 2.0), vec![0.5, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_uncovered_chars() {
+        let font = InternalAttrsOwned::new(AttrsOwned::new(Attrs::new()));
+        let ch_dict: IndexMap<&str, Vec<InternalAttrsOwned>> =
+            [("天", vec![font]), ("地", vec![]), ("\u{20000}", vec![])]
+                .into_iter()
+                .collect();
+
+        assert_eq!(uncovered_chars(&ch_dict), vec!["地", "\u{20000}"]);
+        assert_eq!(uncovered_supplementary_chars(&ch_dict), vec!["\u{20000}"]);
+    }
+
+    #[test]
+    fn test_init_word_dict() {
+        let font = |family: &'static str| {
+            InternalAttrsOwned::new(AttrsOwned::new(
+                Attrs::new().family(cosmic_text::Family::Name(family)),
+            ))
+        };
+        let ch_dict: IndexMap<String, Vec<InternalAttrsOwned>> = [
+            ("س", vec![font("A"), font("B")]),
+            ("ل", vec![font("B")]),
+            ("ا", vec![font("A"), font("B")]),
+            ("م", vec![font("A"), font("B")]),
+        ]
+        .into_iter()
+        .map(|(ch, font_list)| (ch.to_string(), font_list))
+        .collect();
+
+        let word_dict = init_word_dict(["سم", "سلام", "سx"].into_iter(), &ch_dict);
+        assert_eq!(word_dict["سم"], vec![font("A"), font("B")]);
+        assert_eq!(word_dict["سلام"], vec![font("B")]);
+        assert!(word_dict["سx"].is_empty());
+    }
+
+    #[test]
+    fn test_read_char_file() {
+        let path = std::env::temp_dir().join("tig_read_char_file_test.txt");
+        fs::write(&path, "天\n地\r\n 玄 天\ne\u{301}\n").unwrap();
+        assert_eq!(
+            read_char_file(path.to_str().unwrap()).unwrap(),
+            vec!["天", "地", "玄", "e\u{301}"]
+        );
+        fs::remove_file(&path).unwrap();
+
+        assert!(read_char_file("./no_such_charset.txt").is_err());
+    }
+
+    #[test]
+    fn test_count_corpus_freqs() {
+        let character_file_data = "天\t5\n地\n玄\n";
+        let corpus = "天地天，天！";
+
+        assert_eq!(
+            count_corpus_freqs(character_file_data, corpus, 1.0),
+            "天\t4\n地\t2\n玄\t1"
+        );
+        assert_eq!(
+            count_corpus_freqs(character_file_data, corpus, 0.5),
+            "天\t3.5\n地\t1.5\n玄\t0.5"
+        );
+        // without smoothing a character missing from the corpus gets no weight
+        assert_eq!(
+            count_corpus_freqs(character_file_data, corpus, 0.0),
+            "天\t3\n地\t1\n玄\t0"
+        );
+    }
 }
@@ -0,0 +1,106 @@
+//! Label noise: with a small probability the transcript of a sample is perturbed (one cluster
+//! substituted, deleted or inserted) while the image keeps the original text.
+
+use std::collections::HashMap;
+
+use rand::{seq::SliceRandom, Rng};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::rng::sample_rng;
+
+#[derive(Clone, Debug, Default)]
+pub struct LabelNoise {
+    pub prob: f64,
+    // each member of a confusable group maps to the other members
+    confusions: HashMap<String, Vec<String>>,
+}
+
+impl LabelNoise {
+    pub fn new(prob: f64, confusable_groups: &[Vec<String>]) -> Self {
+        let mut confusions: HashMap<String, Vec<String>> = HashMap::new();
+        for group in confusable_groups {
+            for member in group {
+                let others = group.iter().filter(|each| *each != member).cloned();
+                confusions.entry(member.clone()).or_default().extend(others);
+            }
+        }
+
+        Self { prob, confusions }
+    }
+
+    /// With probability `prob`, returns the label with one cluster substituted (by a confusable
+    /// cluster if the confusion table has one, otherwise by `random_cluster`), deleted, or
+    /// inserted (from `random_cluster`).
+    pub fn perturb<F: FnMut() -> String>(
+        &self,
+        label: &str,
+        mut random_cluster: F,
+    ) -> Option<String> {
+        let mut rng = sample_rng();
+        if !rng.gen_bool(self.prob) {
+            return None;
+        }
+
+        let mut clusters: Vec<String> = label.graphemes(true).map(String::from).collect();
+        let op = if clusters.is_empty() {
+            2
+        } else {
+            rng.gen_range(0..3)
+        };
+        match op {
+            0 => {
+                let idx = rng.gen_range(0..clusters.len());
+                let substitute = match self.confusions.get(&clusters[idx]) {
+                    Some(others) => others.choose(&mut rng).unwrap().clone(),
+                    None => {
+                        // a few tries to draw a different cluster
+                        let mut substitute = random_cluster();
+                        for _ in 0..8 {
+                            if substitute != clusters[idx] {
+                                break;
+                            }
+                            substitute = random_cluster();
+                        }
+                        substitute
+                    }
+                };
+                clusters[idx] = substitute;
+            }
+            1 => {
+                clusters.remove(rng.gen_range(0..clusters.len()));
+            }
+            _ => {
+                let idx = rng.gen_range(0..=clusters.len());
+                clusters.insert(idx, random_cluster());
+            }
+        }
+
+        Some(clusters.concat())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_label_noise() {
+        let groups = vec![vec!["己".to_string(), "已".to_string(), "巳".to_string()]];
+
+        let noise = LabelNoise::new(0.0, &groups);
+        assert_eq!(noise.perturb("自己", || "天".to_string()), None);
+
+        let noise = LabelNoise::new(1.0, &groups);
+        for _ in 0..100 {
+            let noisy = noise.perturb("己", || "天".to_string()).unwrap();
+            assert!(["已", "巳", "", "天己", "己天"].contains(&noisy.as_str()));
+        }
+
+        let noisy = noise.perturb("", || "天".to_string()).unwrap();
+        assert_eq!(noisy, "天");
+
+        let noisy = noise.perturb("天地玄黃", || "宇".to_string()).unwrap();
+        let len = noisy.graphemes(true).count();
+        assert!((3..=5).contains(&len) && noisy != "天地玄黃");
+    }
+}
@@ -1,218 +1,974 @@
-use std::fs;
-
-use corpus::{get_random_chinese_text_with_font_list, wrap_text_with_font_list};
-use cosmic_text::{
-    Attrs, AttrsList, Buffer, BufferLine, Color, Family, FontSystem, Metrics, Style, SwashCache,
-    Weight,
+#[cfg(feature = "python")]
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::Arc,
 };
-use cv_util::CvUtil;
-use font_util::FontUtil;
-use image_process::generate_image;
-use indexmap::IndexMap;
-use merge_util::{BgFactory, MergeUtil};
-use numpy::{PyArray, PyArrayDyn};
-use parse_config::Config;
-use pyo3::{prelude::*, types::PyList};
-use rand_distr::WeightedAliasIndex;
-use utils::InternalAttrsOwned;
-
-use crate::{
-    init::{init_ch_dict, init_ch_dict_and_weight},
-    utils::StringUsefulUtils,
+
+#[cfg(feature = "python")]
+use {
+    builder::GeneratorBuilder,
+    coco::CocoExporter,
+    corpus::{
+        count_rtl_words, count_words, get_random_chinese_text_by_width,
+        get_random_chinese_text_with_font_list, get_random_mixed_line_with_font_list,
+        get_random_mixed_text_with_font_list, get_random_word_units_with_font_list,
+        get_random_words_with_font_list, wrap_text_with_dicts, wrap_words_with_font_list, Script,
+        ScriptConverter, SpaceInsertion, SymbolInsertion, TextPool, DIGITS,
+    },
+    cosmic_text::{Attrs, BufferLine, Color, Family, FontSystem, Metrics, Style, Weight},
+    cv_util::{AppliedEffect, CvUtil, Transform},
+    dataset::{encode_image, parse_extension, DatasetWriter, SampleWriter},
+    effect_helper::math::Random,
+    font_util::{coverage_report, describe_face, FontUsage, FontUtil},
+    image::{DynamicImage, GrayImage, ImageBuffer},
+    image_glyph::ImageGlyphs,
+    image_process::{
+        build_line, composite, finish_image, generate_image, line_boxes, rasterize_text,
+        resize_to_heights, single_line, split_by_width, BBox, CharColors, ClusterBox, ColorScheme,
+        FauxStyle, GlyphJitter, InkTexture, Kerning, LineMetrics, LineStyling, LineWidth, Padding,
+        RasterizedText, RenderOptions, Spacing, VerticalLayout,
+    },
+    indexmap::{IndexMap, IndexSet},
+    init::{
+        init_word_dict, read_char_file, scale_freqs, uncovered_chars, uncovered_supplementary_chars,
+    },
+    label_noise::LabelNoise,
+    mask::{binary_mask, cluster_boxes, cluster_x_ranges, glyph_coverage, instance_mask},
+    merge_util::{BgFactory, MergeUtil},
+    metadata::{save_with_metadata, ImageMetadata},
+    ngram::NgramModel,
+    normalize::LabelNormalizer,
+    numeric::{random_numeric, NumericKind, NumericLocale},
+    numpy::{PyArray, PyArrayDyn, PyReadonlyArray2, PyReadonlyArrayDyn},
+    opentype::OpenTypeFeatures,
+    parse_config::{difficulty_levels, difficulty_preset, Config, ConfigOverride},
+    pyo3::{
+        exceptions::PyValueError,
+        prelude::*,
+        types::{PyBytes, PyDict, PyList},
+    },
+    rand::{seq::SliceRandom, Rng},
+    rand_distr::{Distribution, WeightedAliasIndex, WeightedIndex},
+    rayon::prelude::*,
+    render_pool::{RenderPool, RenderState},
+    rng::{effect_rng, reseed, reseed_effect_rng, sample_rng, RngState},
+    sampler::CharSampler,
+    scene::{crop_to_ink, paste_instances, Polygon, TextInstance},
+    segment::WordDict,
+    source::{get_random_sentence, read_sentences, CorpusSources, SourceKind},
+    template::Template,
+    unicode_segmentation::UnicodeSegmentation,
+    utils::{
+        array_to_image, write_into_array, FontAttrs, FontAttrsLike, InternalAttrsOwned, PyStretch,
+        PyStyle, PyWeight, StringUsefulUtils,
+    },
 };
 
+pub mod builder;
+pub mod coco;
 pub mod corpus;
+pub mod coverage_cache;
 pub mod cv_util;
+pub mod dataset;
 pub mod effect_helper;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
 pub mod font_util;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod image_glyph;
 pub mod image_process;
 pub mod init;
+pub mod label_noise;
+#[cfg(feature = "lmdb")]
+pub mod lmdb_writer;
+pub mod mask;
 pub mod merge_util;
+pub mod metadata;
+pub mod ngram;
+pub mod normalize;
+pub mod numeric;
+pub mod opentype;
 pub mod parse_config;
+pub mod render_pool;
+pub mod rng;
+pub mod sampler;
+pub mod scene;
+pub mod segment;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod source;
+pub mod template;
 pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+// 每個字符與可用於繪製它的字體
+#[cfg(feature = "python")]
+type TextWithFontList = Vec<(String, Vec<InternalAttrsOwned>)>;
+
+// 繪製用的 FontSystem、Buffer 與 SwashCache 放在 render_pool 中，每個線程各取一份，
+// 繪製時釋放 GIL，故多個線程可共用同一個 Generator 並行繪製
+#[cfg(feature = "python")]
 #[pyclass]
 struct Generator {
-    font_system: FontSystem,
     font_util: FontUtil,
-    editor_buffer: Buffer,
-    swash_cache: SwashCache,
+    render_pool: RenderPool,
     #[pyo3(get)]
     cv_util: CvUtil,
     #[pyo3(get)]
     merge_util: MergeUtil,
     #[pyo3(get)]
     bg_factory: BgFactory,
+    config_path: String,
+    #[pyo3(get)]
+    profile: Option<String>,
+    #[pyo3(get)]
+    difficulty: Option<String>,
+    overrides: Vec<ConfigOverride>,
+    #[pyo3(get)]
+    seed: Option<u64>, // 最近一次設置的隨機種子
+    rng: RngState, // 本生成器的隨機數狀態，每次生成時播種當前線程的隨機數生成器
     #[pyo3(get)]
     font_list: Vec<InternalAttrsOwned>,
+    font_index: HashMap<InternalAttrsOwned, usize>, // 字體在 font_list 中的位置
+    font_usage: FontUsage,
     #[pyo3(get)]
     chinese_ch_dict: IndexMap<String, Vec<InternalAttrsOwned>>,
-    chinese_ch_weights: WeightedAliasIndex<f64>,
+    chinese_ch_weights: CharSampler,
+    chinese_ch_freqs: Vec<f64>, // 與 chinese_ch_dict 一一對應，用於重建 chinese_ch_weights
+    inverse_frequency: bool,
+    sampling_temperature: f64,
+    charset: Option<HashSet<String>>, // 若不爲空，則只從其中抽樣
+    ngram: Option<NgramModel>,        // 由 ngram_corpus_file_path 統計的字符 n 元模型
+    sentences: Vec<String>,           // 不爲空時 get_random_chinese 從中抽取句子
+    sentence_substring: bool,
+    script_converter: Option<ScriptConverter>, // 由 script_conversion_file_path 讀取的繁簡轉換表
+    converted_dict: IndexMap<String, Vec<InternalAttrsOwned>>, // 轉換後不在 chinese_ch_dict 中的字符
     #[pyo3(get)]
     latin_corpus: Option<String>,
+    latin_words: Vec<String>,
+    latin_vocab: Vec<String>, // latin_corpus 中不重複的單詞
+    latin_vocab_weights: Option<WeightedAliasIndex<f64>>, // 與 latin_vocab 一一對應，按出現次數
+    mixed_pool_ratios: (f64, f64, f64), // 中文、拉丁單詞、標點
     symbol: Option<Vec<String>>,
+    symbol_insertion: SymbolInsertion, // get_random_chinese 的 add_extra_symbol 如何插入標點
     #[pyo3(get)]
     latin_ch_dict: Option<IndexMap<String, Vec<InternalAttrsOwned>>>,
     #[pyo3(get)]
     symbol_dict: Option<IndexMap<String, Vec<InternalAttrsOwned>>>,
     #[pyo3(get)]
+    rtl_corpus: Option<String>,
+    #[pyo3(get)]
+    rtl_ch_dict: Option<IndexMap<String, Vec<InternalAttrsOwned>>>,
+    rtl_words: IndexMap<String, Vec<InternalAttrsOwned>>, // rtl_corpus 中不重複的單詞及能繪製整個單詞的字體
+    rtl_word_weights: Option<WeightedAliasIndex<f64>>,    // 與 rtl_words 一一對應，按出現次數
+    digit_dict: IndexMap<String, Vec<InternalAttrsOwned>>, // get_random_mixed 的數字組
+    #[pyo3(get)]
     main_font_list: Vec<String>, // 若字符的字體列表爲空，則隨機從 main_font_list 中擇一字體
+    advance_cache: HashMap<String, f32>, // 字符在其字體列表中的平均寬度（em），用於按寬度抽樣
+    word_dict: Option<WordDict>,
+    confusable_groups: Vec<Vec<String>>,
+    corpus_sources: CorpusSources,
+    space_insertion: SpaceInsertion,
+    wrap_dict_order: Vec<String>,
+    opentype_features: OpenTypeFeatures,
+    label_normalizer: LabelNormalizer,
+    label_noise: LabelNoise,
+    ligatures: bool,
+    kerning: Kerning,
+    glyph_size_jitter: f64,
+    line_styling: LineStyling,
+    glyph_jitter: GlyphJitter,
+    ink_texture: InkTexture,
+    image_glyphs: Arc<ImageGlyphs>,
+    char_colors: Option<CharColors>,
+    color_scheme: Option<ColorScheme>, // 未給出顏色時的隨機文字與背景顏色
+    vertical: Option<VerticalLayout>,  // 爲空時橫排
+    line_metrics: LineMetrics,
+    line_width: LineWidth,
+    #[cfg(feature = "gpu")]
+    gpu_compositor: Option<gpu::GpuCompositor>,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl Generator {
+    // profile: 配置文件 PROFILES 中的配置名，覆蓋 CV 與 MERGE 中的部分參數
+    // overrides: 覆蓋配置文件中的參數，如 {"MERGE.reverse_prob": 0.2}，在 profile 與環境變量（如 TIG_MERGE__REVERSE_PROB=0.2）之後應用
+    // seed: 隨機種子，給定時生成的文本、字體、版面、效果與背景均可復現，見 reseed
     #[new]
-    #[pyo3(signature = (config_path="./config.yaml"))]
-    fn py_new(config_path: &str) -> PyResult<Self> {
-        let config = Config::from_yaml(config_path);
-
-        let mut font_system = FontSystem::new();
-        let db = font_system.db_mut();
-        db.load_fonts_dir(&config.font_dir);
+    #[pyo3(signature = (config_path="./config.yaml", profile=None, overrides=None, seed=None))]
+    fn py_new(
+        config_path: &str,
+        profile: Option<String>,
+        overrides: Option<HashMap<String, &PyAny>>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let overrides = Self::parse_overrides(overrides.unwrap_or_default())?;
+        let config = Config::from_yaml_with(config_path, profile.as_deref(), &overrides)
+            .map_err(PyValueError::new_err)?;
+        if !config.freq_corpus_file_path.is_empty() {
+            println!("正在統計語料中的字頻...");
+        }
+        println!("正在分析字體所包含的字符...");
+        let mut builder = GeneratorBuilder::new(&config).map_err(PyValueError::new_err)?;
+        let char_filter = builder.char_filter.clone();
 
         // 加載 latin 語料文件
         let latin_corpus_file_data = if config.latin_corpus_file_path.len() > 0 {
             let data = fs::read_to_string(&config.latin_corpus_file_path).unwrap();
-            Some(data)
+            Some(char_filter.filter_corpus(&data))
+        } else {
+            None
+        };
+
+        // 加載從右到左書寫的語料文件（阿拉伯文、希伯來文等）
+        let rtl_corpus_file_data = if !config.rtl_corpus_file_path.is_empty() {
+            let data = fs::read_to_string(&config.rtl_corpus_file_path).map_err(|err| {
+                PyValueError::new_err(format!(
+                    "failed to read `{}`: {}",
+                    config.rtl_corpus_file_path, err
+                ))
+            })?;
+            Some(char_filter.filter_corpus(&data))
         } else {
             None
         };
 
-        // 加載 symbol 文件
+        // 加載 symbol 文件，每行一個標點，可以製表符分隔附上抽樣權重
+        let mut symbol_weights = vec![];
         let symbol_file_data = if config.symbol_file_path.len() > 0 {
             let data: Vec<_> = fs::read_to_string(&config.symbol_file_path)
                 .unwrap()
                 .trim_matches('\n')
                 .split("\n")
-                .map(String::from)
+                .map(|line| {
+                    let (symbol, weight) = line.split_once('\t').unwrap_or((line, ""));
+                    symbol_weights.push(weight.trim().parse::<f64>().ok());
+                    symbol.to_string()
+                })
                 .collect();
             Some(data)
         } else {
             None
         };
-
-        let (
-            full_font_list,
-            chinesecharacter_file_data,
-            chinese_ch_dict,
-            chinese_ch_weights,
-            latin_ch_dict,
-            symbol_dict,
-        );
-
-        {
-            let mut font_util = font_util::FontUtil::new(&font_system);
-            full_font_list = font_util.get_full_font_list();
-            chinesecharacter_file_data = fs::read_to_string(config.chinese_ch_file_path).unwrap();
-            println!("正在分析字體所包含的字符...");
-            (chinese_ch_dict, chinese_ch_weights) = init_ch_dict_and_weight(
-                &mut font_util,
-                &full_font_list,
-                &chinesecharacter_file_data,
-            );
-
-            latin_ch_dict = if let Some(ref latin_corpus_file_data) = latin_corpus_file_data {
-                let temp = latin_corpus_file_data.dedup_to_vec().into_iter();
-                Some(init_ch_dict(&mut font_util, &full_font_list, temp))
+        let symbol_insertion = SymbolInsertion {
+            count: config.symbol_count,
+            prob: config.symbol_prob,
+            // 沒有任何權重時等概率抽樣，未給出權重的標點權重爲 1
+            weights: if symbol_weights.iter().any(Option::is_some) {
+                Some(
+                    WeightedIndex::new(symbol_weights.iter().map(|weight| weight.unwrap_or(1.0)))
+                        .map_err(|err| {
+                        PyValueError::new_err(format!("invalid symbol weights: {}", err))
+                    })?,
+                )
             } else {
                 None
-            };
+            },
+            at_start: config.symbol_at_start,
+            at_end: config.symbol_at_end,
+        };
+        if symbol_insertion.count.0 > symbol_insertion.count.1
+            || !(0.0..=1.0).contains(&symbol_insertion.prob)
+        {
+            return Err(PyValueError::new_err(
+                "`symbol_count` should be [min, max] and `symbol_prob` in [0, 1]",
+            ));
+        }
 
-            symbol_dict = if let Some(ref symbol_file_data) = symbol_file_data {
-                let data = symbol_file_data.iter();
-                Some(init_ch_dict(&mut font_util, &full_font_list, data))
-            } else {
-                None
-            };
+        // OpenCC 格式的繁簡轉換表，每行一個簡體字、製表符及其繁體形式
+        let script = Script::from_name(&config.script).map_err(PyValueError::new_err)?;
+        let script_converter = if !config.script_conversion_file_path.is_empty() {
+            let table = fs::read_to_string(&config.script_conversion_file_path).map_err(|err| {
+                PyValueError::new_err(format!(
+                    "failed to read `{}`: {}",
+                    config.script_conversion_file_path, err
+                ))
+            })?;
+            Some(ScriptConverter::new(&table, script).map_err(PyValueError::new_err)?)
+        } else if script != Script::Original {
+            return Err(PyValueError::new_err(
+                "`script` needs `script_conversion_file_path` in the config file",
+            ));
+        } else {
+            None
+        };
 
-            println!("分析完成!");
+        let latin_ch_dict = latin_corpus_file_data
+            .as_ref()
+            .map(|data| builder.init_ch_dict(data.dedup_to_vec().into_iter()));
+        let rtl_ch_dict = rtl_corpus_file_data
+            .as_ref()
+            .map(|data| builder.init_ch_dict(data.dedup_to_vec().into_iter()));
+        let symbol_dict = symbol_file_data
+            .as_ref()
+            .map(|data| builder.init_ch_dict(data.iter()));
+        // 數字及分隔數字組的空格
+        let digit_dict = builder.init_ch_dict(DIGITS.iter().chain([&" "]));
+        // 轉換後的字符按自身查找字體
+        let converted_dict = match script_converter {
+            Some(ref converter) => {
+                let targets: IndexSet<&String> = converter
+                    .targets()
+                    .filter(|ch| {
+                        !builder.chinese_ch_dict.contains_key(ch.as_str())
+                            && char_filter.accepts(ch)
+                    })
+                    .collect();
+                builder.init_ch_dict(targets.into_iter())
+            }
+            None => IndexMap::new(),
+        };
+        if let Err(err) = builder.save_coverage_cache() {
+            println!("無法寫入字體覆蓋緩存: {}", err);
         }
+        println!("分析完成!");
 
-        let font_util = font_util::FontUtil::new(&font_system);
+        let font_util = builder.font_util(&config).map_err(PyValueError::new_err)?;
+        let chinese_ch_weights = builder.chinese_ch_weights(&config);
 
-        // create one per application
-        let swash_cache = SwashCache::new();
+        #[cfg(feature = "gpu")]
+        let gpu_compositor = match config.render_backend.as_str() {
+            "gpu" => {
+                let compositor = gpu::GpuCompositor::new();
+                if compositor.is_none() {
+                    println!("找不到可用的 GPU，改用 CPU 繪製");
+                }
+                compositor
+            }
+            _ => None,
+        };
+        #[cfg(not(feature = "gpu"))]
+        if config.render_backend == "gpu" {
+            println!("未啓用 gpu 功能，改用 CPU 繪製");
+        }
 
-        let mut buffer = Buffer::new(
-            &mut font_system,
+        let render_pool = RenderPool::new(
+            builder.font_system,
             Metrics::new(config.font_size as f32, config.line_height as f32),
-        );
-        buffer.set_size(
-            &mut font_system,
-            config.font_img_width as f32,
-            config.font_img_height as f32,
+            (config.font_img_width as f32, config.font_img_height as f32),
         );
 
-        let main_font_list: Vec<_> = if config.main_font_list_file_path.len() > 0 {
-            fs::read_to_string(&config.main_font_list_file_path)
-                .unwrap()
-                .trim()
-                .split("\n")
-                .map(String::from)
+        if let Some(name) = config
+            .wrap_dict_order
+            .iter()
+            .find(|name| Self::check_dict_name(name).is_err())
+        {
+            return Err(PyValueError::new_err(format!(
+                "unknown dict in `wrap_dict_order`: {}",
+                name
+            )));
+        }
+
+        let word_dict = if !config.word_dict_file_path.is_empty() {
+            Some(WordDict::from_file(&config.word_dict_file_path))
+        } else {
+            None
+        };
+
+        // 每行一組易混淆的字符，以空白分隔
+        let confusable_groups = if !config.confusable_file_path.is_empty() {
+            fs::read_to_string(&config.confusable_file_path)
+                .map_err(|err| {
+                    PyValueError::new_err(format!(
+                        "failed to read `{}`: {}",
+                        config.confusable_file_path, err
+                    ))
+                })?
+                .lines()
+                .map(|line| {
+                    line.split_whitespace()
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                })
+                .filter(|group| group.len() >= 2)
                 .collect()
         } else {
             vec![]
         };
 
-        Ok(Self {
-            font_system,
+        let mut corpus_sources =
+            CorpusSources::new(&config.corpus_sources).map_err(PyValueError::new_err)?;
+        for source in corpus_sources.sources.iter_mut() {
+            if source.kind == SourceKind::Latin && latin_ch_dict.is_none() {
+                return Err(PyValueError::new_err(
+                    "a `latin` corpus source needs `latin_corpus_file_path` in the config file",
+                ));
+            }
+            // 含有被過濾字符的句子整句丟棄
+            if !char_filter.is_empty() {
+                source.lines.retain(|line| {
+                    line.graphemes(true)
+                        .all(|cluster| char_filter.accepts(cluster))
+                });
+            }
+            if source.kind == SourceKind::Sentence && source.lines.is_empty() {
+                return Err(PyValueError::new_err(
+                    "no sentence of a `sentence` corpus source is left after filtering",
+                ));
+            }
+        }
+
+        let mut sentences = if !config.sentence_file_path.is_empty() {
+            read_sentences(&config.sentence_file_path).map_err(PyValueError::new_err)?
+        } else {
+            vec![]
+        };
+        if !char_filter.is_empty() && !sentences.is_empty() {
+            sentences.retain(|line| {
+                line.graphemes(true)
+                    .all(|cluster| char_filter.accepts(cluster))
+            });
+            if sentences.is_empty() {
+                return Err(PyValueError::new_err(
+                    "no sentence of `sentence_file_path` is left after filtering",
+                ));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&config.label_noise_prob) {
+            return Err(PyValueError::new_err(
+                "`label_noise_prob` should be in [0, 1]",
+            ));
+        }
+        let label_noise = LabelNoise::new(config.label_noise_prob, &confusable_groups);
+
+        let space_insertion = SpaceInsertion {
+            prob: config.space_prob,
+            run_len: config.space_run_len,
+            indent_prob: config.indent_prob,
+            indent_len: config.indent_len,
+        };
+        if !(0.0..=1.0).contains(&space_insertion.prob)
+            || !(0.0..=1.0).contains(&space_insertion.indent_prob)
+            || space_insertion.run_len.0 > space_insertion.run_len.1
+            || space_insertion.indent_len.0 > space_insertion.indent_len.1
+        {
+            return Err(PyValueError::new_err(
+                "`space_prob` and `indent_prob` should be in [0, 1], and the lengths should be [min, max]",
+            ));
+        }
+        let ngram = if !config.ngram_corpus_file_path.is_empty() {
+            println!("正在統計語料中的 n 元組...");
+            let corpus = fs::read_to_string(&config.ngram_corpus_file_path).unwrap();
+            Some(
+                NgramModel::new(&corpus, &builder.chinese_ch_dict, config.ngram_order)
+                    .map_err(PyValueError::new_err)?,
+            )
+        } else {
+            None
+        };
+        let balanced_min_count = config.balanced_min_count;
+        let excluded_chars = if !config.exclude_chars_file_path.is_empty() {
+            read_char_file(&config.exclude_chars_file_path).map_err(PyValueError::new_err)?
+        } else {
+            vec![]
+        };
+        let charset = if !config.charset_file_path.is_empty() {
+            Some(read_char_file(&config.charset_file_path).map_err(PyValueError::new_err)?)
+        } else {
+            None
+        };
+        let latin_word_counts = latin_corpus_file_data
+            .as_deref()
+            .map(count_words)
+            .unwrap_or_default();
+        // 阿拉伯文等連寫文字的單詞整體使用同一字體，才能正確連接
+        let rtl_word_counts = rtl_corpus_file_data
+            .as_deref()
+            .map(count_rtl_words)
+            .unwrap_or_default();
+        let rtl_words = match &rtl_ch_dict {
+            Some(ch_dict) => init_word_dict(rtl_word_counts.keys().map(String::as_str), ch_dict),
+            None => IndexMap::new(),
+        };
+
+        let mut generator = Self {
             font_util,
-            editor_buffer: buffer,
-            swash_cache,
-            font_list: full_font_list,
-            chinese_ch_dict: chinese_ch_dict
-                .into_iter()
-                .map(|(ch, dic)| (ch.to_string(), dic))
+            render_pool,
+            font_usage: FontUsage::default(),
+            font_index: builder
+                .full_font_list
+                .iter()
+                .enumerate()
+                .map(|(idx, font)| (font.clone(), idx))
                 .collect(),
-            chinese_ch_weights,
+            font_list: builder.full_font_list,
+            chinese_ch_dict: builder.chinese_ch_dict,
+            chinese_ch_weights: CharSampler::new(chinese_ch_weights).unwrap(),
+            chinese_ch_freqs: builder.chinese_ch_freqs,
+            inverse_frequency: config.inverse_frequency,
+            sampling_temperature: config.sampling_temperature,
+            charset: None,
+            ngram,
+            sentences,
+            sentence_substring: config.sentence_substring,
+            script_converter,
+            converted_dict,
+            latin_words: latin_corpus_file_data
+                .as_ref()
+                .map(|data| data.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+            latin_vocab_weights: WeightedAliasIndex::new(
+                latin_word_counts
+                    .values()
+                    .map(|&count| count as f64)
+                    .collect(),
+            )
+            .ok(),
+            latin_vocab: latin_word_counts.into_keys().collect(),
+            mixed_pool_ratios: config.mixed_pool_ratios,
             latin_corpus: latin_corpus_file_data.clone(),
             symbol: symbol_file_data.clone(),
-            latin_ch_dict: if let Some(ch_dict) = latin_ch_dict {
-                Some(
-                    ch_dict
-                        .into_iter()
-                        .map(|(ch, dic)| (ch.to_string(), dic.clone()))
-                        .collect(),
-                )
-            } else {
-                None
-            },
-            symbol_dict: if let Some(symbol_dict) = symbol_dict {
-                Some(
-                    symbol_dict
-                        .into_iter()
-                        .map(|(ch, dic)| (ch.to_string(), dic.clone()))
-                        .collect(),
-                )
-            } else {
-                None
-            },
-            main_font_list,
-            cv_util: CvUtil {
-                box_prob: config.box_prob,
-                perspective_prob: config.perspective_prob,
-                perspective_x: config.perspective_x,
-                perspective_y: config.perspective_y,
-                perspective_z: config.perspective_z,
-                blur_prob: config.blur_prob,
-                blur_sigma: config.blur_sigma,
-                filter_prob: config.filter_prob,
-                emboss_prob: config.emboss_prob,
-                sharp_prob: config.sharp_prob,
+            symbol_insertion,
+            latin_ch_dict,
+            symbol_dict,
+            rtl_corpus: rtl_corpus_file_data,
+            rtl_ch_dict,
+            rtl_words,
+            rtl_word_weights: WeightedAliasIndex::new(
+                rtl_word_counts
+                    .values()
+                    .map(|&count| count as f64)
+                    .collect(),
+            )
+            .ok(),
+            digit_dict,
+            main_font_list: builder.main_font_list,
+            advance_cache: HashMap::new(),
+            word_dict,
+            confusable_groups,
+            corpus_sources,
+            space_insertion,
+            wrap_dict_order: config.wrap_dict_order.clone(),
+            opentype_features: builder.opentype_features,
+            label_normalizer: builder.label_normalizer,
+            label_noise,
+            ligatures: config.ligatures,
+            kerning: Kerning {
+                enabled: config.kerning,
+                jitter: config.kerning_jitter,
             },
-            merge_util: MergeUtil {
-                height_diff: config.height_diff,
-                bg_alpha: config.bg_alpha,
-                bg_beta: config.bg_beta,
-                font_alpha: config.font_alpha,
-                reverse_prob: config.reverse_prob,
+            glyph_size_jitter: config.glyph_size_jitter,
+            line_styling: builder.line_styling,
+            glyph_jitter: GlyphJitter::from_config(&config),
+            ink_texture: InkTexture {
+                strength: config.ink_texture_strength,
+                scale: config.ink_texture_scale,
             },
-            bg_factory: BgFactory::new(config.bg_dir, config.bg_height, config.bg_width),
-        })
+            image_glyphs: Arc::new(builder.image_glyphs),
+            char_colors: CharColors::from_config(&config),
+            color_scheme: ColorScheme::from_config(&config),
+            vertical: VerticalLayout::from_config(&config),
+            line_metrics: LineMetrics::from_config(&config),
+            line_width: LineWidth::from_config(&config),
+            #[cfg(feature = "gpu")]
+            gpu_compositor,
+            cv_util: CvUtil::from_config(&config),
+            merge_util: MergeUtil::from_config(&config),
+            bg_factory: BgFactory::with_color(
+                config.bg_dir,
+                config.bg_height,
+                config.bg_width,
+                config.color_merge,
+            ),
+            config_path: config_path.to_string(),
+            profile,
+            difficulty: None,
+            overrides,
+            seed,
+            rng: RngState::new(seed),
+        };
+        if !excluded_chars.is_empty() {
+            generator.remove_characters(excluded_chars, "chinese")?;
+        }
+        if let Some(charset) = charset {
+            generator.restrict_charset(charset)?;
+        }
+        generator.set_balanced_sampling(Some(balanced_min_count));
+
+        let uncovered = generator.get_uncovered_chars();
+        for (dict, chars) in uncovered.iter() {
+            println!(
+                "{} 字典中有 {} 個字符沒有可用的字體: {}",
+                dict,
+                chars.len(),
+                chars.join("")
+            );
+        }
+        if config.strict_coverage && !uncovered.is_empty() {
+            return Err(PyValueError::new_err(format!(
+                "characters without any font (strict_coverage): {}",
+                uncovered
+                    .iter()
+                    .map(|(dict, chars)| format!("{}: {}", dict, chars.join("")))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )));
+        }
+
+        Ok(generator)
+    }
+
+    // 重設本生成器的隨機數狀態，之後生成的樣本與以同一種子新建 Generator 後生成的相同
+    // 每個 Generator 與 ImageEffect 各有自己的隨機數狀態，互不影響；gen_batch 爲每個樣本單獨播種，不受線程調度影響
+    // 同時從多個線程調用同一個生成器時，各次調用取得不同的種子，結果取決於調用的先後
+    fn reseed(&mut self, seed: u64) {
+        self.rng.reseed(seed);
+        self.seed = Some(seed);
+    }
+
+    // 切換到配置文件中的另一個配置，重建 cv_util 與 merge_util，背景目錄或大小改變時重新加載背景
+    // profile: 配置名，爲 None 時使用配置文件本身的 CV 與 MERGE
+    #[pyo3(signature = (profile=None))]
+    fn set_profile(&mut self, profile: Option<String>) -> PyResult<()> {
+        self.reload_effects(profile, self.difficulty.clone())
+    }
+
+    // 切換到內置的難度 "easy"、"medium" 或 "hard"，同時替換 cv_util 與 merge_util 的參數，便於課程式訓練
+    // 難度在 profile 之後、overrides 之前應用；爲 None 時恢復配置文件中的參數
+    #[pyo3(signature = (level=None))]
+    fn set_difficulty(&mut self, level: Option<String>) -> PyResult<()> {
+        self.reload_effects(self.profile.clone(), level)
+    }
+
+    // 所有內置的難度，從易到難
+    #[getter]
+    fn difficulty_levels(&self) -> Vec<&'static str> {
+        difficulty_levels()
+    }
+
+    // 配置文件中定義的所有配置名
+    #[getter]
+    fn profiles(&self) -> PyResult<Vec<String>> {
+        let yaml_str = fs::read_to_string(&self.config_path)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Config::profile_names(&yaml_str))
     }
 
     fn set_bg_size(&mut self, height: usize, width: usize) {
-        self.bg_factory = BgFactory::new(&self.bg_factory.bg_dir, height, width);
+        self.bg_factory = BgFactory::with_color(
+            &self.bg_factory.bg_dir,
+            height,
+            width,
+            self.bg_factory.is_color(),
+        );
+    }
+
+    #[pyo3(signature = (chars, dict="chinese", freq=1.0))]
+    fn add_characters(&mut self, chars: Vec<String>, dict: &str, freq: f64) -> PyResult<()> {
+        if freq.is_nan() || freq < 0.0 {
+            return Err(PyValueError::new_err("`freq` should be non-negative"));
+        }
+        let is_chinese = Self::check_dict_name(dict)?;
+
+        for ch in chars {
+            if is_chinese {
+                if let Some(idx) = self.chinese_ch_dict.get_index_of(&ch) {
+                    self.chinese_ch_freqs[idx] = freq;
+                    continue;
+                }
+            }
+            let font_list = self.font_util.fonts_containing(&ch);
+            self.advance_cache.remove(&ch);
+            let is_new = self.dict_mut(dict).insert(ch, font_list).is_none();
+            if is_new && is_chinese {
+                self.chinese_ch_freqs.push(freq);
+            }
+        }
+
+        if is_chinese {
+            self.rebuild_chinese_weights()?;
+        }
+        Ok(())
+    }
+
+    #[pyo3(signature = (chars, dict="chinese"))]
+    fn remove_characters(&mut self, chars: Vec<String>, dict: &str) -> PyResult<()> {
+        if !Self::check_dict_name(dict)? {
+            let ch_dict = self.dict_mut(dict);
+            for ch in chars.iter() {
+                ch_dict.shift_remove(ch);
+            }
+            return Ok(());
+        }
+
+        // 先檢查刪除後是否還有可抽樣的字符，避免字典與權重不一致
+        let removed: Vec<_> = chars
+            .iter()
+            .filter_map(|ch| self.chinese_ch_dict.get_index_of(ch))
+            .collect();
+        let remaining =
+            self.chinese_ch_freqs.iter().enumerate().any(|(idx, freq)| {
+                *freq > 0.0 && !removed.contains(&idx) && self.is_in_charset(idx)
+            });
+        if !remaining {
+            return Err(PyValueError::new_err(
+                "no character with a positive frequency would remain",
+            ));
+        }
+
+        for ch in chars.iter() {
+            if let Some((idx, _, _)) = self.chinese_ch_dict.shift_remove_full(ch) {
+                self.chinese_ch_freqs.remove(idx);
+                self.chinese_ch_weights.remove(idx);
+            }
+        }
+        self.rebuild_chinese_weights()
+    }
+
+    // 加載一個目錄中的字體，只分析新字體，並把它們加入各字典中其所包含字符的字體列表，返回新加載的字體
+    fn add_font_dir(&mut self, path: &str) -> PyResult<Vec<FontAttrs>> {
+        if !std::path::Path::new(path).is_dir() {
+            return Err(PyValueError::new_err(format!("not a directory: {}", path)));
+        }
+        self.font_util.load_fonts_dir(path);
+        self.render_pool.load_fonts_dir(path);
+
+        Ok(self.register_new_fonts())
+    }
+
+    // 從字節加載字體文件（TTF/OTF/TTC），如下載或內嵌的字體，其餘同 add_font_dir
+    // data: 字體文件的內容
+    fn add_font_data(&mut self, data: &[u8]) -> PyResult<Vec<FontAttrs>> {
+        if !self.font_util.load_font_data(data.to_vec()) {
+            return Err(PyValueError::new_err("no font found in the data"));
+        }
+        self.render_pool.load_font_data(data.to_vec());
+
+        Ok(self.register_new_fonts())
+    }
+
+    // 按字體家族的權重從字符的字體列表中選擇字體
+    // weights: {字體家族: 權重}，未列出的家族權重爲 1，爲空時等概率選擇
+    fn set_font_weights(&mut self, weights: HashMap<String, f64>) -> PyResult<()> {
+        if let Some((name, _)) = weights
+            .iter()
+            .find(|(_, weight)| !(**weight >= 0.0 && weight.is_finite()))
+        {
+            return Err(PyValueError::new_err(format!(
+                "the weight of font `{}` should be non-negative",
+                name
+            )));
+        }
+        self.font_util.set_font_weights(weights);
+        Ok(())
+    }
+
+    #[pyo3(signature = (ch, font_list, dict="chinese"))]
+    fn set_char_fonts(
+        &mut self,
+        ch: String,
+        font_list: Vec<FontAttrsLike>,
+        dict: &str,
+    ) -> PyResult<()> {
+        let is_chinese = Self::check_dict_name(dict)?;
+        let font_list = font_list
+            .into_iter()
+            .map(|font| font.into_internal(&self.font_list))
+            .collect::<PyResult<_>>()?;
+
+        self.advance_cache.remove(&ch);
+        let is_new = self.dict_mut(dict).insert(ch, font_list).is_none();
+        if is_new && is_chinese {
+            self.chinese_ch_freqs.push(1.0);
+            self.rebuild_chinese_weights()?;
+        }
+        Ok(())
+    }
+
+    // inverse: 是否按頻率的倒數抽樣，使罕見字更常出現
+    // temperature: 權重爲 freq^(1/temperature)，大於 1 時分佈更平坦
+    #[pyo3(signature = (inverse=false, temperature=1.0))]
+    fn set_sampling_weights(&mut self, inverse: bool, temperature: f64) -> PyResult<()> {
+        if !(temperature > 0.0 && temperature.is_finite()) {
+            return Err(PyValueError::new_err("`temperature` should be positive"));
+        }
+        self.inverse_frequency = inverse;
+        self.sampling_temperature = temperature;
+        self.rebuild_chinese_weights()
+    }
+
+    // 只從 chinese_ch_dict 中給定的字符抽樣，直到調用 reset_charset；不在字典中的字符被忽略
+    fn restrict_charset(&mut self, chars: Vec<String>) -> PyResult<()> {
+        let previous = self.charset.replace(chars.into_iter().collect());
+        if self.rebuild_chinese_weights().is_err() {
+            self.charset = previous;
+            return Err(PyValueError::new_err(
+                "no character of the charset can be sampled",
+            ));
+        }
+        Ok(())
+    }
+
+    // 從字典中刪除文件中的字符，見配置文件中的 exclude_chars_file_path，文件中的空白被忽略
+    #[pyo3(signature = (path, dict="chinese"))]
+    fn remove_chars_from_file(&mut self, path: &str, dict: &str) -> PyResult<()> {
+        let chars = read_char_file(path).map_err(PyValueError::new_err)?;
+        self.remove_characters(chars, dict)
+    }
+
+    // 以指定的寫法輸出中文文本，需要配置文件中的 script_conversion_file_path
+    // script: "none"、"traditional"、"simplified" 或 "mixed"（每個字隨機取其中一種寫法）
+    fn set_script(&mut self, script: &str) -> PyResult<()> {
+        let script = Script::from_name(script).map_err(PyValueError::new_err)?;
+        match self.script_converter.as_mut() {
+            Some(converter) => converter.script = script,
+            None if script == Script::Original => {}
+            None => {
+                return Err(PyValueError::new_err(
+                    "`script_conversion_file_path` is not set in the config file",
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    // 每個樣本的字號與行高從 (min, max) 範圍中均勻抽取，覆蓋配置文件中的 font_size_range 與 line_height_range
+    // font_size: 爲 None 時使用 font_size
+    // line_height: 爲 None 時使用 line_height；給定範圍時圖片與行同高
+    #[pyo3(signature = (font_size=None, line_height=None))]
+    fn set_line_metrics(
+        &mut self,
+        font_size: Option<(f64, f64)>,
+        line_height: Option<(f64, f64)>,
+    ) -> PyResult<()> {
+        let to_random = |range: Option<(f64, f64)>| -> PyResult<Option<Random>> {
+            match range {
+                Some((min, max)) if !(min >= 1.0 && min <= max) => Err(PyValueError::new_err(
+                    "a range should be (min, max) with 1 <= min <= max",
+                )),
+                range => Ok(range.map(|(min, max)| Random::new_uniform(min, max))),
+            }
+        };
+        self.line_metrics.font_size = to_random(font_size)?;
+        self.line_metrics.line_height = to_random(line_height)?;
+
+        Ok(())
+    }
+
+    // 從現在起，每個可抽樣的字符都至少抽到 min_count 次後才恢復按權重抽樣，未達到次數的字符按打亂的輪次發放
+    // min_count: 爲 None 或 0 時關閉
+    #[pyo3(signature = (min_count=None))]
+    fn set_balanced_sampling(&mut self, min_count: Option<u32>) {
+        self.chinese_ch_weights.set_balance(min_count);
+    }
+
+    // 自 set_balanced_sampling 以來 chinese_ch_dict 中各字符被抽到的次數
+    fn get_char_counts(&self) -> PyResult<HashMap<String, u32>> {
+        let counts = self.chinese_ch_weights.counts().ok_or_else(|| {
+            PyValueError::new_err("balanced sampling is off, see set_balanced_sampling")
+        })?;
+
+        Ok(self.chinese_ch_dict.keys().cloned().zip(counts).collect())
+    }
+
+    // 只從文件中的字符抽樣，見 restrict_charset 與配置文件中的 charset_file_path
+    fn restrict_charset_from_file(&mut self, path: &str) -> PyResult<()> {
+        let chars = read_char_file(path).map_err(PyValueError::new_err)?;
+        self.restrict_charset(chars)
+    }
+
+    fn reset_charset(&mut self) -> PyResult<()> {
+        self.charset = None;
+        self.rebuild_chinese_weights()
+    }
+
+    // 以本生成器已加載的字體構建的 FontUtil
+    #[getter]
+    fn font_util(&self) -> FontUtil {
+        FontUtil::new(&self.render_pool.get().font_system)
+    }
+
+    // 註冊一個字符的圖片字形（如手寫樣本的截圖），深色筆畫、淺色背景，繪製時代替字體縮放到該字符的字框中
+    // 同一字符可註冊多張，每次隨機選取；按 image_glyph_prob 的概率使用圖片字形
+    fn add_image_glyph(&mut self, ch: String, image: PyReadonlyArray2<'_, u8>) -> PyResult<()> {
+        let shape = image.shape();
+        if shape[0] == 0 || shape[1] == 0 {
+            return Err(PyValueError::new_err("`image` should not be empty"));
+        }
+        let raw = image.as_array().iter().copied().collect();
+        let img = GrayImage::from_vec(shape[1] as u32, shape[0] as u32, raw)
+            .ok_or_else(|| PyValueError::new_err("fail to read `image`"))?;
+        Arc::make_mut(&mut self.image_glyphs).insert(ch, img);
+
+        Ok(())
+    }
+
+    // 刪除一個字符的所有圖片字形，之後用字體繪製
+    fn remove_image_glyph(&mut self, ch: &str) {
+        Arc::make_mut(&mut self.image_glyphs).remove(ch);
+    }
+
+    // 各字體家族包含 chinese_ch_dict（限定了字符集時爲字符集）中的多少字符，返回 [(字體家族, 包含的字數, 缺少的常用字)]
+    // common: 常用字爲頻率最高的前 common 個字符
+    #[pyo3(signature = (common=500))]
+    fn font_coverage_report(&self, common: usize) -> Vec<(String, usize, Vec<String>)> {
+        let chars: Vec<(&str, &[InternalAttrsOwned], f64)> = self
+            .chinese_ch_dict
+            .iter()
+            .zip(self.chinese_ch_freqs.iter())
+            .enumerate()
+            .filter(|(idx, _)| self.is_in_charset(*idx))
+            .map(|(_, ((ch, font_list), freq))| (ch.as_str(), font_list.as_slice(), *freq))
+            .collect();
+
+        coverage_report(&chars, &self.font_list, common)
+            .into_iter()
+            .map(|(family, covered, missing)| {
+                (
+                    family,
+                    covered,
+                    missing.into_iter().map(String::from).collect(),
+                )
+            })
+            .collect()
+    }
+
+    // 各字體實際繪製的字符數（隨機選擇與回退到 main_font_list 之後），返回 [(字體, 字體文件, 次數)]，按次數從多到少排列，包括未用到的字體
+    // reset: 返回後清零，用於統計每個批次
+    #[pyo3(signature = (reset=false))]
+    fn get_font_usage_stats(&self, reset: bool) -> Vec<(FontAttrs, String, usize)> {
+        let state = self.render_pool.get();
+        self.font_usage
+            .stats(state.font_system.db(), reset)
+            .into_iter()
+            .filter_map(|(font_id, count)| {
+                let (attrs, file) = describe_face(&state.font_system, font_id)?;
+                Some((FontAttrs::from(&attrs), file, count))
+            })
+            .collect()
+    }
+
+    // 各字典中沒有任何已加載字體能繪製的字符，按字典名返回，略去沒有這類字符的字典
+    fn get_uncovered_chars(&self) -> IndexMap<String, Vec<String>> {
+        [
+            ("chinese", Some(&self.chinese_ch_dict)),
+            ("latin", self.latin_ch_dict.as_ref()),
+            ("symbol", self.symbol_dict.as_ref()),
+            ("rtl", self.rtl_ch_dict.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(name, ch_dict)| {
+            let chars: Vec<String> = uncovered_chars(ch_dict?)
+                .into_iter()
+                .map(String::from)
+                .collect();
+            (!chars.is_empty()).then(|| (name.to_string(), chars))
+        })
+        .collect()
+    }
+
+    fn get_uncovered_supplementary_chars(&self) -> Vec<String> {
+        uncovered_supplementary_chars(&self.chinese_ch_dict)
+            .into_iter()
+            .map(String::from)
+            .collect()
     }
 
     // fn set_latin_ch_dict(&mut self, ch: String, font_list: Vec<String>) {
@@ -224,36 +980,62 @@ impl Generator {
     // min: 指定生成文本的字數下限
     // max: 指定生成文本的字數上限
     // add_extra_symbol: 是否額外爲生成文本增加標點
-    #[pyo3(signature = (min=5, max=10, add_extra_symbol=false))]
+    // font_indices: 以 font_list 中的下標代替 FontAttrs 返回字體，可直接傳回 gen_image_from_text_with_font_list
+    // 配置了 sentence_file_path 時改爲抽取真實句子（sentence_substring 爲真時取其中 min 至 max 字的片段），不再另加標點
+    #[pyo3(signature = (min=5, max=10, add_extra_symbol=false, font_indices=false))]
     fn get_random_chinese(
         &self,
         min: u32,
         max: u32,
         add_extra_symbol: bool,
+        font_indices: bool,
     ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        if !self.sentences.is_empty() {
+            if min > max {
+                return Err(PyValueError::new_err("`min` should not exceed `max`"));
+            }
+            let sentence = if self.sentence_substring {
+                get_random_sentence(&self.sentences, min..=max)
+            } else {
+                self.sentences.choose(&mut sample_rng()).unwrap().as_str()
+            };
+            let text_with_font_list =
+                self.convert_script(wrap_text_with_dicts(sentence, &self.wrap_dicts()));
+            return Python::with_gil(|py| -> PyResult<Py<PyList>> {
+                let list: Py<PyList> = PyList::empty(py).into();
+                for (ch, font_list) in text_with_font_list {
+                    let font_list = match font_list {
+                        Some(content) => self.fonts_to_py(py, content, font_indices),
+                        None => Vec::<String>::new().into_py(py),
+                    };
+                    list.as_ref(py).append((ch, font_list)).unwrap();
+                }
+
+                Ok(list)
+            });
+        }
+
         let symbol = if add_extra_symbol {
-            self.symbol.as_ref()
+            self.symbol
+                .as_ref()
+                .map(|symbol| (symbol, &self.symbol_insertion))
         } else {
             None
         };
-        let chinese_text_with_font_list = get_random_chinese_text_with_font_list(
-            &self.chinese_ch_dict,
-            &self.chinese_ch_weights,
-            symbol,
-            min..=max,
-        );
+        let chinese_text_with_font_list =
+            self.convert_script(get_random_chinese_text_with_font_list(
+                &self.chinese_ch_dict,
+                &self.chinese_ch_weights,
+                symbol,
+                min..=max,
+            ));
         Python::with_gil(|py| -> PyResult<Py<PyList>> {
             let list: Py<PyList> = PyList::empty(py).into();
             for (ch, font_list) in chinese_text_with_font_list {
                 if let Some(content) = font_list {
                     list.as_ref(py)
-                        .append((
-                            ch,
-                            content
-                                .iter()
-                                .map(|each| each.to_tuple())
-                                .collect::<Vec<_>>(),
-                        ))
+                        .append((ch, self.fonts_to_py(py, content, font_indices)))
                         .unwrap();
                 } else {
                     list.as_ref(py)
@@ -266,61 +1048,1973 @@ impl Generator {
         })
     }
 
-    fn wrap_text_with_font_list(&self, text: &str) -> PyResult<Py<PyList>> {
-        let chinese_text_with_font_list = wrap_text_with_font_list(text, &self.chinese_ch_dict);
+    // 按 ngram_corpus_file_path 統計的 n 元模型抽樣，每個字依前一兩個字在語料中的後繼抽取，比逐字獨立抽樣更接近自然文本
+    // min: 指定生成文本的字數下限
+    // max: 指定生成文本的字數上限
+    // font_indices: 見 get_random_chinese
+    #[pyo3(signature = (min=5, max=10, font_indices=false))]
+    fn get_random_chinese_ngram(
+        &self,
+        min: u32,
+        max: u32,
+        font_indices: bool,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        if min > max {
+            return Err(PyValueError::new_err("`min` should not exceed `max`"));
+        }
+        let ngram = self.ngram.as_ref().ok_or_else(|| {
+            PyValueError::new_err("`ngram_corpus_file_path` is not set in the config file")
+        })?;
+
+        // 受 restrict_charset 限制的字符不會被抽到
+        let allowed = |ch: &str| self.charset.as_ref().is_none_or(|set| set.contains(ch));
+        let text_with_font_list = self.convert_script(ngram.sample(
+            &self.chinese_ch_dict,
+            &self.chinese_ch_weights,
+            allowed,
+            min..=max,
+        ));
         Python::with_gil(|py| -> PyResult<Py<PyList>> {
             let list: Py<PyList> = PyList::empty(py).into();
-            for (ch, font_list) in chinese_text_with_font_list {
-                if let Some(content) = font_list {
-                    list.as_ref(py)
-                        .append((
-                            ch,
-                            content
-                                .iter()
-                                .map(|each| each.to_tuple())
-                                .collect::<Vec<_>>(),
-                        ))
-                        .unwrap();
-                } else {
-                    list.as_ref(py)
-                        .append::<(&str, &Vec<String>)>((ch, &vec![]))
-                        .unwrap();
-                }
+            for (ch, font_list) in text_with_font_list {
+                let font_list = match font_list {
+                    Some(content) => self.fonts_to_py(py, content, font_indices),
+                    None => Vec::<String>::new().into_py(py),
+                };
+                list.as_ref(py).append((ch, font_list)).unwrap();
             }
 
             Ok(list)
         })
     }
 
-    #[pyo3(signature = (text_with_font_list, text_color=(0, 0, 0), background_color=(255, 255, 255), apply_effect=false))]
-    fn gen_image_from_text_with_font_list<'py>(
+    // min_width: 生成文本的目標寬度下限（像素），默認爲 font_img_width 的 80%
+    // max_width: 生成文本的目標寬度上限（像素），默認爲 font_img_width
+    #[pyo3(signature = (min_width=None, max_width=None))]
+    fn get_random_chinese_by_width(
         &mut self,
-        text_with_font_list: Vec<(String, Vec<(String, u16, u16, u16)>)>,
-        text_color: (u8, u8, u8),
-        background_color: (u8, u8, u8),
-        apply_effect: bool,
-        _py: Python<'py>,
-    ) -> &'py PyArrayDyn<u8> {
-        self.editor_buffer.lines.clear();
+        min_width: Option<f32>,
+        max_width: Option<f32>,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        let max_width = max_width.unwrap_or(self.line_width.width);
+        let min_width = min_width.unwrap_or(max_width * 0.8);
+        if !(min_width > 0.0 && min_width <= max_width) {
+            return Err(PyValueError::new_err(
+                "expected 0 < `min_width` <= `max_width`",
+            ));
+        }
+        let target_width = sample_rng().gen_range(min_width..=max_width);
 
-        let attrs = Attrs::new()
+        let font_size = self.render_pool.metrics().font_size;
+        let font_util = &mut self.font_util;
+        let advance_cache = &mut self.advance_cache;
+        let chinese_text_with_font_list = get_random_chinese_text_by_width(
+            &self.chinese_ch_dict,
+            &self.chinese_ch_weights,
+            target_width,
+            |ch, font_list| {
+                let advance = *advance_cache.entry(ch.to_string()).or_insert_with(|| {
+                    // 沒有字體的字符由 main_font_list 中的字體繪製，按全角估計
+                    if font_list.is_empty() {
+                        return 1.0;
+                    }
+                    font_list
+                        .iter()
+                        .map(|font| font_util.cluster_advance(font.as_attrs(), ch))
+                        .sum::<f32>()
+                        / font_list.len() as f32
+                });
+                advance * font_size
+            },
+        );
+        let chinese_text_with_font_list = self.convert_script(chinese_text_with_font_list);
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in chinese_text_with_font_list {
+                let font_list = font_list
+                    .map(|content| content.iter().map(FontAttrs::from).collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .into_py(py);
+                list.as_ref(py).append((ch, font_list)).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // min: 指定生成文本的位置數下限，每個位置是一個漢字、一個拉丁單詞或一個標點
+    // max: 指定生成文本的位置數上限
+    // ratios: 三者的比例，默認爲配置文件中的 mixed_pool_ratios
+    #[pyo3(signature = (min=5, max=10, ratios=None))]
+    fn get_random_text(
+        &self,
+        min: u32,
+        max: u32,
+        ratios: Option<(f64, f64, f64)>,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        let (chinese, latin, symbol) = ratios.unwrap_or(self.mixed_pool_ratios);
+        let latin_pool = self.latin_ch_dict.as_ref().map(|ch_dict| TextPool {
+            items: &self.latin_words[..],
+            ch_dict,
+        });
+        let symbol_pool = match (&self.symbol, &self.symbol_dict) {
+            (Some(symbol), Some(ch_dict)) => Some(TextPool {
+                items: &symbol[..],
+                ch_dict,
+            }),
+            _ => None,
+        };
+        let pool_weights = WeightedIndex::new([
+            chinese,
+            if latin_pool.is_some() { latin } else { 0.0 },
+            if symbol_pool.is_some() { symbol } else { 0.0 },
+        ])
+        .map_err(|err| PyValueError::new_err(format!("invalid ratios: {}", err)))?;
+
+        let text_with_font_list = self.convert_script(get_random_mixed_text_with_font_list(
+            &self.chinese_ch_dict,
+            &self.chinese_ch_weights,
+            latin_pool,
+            symbol_pool,
+            &pool_weights,
+            min..=max,
+        ));
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in text_with_font_list {
+                let font_list = font_list
+                    .map(|content| content.iter().map(FontAttrs::from).collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .into_py(py);
+                list.as_ref(py).append((ch, font_list)).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // 在同一行中交錯生成漢字、拉丁單詞與數字組（1 至 6 位），相鄰的單詞與數字組之間以空格分隔
+    // min: 指定生成文本的位置數下限，每個位置是一個漢字、一個拉丁單詞或一個數字組
+    // max: 指定生成文本的位置數上限
+    // latin_ratio: 每個位置是拉丁單詞的概率，需要配置 latin_corpus_file_path
+    // digit_ratio: 每個位置是數字組的概率，其餘爲漢字
+    // font_indices: 見 get_random_chinese
+    #[pyo3(signature = (min=5, max=10, latin_ratio=0.2, digit_ratio=0.1, font_indices=false))]
+    fn get_random_mixed(
+        &self,
+        min: u32,
+        max: u32,
+        latin_ratio: f64,
+        digit_ratio: f64,
+        font_indices: bool,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        if min > max {
+            return Err(PyValueError::new_err("`min` should not exceed `max`"));
+        }
+        if !(latin_ratio >= 0.0 && digit_ratio >= 0.0 && latin_ratio + digit_ratio <= 1.0) {
+            return Err(PyValueError::new_err(
+                "`latin_ratio` and `digit_ratio` should be non-negative and sum to at most 1",
+            ));
+        }
+        let latin_pool = self.latin_ch_dict.as_ref().map(|ch_dict| TextPool {
+            items: &self.latin_words[..],
+            ch_dict,
+        });
+        if latin_ratio > 0.0 && latin_pool.is_none() {
+            return Err(PyValueError::new_err(
+                "`latin_corpus_file_path` is not set in the config file",
+            ));
+        }
+
+        let text_with_font_list = self.convert_script(get_random_mixed_line_with_font_list(
+            &self.chinese_ch_dict,
+            &self.chinese_ch_weights,
+            latin_pool,
+            &self.digit_dict,
+            latin_ratio,
+            digit_ratio,
+            min..=max,
+        ));
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in text_with_font_list {
+                let font_list = match font_list {
+                    Some(content) => self.fonts_to_py(py, content, font_indices),
+                    None => Vec::<String>::new().into_py(py),
+                };
+                list.as_ref(py).append((ch, font_list)).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // 從 latin_corpus 中按整詞抽樣，生成 min 至 max 個以空格分隔的單詞，單詞兩端的標點已去除
+    // by_frequency: 按單詞在語料中出現的次數抽樣，否則每個單詞等概率
+    // font_indices: 見 get_random_chinese
+    #[pyo3(signature = (min=3, max=8, by_frequency=true, font_indices=false))]
+    fn get_random_latin_words(
+        &self,
+        min: u32,
+        max: u32,
+        by_frequency: bool,
+        font_indices: bool,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        if min > max {
+            return Err(PyValueError::new_err("`min` should not exceed `max`"));
+        }
+        let ch_dict = match &self.latin_ch_dict {
+            Some(ch_dict) if !self.latin_vocab.is_empty() => ch_dict,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "there are no words, `latin_corpus_file_path` is not set in the config file or the corpus is empty",
+                ))
+            }
+        };
+
+        let weights = self.latin_vocab_weights.as_ref().filter(|_| by_frequency);
+        let text_with_font_list =
+            get_random_words_with_font_list(&self.latin_vocab, weights, ch_dict, min..=max);
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in text_with_font_list {
+                let font_list = match font_list {
+                    Some(content) => self.fonts_to_py(py, content, font_indices),
+                    None => Vec::<String>::new().into_py(py),
+                };
+                list.as_ref(py).append((ch, font_list)).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // 從 rtl_corpus 中按整詞抽樣，生成 min 至 max 個以空格分隔的單詞（阿拉伯文、希伯來文等）
+    // 每個單詞作爲一個整體，其字體列表爲能繪製整個單詞的字體，使字母以同一字體連寫；按邏輯順序返回，繪製時從右到左排列
+    // by_frequency: 按單詞在語料中出現的次數抽樣，否則每個單詞等概率
+    // font_indices: 見 get_random_chinese
+    #[pyo3(signature = (min=3, max=8, by_frequency=true, font_indices=false))]
+    fn get_random_rtl_words(
+        &self,
+        min: u32,
+        max: u32,
+        by_frequency: bool,
+        font_indices: bool,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        if min > max {
+            return Err(PyValueError::new_err("`min` should not exceed `max`"));
+        }
+        if self.rtl_words.is_empty() {
+            return Err(PyValueError::new_err(
+                "there are no words, `rtl_corpus_file_path` is not set in the config file or the corpus is empty",
+            ));
+        }
+
+        let weights = self.rtl_word_weights.as_ref().filter(|_| by_frequency);
+        let space = self
+            .rtl_ch_dict
+            .as_ref()
+            .and_then(|ch_dict| ch_dict.get(" "));
+        let text_with_font_list =
+            get_random_word_units_with_font_list(&self.rtl_words, weights, space, min..=max);
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (word, font_list) in text_with_font_list {
+                let font_list = match font_list {
+                    Some(content) => self.fonts_to_py(py, content, font_indices),
+                    None => Vec::<String>::new().into_py(py),
+                };
+                list.as_ref(py).append((word, font_list)).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // 按模板生成日期、金額、電話、編號等結構化文本，如 "{YYYY}年{MM}月{DD}日"、"¥{0-9}{3},{0-9}{3}.{0-9}{2}"
+    // 花括號內可爲日期時間字段（YYYY、YY、MM、M、DD、D、hh、mm、ss）、字符範圍（0-9A-F）、選項（a|b）或前一項的重複次數（3 或 2,4）
+    // font_indices: 見 get_random_chinese
+    #[pyo3(signature = (template, font_indices=false))]
+    fn get_random_template(&self, template: &str, font_indices: bool) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        let text = Template::parse(template)
+            .map_err(PyValueError::new_err)?
+            .render();
+        let text_with_font_list = wrap_text_with_dicts(&text, &self.wrap_dicts());
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in text_with_font_list {
+                let font_list = match font_list {
+                    Some(content) => self.fonts_to_py(py, content, font_indices),
+                    None => Vec::<String>::new().into_py(py),
+                };
+                list.as_ref(py).append((ch, font_list)).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // kind: "amount"、"phone"、"serial" 或 "percent"
+    // locale: 數字格式，"en"、"zh"、"de"、"fr" 或 "in"
+    #[pyo3(signature = (kind="amount", locale="en"))]
+    fn get_random_numeric(&self, kind: &str, locale: &str) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        let kind = NumericKind::from_name(kind).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown kind `{}`, expected one of amount, phone, serial, percent",
+                kind
+            ))
+        })?;
+        let locale = NumericLocale::from_name(locale).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown locale `{}`, expected one of en, zh, de, fr, in",
+                locale
+            ))
+        })?;
+
+        let text = random_numeric(kind, locale);
+        let text_with_font_list = wrap_text_with_dicts(&text, &self.wrap_dicts());
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in text_with_font_list {
+                let font_list = font_list
+                    .map(|content| content.iter().map(FontAttrs::from).collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .into_py(py);
+                list.as_ref(py).append((ch, font_list)).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // 按 corpus_sources 的權重爲每個樣本選取一個語料來源
+    // min: 生成文本的長度下限，句子爲字數，其餘同各 get_random_* 方法
+    // max: 生成文本的長度上限
+    #[pyo3(signature = (min=5, max=10))]
+    fn get_random_sample(&self, min: u32, max: u32) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        let source = self.corpus_sources.choose().ok_or_else(|| {
+            PyValueError::new_err("`corpus_sources` is not set in the config file")
+        })?;
+
+        match source.kind {
+            SourceKind::Chinese => self.get_random_chinese(min, max, false, false),
+            SourceKind::Mixed => self.get_random_text(min, max, None),
+            SourceKind::Latin => self.get_random_text(min, max, Some((0.0, 1.0, 0.0))),
+            SourceKind::Sentence => {
+                let sentence = get_random_sentence(&source.lines, min..=max);
+                let text_with_font_list = wrap_text_with_dicts(sentence, &self.wrap_dicts());
+                Python::with_gil(|py| -> PyResult<Py<PyList>> {
+                    let list: Py<PyList> = PyList::empty(py).into();
+                    for (ch, font_list) in text_with_font_list {
+                        let font_list = font_list
+                            .map(|content| content.iter().map(FontAttrs::from).collect::<Vec<_>>())
+                            .unwrap_or_default()
+                            .into_py(py);
+                        list.as_ref(py).append((ch, font_list)).unwrap();
+                    }
+
+                    Ok(list)
+                })
+            }
+        }
+    }
+
+    // 在字符之間隨機插入連續空格，並可能在行首縮進，參數見配置文件中的 space_prob 等
+    fn insert_spaces(
+        &self,
+        text_with_font_list: Vec<(String, Vec<FontAttrsLike>)>,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        let text_with_font_list: Vec<_> = self
+            .to_internal(text_with_font_list)?
+            .into_iter()
+            .map(|(ch, font_list)| {
+                let font_list: Vec<FontAttrs> = font_list.iter().map(FontAttrs::from).collect();
+                (ch, font_list)
+            })
+            .collect();
+        let space_font_list = self
+            .wrap_dicts()
+            .into_iter()
+            .find_map(|ch_dict| ch_dict.get(" "))
+            .map(|font_list| font_list.iter().map(FontAttrs::from).collect())
+            .unwrap_or_default();
+
+        let text_with_font_list = self
+            .space_insertion
+            .insert(text_with_font_list, (" ".to_string(), space_font_list));
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in text_with_font_list {
+                list.as_ref(py).append((ch, font_list.into_py(py))).unwrap();
+            }
+
+            Ok(list)
+        })
+    }
+
+    // 按配置文件中的 label_normalization、fold_full_width 與 collapse_label_spaces 規範化標籤
+    fn normalize_label(&self, text: &str) -> String {
+        self.label_normalizer.normalize(text)
+    }
+
+    // 帶字體列表的文本的原始標籤與規範化後的標籤
+    fn get_label(
+        &self,
+        text_with_font_list: Vec<(String, Vec<FontAttrsLike>)>,
+    ) -> (String, String) {
+        let raw: String = text_with_font_list
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect();
+        let normalized = self.label_normalizer.normalize(&raw);
+
+        (raw, normalized)
+    }
+
+    // 以 label_noise_prob 的概率擾動標籤（替換、刪除或插入一個字符），圖片仍按原文本繪製
+    // 返回 (標籤, 是否被擾動)
+    fn get_noisy_label(
+        &self,
+        text_with_font_list: Vec<(String, Vec<FontAttrsLike>)>,
+    ) -> (String, bool) {
+        let _rng = self.rng.enter();
+        let (_, label) = self.get_label(text_with_font_list);
+        let mut rng = sample_rng();
+        let noisy = self.label_noise.perturb(&label, || {
+            let idx = self.chinese_ch_weights.sample(&mut rng);
+            self.chinese_ch_dict.get_index(idx).unwrap().0.clone()
+        });
+
+        match noisy {
+            Some(noisy) => (noisy, true),
+            None => (label, false),
+        }
+    }
+
+    #[pyo3(signature = (text, segment=false, font_indices=false))]
+    fn wrap_text_with_font_list(
+        &self,
+        text: &str,
+        segment: bool,
+        font_indices: bool,
+    ) -> PyResult<Py<PyList>> {
+        let _rng = self.rng.enter();
+        if segment {
+            let word_dict = self.word_dict.as_ref().ok_or_else(|| {
+                PyValueError::new_err("`word_dict_file_path` is not set in the config file")
+            })?;
+            let words_with_font_list =
+                wrap_words_with_font_list(text, &self.wrap_dicts(), word_dict);
+            return Python::with_gil(|py| -> PyResult<Py<PyList>> {
+                let list: Py<PyList> = PyList::empty(py).into();
+                for (word, font_list) in words_with_font_list {
+                    let font_list =
+                        self.fonts_to_py(py, &font_list.unwrap_or_default(), font_indices);
+                    list.as_ref(py).append((word, font_list)).unwrap();
+                }
+
+                Ok(list)
+            });
+        }
+
+        let chinese_text_with_font_list = wrap_text_with_dicts(text, &self.wrap_dicts());
+        Python::with_gil(|py| -> PyResult<Py<PyList>> {
+            let list: Py<PyList> = PyList::empty(py).into();
+            for (ch, font_list) in chinese_text_with_font_list {
+                if let Some(content) = font_list {
+                    list.as_ref(py)
+                        .append((ch, self.fonts_to_py(py, content, font_indices)))
+                        .unwrap();
+                } else {
+                    list.as_ref(py)
+                        .append::<(&str, &Vec<String>)>((ch, &vec![]))
+                        .unwrap();
+                }
+            }
+
+            Ok(list)
+        })
+    }
+
+    // 返回圖片；要求了 return_clusters、mask、return_x_ranges、return_fonts 或 return_metadata 中任一附加輸出時，返回字典 {"image": 圖片, "clusters", "mask", "x_ranges", "fonts", "metadata"}，只含所要求的鍵
+    // return_clusters: 字典的 clusters 爲每個字符的框 [(字符, (x0, y0, x1, y1))]；apply_effect 時爲字符墨跡經過與圖片相同的變換後的框，被裁掉的字符爲 None
+    // heights: 輸出圖片的高度列表，如 [32, 48, 64]，寬度等比縮放，圖片（及 clusters）爲各高度的列表
+    // return_fonts: 字典的 fonts 爲實際繪製每個字符的字體 [(字符, 字體, 字體文件)]，包括回退到 main_font_list 的字體
+    // width, height: 僅本次調用的畫布大小，覆蓋 font_img_width 與 font_img_height（及 auto_width），輸出仍裁剪到文本寬度
+    // return_metadata: 字典的 metadata 爲一個字典：文本、每個字符的字體、觸發的效果及其參數、所用的背景文件等，用於審查數據集
+    // return_x_ranges: 字典的 x_ranges 爲每個字符在最終圖片中所佔的橫向範圍 [(字符, (x0, x1))]，經過與圖片相同的變換，用於對齊監督的訓練與字符級評測
+    // mask: "binary" 時字典的 mask 爲字形像素的掩碼（255 爲字形），"instance" 時每個字符的像素爲其下標加一；掩碼經過與圖片相同的透視、填充等變換，與最終圖片對齊
+    // output: 圖片的返回形式，"numpy" 爲數組，"png" 或 "jpg" 爲編碼後的圖片文件 bytes，"pil" 爲 PIL.Image
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (text_with_font_list, text_color=None, background_color=None, apply_effect=false, return_clusters=false, features=None, ligatures=None, heights=None, return_fonts=false, width=None, height=None, return_metadata=false, mask=None, return_x_ranges=false, output="numpy"))]
+    fn gen_image_from_text_with_font_list<'py>(
+        &self,
+        text_with_font_list: Vec<(String, Vec<FontAttrsLike>)>,
+        text_color: Option<(u8, u8, u8)>,
+        background_color: Option<(u8, u8, u8)>,
+        apply_effect: bool,
+        return_clusters: bool,
+        features: Option<Vec<String>>,
+        ligatures: Option<bool>,
+        heights: Option<Vec<u32>>,
+        return_fonts: bool,
+        width: Option<u32>,
+        height: Option<u32>,
+        return_metadata: bool,
+        mask: Option<&str>,
+        return_x_ranges: bool,
+        output: &str,
+        _py: Python<'py>,
+    ) -> PyResult<PyObject> {
+        let _rng = self.rng.enter();
+        let output = OutputFormat::from_name(output)?;
+        if !matches!(mask, None | Some("binary") | Some("instance")) {
+            return Err(PyValueError::new_err(
+                "`mask` should be `binary` or `instance`",
+            ));
+        }
+        if (mask.is_some() || return_x_ranges) && heights.is_some() {
+            return Err(PyValueError::new_err(
+                "`mask` and `return_x_ranges` are not available with `heights`",
+            ));
+        }
+        if let Some(heights) = &heights {
+            if heights.is_empty() || heights.contains(&0) {
+                return Err(PyValueError::new_err(
+                    "`heights` should be a non-empty list of positive heights",
+                ));
+            }
+        }
+        if width == Some(0) || height == Some(0) {
+            return Err(PyValueError::new_err(
+                "`width` and `height` should be positive",
+            ));
+        }
+
+        let mut options = self.render_options(features)?;
+        let text: String = text_with_font_list
+            .iter()
+            .map(|(text, _)| text.as_str())
+            .collect();
+        let text_with_font_list = self.to_internal(text_with_font_list)?;
+        let ligatures = ligatures.unwrap_or(self.ligatures);
+        let (text_color, background_color) = self.sample_colors(text_color, background_color);
+        let text_rgb = image::Rgb([text_color.r(), text_color.g(), text_color.b()]);
+
+        let (img, clusters, fonts, merged, record, glyph_mask, x_ranges, effect_boxes) = _py
+            .allow_threads(|| {
+                let mut state = self.render_pool.get();
+                let (img, clusters) = self.render_line(
+                    &mut state,
+                    text_with_font_list,
+                    &mut options,
+                    ligatures,
+                    (text_color, background_color),
+                    (width, height),
+                );
+
+                let fonts = (return_fonts || return_metadata)
+                    .then(|| self.cluster_fonts(&state.font_system, &clusters));
+                let mut record = MergeRecord {
+                    styles: options.styles(),
+                    ..Default::default()
+                };
+                let merged =
+                    apply_effect.then(|| self.apply_effect_and_merge_recorded(&img, &mut record));
+                let coverage =
+                    (mask.is_some() || return_x_ranges || (apply_effect && return_clusters))
+                        .then(|| glyph_coverage(&img, text_rgb, background_color));
+                let glyph_mask = mask
+                    .zip(coverage.as_ref())
+                    .map(|(mask, coverage)| match mask {
+                        "binary" => binary_mask(coverage, &record.transforms),
+                        _ => instance_mask(coverage, &clusters, &record.transforms),
+                    });
+                let x_ranges = coverage
+                    .as_ref()
+                    .filter(|_| return_x_ranges)
+                    .map(|coverage| {
+                        clusters
+                            .iter()
+                            .map(|cluster| cluster.text.clone())
+                            .zip(cluster_x_ranges(coverage, &clusters, &record.transforms))
+                            .collect::<Vec<_>>()
+                    });
+                // 應用效果時字符框取自經過同樣變換的墨跡
+                let effect_boxes = coverage
+                    .as_ref()
+                    .filter(|_| apply_effect && return_clusters)
+                    .map(|coverage| cluster_boxes(coverage, &clusters, &record.transforms));
+                (
+                    img,
+                    clusters,
+                    fonts,
+                    merged,
+                    record,
+                    glyph_mask,
+                    x_ranges,
+                    effect_boxes,
+                )
+            });
+
+        let metadata = match return_metadata {
+            true => Some(self.metadata_to_py(
+                _py,
+                &text,
+                fonts.as_deref().unwrap_or_default(),
+                &record,
+            )?),
+            false => None,
+        };
+        let image = match merged {
+            Some(merge_img) => merge_img,
+            None => DynamicImage::ImageRgb8(img),
+        };
+        let size = (image.width(), image.height());
+        let images: Vec<DynamicImage> = match &heights {
+            Some(heights) => match &image {
+                DynamicImage::ImageRgb8(rgb) => resize_to_heights(rgb, heights)
+                    .into_iter()
+                    .map(DynamicImage::from)
+                    .collect(),
+                _ => resize_to_heights(&image.to_luma8(), heights)
+                    .into_iter()
+                    .map(DynamicImage::from)
+                    .collect(),
+            },
+            None => vec![image],
+        };
+        // 各字符在縮放後的圖片中的框，應用效果時取自經過同樣變換的墨跡
+        let cluster_boxes_at = |resized: (u32, u32)| match &effect_boxes {
+            Some(boxes) => clusters
+                .iter()
+                .zip(boxes)
+                .map(|(cluster, bbox)| {
+                    let bbox = bbox.map(|bbox| {
+                        let cluster = ClusterBox {
+                            bbox,
+                            ..cluster.clone()
+                        };
+                        cluster.resized(size, resized).bbox
+                    });
+                    (cluster.text.clone(), bbox)
+                })
+                .collect::<Vec<_>>()
+                .into_py(_py),
+            None => clusters
+                .iter()
+                .map(|cluster| {
+                    let cluster = cluster.resized(size, resized);
+                    (cluster.text, cluster.bbox)
+                })
+                .collect::<Vec<_>>()
+                .into_py(_py),
+        };
+        let mut py_images = Vec::with_capacity(images.len());
+        let mut py_clusters = Vec::with_capacity(images.len());
+        for each in images {
+            if return_clusters {
+                py_clusters.push(cluster_boxes_at((each.width(), each.height())));
+            }
+            py_images.push(output.to_py(_py, each)?);
+        }
+        let (image, clusters) = match heights {
+            Some(_) => (py_images.into_py(_py), py_clusters.into_py(_py)),
+            None => (py_images.remove(0), py_clusters.pop().into_py(_py)),
+        };
+
+        let extras =
+            return_clusters || return_fonts || return_metadata || mask.is_some() || return_x_ranges;
+        if !extras {
+            return Ok(image);
+        }
+        let res = PyDict::new(_py);
+        res.set_item("image", image)?;
+        if return_clusters {
+            res.set_item("clusters", clusters)?;
+        }
+        if let Some(glyph_mask) = glyph_mask {
+            let shape = [glyph_mask.height() as usize, glyph_mask.width() as usize];
+            let glyph_mask = PyArray::from_vec(_py, glyph_mask.into_raw())
+                .reshape(shape)
+                .unwrap();
+            res.set_item("mask", glyph_mask)?;
+        }
+        if let Some(x_ranges) = x_ranges {
+            res.set_item("x_ranges", x_ranges)?;
+        }
+        if let (true, Some(fonts)) = (return_fonts, fonts) {
+            res.set_item("fonts", fonts.into_py(_py))?;
+        }
+        if let Some(metadata) = metadata {
+            res.set_item("metadata", metadata)?;
+        }
+
+        Ok(res.into_py(_py))
+    }
+
+    // 將圖片寫入調用方預先分配的數組 out 的左上角，省去每個樣本的分配與複製，返回所用的 (高, 寬)
+    // out: apply_effect 時爲 (高, 寬) 的 uint8 數組（color_merge 時爲 (高, 寬, 3)），否則爲 (高, 寬, 3)，須可寫且 C 連續
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (text_with_font_list, out, text_color=None, background_color=None, apply_effect=false, features=None, ligatures=None))]
+    fn gen_image_into<'py>(
+        &self,
+        text_with_font_list: Vec<(String, Vec<FontAttrsLike>)>,
+        out: &'py PyArrayDyn<u8>,
+        text_color: Option<(u8, u8, u8)>,
+        background_color: Option<(u8, u8, u8)>,
+        apply_effect: bool,
+        features: Option<Vec<String>>,
+        ligatures: Option<bool>,
+        _py: Python<'py>,
+    ) -> PyResult<(usize, usize)> {
+        let _rng = self.rng.enter();
+        let mut options = self.render_options(features)?;
+        let text_with_font_list = self.to_internal(text_with_font_list)?;
+        let ligatures = ligatures.unwrap_or(self.ligatures);
+        let (text_color, background_color) = self.sample_colors(text_color, background_color);
+
+        let (img, merged) = _py.allow_threads(|| {
+            let mut state = self.render_pool.get();
+            let (img, _) = self.render_line(
+                &mut state,
+                text_with_font_list,
+                &mut options,
+                ligatures,
+                (text_color, background_color),
+                (None, None),
+            );
+            let merged = apply_effect.then(|| self.apply_effect_and_merge(&img));
+            (img, merged)
+        });
+
+        match merged {
+            Some(merge_img) => write_into_array(
+                out,
+                merge_img.as_bytes(),
+                merge_img.height() as usize,
+                merge_img.width() as usize,
+                merge_img.color().channel_count() as usize,
+            ),
+            None => write_into_array(
+                out,
+                img.as_raw(),
+                img.height() as usize,
+                img.width() as usize,
+                3,
+            ),
+        }
+    }
+
+    // group: 一組易混淆的字符，如 ["己", "已", "巳"]，爲空時從 confusable_file_path 中隨機選取一組
+    // context: 上下文的字數範圍，組內各樣本的上下文相同
+    #[pyo3(signature = (group=None, apply_effect=true, context=(0, 0)))]
+    fn gen_confusable_images(
+        &mut self,
+        group: Option<Vec<String>>,
+        apply_effect: bool,
+        context: (u32, u32),
+        py: Python<'_>,
+    ) -> PyResult<Vec<(String, PyObject)>> {
+        let _rng = self.rng.enter();
+        let mut rng = sample_rng();
+
+        let group = match group {
+            Some(group) => group,
+            None => self
+                .confusable_groups
+                .choose(&mut rng)
+                .cloned()
+                .ok_or_else(|| {
+                    PyValueError::new_err("`confusable_file_path` is not set in the config file")
+                })?,
+        };
+        if group.len() < 2 {
+            return Err(PyValueError::new_err(
+                "a confusable group needs at least 2 members",
+            ));
+        }
+        if context.0 > context.1 {
+            return Err(PyValueError::new_err("expected `context[0] <= context[1]`"));
+        }
+
+        // 組內所有字符都用同一個字體繪製
+        let mut fonts: Option<Vec<InternalAttrsOwned>> = None;
+        for cluster in group.iter().flat_map(|member| member.graphemes(true)) {
+            let font_list = self.font_util.fonts_containing(cluster);
+            fonts = Some(match fonts {
+                Some(fonts) => fonts
+                    .into_iter()
+                    .filter(|font| font_list.contains(font))
+                    .collect(),
+                None => font_list,
+            });
+        }
+        let font = fonts
+            .unwrap_or_default()
+            .choose(&mut rng)
+            .cloned()
+            .ok_or_else(|| PyValueError::new_err("no font can render every member of the group"))?;
+
+        // 上下文只取該字體能繪製的字符
+        let context_len = rng.gen_range(context.0..=context.1) as usize;
+        let mut context_chars = Vec::with_capacity(context_len);
+        for _ in 0..context_len * 100 {
+            if context_chars.len() == context_len {
+                break;
+            }
+            let (ch, font_list) = self
+                .chinese_ch_dict
+                .get_index(self.chinese_ch_weights.sample(&mut rng))
+                .unwrap();
+            if font_list.contains(&font) {
+                context_chars.push(ch.clone());
+            }
+        }
+        let insert_idx = rng.gen_range(0..=context_chars.len());
+        let layout_seed: u64 = rng.gen();
+        let seed: u64 = rng.gen();
+
+        // 組內各樣本的行高和留白相同
+        let mut state = self.render_pool.get();
+        let mut options = self.render_options(None)?;
+        options.padding = self.sample_line_metrics(&mut state);
+        let mut res = Vec::with_capacity(group.len());
+        for member in group.iter() {
+            let mut text_with_font_list: Vec<_> = context_chars
+                .iter()
+                .map(|ch| (ch.clone(), vec![font.clone()]))
+                .collect();
+            text_with_font_list.splice(
+                insert_idx..insert_idx,
+                member
+                    .graphemes(true)
+                    .map(|cluster| (cluster.to_string(), vec![font.clone()])),
+            );
+            let label: String = text_with_font_list
+                .iter()
+                .map(|(ch, _)| ch.as_str())
+                .collect();
+
+            // 每個樣本的字形抖動、字距與顏色都從同一個種子開始，上下文的繪製因而相同
+            reseed(layout_seed);
+            self.layout_line(&mut state, text_with_font_list, self.ligatures, None);
+            let RenderState {
+                font_system,
+                editor_buffer,
+                swash_cache,
+            } = &mut *state;
+            let (img, clusters) = generate_image(
+                editor_buffer,
+                font_system,
+                swash_cache,
+                Color::rgb(0, 0, 0),
+                image::Rgb([255, 255, 255]),
+                &options,
+            );
+            self.font_usage
+                .record(clusters.iter().map(|cluster| cluster.font_id));
+
+            let image = if apply_effect {
+                // 每個樣本的效果與背景都從同一個種子開始
+                reseed_effect_rng(seed);
+                Self::merged_to_py(py, self.apply_effect_and_merge(&img))
+            } else {
+                let (img_height, img_width) = (img.height() as usize, img.width() as usize);
+                PyArray::from_vec(py, img.into_vec())
+                    .reshape([img_height, img_width, 3])
+                    .unwrap()
+                    .to_dyn()
+                    .into_py(py)
+            };
+            res.push((label, image));
+        }
+
+        Ok(res)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (batch, text_color=None, background_color=None, features=None, ligatures=None, output="numpy"))]
+    fn gen_images_from_text_with_font_list<'py>(
+        &self,
+        batch: Vec<Vec<(String, Vec<FontAttrsLike>)>>,
+        text_color: Option<(u8, u8, u8)>,
+        background_color: Option<(u8, u8, u8)>,
+        features: Option<Vec<String>>,
+        ligatures: Option<bool>,
+        output: &str,
+        _py: Python<'py>,
+    ) -> PyResult<Vec<PyObject>> {
+        let _rng = self.rng.enter();
+        let output = OutputFormat::from_name(output)?;
+        let mut options = self.render_options(features)?;
+        let ligatures = ligatures.unwrap_or(self.ligatures);
+
+        let batch = batch
+            .into_iter()
+            .map(|text_with_font_list| self.to_internal(text_with_font_list))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let images = _py.allow_threads(|| {
+            let mut state = self.render_pool.get();
+            let mut texts = Vec::with_capacity(batch.len());
+            let mut backgrounds = Vec::with_capacity(batch.len());
+            for text_with_font_list in batch {
+                let (text_color, background_color) =
+                    self.sample_colors(text_color, background_color);
+                backgrounds.push(background_color);
+                options.padding = self.sample_line_metrics(&mut state);
+                self.layout_line(&mut state, text_with_font_list, ligatures, None);
+                let RenderState {
+                    font_system,
+                    editor_buffer,
+                    swash_cache,
+                } = &mut *state;
+                let (img_width, img_height) = editor_buffer.size();
+                texts.push(rasterize_text(
+                    editor_buffer,
+                    font_system,
+                    swash_cache,
+                    text_color,
+                    img_width as usize,
+                    img_height as usize,
+                    &options,
+                ));
+            }
+
+            let raw_images = self.composite_batch(&texts, &backgrounds);
+            raw_images
+                .into_iter()
+                .zip(texts)
+                .map(|(raw_image, text)| {
+                    let (img, clusters) = finish_image(raw_image, text);
+                    self.font_usage
+                        .record(clusters.iter().map(|cluster| cluster.font_id));
+                    img
+                })
+                .collect::<Vec<_>>()
+        });
+
+        images
+            .into_iter()
+            .map(|img| output.to_py(_py, img.into()))
+            .collect()
+    }
+
+    // 將任意長的文本按 font_img_width 切分成多行並逐行繪製，返回 [(圖片, 標籤)]
+    // 換行符處必定切分；優先在空格處切分，否則在字符（segment 時爲詞）之間切分
+    // missing: 字典中沒有的字符的處理方式，"fallback" 用 main_font_list 繪製，"skip" 從圖片和標籤中刪去，"error" 報錯
+    // output: 見 gen_image_from_text_with_font_list
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (text, segment=false, missing="fallback", apply_effect=false, text_color=None, background_color=None, features=None, ligatures=None, output="numpy"))]
+    fn gen_images_from_long_text<'py>(
+        &self,
+        text: &str,
+        segment: bool,
+        missing: &str,
+        apply_effect: bool,
+        text_color: Option<(u8, u8, u8)>,
+        background_color: Option<(u8, u8, u8)>,
+        features: Option<Vec<String>>,
+        ligatures: Option<bool>,
+        output: &str,
+        _py: Python<'py>,
+    ) -> PyResult<Vec<(PyObject, String)>> {
+        let _rng = self.rng.enter();
+        let output = OutputFormat::from_name(output)?;
+        let paragraphs = self.wrap_long_text(text, segment, missing)?;
+
+        let mut options = self.render_options(features)?;
+        let ligatures = ligatures.unwrap_or(self.ligatures);
+        let (text_color, background_color) = self.sample_colors(text_color, background_color);
+
+        let images = _py.allow_threads(|| {
+            let mut state = self.render_pool.get();
+            // one em to spare for kerning and size jitter, like `LineWidth::fit`
+            let max_width = self.line_width.width - self.render_pool.metrics().font_size;
+            let mut unit_widths: HashMap<&str, f32> = HashMap::new();
+            let mut res = vec![];
+            for paragraph in paragraphs.iter() {
+                let mut widths = Vec::with_capacity(paragraph.len());
+                for (unit, font_list) in paragraph.iter() {
+                    let width = match unit_widths.get(unit.as_str()) {
+                        Some(width) => *width,
+                        None => {
+                            self.layout_line(
+                                &mut state,
+                                vec![(unit.clone(), font_list.clone())],
+                                ligatures,
+                                None,
+                            );
+                            let width: f32 = state
+                                .editor_buffer
+                                .layout_runs()
+                                .map(|run| run.line_w)
+                                .sum();
+                            unit_widths.insert(unit.as_str(), width);
+                            width
+                        }
+                    };
+                    widths.push(width);
+                }
+                let breaks: Vec<_> = paragraph
+                    .iter()
+                    .map(|(unit, _)| unit.trim().is_empty())
+                    .collect();
+
+                for range in split_by_width(&widths, &breaks, max_width) {
+                    let line = paragraph[range].to_vec();
+                    let label: String = line.iter().map(|(unit, _)| unit.as_str()).collect();
+                    let (img, _) = self.render_line(
+                        &mut state,
+                        line,
+                        &mut options,
+                        ligatures,
+                        (text_color, background_color),
+                        (None, None),
+                    );
+                    let merged = apply_effect.then(|| self.apply_effect_and_merge(&img));
+                    res.push((img, merged, self.label_normalizer.normalize(&label)));
+                }
+            }
+
+            res
+        });
+
+        images
+            .into_iter()
+            .map(|(img, merged, label)| Ok((Self::sample_to_py(_py, img, merged, output)?, label)))
+            .collect()
+    }
+
+    // 在多個線程中並行生成 n 個隨機中文樣本，返回 [(圖片, 標籤)]，生成期間釋放 GIL
+    // min, max, add_extra_symbol: 見 get_random_chinese
+    // num_threads: 線程數，默認用 rayon 的全局線程池（CPU 核數）
+    // writer: DatasetWriter 或 LmdbWriter，給出時圖片在各線程中編碼後直接寫入數據集，返回 [(圖片在數據集中的位置, 標籤)]
+    // output: 見 gen_image_from_text_with_font_list，"png" 與 "jpg" 同樣在各線程中編碼；給出 writer 時不使用
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n, min=5, max=10, add_extra_symbol=false, apply_effect=true, text_color=None, background_color=None, features=None, ligatures=None, num_threads=None, writer=None, output="numpy"))]
+    fn gen_batch<'py>(
+        &self,
+        n: usize,
+        min: u32,
+        max: u32,
+        add_extra_symbol: bool,
+        apply_effect: bool,
+        text_color: Option<(u8, u8, u8)>,
+        background_color: Option<(u8, u8, u8)>,
+        features: Option<Vec<String>>,
+        ligatures: Option<bool>,
+        num_threads: Option<usize>,
+        mut writer: Option<PySampleWriter>,
+        output: &str,
+        _py: Python<'py>,
+    ) -> PyResult<Vec<(PyObject, String)>> {
+        let _rng = self.rng.enter();
+        let output = OutputFormat::from_name(output)?;
+        if min > max {
+            return Err(PyValueError::new_err("`min` should not exceed `max`"));
+        }
+        let pool = match num_threads {
+            Some(num_threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .map_err(|err| PyValueError::new_err(err.to_string()))?,
+            ),
+            None => None,
+        };
+
+        let options = self.render_options(features)?;
+        let ligatures = ligatures.unwrap_or(self.ligatures);
+        let symbol = if add_extra_symbol {
+            self.symbol
+                .as_ref()
+                .map(|symbol| (symbol, &self.symbol_insertion))
+        } else {
+            None
+        };
+
+        // 每個樣本的種子取自當前線程，樣本在哪個線程上生成都不影響結果；每個線程從 render_pool 取一份繪製狀態
+        let seeds: Vec<u64> = (0..n).map(|_| sample_rng().gen()).collect();
+        let dataset = writer.as_ref().map(PySampleWriter::as_writer);
+        let generate = |&seed: &u64| {
+            reseed(seed);
+            let text_with_font_list = self.convert_script(get_random_chinese_text_with_font_list(
+                &self.chinese_ch_dict,
+                &self.chinese_ch_weights,
+                symbol,
+                min..=max,
+            ));
+            let label: String = text_with_font_list.iter().map(|(ch, _)| *ch).collect();
+            let text_with_font_list = text_with_font_list
+                .into_iter()
+                .map(|(ch, font_list)| (ch.to_string(), font_list.cloned().unwrap_or_default()))
+                .collect();
+
+            let mut options = options.clone();
+            let colors = self.sample_colors(text_color, background_color);
+            let (img, _) = self.render_line(
+                &mut self.render_pool.get(),
+                text_with_font_list,
+                &mut options,
+                ligatures,
+                colors,
+                (None, None),
+            );
+            let mut record = MergeRecord {
+                styles: options.styles(),
+                ..Default::default()
+            };
+            let merged =
+                apply_effect.then(|| self.apply_effect_and_merge_recorded(&img, &mut record));
+            let label = self.label_normalizer.normalize(&label);
+
+            // 編碼是寫入樣本最慢的一步，留在各工作線程中進行
+            let encoded = match dataset {
+                Some(dataset) => {
+                    let metadata = ImageMetadata {
+                        label: label.clone(),
+                        seed: Some(seed),
+                        effects: record.effects.iter().map(ToString::to_string).collect(),
+                        styles: record.styles.clone(),
+                        ..Default::default()
+                    };
+                    match &merged {
+                        Some(merged) => Some(dataset.encode(merged, Some(&metadata))),
+                        None => Some(
+                            dataset.encode(&DynamicImage::ImageRgb8(img.clone()), Some(&metadata)),
+                        ),
+                    }
+                }
+                None => match &merged {
+                    Some(merged) => output.encode(merged),
+                    None => output.encode(&DynamicImage::ImageRgb8(img.clone())),
+                },
+            };
+            (img, merged, label, encoded)
+        };
+        let samples: Vec<_> = _py.allow_threads(|| match &pool {
+            Some(pool) => pool.install(|| seeds.par_iter().map(generate).collect()),
+            None => seeds.par_iter().map(generate).collect(),
+        });
+
+        if let Some(writer) = writer.as_mut().map(PySampleWriter::as_writer_mut) {
+            let mut res = Vec::with_capacity(samples.len());
+            for (_, _, label, encoded) in samples {
+                let name = encoded
+                    .expect("samples are encoded when there is a writer")
+                    .and_then(|encoded| writer.append(&encoded, &label))
+                    .map_err(PyValueError::new_err)?;
+                res.push((name.into_py(_py), label));
+            }
+            writer.flush().map_err(PyValueError::new_err)?;
+            return Ok(res);
+        }
+
+        samples
+            .into_iter()
+            .map(|(img, merged, label, encoded)| {
+                let image = match encoded {
+                    Some(encoded) => {
+                        let encoded = encoded.map_err(PyValueError::new_err)?;
+                        PyBytes::new(_py, &encoded).into_py(_py)
+                    }
+                    None => Self::sample_to_py(_py, img, merged, output)?,
+                };
+                Ok((image, label))
+            })
+            .collect()
+    }
+
+    // 將文本自動換行繪製成一張多行的段落圖片，返回 (圖片, [(每行的標籤, 每行的框 (x0, y0, x1, y1))])
+    // 換行符處必定換行，其餘按 max_width（默認爲 font_img_width）在詞或字符之間換行
+    // line_spacing: 行高相對 line_height 的倍數；段落總是橫排，不應用效果
+    // missing: 見 gen_images_from_long_text
+    // output: 見 gen_image_from_text_with_font_list
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (text, max_width=None, line_spacing=1.0, missing="fallback", text_color=None, background_color=None, features=None, ligatures=None, output="numpy"))]
+    fn gen_paragraph_image<'py>(
+        &self,
+        text: &str,
+        max_width: Option<u32>,
+        line_spacing: f32,
+        missing: &str,
+        text_color: Option<(u8, u8, u8)>,
+        background_color: Option<(u8, u8, u8)>,
+        features: Option<Vec<String>>,
+        ligatures: Option<bool>,
+        output: &str,
+        _py: Python<'py>,
+    ) -> PyResult<(PyObject, Vec<(String, BBox)>)> {
+        let _rng = self.rng.enter();
+        let output = OutputFormat::from_name(output)?;
+        if max_width == Some(0) || line_spacing <= 0.0 {
+            return Err(PyValueError::new_err(
+                "`max_width` and `line_spacing` should be positive",
+            ));
+        }
+
+        let paragraphs = self.wrap_long_text(text, false, missing)?;
+        let mut options = self.render_options(features)?;
+        options.vertical = None;
+        let ligatures = ligatures.unwrap_or(self.ligatures);
+        let (text_color, background_color) = self.sample_colors(text_color, background_color);
+        let max_width = max_width.map_or(self.line_width.width, |width| width as f32);
+
+        let (img, lines) = _py.allow_threads(|| {
+            let mut state = self.render_pool.get();
+            options.padding = self.sample_line_metrics(&mut state);
+            let RenderState {
+                font_system,
+                editor_buffer,
+                swash_cache,
+            } = &mut *state;
+            let (buffer_width, buffer_height) = editor_buffer.size();
+            let metrics = editor_buffer.metrics();
+            let line_height = (metrics.line_height * line_spacing).round().max(1.0);
+            editor_buffer.set_metrics(font_system, Metrics::new(metrics.font_size, line_height));
+
+            // lay out every line first, then shrink the buffer to the lines
+            editor_buffer.set_size(font_system, max_width, f32::MAX);
+            editor_buffer.lines.clear();
+            for paragraph in paragraphs {
+                let line = self.with_line_attrs(paragraph, |spans, attrs| {
+                    let (line_text, attrs_list) = build_line(spans, attrs, ligatures);
+                    BufferLine::new(&line_text, attrs_list, cosmic_text::Shaping::Advanced)
+                });
+                editor_buffer.lines.push(line);
+            }
+            editor_buffer.shape_until_scroll(font_system, false);
+            let height = editor_buffer.layout_runs().count().max(1) as f32 * line_height;
+            editor_buffer.set_size(font_system, max_width, height);
+
+            let (img, clusters) = generate_image(
+                editor_buffer,
+                font_system,
+                swash_cache,
+                text_color,
+                background_color,
+                &options,
+            );
+            editor_buffer.set_metrics(font_system, metrics);
+            editor_buffer.set_size(font_system, buffer_width, buffer_height);
+            self.font_usage
+                .record(clusters.iter().map(|cluster| cluster.font_id));
+
+            let lines: Vec<_> = line_boxes(&clusters)
+                .into_iter()
+                .map(|(label, bbox)| (self.label_normalizer.normalize(&label), bbox))
+                .collect();
+            (img, lines)
+        });
+
+        Ok((output.to_py(_py, img.into())?, lines))
+    }
+
+    // 場景文本（SynthText 式）：繪製多段獨立的文本，各自做隨機透視變換後貼到 bg_factory 中一張整幅背景上互不重疊的隨機位置
+    // 返回 (圖片, [(標籤, 四邊形 [(x, y)]，依次爲左上、右上、右下、左下)])，bg_factory 爲彩色時返回 RGB 圖片，否則爲灰度圖
+    // bg_factory: 背景尺寸即場景尺寸的 BgFactory，如 BgFactory("./background", 512, 512)
+    // texts: 各段文本，默認隨機生成 num_instances 段 min 至 max 個字的中文
+    // instance_height: 每段文本（透視變換後）高度的範圍
+    // max_tries: 每段文本尋找不重疊位置的次數，找不到時捨棄該段
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (bg_factory, texts=None, num_instances=(2, 5), min=2, max=8, instance_height=(24, 64), max_tries=20, features=None, ligatures=None))]
+    fn gen_scene_image<'py>(
+        &self,
+        bg_factory: PyRef<BgFactory>,
+        texts: Option<Vec<String>>,
+        num_instances: (usize, usize),
+        min: u32,
+        max: u32,
+        instance_height: (u32, u32),
+        max_tries: usize,
+        features: Option<Vec<String>>,
+        ligatures: Option<bool>,
+        _py: Python<'py>,
+    ) -> PyResult<(PyObject, Vec<(String, Polygon)>)> {
+        let _rng = self.rng.enter();
+        if min > max || num_instances.0 > num_instances.1 {
+            return Err(PyValueError::new_err(
+                "`min` should not exceed `max`, nor the lower bound of `num_instances` its upper bound",
+            ));
+        }
+        if instance_height.0 == 0 || instance_height.0 > instance_height.1 {
+            return Err(PyValueError::new_err(
+                "`instance_height` should be a positive range (low, high)",
+            ));
+        }
+
+        let texts: Vec<TextWithFontList> = match texts {
+            Some(texts) => texts
+                .iter()
+                .map(|text| {
+                    wrap_text_with_dicts(text, &self.wrap_dicts())
+                        .into_iter()
+                        .map(|(cluster, font_list)| {
+                            (cluster.to_string(), font_list.cloned().unwrap_or_default())
+                        })
+                        .collect()
+                })
+                .collect(),
+            None => (0..sample_rng().gen_range(num_instances.0..=num_instances.1))
+                .map(|_| {
+                    self.convert_script(get_random_chinese_text_with_font_list::<_, String, _>(
+                        &self.chinese_ch_dict,
+                        &self.chinese_ch_weights,
+                        None,
+                        min..=max,
+                    ))
+                    .into_iter()
+                    .map(|(ch, font_list)| (ch.to_string(), font_list.cloned().unwrap_or_default()))
+                    .collect()
+                })
+                .collect(),
+        };
+        let mut options = self.render_options(features)?;
+        options.vertical = None;
+        let ligatures = ligatures.unwrap_or(self.ligatures);
+        let colors = (Color::rgb(0, 0, 0), image::Rgb([255, 255, 255]));
+        let bg_factory = &*bg_factory;
+
+        let (scene, instances) = _py.allow_threads(|| {
+            let mut state = self.render_pool.get();
+            let mut instances = Vec::with_capacity(texts.len());
+            for text_with_font_list in texts {
+                let label: String = text_with_font_list
+                    .iter()
+                    .map(|(cluster, _)| cluster.as_str())
+                    .collect();
+                let (img, _) = self.render_line(
+                    &mut state,
+                    text_with_font_list,
+                    &mut options,
+                    ligatures,
+                    colors,
+                    (None, None),
+                );
+                let mut mask = image::imageops::grayscale(&img);
+                image::imageops::invert(&mut mask);
+                let mask = match crop_to_ink(&mask) {
+                    Some(mask) => mask,
+                    None => continue,
+                };
+
+                let (mask, polygon) = self.cv_util.random_perspective_quad(&mask);
+                let height = effect_rng().gen_range(instance_height.0..=instance_height.1);
+                instances.push(
+                    TextInstance {
+                        text: self.label_normalizer.normalize(&label),
+                        mask,
+                        polygon,
+                    }
+                    .resize_to_height(height),
+                );
+            }
+
+            let index = bg_factory.random_index();
+            match bg_factory.rgb(index) {
+                Some(rgb) => {
+                    let mut scene = rgb.clone();
+                    let instances = paste_instances(&mut scene, instances, max_tries);
+                    (DynamicImage::from(scene), instances)
+                }
+                None => {
+                    let mut scene = bg_factory[index].clone();
+                    let instances = paste_instances(&mut scene, instances, max_tries);
+                    (DynamicImage::from(scene), instances)
+                }
+            }
+        });
+
+        let instances = instances
+            .into_iter()
+            .map(|instance| (instance.text, instance.polygon))
+            .collect();
+
+        Ok((Self::merged_to_py(_py, scene), instances))
+    }
+}
+
+#[cfg(feature = "python")]
+impl Generator {
+    /// Rebuild `cv_util` and `merge_util` from the config file with the profile, the difficulty
+    /// preset and the overrides, and the backgrounds if their directory or size changed. Nothing
+    /// is replaced on error.
+    fn reload_effects(
+        &mut self,
+        profile: Option<String>,
+        difficulty: Option<String>,
+    ) -> PyResult<()> {
+        let mut overrides = match &difficulty {
+            Some(level) => difficulty_preset(level).map_err(PyValueError::new_err)?,
+            None => vec![],
+        };
+        overrides.extend_from_slice(&self.overrides);
+        let config = Config::from_yaml_with(&self.config_path, profile.as_deref(), &overrides)
+            .map_err(PyValueError::new_err)?;
+
+        self.cv_util = CvUtil::from_config(&config);
+        self.merge_util = MergeUtil::from_config(&config);
+        if config.bg_dir != self.bg_factory.bg_dir
+            || config.bg_height != self.bg_factory.height
+            || config.bg_width != self.bg_factory.width
+            || config.color_merge != self.bg_factory.is_color()
+        {
+            self.bg_factory = BgFactory::with_color(
+                config.bg_dir,
+                config.bg_height,
+                config.bg_width,
+                config.color_merge,
+            );
+        }
+        self.profile = profile;
+        self.difficulty = difficulty;
+
+        Ok(())
+    }
+
+    /// Split the text into paragraphs at line breaks and wrap each with the dicts, by cluster
+    /// or with `segment` by word. `missing` is the handling of units in none of the dicts, see
+    /// `gen_images_from_long_text`; empty paragraphs are dropped.
+    fn wrap_long_text(
+        &self,
+        text: &str,
+        segment: bool,
+        missing: &str,
+    ) -> PyResult<Vec<TextWithFontList>> {
+        if !["fallback", "skip", "error"].contains(&missing) {
+            return Err(PyValueError::new_err(
+                "`missing` should be `fallback`, `skip` or `error`",
+            ));
+        }
+        let word_dict = if segment {
+            Some(self.word_dict.as_ref().ok_or_else(|| {
+                PyValueError::new_err("`word_dict_file_path` is not set in the config file")
+            })?)
+        } else {
+            None
+        };
+
+        let mut paragraphs = vec![];
+        for paragraph in text.lines() {
+            let units: Vec<(&str, Option<Vec<InternalAttrsOwned>>)> = match word_dict {
+                Some(word_dict) => {
+                    wrap_words_with_font_list(paragraph, &self.wrap_dicts(), word_dict)
+                }
+                None => wrap_text_with_dicts(paragraph, &self.wrap_dicts())
+                    .into_iter()
+                    .map(|(cluster, font_list)| (cluster, font_list.cloned()))
+                    .collect(),
+            };
+
+            let mut line = Vec::with_capacity(units.len());
+            for (unit, font_list) in units {
+                match font_list {
+                    Some(font_list) => line.push((unit.to_string(), font_list)),
+                    None if unit.trim().is_empty() || missing == "fallback" => {
+                        line.push((unit.to_string(), vec![]))
+                    }
+                    None if missing == "skip" => {}
+                    None => {
+                        return Err(PyValueError::new_err(format!(
+                            "`{}` is in none of the dicts",
+                            unit
+                        )))
+                    }
+                }
+            }
+            if !line.is_empty() {
+                paragraphs.push(line);
+            }
+        }
+
+        Ok(paragraphs)
+    }
+
+    /// The merged image if there is one, otherwise the RGB text image, in the `output` format.
+    fn sample_to_py(
+        py: Python<'_>,
+        img: ImageBuffer<image::Rgb<u8>, Vec<u8>>,
+        merged: Option<DynamicImage>,
+        output: OutputFormat,
+    ) -> PyResult<PyObject> {
+        output.to_py(py, merged.unwrap_or_else(|| img.into()))
+    }
+
+    /// A merged image as a numpy array, (height, width) or (height, width, 3) if it is in color.
+    fn merged_to_py(py: Python<'_>, merged: DynamicImage) -> PyObject {
+        let (img_height, img_width) = (merged.height() as usize, merged.width() as usize);
+        match merged {
+            DynamicImage::ImageRgb8(rgb) => PyArray::from_vec(py, rgb.into_vec())
+                .reshape([img_height, img_width, 3])
+                .unwrap()
+                .to_dyn()
+                .into_py(py),
+            gray => PyArray::from_vec(py, gray.into_luma8().into_vec())
+                .reshape([img_height, img_width])
+                .unwrap()
+                .to_dyn()
+                .into_py(py),
+        }
+    }
+
+    /// The padding is set per sample by `sample_line_metrics`.
+    fn render_options(&self, features: Option<Vec<String>>) -> PyResult<RenderOptions> {
+        let features = match features {
+            Some(features) => self
+                .opentype_features
+                .with_default(opentype::parse_tags(&features).map_err(PyValueError::new_err)?),
+            None => self.opentype_features.clone(),
+        };
+
+        Ok(RenderOptions {
+            features,
+            kerning: self.kerning,
+            ink_texture: self.ink_texture,
+            padding: Padding::default(),
+            glyph_size_jitter: self.glyph_size_jitter,
+            image_glyphs: (!self.image_glyphs.is_empty()).then(|| self.image_glyphs.clone()),
+            char_colors: self.char_colors.clone(),
+            vertical: self.vertical,
+            faux_style: FauxStyle::default(),
+            spacing: Spacing::default(),
+            glyph_jitter: self.glyph_jitter,
+            text_path: None,
+            drop_shadow: None,
+            decoration: None,
+        })
+    }
+
+    /// The face that rendered each cluster: (cluster, font, file).
+    fn cluster_fonts(
+        &self,
+        font_system: &FontSystem,
+        clusters: &[ClusterBox],
+    ) -> Vec<(String, FontAttrs, String)> {
+        clusters
+            .iter()
+            .filter_map(|cluster| {
+                let (attrs, file) = describe_face(font_system, cluster.font_id?)?;
+                Some((cluster.text.clone(), FontAttrs::from(&attrs), file))
+            })
+            .collect()
+    }
+
+    /// The text and background colors of a sample: the ones given by the caller, the others
+    /// drawn from the color scheme of the config, or black on white without one.
+    fn sample_colors(
+        &self,
+        text_color: Option<(u8, u8, u8)>,
+        background_color: Option<(u8, u8, u8)>,
+    ) -> (Color, image::Rgb<u8>) {
+        let text_color = text_color.map(|(r, g, b)| image::Rgb([r, g, b]));
+        let background_color = background_color.map(|(r, g, b)| image::Rgb([r, g, b]));
+        let (text_color, background_color) = match &self.color_scheme {
+            Some(color_scheme) => color_scheme.sample(text_color, background_color),
+            None => (
+                text_color.unwrap_or(image::Rgb([0, 0, 0])),
+                background_color.unwrap_or(image::Rgb([255, 255, 255])),
+            ),
+        };
+        let [r, g, b] = text_color.0;
+
+        (Color::rgb(r, g, b), background_color)
+    }
+
+    /// Draw the line height of the next sample on the buffer of `state`, and its padding.
+    fn sample_line_metrics(&self, state: &mut RenderState) -> Padding {
+        self.line_metrics
+            .sample(&mut state.editor_buffer, &mut state.font_system)
+    }
+
+    /// Returns whether the dict is `chinese_ch_dict`.
+    fn check_dict_name(dict: &str) -> PyResult<bool> {
+        match dict {
+            "chinese" => Ok(true),
+            "latin" | "symbol" | "rtl" => Ok(false),
+            _ => Err(PyValueError::new_err(
+                "`dict` should be `chinese`, `latin`, `symbol` or `rtl`",
+            )),
+        }
+    }
+
+    /// The dict must have been checked by `check_dict_name`. A missing Latin, symbol or RTL
+    /// dict is created.
+    fn dict_mut(&mut self, dict: &str) -> &mut IndexMap<String, Vec<InternalAttrsOwned>> {
+        match dict {
+            "latin" => self.latin_ch_dict.get_or_insert_with(IndexMap::new),
+            "symbol" => self.symbol_dict.get_or_insert_with(IndexMap::new),
+            "rtl" => self.rtl_ch_dict.get_or_insert_with(IndexMap::new),
+            _ => &mut self.chinese_ch_dict,
+        }
+    }
+
+    /// The dicts in `wrap_dict_order`, a missing Latin, symbol or RTL dict is skipped.
+    fn wrap_dicts(&self) -> Vec<&IndexMap<String, Vec<InternalAttrsOwned>>> {
+        self.wrap_dict_order
+            .iter()
+            .filter_map(|name| match name.as_str() {
+                "chinese" => Some(&self.chinese_ch_dict),
+                "latin" => self.latin_ch_dict.as_ref(),
+                "rtl" => self.rtl_ch_dict.as_ref(),
+                _ => self.symbol_dict.as_ref(),
+            })
+            .collect()
+    }
+
+    // 按 script 轉換生成的中文文本，字體列表取轉換後的字符的
+    fn convert_script<'a>(
+        &'a self,
+        text: Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>,
+    ) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)> {
+        match &self.script_converter {
+            Some(converter) if converter.script != Script::Original => converter
+                .convert_with_font_list(
+                    text,
+                    &[&self.chinese_ch_dict, &self.converted_dict],
+                    |ch| self.charset.as_ref().is_none_or(|set| set.contains(ch)),
+                ),
+            _ => text,
+        }
+    }
+
+    /// Add the faces loaded since to `font_list`, and to the font lists of the characters they
+    /// cover in every dict.
+    fn register_new_fonts(&mut self) -> Vec<FontAttrs> {
+        let new_fonts: Vec<_> = self
+            .font_util
+            .get_full_font_list()
+            .into_iter()
+            .filter(|font| !self.font_index.contains_key(font))
+            .collect();
+        for font in new_fonts.iter() {
+            self.font_index.insert(font.clone(), self.font_list.len());
+            self.font_list.push(font.clone());
+        }
+
+        let font_util = &mut self.font_util;
+        let dicts = [
+            Some(&mut self.chinese_ch_dict),
+            self.latin_ch_dict.as_mut(),
+            self.symbol_dict.as_mut(),
+            self.rtl_ch_dict.as_mut(),
+            Some(&mut self.digit_dict),
+            Some(&mut self.converted_dict),
+        ];
+        for ch_dict in dicts.into_iter().flatten() {
+            for (ch, font_list) in ch_dict.iter_mut() {
+                for font in new_fonts.iter() {
+                    if font_util.is_font_contain_cluster(font.as_attrs(), ch) {
+                        font_list.push(font.clone());
+                    }
+                }
+            }
+        }
+        for (word, font_list) in self.rtl_words.iter_mut() {
+            for font in new_fonts.iter() {
+                if word
+                    .graphemes(true)
+                    .all(|cluster| font_util.is_font_contain_cluster(font.as_attrs(), cluster))
+                {
+                    font_list.push(font.clone());
+                }
+            }
+        }
+        self.advance_cache.clear();
+
+        new_fonts.iter().map(FontAttrs::from).collect()
+    }
+
+    fn is_in_charset(&self, idx: usize) -> bool {
+        match &self.charset {
+            Some(charset) => self
+                .chinese_ch_dict
+                .get_index(idx)
+                .is_some_and(|(ch, _)| charset.contains(ch)),
+            None => true,
+        }
+    }
+
+    fn rebuild_chinese_weights(&mut self) -> PyResult<()> {
+        let freqs: Vec<_> = self
+            .chinese_ch_freqs
+            .iter()
+            .enumerate()
+            .map(|(idx, freq)| if self.is_in_charset(idx) { *freq } else { 0.0 })
+            .collect();
+        self.chinese_ch_weights
+            .reweight(scale_freqs(
+                &freqs,
+                self.inverse_frequency,
+                self.sampling_temperature,
+            ))
+            .map_err(|err| PyValueError::new_err(format!("fail to rebuild weights: {}", err)))?;
+        Ok(())
+    }
+
+    /// Composite a batch, each text onto its background color, on the GPU if one is in use,
+    /// otherwise (or if the batch does not fit) on the CPU.
+    fn composite_batch(
+        &self,
+        texts: &[RasterizedText],
+        backgrounds: &[image::Rgb<u8>],
+    ) -> Vec<ImageBuffer<image::Rgb<u8>, Vec<u8>>> {
+        #[cfg(feature = "gpu")]
+        if let Some(compositor) = &self.gpu_compositor {
+            let jobs: Vec<_> = texts
+                .iter()
+                .zip(backgrounds)
+                .map(|(text, &background_color)| gpu::CompositeJob {
+                    tiles: &text.tiles,
+                    background_color,
+                    width: text.width,
+                    height: text.height,
+                })
+                .collect();
+            if let Some(images) = compositor.composite(&jobs) {
+                return images;
+            }
+        }
+
+        texts
+            .iter()
+            .zip(backgrounds)
+            .map(|(text, &background_color)| {
+                composite(&text.tiles, background_color, text.width, text.height)
+            })
+            .collect()
+    }
+
+    /// Apply the cv effects to the text image and merge it onto a random background. The
+    /// merged image is RGB if the backgrounds are loaded in color (`color_merge`), otherwise
+    /// grayscale.
+    fn apply_effect_and_merge(&self, img: &ImageBuffer<image::Rgb<u8>, Vec<u8>>) -> DynamicImage {
+        self.apply_effect_and_merge_recorded(img, &mut MergeRecord::default())
+    }
+
+    /// `apply_effect_and_merge`, recording the effects that fire and the backgrounds used.
+    fn apply_effect_and_merge_recorded(
+        &self,
+        img: &ImageBuffer<image::Rgb<u8>, Vec<u8>>,
+        record: &mut MergeRecord,
+    ) -> DynamicImage {
+        let gray = image::imageops::grayscale(img);
+        let font_img =
+            self.cv_util
+                .apply_effect_traced(gray, &mut record.effects, &mut record.transforms);
+
+        merge_with_background(
+            &self.cv_util,
+            &self.merge_util,
+            &self.bg_factory,
+            &font_img,
+            record,
+        )
+    }
+
+    /// The metadata dict of `return_metadata`.
+    fn metadata_to_py(
+        &self,
+        py: Python<'_>,
+        text: &str,
+        fonts: &[(String, FontAttrs, String)],
+        record: &MergeRecord,
+    ) -> PyResult<PyObject> {
+        let effects = PyList::empty(py);
+        for effect in &record.effects {
+            let params = PyDict::new(py);
+            for (key, value) in &effect.params {
+                params.set_item(key, value)?;
+            }
+            let each = PyDict::new(py);
+            each.set_item("name", effect.name)?;
+            each.set_item("params", params)?;
+            each.set_item("description", effect.to_string())?;
+            effects.append(each)?;
+        }
+
+        let metadata = PyDict::new(py);
+        metadata.set_item("text", text)?;
+        metadata.set_item("fonts", fonts.to_vec().into_py(py))?;
+        metadata.set_item("effects", effects)?;
+        metadata.set_item("styles", &record.styles)?;
+        metadata.set_item("backgrounds", &record.backgrounds)?;
+        metadata.set_item("seed", self.seed)?;
+        metadata.set_item("profile", &self.profile)?;
+        metadata.set_item("difficulty", &self.difficulty)?;
+
+        Ok(metadata.into_py(py))
+    }
+
+    /// Python values are converted through JSON.
+    fn parse_overrides(overrides: HashMap<String, &PyAny>) -> PyResult<Vec<ConfigOverride>> {
+        let mut res = Vec::with_capacity(overrides.len());
+        for (path, value) in overrides {
+            let json: String = value
+                .py()
+                .import("json")?
+                .call_method1("dumps", (value,))?
+                .extract()?;
+            let value = serde_json::from_str(&json).map_err(|err| {
+                PyValueError::new_err(format!("invalid value of `{}`: {}", path, err))
+            })?;
+            res.push(ConfigOverride::new(&path, value).map_err(PyValueError::new_err)?);
+        }
+
+        Ok(res)
+    }
+
+    /// Font indices are resolved against `font_list`.
+    fn to_internal(
+        &self,
+        text_with_font_list: Vec<(String, Vec<FontAttrsLike>)>,
+    ) -> PyResult<TextWithFontList> {
+        text_with_font_list
+            .into_iter()
+            .map(|(ch, font_list)| {
+                let font_list = font_list
+                    .into_iter()
+                    .map(|font| font.into_internal(&self.font_list))
+                    .collect::<PyResult<_>>()?;
+                Ok((ch, font_list))
+            })
+            .collect()
+    }
+
+    /// The fonts as `FontAttrs`, or as indices into `font_list` with `font_indices`.
+    fn fonts_to_py(
+        &self,
+        py: Python<'_>,
+        fonts: &[InternalAttrsOwned],
+        font_indices: bool,
+    ) -> PyObject {
+        if font_indices {
+            fonts
+                .iter()
+                .filter_map(|font| self.font_index.get(font).copied())
+                .collect::<Vec<_>>()
+                .into_py(py)
+        } else {
+            fonts
+                .iter()
+                .map(FontAttrs::from)
+                .collect::<Vec<_>>()
+                .into_py(py)
+        }
+    }
+
+    /// Lay out and render a single line with the buffer of `state`, drawing its line height and
+    /// padding. `size` (width, height) overrides the size of the canvas for this line.
+    fn render_line(
+        &self,
+        state: &mut RenderState,
+        text_with_font_list: TextWithFontList,
+        options: &mut RenderOptions,
+        ligatures: bool,
+        (text_color, background_color): (Color, image::Rgb<u8>),
+        (width, height): (Option<u32>, Option<u32>),
+    ) -> (ImageBuffer<image::Rgb<u8>, Vec<u8>>, Vec<ClusterBox>) {
+        options.padding = self.sample_line_metrics(state);
+        let font_size = state.editor_buffer.metrics().font_size;
+        self.line_styling.sample(options, font_size);
+        let buffer_height = state.editor_buffer.size().1;
+        if let Some(height) = height {
+            let RenderState {
+                font_system,
+                editor_buffer,
+                ..
+            } = &mut *state;
+            let buffer_width = editor_buffer.size().0;
+            editor_buffer.set_size(font_system, buffer_width, height as f32);
+        }
+        self.layout_line(
+            state,
+            text_with_font_list,
+            ligatures,
+            width.map(|width| width as f32),
+        );
+
+        let RenderState {
+            font_system,
+            editor_buffer,
+            swash_cache,
+        } = state;
+        let (img_width, _) = editor_buffer.size();
+        let res = generate_image(
+            editor_buffer,
+            font_system,
+            swash_cache,
+            text_color,
+            background_color,
+            options,
+        );
+        if height.is_some() {
+            editor_buffer.set_size(font_system, img_width, buffer_height);
+        }
+        self.font_usage
+            .record(res.1.iter().map(|cluster| cluster.font_id));
+
+        res
+    }
+
+    /// Put the text into the buffer of `state` as a single line and shape it, in vertical mode
+    /// one line per vertical unit. `width` overrides the width of the buffer for this line.
+    fn layout_line(
+        &self,
+        state: &mut RenderState,
+        text_with_font_list: TextWithFontList,
+        ligatures: bool,
+        width: Option<f32>,
+    ) {
+        let RenderState {
+            font_system,
+            editor_buffer,
+            ..
+        } = state;
+        self.line_width.prepare(editor_buffer, font_system, width);
+        editor_buffer.lines.clear();
+
+        self.with_line_attrs(text_with_font_list, |spans, attrs| match &self.vertical {
+            Some(vertical) => {
+                vertical.set_lines(editor_buffer, font_system, spans, attrs, ligatures)
+            }
+            None => {
+                let (line_text, attrs_list) = build_line(spans, attrs, ligatures);
+                editor_buffer
+                    .lines
+                    .push(single_line(&line_text, attrs_list));
+                editor_buffer.shape_until_scroll(font_system, false);
+                self.line_width.fit(editor_buffer, font_system, width);
+            }
+        });
+    }
+
+    /// Map the text to its attrs, choosing a font for each unit from its font list or the main
+    /// font list, and hand the spans and the default attrs to `f`.
+    fn with_line_attrs<R>(
+        &self,
+        text_with_font_list: TextWithFontList,
+        f: impl FnOnce(Vec<(&&String, Attrs)>, Attrs) -> R,
+    ) -> R {
+        let attrs = Attrs::new()
             .family(Family::Name("Gandhari Unicode"))
             .style(Style::Normal)
             .weight(Weight::NORMAL);
 
         let temp: Vec<_> = text_with_font_list
             .into_iter()
-            .map(|(ch, font_list)| {
-                (
-                    ch,
-                    Some(
-                        font_list
-                            .into_iter()
-                            .map(|each| InternalAttrsOwned::from_tuple(each))
-                            .collect::<Vec<_>>(),
-                    ),
-                )
-            })
+            .map(|(ch, font_list)| (ch, Some(font_list)))
             .collect();
         let temp = temp
             .iter()
@@ -331,74 +3025,348 @@ impl Generator {
             .font_util
             .map_chinese_corpus_with_attrs(&temp, &self.main_font_list);
 
-        // let mut line_text = String::with_capacity(text.len());
-        let mut line_text = String::new();
-        let mut attrs_list = AttrsList::new(attrs);
-        for (text, attrs) in res {
-            let start = line_text.len();
-            line_text.push_str(&text);
-            let end = line_text.len();
-            attrs_list.add_span(start..end, attrs);
+        f(res, attrs)
+    }
+}
+
+/// The effects that fire on a sample, the files of the backgrounds it is merged onto and the
+/// geometric steps it goes through.
+#[cfg(feature = "python")]
+#[derive(Default)]
+struct MergeRecord {
+    // the synthetic styles of the line, see RenderOptions::styles
+    styles: Vec<String>,
+    effects: Vec<AppliedEffect>,
+    backgrounds: Vec<String>,
+    // the geometric steps, to keep masks aligned with the merged image
+    transforms: Vec<Transform>,
+}
+
+/// How the generation methods hand their images to Python.
+#[cfg(feature = "python")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Numpy,
+    // the bytes of an image file, "png" or "jpg"
+    Encoded(&'static str),
+    Pil,
+}
+
+#[cfg(feature = "python")]
+impl OutputFormat {
+    fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "numpy" => Ok(Self::Numpy),
+            "pil" => Ok(Self::Pil),
+            _ => parse_extension(name).map(Self::Encoded).map_err(|_| {
+                PyValueError::new_err("`output` should be `numpy`, `png`, `jpg` or `pil`")
+            }),
         }
+    }
 
-        self.editor_buffer.lines.push(BufferLine::new(
-            &line_text,
-            attrs_list,
-            cosmic_text::Shaping::Advanced,
-        ));
+    /// The image file, `None` unless the format is `png` or `jpg`. Batches call this in their
+    /// worker threads, so that the samples are encoded in parallel.
+    fn encode(self, img: &DynamicImage) -> Option<Result<Vec<u8>, String>> {
+        match self {
+            Self::Encoded(extension) => Some(encode_image(img, extension, None)),
+            _ => None,
+        }
+    }
 
-        self.editor_buffer
-            .shape_until_scroll(&mut self.font_system, false);
+    /// A numpy array of shape (height, width), or (height, width, 3) for an RGB image, the
+    /// `bytes` of the image file, or a `PIL.Image.Image`.
+    fn to_py(self, py: Python<'_>, img: DynamicImage) -> PyResult<PyObject> {
+        match self {
+            Self::Numpy => Ok(Generator::merged_to_py(py, img)),
+            Self::Encoded(_) => {
+                let encoded = self
+                    .encode(&img)
+                    .expect("the format is a file format")
+                    .map_err(PyValueError::new_err)?;
+                Ok(PyBytes::new(py, &encoded).into_py(py))
+            }
+            Self::Pil => {
+                let size = (img.width(), img.height());
+                let (mode, raw) = match img {
+                    DynamicImage::ImageRgb8(rgb) => ("RGB", rgb.into_raw()),
+                    gray => ("L", gray.into_luma8().into_raw()),
+                };
+                let image = py
+                    .import("PIL.Image")?
+                    .call_method1("frombytes", (mode, size, PyBytes::new(py, &raw)))?;
+                Ok(image.into_py(py))
+            }
+        }
+    }
+}
 
-        let text_color = Color::rgb(text_color.0, text_color.1, text_color.2);
-        let background_color =
-            image::Rgb([background_color.0, background_color.1, background_color.2]);
+/// The sinks `gen_batch` can write its samples into.
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+enum PySampleWriter<'py> {
+    Dataset(PyRefMut<'py, DatasetWriter>),
+    #[cfg(feature = "lmdb")]
+    Lmdb(PyRefMut<'py, lmdb_writer::LmdbWriter>),
+}
 
-        let (img_width, img_height) = self.editor_buffer.size();
-        let img = generate_image(
-            &mut self.editor_buffer,
-            &mut self.font_system,
-            &mut self.swash_cache,
-            text_color,
-            background_color,
-            img_width as usize,
-            img_height as usize,
-        );
+#[cfg(feature = "python")]
+impl PySampleWriter<'_> {
+    fn as_writer(&self) -> &dyn SampleWriter {
+        match self {
+            Self::Dataset(writer) => &**writer,
+            #[cfg(feature = "lmdb")]
+            Self::Lmdb(writer) => &**writer,
+        }
+    }
+
+    fn as_writer_mut(&mut self) -> &mut dyn SampleWriter {
+        match self {
+            Self::Dataset(writer) => &mut **writer,
+            #[cfg(feature = "lmdb")]
+            Self::Lmdb(writer) => &mut **writer,
+        }
+    }
+}
 
-        if apply_effect {
-            let gray = image::imageops::grayscale(&img);
-            let font_img = self.cv_util.apply_effect(gray);
-            let bg_img = self.bg_factory.random();
-            let merge_img = self.merge_util.poisson_edit(&font_img, bg_img);
+/// Merge a text image (after the cv effects) onto a random background, curve and bend it and add
+/// noise, in color if the backgrounds are loaded in color.
+#[cfg(feature = "python")]
+fn merge_with_background(
+    cv_util: &CvUtil,
+    merge_util: &MergeUtil,
+    bg_factory: &BgFactory,
+    font_img: &GrayImage,
+    record: &mut MergeRecord,
+) -> DynamicImage {
+    let (merged, sources): (DynamicImage, _) = if bg_factory.is_color() {
+        let (bg_img, sources) = merge_util.random_background_rgb_with_sources(bg_factory);
+        let merged = merge_util.poisson_edit_rgb_traced(font_img, &bg_img, &mut record.transforms);
+        let merged =
+            cv_util.apply_curvature_traced(merged, &mut record.effects, &mut record.transforms);
+        let merged = cv_util.apply_wave_traced(merged, &mut record.effects, &mut record.transforms);
+        let merged = cv_util.apply_noise_recorded(merged, &mut record.effects);
+        (merged.into(), sources)
+    } else {
+        let (bg_img, sources) = merge_util.random_background_with_sources(bg_factory);
+        let merged = merge_util.poisson_edit_traced(font_img, &bg_img, &mut record.transforms);
+        let merged =
+            cv_util.apply_curvature_traced(merged, &mut record.effects, &mut record.transforms);
+        let merged = cv_util.apply_wave_traced(merged, &mut record.effects, &mut record.transforms);
+        let merged = cv_util.apply_noise_recorded(merged, &mut record.effects);
+        (merged.into(), sources)
+    };
+    record.backgrounds.extend(
+        sources
+            .into_iter()
+            .map(|index| bg_factory.path(index).to_string()),
+    );
 
-            let img_height = merge_img.height() as usize;
-            let img_width = merge_img.width() as usize;
+    merged
+}
 
-            let raw = merge_img.into_vec();
+/// The effects and background merging of `Generator` on images given by the caller, e.g. scans
+/// of real text lines, without rendering any text.
+#[cfg(feature = "python")]
+#[pyclass]
+struct ImageEffect {
+    cv_util: CvUtil,
+    merge_util: MergeUtil,
+    bg_factory: BgFactory,
+    #[pyo3(get)]
+    seed: Option<u64>,
+    rng: RngState,
+}
 
-            let initial = PyArray::from_vec(_py, raw);
-            let res = initial.reshape([img_height, img_width]).unwrap();
+#[cfg(feature = "python")]
+#[pymethods]
+impl ImageEffect {
+    // 只使用配置文件中的 CV 與 MERGE 部分，profile、overrides 與 seed 見 Generator
+    #[new]
+    #[pyo3(signature = (config_path="./config.yaml", profile=None, overrides=None, seed=None))]
+    fn py_new(
+        config_path: &str,
+        profile: Option<String>,
+        overrides: Option<HashMap<String, &PyAny>>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let overrides = Generator::parse_overrides(overrides.unwrap_or_default())?;
+        let config = Config::from_yaml_with(config_path, profile.as_deref(), &overrides)
+            .map_err(PyValueError::new_err)?;
 
-            return res.to_dyn();
-        }
+        Ok(Self {
+            cv_util: CvUtil::from_config(&config),
+            merge_util: MergeUtil::from_config(&config),
+            bg_factory: BgFactory::with_color(
+                config.bg_dir,
+                config.bg_height,
+                config.bg_width,
+                config.color_merge,
+            ),
+            seed,
+            rng: RngState::new(seed),
+        })
+    }
+
+    // 見 Generator.reseed
+    fn reseed(&mut self, seed: u64) {
+        self.rng.reseed(seed);
+        self.seed = Some(seed);
+    }
+
+    // 對一張深色文字、淺色背景的圖片應用 cv 效果，並融合到隨機背景上，與 Generator 的 apply_effect 相同
+    // image: (高, 寬) 的灰度圖或 (高, 寬, 3) 的 RGB 圖，RGB 圖先轉爲灰度圖
+    // merge: 爲 false 時只應用 cv 效果，返回灰度圖
+    // 返回融合後的圖片，color_merge 時爲 (高, 寬, 3)，否則爲 (高, 寬)
+    #[pyo3(signature = (image, merge=true))]
+    fn apply<'py>(
+        &self,
+        image: PyReadonlyArrayDyn<'py, u8>,
+        merge: bool,
+        _py: Python<'py>,
+    ) -> PyResult<PyObject> {
+        let _rng = self.rng.enter();
+        let gray = Self::to_gray(&image)?;
+        let res = _py.allow_threads(|| self.apply_to(gray, merge));
+
+        Ok(Generator::merged_to_py(_py, res))
+    }
+
+    // 在多個線程中並行處理多張圖片，生成期間釋放 GIL，參數見 apply
+    // 每張圖片的效果與背景各自播種，結果不受線程調度影響
+    // num_threads: 線程數，默認用 rayon 的全局線程池（CPU 核數）
+    #[pyo3(signature = (images, merge=true, num_threads=None))]
+    fn apply_batch<'py>(
+        &self,
+        images: Vec<PyReadonlyArrayDyn<'py, u8>>,
+        merge: bool,
+        num_threads: Option<usize>,
+        _py: Python<'py>,
+    ) -> PyResult<Vec<PyObject>> {
+        let _rng = self.rng.enter();
+        let pool = match num_threads {
+            Some(num_threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .map_err(|err| PyValueError::new_err(err.to_string()))?,
+            ),
+            None => None,
+        };
+        let images = images
+            .iter()
+            .map(|image| Ok((Self::to_gray(image)?, effect_rng().gen())))
+            .collect::<PyResult<Vec<(GrayImage, u64)>>>()?;
+
+        let apply = |(gray, seed): (GrayImage, u64)| {
+            reseed_effect_rng(seed);
+            self.apply_to(gray, merge)
+        };
+        let res: Vec<_> = _py.allow_threads(|| match &pool {
+            Some(pool) => pool.install(|| images.into_par_iter().map(apply).collect()),
+            None => images.into_par_iter().map(apply).collect(),
+        });
+
+        Ok(res
+            .into_iter()
+            .map(|each| Generator::merged_to_py(_py, each))
+            .collect())
+    }
+
+    // 重設背景大小，見 Generator.set_bg_size
+    fn set_bg_size(&mut self, height: usize, width: usize) {
+        self.bg_factory = BgFactory::with_color(
+            &self.bg_factory.bg_dir,
+            height,
+            width,
+            self.bg_factory.is_color(),
+        );
+    }
+}
 
-        let img_height = img.height() as usize;
-        let img_width = img.width() as usize;
+#[cfg(feature = "python")]
+impl ImageEffect {
+    fn apply_to(&self, gray: GrayImage, merge: bool) -> DynamicImage {
+        let font_img = self.cv_util.apply_effect(gray);
+        if !merge {
+            return font_img.into();
+        }
 
-        let raw = img.into_vec();
+        merge_with_background(
+            &self.cv_util,
+            &self.merge_util,
+            &self.bg_factory,
+            &font_img,
+            &mut MergeRecord::default(),
+        )
+    }
 
-        let initial = PyArray::from_vec(_py, raw);
-        let res = initial.reshape([img_height, img_width, 3]).unwrap();
-        res.to_dyn()
+    fn to_gray(image: &PyReadonlyArrayDyn<'_, u8>) -> PyResult<GrayImage> {
+        Ok(array_to_image(image)?.into_luma8())
     }
 }
 
-#[pyclass]
-struct ImageEffect {}
+/// 以 HTTP 服務的形式運行，阻塞當前線程，見 `server` 模塊
+/// configs: 請求中可以指定的其他配置文件，與 config_path 一起在啓動時加載
+#[cfg(all(feature = "python", feature = "server"))]
+#[pyfunction]
+#[pyo3(signature = (addr="127.0.0.1:8000", config_path="./config.yaml", configs=None))]
+fn serve(
+    py: Python<'_>,
+    addr: &str,
+    config_path: &str,
+    configs: Option<Vec<String>>,
+) -> PyResult<()> {
+    let configs = configs.unwrap_or_default();
+    py.allow_threads(|| server::serve(addr, config_path, &configs))
+        .map_err(PyValueError::new_err)
+}
+
+/// 保存圖片爲 PNG 或 JPEG（按擴展名），並把標籤、種子、字體、效果與樣式寫入 PNG 的 iTXt 或 JPEG 的 EXIF 中
+/// image: (高, 寬) 的灰度圖或 (高, 寬, 3) 的 RGB 圖
+#[cfg(feature = "python")]
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path, image, label, seed=None, fonts=None, effects=None, embed_metadata=true, styles=None))]
+fn save_image(
+    path: &str,
+    image: PyReadonlyArrayDyn<'_, u8>,
+    label: String,
+    seed: Option<u64>,
+    fonts: Option<Vec<String>>,
+    effects: Option<Vec<String>>,
+    embed_metadata: bool,
+    styles: Option<Vec<String>>,
+) -> PyResult<()> {
+    let img = array_to_image(&image)?;
+
+    let metadata = ImageMetadata {
+        label,
+        seed,
+        fonts: fonts.unwrap_or_default(),
+        effects: effects.unwrap_or_default(),
+        styles: styles.unwrap_or_default(),
+    };
+    save_with_metadata(&img, path, embed_metadata.then_some(&metadata))
+        .map_err(PyValueError::new_err)
+}
 
+#[cfg(feature = "python")]
 #[pymodule]
 fn text_image_generator(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<Generator>()?;
     m.add_class::<BgFactory>()?;
+    m.add_class::<ImageEffect>()?;
+    m.add_class::<DatasetWriter>()?;
+    m.add_class::<CocoExporter>()?;
+    #[cfg(feature = "lmdb")]
+    m.add_class::<lmdb_writer::LmdbWriter>()?;
+    m.add_class::<FontAttrs>()?;
+    m.add_class::<FontUtil>()?;
+    m.add_class::<PyStyle>()?;
+    m.add_class::<PyWeight>()?;
+    m.add_class::<PyStretch>()?;
+    m.add_function(wrap_pyfunction!(save_image, m)?)?;
+    #[cfg(feature = "server")]
+    m.add_function(wrap_pyfunction!(serve, m)?)?;
     Ok(())
 }
@@ -0,0 +1,241 @@
+//! Writing samples into an LMDB database in the layout of the crnn text recognition datasets:
+//! sample `i` (counting from 1) is stored as `image-%09d` with the encoded image and
+//! `label-%09d` with the UTF-8 label, and `num-samples` holds the number of samples.
+
+use std::{fs, path::Path};
+
+use image::DynamicImage;
+use lmdb::{Database, Environment, Transaction, WriteFlags};
+#[cfg(feature = "python")]
+use numpy::PyReadonlyArrayDyn;
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, pyclass, pymethods, PyResult};
+
+#[cfg(feature = "python")]
+use crate::utils::array_to_image;
+use crate::{
+    dataset::{encode_image, parse_extension, SampleWriter},
+    metadata::ImageMetadata,
+};
+
+pub const NUM_SAMPLES_KEY: &str = "num-samples";
+
+/// The samples are committed in transactions of this size.
+const CACHE_SIZE: usize = 1000;
+
+/// The default map size, 1 TiB: the database file only grows as needed on Linux and macOS.
+pub const DEFAULT_MAP_SIZE: usize = 1 << 40;
+
+#[cfg_attr(feature = "python", pyclass)]
+pub struct LmdbWriter {
+    env: Environment,
+    db: Database,
+    // "png" or "jpg"
+    extension: String,
+    embed_metadata: bool,
+    // the keys and values not committed yet
+    cache: Vec<(String, Vec<u8>)>,
+    // samples in the database, including the ones not committed yet
+    count: usize,
+}
+
+impl LmdbWriter {
+    /// Open the database in the directory `path`, creating it if needed. Samples are appended
+    /// after the `num-samples` already in the database.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        extension: &str,
+        embed_metadata: bool,
+        map_size: usize,
+    ) -> Result<Self, String> {
+        let extension = parse_extension(extension)?;
+        let path = path.as_ref();
+        fs::create_dir_all(path)
+            .map_err(|err| format!("fail to create `{}`: {}", path.display(), err))?;
+
+        let lmdb_err = |err: lmdb::Error| format!("fail to open `{}`: {}", path.display(), err);
+        let env = Environment::new()
+            .set_map_size(map_size)
+            .open(path)
+            .map_err(lmdb_err)?;
+        let db = env.open_db(None).map_err(lmdb_err)?;
+
+        let txn = env.begin_ro_txn().map_err(lmdb_err)?;
+        let count = match txn.get(db, &NUM_SAMPLES_KEY) {
+            Ok(value) => std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| {
+                    format!(
+                        "`{}` of `{}` is not a number",
+                        NUM_SAMPLES_KEY,
+                        path.display()
+                    )
+                })?,
+            Err(lmdb::Error::NotFound) => 0,
+            Err(err) => return Err(lmdb_err(err)),
+        };
+        txn.commit().map_err(lmdb_err)?;
+
+        Ok(Self {
+            env,
+            db,
+            extension: extension.to_string(),
+            embed_metadata,
+            cache: Vec::with_capacity(CACHE_SIZE * 2),
+            count,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Commit the cached samples together with the new `num-samples`.
+    fn commit(&mut self) -> Result<(), lmdb::Error> {
+        let mut txn = self.env.begin_rw_txn()?;
+        for (key, value) in self.cache.drain(..) {
+            txn.put(self.db, &key, &value, WriteFlags::empty())?;
+        }
+        txn.put(
+            self.db,
+            &NUM_SAMPLES_KEY,
+            &self.count.to_string(),
+            WriteFlags::empty(),
+        )?;
+        txn.commit()
+    }
+}
+
+impl SampleWriter for LmdbWriter {
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        metadata: Option<&ImageMetadata>,
+    ) -> Result<Vec<u8>, String> {
+        encode_image(
+            img,
+            &self.extension,
+            metadata.filter(|_| self.embed_metadata),
+        )
+    }
+
+    /// Cache the image and its label, returns the key of the image. The samples are committed
+    /// every `CACHE_SIZE` samples and by `flush`.
+    fn append(&mut self, encoded: &[u8], label: &str) -> Result<String, String> {
+        self.count += 1;
+        let image_key = format!("image-{:09}", self.count);
+        self.cache.push((image_key.clone(), encoded.to_vec()));
+        self.cache.push((
+            format!("label-{:09}", self.count),
+            label.as_bytes().to_vec(),
+        ));
+
+        if self.cache.len() >= CACHE_SIZE * 2 {
+            self.flush()?;
+        }
+
+        Ok(image_key)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if self.cache.is_empty() {
+            return Ok(());
+        }
+        self.commit()
+            .map_err(|err| format!("fail to write the samples: {}", err))
+    }
+}
+
+impl Drop for LmdbWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl LmdbWriter {
+    #[new]
+    #[pyo3(signature = (path, extension="png", embed_metadata=false, map_size=DEFAULT_MAP_SIZE))]
+    fn py_new(
+        path: &str,
+        extension: &str,
+        embed_metadata: bool,
+        map_size: usize,
+    ) -> PyResult<Self> {
+        Self::new(path, extension, embed_metadata, map_size).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "__len__")]
+    fn py_len(&self) -> usize {
+        self.len()
+    }
+
+    #[pyo3(name = "write", signature = (image, label, seed=None))]
+    fn py_write(
+        &mut self,
+        image: PyReadonlyArrayDyn<'_, u8>,
+        label: &str,
+        seed: Option<u64>,
+    ) -> PyResult<String> {
+        let img = array_to_image(&image)?;
+        let metadata = ImageMetadata {
+            label: label.to_string(),
+            seed,
+            ..Default::default()
+        };
+
+        self.write(&img, label, Some(&metadata))
+            .map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "flush")]
+    fn py_flush(&mut self) -> PyResult<()> {
+        self.flush().map_err(PyValueError::new_err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::GrayImage;
+
+    use super::*;
+
+    #[test]
+    fn test_lmdb_writer() {
+        let path = std::env::temp_dir().join("tig_lmdb_writer_test");
+        let _ = fs::remove_dir_all(&path);
+        let img = DynamicImage::ImageLuma8(GrayImage::new(4, 2));
+
+        let mut writer = LmdbWriter::new(&path, "png", false, 1 << 24).unwrap();
+        assert_eq!(writer.write(&img, "一", None).unwrap(), "image-000000001");
+        writer.write(&img, "二", None).unwrap();
+        drop(writer);
+
+        // a second run continues after the existing samples
+        let mut writer = LmdbWriter::new(&path, "png", false, 1 << 24).unwrap();
+        assert_eq!(writer.len(), 2);
+        assert_eq!(writer.write(&img, "三", None).unwrap(), "image-000000003");
+        writer.flush().unwrap();
+
+        let txn = writer.env.begin_ro_txn().unwrap();
+        assert_eq!(txn.get(writer.db, &NUM_SAMPLES_KEY).unwrap(), b"3");
+        assert_eq!(
+            txn.get(writer.db, &"label-000000003").unwrap(),
+            "三".as_bytes()
+        );
+        assert!(txn
+            .get(writer.db, &"image-000000002")
+            .unwrap()
+            .starts_with(b"\x89PNG"));
+        drop(txn);
+        drop(writer);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
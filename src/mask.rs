@@ -0,0 +1,208 @@
+//! Masks of where the glyph pixels of a sample are. The coverage of the ink is taken from the
+//! rendered line, and the geometric steps of the effects recorded as `Transform`s are replayed
+//! on it, so that the mask stays aligned with the merged image. The box and the horizontal range
+//! of each cluster in the merged image are read off the same way.
+
+use image::{GrayImage, Luma, Rgb, RgbImage};
+
+use crate::{
+    cv_util::{replay_transforms, Transform},
+    image_process::ClusterBox,
+};
+
+/// Pixels with at least this coverage after the transforms belong to the mask.
+const THRESHOLD: u8 = 128;
+
+/// How much of each pixel is ink, from 0 (the background color) to 255 (the text color): the
+/// projection of the pixel onto the line between the two colors. The pixels of color glyphs
+/// (emoji) lie off that line, their distance from the background counts if it is larger.
+pub fn glyph_coverage(img: &RgbImage, text_color: Rgb<u8>, background_color: Rgb<u8>) -> GrayImage {
+    let direction: [f64; 3] =
+        [0, 1, 2].map(|c| text_color.0[c] as f64 - background_color.0[c] as f64);
+    let norm: f64 = direction.iter().map(|each| each * each).sum();
+    if norm == 0.0 {
+        return GrayImage::new(img.width(), img.height());
+    }
+
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let pixel = img.get_pixel(x, y);
+        let offset = [0, 1, 2].map(|c| pixel.0[c] as f64 - background_color.0[c] as f64);
+        let dot: f64 = (0..3).map(|c| offset[c] * direction[c]).sum();
+        let distance = (offset.iter().map(|each| each * each).sum::<f64>() / norm).sqrt();
+        Luma([((dot / norm).max(distance) * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8])
+    })
+}
+
+/// 255 where there is ink after the transforms, 0 elsewhere.
+pub fn binary_mask(coverage: &GrayImage, transforms: &[Transform]) -> GrayImage {
+    let mut mask = replay_transforms(coverage.clone(), transforms);
+    binarize(&mut mask, 255);
+
+    mask
+}
+
+/// The index of the cluster plus one where there is ink of it after the transforms, 0
+/// elsewhere. Clusters after the 255th share the id 255; where two clusters overlap, the later
+/// one wins.
+pub fn instance_mask(
+    coverage: &GrayImage,
+    clusters: &[ClusterBox],
+    transforms: &[Transform],
+) -> GrayImage {
+    let mut res: Option<GrayImage> = None;
+    for (index, cluster) in clusters.iter().enumerate() {
+        // the ink of this cluster only, so that the ids are not blurred by the transforms
+        let mut own = replay_transforms(cluster_coverage(coverage, cluster, false), transforms);
+        let id = (index + 1).min(255) as u8;
+        binarize(&mut own, id);
+
+        match &mut res {
+            Some(res) => {
+                for (target, id) in res.pixels_mut().zip(own.pixels()) {
+                    if id.0[0] != 0 {
+                        *target = *id;
+                    }
+                }
+            }
+            None => res = Some(own),
+        }
+    }
+
+    res.unwrap_or_else(|| {
+        replay_transforms(
+            GrayImage::new(coverage.width(), coverage.height()),
+            transforms,
+        )
+    })
+}
+
+/// The box (x0, y0, x1, y1) each cluster covers after the transforms, with the right and bottom
+/// edges exclusive, `None` for the clusters that are gone, e.g. cut off by a crop. A cluster
+/// without ink (a space) covers its advance box.
+pub fn cluster_boxes(
+    coverage: &GrayImage,
+    clusters: &[ClusterBox],
+    transforms: &[Transform],
+) -> Vec<Option<(u32, u32, u32, u32)>> {
+    clusters
+        .iter()
+        .map(|cluster| {
+            let own = replay_transforms(cluster_coverage(coverage, cluster, true), transforms);
+            own.enumerate_pixels()
+                .filter(|(_, _, pixel)| pixel.0[0] >= THRESHOLD)
+                .fold(None, |bbox, (x, y, _)| match bbox {
+                    Some((x0, y0, x1, y1)) => {
+                        Some((x.min(x0), y.min(y0), (x + 1).max(x1), (y + 1).max(y1)))
+                    }
+                    None => Some((x, y, x + 1, y + 1)),
+                })
+        })
+        .collect()
+}
+
+/// The range [x0, x1) of the columns each cluster covers after the transforms, see
+/// `cluster_boxes`.
+pub fn cluster_x_ranges(
+    coverage: &GrayImage,
+    clusters: &[ClusterBox],
+    transforms: &[Transform],
+) -> Vec<Option<(u32, u32)>> {
+    cluster_boxes(coverage, clusters, transforms)
+        .into_iter()
+        .map(|bbox| bbox.map(|(x0, _, x1, _)| (x0, x1)))
+        .collect()
+}
+
+/// The coverage inside the box of the cluster, and nothing outside. With `fill_empty`, a box
+/// without ink is filled completely.
+fn cluster_coverage(coverage: &GrayImage, cluster: &ClusterBox, fill_empty: bool) -> GrayImage {
+    let (x0, y0, x1, y1) = cluster.bbox;
+    let inside = |x: u32, y: u32| (x0..x1).contains(&x) && (y0..y1).contains(&y);
+    let empty = fill_empty
+        && coverage
+            .enumerate_pixels()
+            .all(|(x, y, pixel)| !inside(x, y) || pixel.0[0] < THRESHOLD);
+
+    GrayImage::from_fn(coverage.width(), coverage.height(), |x, y| {
+        match (inside(x, y), empty) {
+            (true, true) => Luma([255]),
+            (true, false) => *coverage.get_pixel(x, y),
+            (false, _) => Luma([0]),
+        }
+    })
+}
+
+fn binarize(mask: &mut GrayImage, value: u8) {
+    for pixel in mask.pixels_mut() {
+        pixel.0[0] = if pixel.0[0] >= THRESHOLD { value } else { 0 };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glyph_coverage() {
+        let img = RgbImage::from_fn(3, 1, |x, _| {
+            [Rgb([255, 255, 255]), Rgb([128, 128, 128]), Rgb([0, 0, 0])][x as usize]
+        });
+        let coverage = glyph_coverage(&img, Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+        assert_eq!(coverage.into_raw(), vec![0, 127, 255]);
+
+        // light text on a dark background
+        let coverage = glyph_coverage(&img, Rgb([255, 255, 255]), Rgb([0, 0, 0]));
+        assert_eq!(coverage.into_raw(), vec![255, 128, 0]);
+
+        // a yellow emoji pixel is far from the white background, though not towards black
+        let img = RgbImage::from_pixel(1, 1, Rgb([255, 200, 0]));
+        let coverage = glyph_coverage(&img, Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+        assert!(coverage.get_pixel(0, 0).0[0] >= 128);
+    }
+
+    #[test]
+    fn test_masks_follow_transforms() {
+        let mut coverage = GrayImage::new(4, 2);
+        for x in [0, 1, 3] {
+            coverage.put_pixel(x, 0, Luma([255]));
+            coverage.put_pixel(x, 1, Luma([255]));
+        }
+        let transforms = [Transform::Pad {
+            left: 2,
+            top: 1,
+            width: 8,
+            height: 4,
+        }];
+
+        let binary = binary_mask(&coverage, &transforms);
+        assert_eq!(binary.dimensions(), (8, 4));
+        assert_eq!(binary.get_pixel(2, 1).0[0], 255);
+        assert_eq!(binary.get_pixel(4, 1).0[0], 0);
+        assert_eq!(binary.get_pixel(0, 0).0[0], 0);
+
+        let cluster = |x0, x1| ClusterBox {
+            text: String::new(),
+            bbox: (x0, 0, x1, 2),
+            font_id: None,
+            line: 0,
+        };
+        let instance = instance_mask(&coverage, &[cluster(0, 2), cluster(2, 4)], &transforms);
+        assert_eq!(instance.get_pixel(3, 2).0[0], 1);
+        assert_eq!(instance.get_pixel(4, 2).0[0], 0);
+        assert_eq!(instance.get_pixel(5, 2).0[0], 2);
+
+        // the second cluster has ink in its right column only, a third one is a space
+        let ranges = cluster_x_ranges(
+            &coverage,
+            &[cluster(0, 2), cluster(2, 4), cluster(4, 4), cluster(4, 5)],
+            &transforms,
+        );
+        assert_eq!(ranges, vec![Some((2, 4)), Some((5, 6)), None, None]);
+        let ranges = cluster_x_ranges(&coverage, &[cluster(2, 3)], &[Transform::Resize(8, 4)]);
+        assert_eq!(ranges, vec![Some((4, 6))]);
+        let boxes = cluster_boxes(&coverage, &[cluster(0, 2), cluster(4, 5)], &transforms);
+        assert_eq!(boxes, vec![Some((2, 1, 4, 3)), None]);
+    }
+}
@@ -1,19 +1,32 @@
-use std::{fs, ops::Index, path::Path};
+use std::{borrow::Cow, fs, ops::Index, path::Path};
 
-use image::{GenericImage, GrayImage, Luma};
-use numpy::{PyArray, PyArray2, PyReadonlyArray2};
+use image::{GenericImage, GrayAlphaImage, GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage};
+#[cfg(feature = "python")]
+use numpy::{PyArray, PyArray2, PyArray3, PyReadonlyArray2, PyReadonlyArray3};
+#[cfg(feature = "python")]
 use pyo3::{pyclass, pymethods, Python};
 use rand::Rng;
 
-use super::effect_helper::{
-    math::Random,
-    poisson_editing::{Gradient, Processor},
+use super::{
+    cv_util::Transform,
+    effect_helper::{
+        math::Random,
+        poisson_editing::{Gradient, Processor},
+    },
+    parse_config::Config,
+    rng::effect_rng,
 };
 
 #[derive(Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct BgFactory {
     images: Vec<GrayImage>,
+    // the alpha channel of each image that has one, e.g. a PNG cut-out or overlay
+    alphas: Vec<Option<GrayImage>>,
+    // the RGB version of each image, empty unless the factory is created with `color`
+    rgb_images: Vec<RgbImage>,
+    // the file of each image
+    paths: Vec<String>,
     pub height: usize,
     pub width: usize,
     pub bg_dir: String,
@@ -21,6 +34,11 @@ pub struct BgFactory {
 
 impl BgFactory {
     pub fn new<P: AsRef<Path>>(dir: P, height: usize, width: usize) -> Self {
+        Self::with_color(dir, height, width, false)
+    }
+
+    /// With `color`, the RGB version of each background is kept as well, for merging in color.
+    pub fn with_color<P: AsRef<Path>>(dir: P, height: usize, width: usize, color: bool) -> Self {
         let dir_list = fs::read_dir(&dir).expect("background images' directory does not exist");
         let mut image_paths = vec![];
 
@@ -36,14 +54,17 @@ impl BgFactory {
         }
 
         let mut images = Vec::with_capacity(image_paths.len());
+        let mut alphas = Vec::with_capacity(image_paths.len());
+        let mut rgb_images = Vec::with_capacity(if color { image_paths.len() } else { 0 });
+        let mut paths = Vec::with_capacity(image_paths.len());
         for image_path in image_paths {
-            let img = match image::open(image_path) {
+            let mut img = match image::open(&image_path) {
                 Ok(img) => img,
                 Err(_) => continue,
             };
-            let mut gray = image::imageops::grayscale(&img);
+            let has_alpha = img.color().has_alpha();
 
-            let [origin_height, origin_width] = [gray.height(), gray.width()];
+            let [origin_height, origin_width] = [img.height(), img.width()];
             if origin_width < width as u32 || origin_height < height as u32 {
                 let [width1, height1] = [
                     (origin_width as f64 * height as f64 / origin_height as f64).ceil() as u32,
@@ -54,29 +75,27 @@ impl BgFactory {
                     (origin_height as f64 * width as f64 / origin_width as f64).ceil() as u32,
                 ];
                 if width1 >= width as u32 && height1 >= width as u32 {
-                    gray = image::imageops::resize(
-                        &gray,
-                        width1,
-                        height1,
-                        image::imageops::FilterType::CatmullRom,
-                    );
+                    img =
+                        img.resize_exact(width1, height1, image::imageops::FilterType::CatmullRom);
                 } else {
-                    gray = image::imageops::resize(
-                        &gray,
-                        width2,
-                        height2,
-                        image::imageops::FilterType::CatmullRom,
-                    );
+                    img =
+                        img.resize_exact(width2, height2, image::imageops::FilterType::CatmullRom);
                 }
             }
 
             // random crop
-            let [resize_height, resize_width] = [gray.height(), gray.width()];
-            let x = rand::thread_rng().gen_range(0..=(resize_width - width as u32));
-            let y = rand::thread_rng().gen_range(0..=(resize_height - height as u32));
-            let cropped = gray.sub_image(x, y, width as u32, height as u32).to_image();
-
-            images.push(cropped)
+            let [resize_height, resize_width] = [img.height(), img.width()];
+            let x = effect_rng().gen_range(0..=(resize_width - width as u32));
+            let y = effect_rng().gen_range(0..=(resize_height - height as u32));
+            let cropped = img.crop_imm(x, y, width as u32, height as u32);
+
+            let (gray, alpha) = split_alpha(&cropped.to_luma_alpha8());
+            images.push(gray);
+            alphas.push(has_alpha.then_some(alpha));
+            if color {
+                rgb_images.push(cropped.to_rgb8());
+            }
+            paths.push(image_path.to_string_lossy().to_string());
         }
 
         if images.len() == 0 {
@@ -85,6 +104,9 @@ impl BgFactory {
 
         Self {
             images,
+            alphas,
+            rgb_images,
+            paths,
             height,
             width,
             bg_dir: dir.as_ref().to_string_lossy().to_string(),
@@ -103,12 +125,45 @@ impl BgFactory {
         self.images.len()
     }
 
+    /// A random image, ignoring its alpha channel.
     pub fn random(&self) -> &GrayImage {
-        let index = rand::thread_rng().gen_range(0..self.len());
-        &self[index]
+        &self[self.random_index()]
+    }
+
+    pub fn random_index(&self) -> usize {
+        effect_rng().gen_range(0..self.len())
+    }
+
+    /// The alpha channel of the image at `index`, `None` if it is opaque.
+    pub fn alpha(&self, index: usize) -> Option<&GrayImage> {
+        self.alphas.get(index)?.as_ref()
+    }
+
+    /// The file the image at `index` is loaded from.
+    pub fn path(&self, index: usize) -> &str {
+        &self.paths[index]
+    }
+
+    /// Whether the RGB version of the images is kept.
+    pub fn is_color(&self) -> bool {
+        !self.rgb_images.is_empty()
+    }
+
+    /// The RGB version of the image at `index`, `None` if the factory is not created with
+    /// `color`.
+    pub fn rgb(&self, index: usize) -> Option<&RgbImage> {
+        self.rgb_images.get(index)
     }
 }
 
+fn split_alpha(img: &GrayAlphaImage) -> (GrayImage, GrayImage) {
+    let (width, height) = img.dimensions();
+    let gray = GrayImage::from_fn(width, height, |x, y| Luma([img.get_pixel(x, y).0[0]]));
+    let alpha = GrayImage::from_fn(width, height, |x, y| Luma([img.get_pixel(x, y).0[1]]));
+
+    (gray, alpha)
+}
+
 impl Index<usize> for BgFactory {
     type Output = GrayImage;
 
@@ -121,11 +176,13 @@ impl Index<usize> for BgFactory {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl BgFactory {
     #[new]
-    pub fn py_new(dir: &str, height: usize, width: usize) -> Self {
-        let res = Self::new(dir, height, width);
+    #[pyo3(signature = (dir, height, width, color=false))]
+    pub fn py_new(dir: &str, height: usize, width: usize, color: bool) -> Self {
+        let res = Self::with_color(dir, height, width, color);
         res
     }
 
@@ -165,29 +222,270 @@ impl BgFactory {
 
         reshape_py
     }
+
+    #[getter]
+    #[pyo3(name = "color")]
+    pub fn py_is_color(&self) -> bool {
+        self.is_color()
+    }
+
+    #[pyo3(name = "rgb")]
+    pub fn py_rgb<'py>(&self, index: usize, _py: Python<'py>) -> Option<&'py PyArray3<u8>> {
+        let res = self.rgb(index)?;
+
+        let res_py = PyArray::from_vec(_py, res.to_vec());
+        let reshape_py = res_py.reshape([self.height(), self.width(), 3]).unwrap();
+
+        Some(reshape_py)
+    }
 }
 
 #[derive(Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct MergeUtil {
     pub height_diff: Random,
     pub bg_alpha: Random,
     pub bg_beta: Random,
     pub font_alpha: Random,
     pub reverse_prob: f64,
+    // probability that two backgrounds are combined, and the weight of the first one when they
+    // are alpha blended
+    pub bg_blend_prob: f64,
+    pub bg_blend_alpha: Random,
+    // a background with transparency is composited over a second background with
+    // `bg_base_bg_prob`, otherwise over a plain gray level drawn from `bg_base_color`
+    pub bg_base_color: Random,
+    pub bg_base_bg_prob: f64,
 }
 
 impl MergeUtil {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            height_diff: config.height_diff,
+            bg_alpha: config.bg_alpha,
+            bg_beta: config.bg_beta,
+            font_alpha: config.font_alpha,
+            reverse_prob: config.reverse_prob,
+            bg_blend_prob: config.bg_blend_prob,
+            bg_blend_alpha: config.bg_blend_alpha,
+            bg_base_color: config.bg_base_color,
+            bg_base_bg_prob: config.bg_base_bg_prob,
+        }
+    }
+
+    /// A random background from the factory. With `bg_blend_prob`, two backgrounds are alpha
+    /// blended or patched together instead. Backgrounds with transparency are made opaque first.
+    pub fn random_background<'a>(&self, bg_factory: &'a BgFactory) -> Cow<'a, GrayImage> {
+        self.random_background_with_sources(bg_factory).0
+    }
+
+    /// `random_background` and the indices of the backgrounds it is made of.
+    pub fn random_background_with_sources<'a>(
+        &self,
+        bg_factory: &'a BgFactory,
+    ) -> (Cow<'a, GrayImage>, Vec<usize>) {
+        let mut sources = vec![];
+        let res = self.random_background_of(bg_factory, &|index| &bg_factory[index], &mut sources);
+        (res, sources)
+    }
+
+    /// The RGB counterpart of `random_background`, the factory should be created with `color`.
+    pub fn random_background_rgb<'a>(&self, bg_factory: &'a BgFactory) -> Cow<'a, RgbImage> {
+        self.random_background_rgb_with_sources(bg_factory).0
+    }
+
+    /// `random_background_rgb` and the indices of the backgrounds it is made of.
+    pub fn random_background_rgb_with_sources<'a>(
+        &self,
+        bg_factory: &'a BgFactory,
+    ) -> (Cow<'a, RgbImage>, Vec<usize>) {
+        assert!(
+            bg_factory.is_color(),
+            "the background factory should be created with `color`"
+        );
+        let mut sources = vec![];
+        let res = self.random_background_of(
+            bg_factory,
+            &|index| bg_factory.rgb(index).unwrap(),
+            &mut sources,
+        );
+        (res, sources)
+    }
+
+    fn random_background_of<'a, P>(
+        &self,
+        bg_factory: &'a BgFactory,
+        image: &dyn Fn(usize) -> &'a ImageBuffer<P, Vec<u8>>,
+        sources: &mut Vec<usize>,
+    ) -> Cow<'a, ImageBuffer<P, Vec<u8>>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let first =
+            self.opaque_background(bg_factory, image, bg_factory.random_index(), true, sources);
+        if effect_rng().gen_range(0.0..1.0) >= self.bg_blend_prob {
+            return first;
+        }
+
+        let second =
+            self.opaque_background(bg_factory, image, bg_factory.random_index(), true, sources);
+        if effect_rng().gen_bool(0.5) {
+            Cow::Owned(Self::blend_backgrounds(
+                &first,
+                &second,
+                self.bg_blend_alpha.sample(),
+            ))
+        } else {
+            let width = first.width();
+            let seam = effect_rng().gen_range(0..=width);
+            let feather = (width / 20).max(1);
+            Cow::Owned(Self::patch_backgrounds(&first, &second, seam, feather))
+        }
+    }
+
+    /// The background at `index`, composited over a base if it has transparency. The base is a
+    /// second background only if `allow_bg_base`, so that bases do not nest.
+    fn opaque_background<'a, P>(
+        &self,
+        bg_factory: &'a BgFactory,
+        image: &dyn Fn(usize) -> &'a ImageBuffer<P, Vec<u8>>,
+        index: usize,
+        allow_bg_base: bool,
+        sources: &mut Vec<usize>,
+    ) -> Cow<'a, ImageBuffer<P, Vec<u8>>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        sources.push(index);
+        let img = image(index);
+        let alpha = match bg_factory.alpha(index) {
+            Some(alpha) => alpha,
+            None => return Cow::Borrowed(img),
+        };
+
+        let base = if allow_bg_base && effect_rng().gen_range(0.0..1.0) < self.bg_base_bg_prob {
+            self.opaque_background(bg_factory, image, bg_factory.random_index(), false, sources)
+        } else {
+            let level = self.bg_base_color.sample().round().clamp(0.0, 255.0) as u8;
+            Cow::Owned(ImageBuffer::from_pixel(
+                img.width(),
+                img.height(),
+                img.get_pixel(0, 0).map(|_| level),
+            ))
+        };
+
+        Cow::Owned(Self::composite_over(img, alpha, &base))
+    }
+
+    /// `img` over `base` with the per-pixel `alpha`, the images should have the same size.
+    pub fn composite_over<P>(
+        img: &ImageBuffer<P, Vec<u8>>,
+        alpha: &GrayImage,
+        base: &ImageBuffer<P, Vec<u8>>,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        assert_eq!(
+            img.dimensions(),
+            base.dimensions(),
+            "a background and its base should have the same size"
+        );
+
+        ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            let a = alpha.get_pixel(x, y).0[0] as u32;
+
+            img.get_pixel(x, y).map2(base.get_pixel(x, y), |fg, bg| {
+                ((fg as u32 * a + bg as u32 * (255 - a) + 127) / 255) as u8
+            })
+        })
+    }
+
+    /// `alpha * first + (1 - alpha) * second`, the images should have the same size.
+    pub fn blend_backgrounds<P>(
+        first: &ImageBuffer<P, Vec<u8>>,
+        second: &ImageBuffer<P, Vec<u8>>,
+        alpha: f64,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        assert_eq!(
+            first.dimensions(),
+            second.dimensions(),
+            "backgrounds to blend should have the same size"
+        );
+        let alpha = alpha.clamp(0.0, 1.0);
+
+        ImageBuffer::from_vec(
+            first.width(),
+            first.height(),
+            first
+                .as_raw()
+                .iter()
+                .zip(second.as_raw())
+                .map(|(&a, &b)| (a as f64 * alpha + b as f64 * (1.0 - alpha)).round() as u8)
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    /// The left of `first` and the right of `second`, split at column `seam` with a linear
+    /// transition `feather` pixels wide. The images should have the same size.
+    pub fn patch_backgrounds<P>(
+        first: &ImageBuffer<P, Vec<u8>>,
+        second: &ImageBuffer<P, Vec<u8>>,
+        seam: u32,
+        feather: u32,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        assert_eq!(
+            first.dimensions(),
+            second.dimensions(),
+            "backgrounds to patch should have the same size"
+        );
+        ImageBuffer::from_fn(first.width(), first.height(), |x, y| {
+            // weight of the first image
+            let alpha = if feather == 0 {
+                if x < seam {
+                    1.0
+                } else {
+                    0.0
+                }
+            } else {
+                let center = x as f64 + 0.5;
+                ((seam as f64 + feather as f64 / 2.0 - center) / feather as f64).clamp(0.0, 1.0)
+            };
+
+            first.get_pixel(x, y).map2(second.get_pixel(x, y), |a, b| {
+                (a as f64 * alpha + b as f64 * (1.0 - alpha)).round() as u8
+            })
+        })
+    }
+
     fn random_range_u32(a: u32, b: u32) -> u32 {
         if a >= b {
-            rand::thread_rng().gen_range(b..=a)
+            effect_rng().gen_range(b..=a)
         } else {
-            rand::thread_rng().gen_range(a..=b)
+            effect_rng().gen_range(a..=b)
         }
     }
 
     /// bg_shape: (height, width)
     pub fn random_pad(&self, font_img: &GrayImage, bg_height: u32, bg_width: u32) -> GrayImage {
+        self.random_pad_traced(font_img, bg_height, bg_width, &mut vec![])
+    }
+
+    /// `random_pad`, pushing the resizing and the padding to `transforms`.
+    pub fn random_pad_traced(
+        &self,
+        font_img: &GrayImage,
+        bg_height: u32,
+        bg_width: u32,
+        transforms: &mut Vec<Transform>,
+    ) -> GrayImage {
         let (font_height, font_width) = (font_img.height(), font_img.width());
 
         let resize_height = (bg_height as f64 - self.height_diff.sample()) as u32;
@@ -206,26 +504,101 @@ impl MergeUtil {
 
         let mut padded_img = GrayImage::from_pixel(bg_width, bg_height, Luma([0]));
         padded_img.copy_from(&font_img, left, top).unwrap();
+        transforms.extend([
+            Transform::Resize(resize_width, resize_height),
+            Transform::Pad {
+                left,
+                top,
+                width: bg_width,
+                height: bg_height,
+            },
+        ]);
 
         padded_img
     }
 
-    pub fn random_change_bgcolor(&self, bg_img: &GrayImage) -> GrayImage {
+    /// The same random contrast and brightness change is applied to every channel.
+    pub fn random_change_bgcolor<P>(
+        &self,
+        bg_img: &ImageBuffer<P, Vec<u8>>,
+    ) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
         let alpha = self.bg_alpha.sample();
         let beta = self.bg_beta.sample();
         let [width, height] = [bg_img.width(), bg_img.height()];
         let new_bg_img_vec: Vec<_> = bg_img
-            .to_vec()
+            .as_raw()
             .iter()
             .map(|&each| ((each as f64 * alpha + beta) as u32).clamp(50, 255) as u8)
             .collect();
 
-        GrayImage::from_vec(width, height, new_bg_img_vec).unwrap()
+        ImageBuffer::from_vec(width, height, new_bg_img_vec).unwrap()
     }
 
     pub fn poisson_edit(&self, font_img: &GrayImage, bg_img: &GrayImage) -> GrayImage {
+        self.poisson_edit_traced(font_img, bg_img, &mut vec![])
+    }
+
+    /// `poisson_edit`, pushing the placement of the text to `transforms`.
+    pub fn poisson_edit_traced(
+        &self,
+        font_img: &GrayImage,
+        bg_img: &GrayImage,
+        transforms: &mut Vec<Transform>,
+    ) -> GrayImage {
+        let bg_img = self.random_change_bgcolor(bg_img);
+        let (source, mask) =
+            self.random_source(font_img, bg_img.height(), bg_img.width(), transforms);
+
+        let final_img = Self::poisson_solve(source, mask, bg_img);
+
+        self.random_reverse(final_img)
+    }
+
+    /// `poisson_edit` onto a color background, the solver runs on each channel with the same
+    /// text.
+    pub fn poisson_edit_rgb(&self, font_img: &GrayImage, bg_img: &RgbImage) -> RgbImage {
+        self.poisson_edit_rgb_traced(font_img, bg_img, &mut vec![])
+    }
+
+    /// `poisson_edit_rgb`, pushing the placement of the text to `transforms`.
+    pub fn poisson_edit_rgb_traced(
+        &self,
+        font_img: &GrayImage,
+        bg_img: &RgbImage,
+        transforms: &mut Vec<Transform>,
+    ) -> RgbImage {
         let bg_img = self.random_change_bgcolor(bg_img);
-        let padded_font_img = self.random_pad(&font_img, bg_img.height(), bg_img.width());
+        let (width, height) = bg_img.dimensions();
+        let (source, mask) = self.random_source(font_img, height, width, transforms);
+
+        let channels: Vec<_> = (0..3)
+            .map(|channel| {
+                let target = GrayImage::from_fn(width, height, |x, y| {
+                    Luma([bg_img.get_pixel(x, y).0[channel]])
+                });
+                Self::poisson_solve(source.clone(), mask.clone(), target)
+            })
+            .collect();
+        let final_img = RgbImage::from_fn(width, height, |x, y| {
+            Rgb([0, 1, 2].map(|channel| channels[channel].get_pixel(x, y).0[0]))
+        });
+
+        self.random_reverse(final_img)
+    }
+
+    /// The source (reversed text scaled by a random `font_alpha`) and the mask (the text) of
+    /// the Poisson editing, padded to the size of the background.
+    fn random_source(
+        &self,
+        font_img: &GrayImage,
+        bg_height: u32,
+        bg_width: u32,
+        transforms: &mut Vec<Transform>,
+    ) -> (GrayImage, GrayImage) {
+        let padded_font_img = self.random_pad_traced(font_img, bg_height, bg_width, transforms);
 
         let alpha = self.font_alpha.sample();
         let reversed_adjust_font_img = GrayImage::from_raw(
@@ -242,35 +615,43 @@ impl MergeUtil {
                 .collect(),
         )
         .unwrap();
-        let mut poisson_processor = Processor::reset(
-            reversed_adjust_font_img,
-            padded_font_img,
-            bg_img,
-            (0, 0),
-            (0, 0),
-            Gradient::Maximum,
-        );
+
+        (reversed_adjust_font_img, padded_font_img)
+    }
+
+    fn poisson_solve(source: GrayImage, mask: GrayImage, target: GrayImage) -> GrayImage {
+        let mut poisson_processor =
+            Processor::reset(source, mask, target, (0, 0), (0, 0), Gradient::Maximum);
         let (target, _) = poisson_processor.step(500);
-        let mut final_img = GrayImage::from_vec(
+
+        GrayImage::from_vec(
             target.ncols() as u32,
             target.nrows() as u32,
             target.transpose().iter().map(|&each| each).collect(),
         )
-        .unwrap();
+        .unwrap()
+    }
 
-        if rand::thread_rng().gen_range(0.0..=1.0) < self.reverse_prob {
-            final_img = GrayImage::from_vec(
-                final_img.width(),
-                final_img.height(),
-                final_img.to_vec().iter().map(|each| 255 - each).collect(),
-            )
-            .unwrap()
+    /// Invert the image with `reverse_prob`.
+    fn random_reverse<P>(&self, img: ImageBuffer<P, Vec<u8>>) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        if effect_rng().gen_range(0.0..=1.0) >= self.reverse_prob {
+            return img;
         }
 
-        final_img
+        let (width, height) = img.dimensions();
+        ImageBuffer::from_vec(
+            width,
+            height,
+            img.into_raw().into_iter().map(|each| 255 - each).collect(),
+        )
+        .unwrap()
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl MergeUtil {
     #[pyo3(name = "random_pad")]
@@ -296,6 +677,38 @@ impl MergeUtil {
         reshape_py
     }
 
+    #[pyo3(name = "random_background")]
+    pub fn random_background_py<'py>(
+        &self,
+        bg_factory: &BgFactory,
+        _py: Python<'py>,
+    ) -> &'py PyArray2<u8> {
+        let res = self.random_background(bg_factory);
+
+        let res_py = PyArray::from_vec(_py, res.to_vec());
+        let reshape_py = res_py
+            .reshape([res.height() as usize, res.width() as usize])
+            .unwrap();
+
+        reshape_py
+    }
+
+    #[pyo3(name = "random_background_rgb")]
+    pub fn random_background_rgb_py<'py>(
+        &self,
+        bg_factory: &BgFactory,
+        _py: Python<'py>,
+    ) -> &'py PyArray3<u8> {
+        let res = self.random_background_rgb(bg_factory);
+
+        let res_py = PyArray::from_vec(_py, res.to_vec());
+        let reshape_py = res_py
+            .reshape([res.height() as usize, res.width() as usize, 3])
+            .unwrap();
+
+        reshape_py
+    }
+
     #[pyo3(name = "random_change_bgcolor")]
     pub fn random_change_bgcolor_py<'py>(
         &self,
@@ -342,6 +755,34 @@ impl MergeUtil {
 
         reshape_py
     }
+
+    #[pyo3(name = "poisson_edit_rgb")]
+    pub fn poisson_edit_rgb_py<'py>(
+        &self,
+        font_img: PyReadonlyArray2<'py, u8>,
+        bg_img: PyReadonlyArray3<'py, u8>,
+        _py: Python<'py>,
+    ) -> &'py PyArray3<u8> {
+        let shape_font = font_img.shape();
+        let shape_bg = bg_img.shape();
+        let font_img = font_img.as_slice().expect("fail to read input `font_img`");
+        let font_img = GrayImage::from_vec(
+            shape_font[1] as u32,
+            shape_font[0] as u32,
+            font_img.to_vec(),
+        )
+        .expect("fail to cast input font_img to GrayImage");
+        let bg_img = bg_img.as_slice().expect("fail to read input `bg_img`");
+        let bg_img = RgbImage::from_vec(shape_bg[1] as u32, shape_bg[0] as u32, bg_img.to_vec())
+            .expect("fail to cast input bg_img to RgbImage");
+
+        let res = self.poisson_edit_rgb(&font_img, &bg_img);
+
+        let res_py = PyArray::from_vec(_py, res.into_vec());
+        let reshape_py = res_py.reshape([shape_bg[0], shape_bg[1], 3]).unwrap();
+
+        reshape_py
+    }
 }
 
 #[cfg(test)]
@@ -361,6 +802,10 @@ mod test {
             bg_beta: Random::new_gaussian(-50.0, 50.0),
             font_alpha: Random::new_uniform(0.2, 1.0),
             reverse_prob: 0.5,
+            bg_blend_prob: 0.0,
+            bg_blend_alpha: Random::new_uniform(0.3, 0.7),
+            bg_base_color: Random::new_uniform(255.0, 255.0),
+            bg_base_bg_prob: 0.0,
         };
 
         let start = Instant::now();
@@ -381,6 +826,10 @@ mod test {
             bg_beta: Random::new_gaussian(-50.0, 50.0),
             font_alpha: Random::new_uniform(0.2, 1.0),
             reverse_prob: 0.5,
+            bg_blend_prob: 0.0,
+            bg_blend_alpha: Random::new_uniform(0.3, 0.7),
+            bg_base_color: Random::new_uniform(255.0, 255.0),
+            bg_base_bg_prob: 0.0,
         };
 
         let start = Instant::now();
@@ -401,6 +850,10 @@ mod test {
             bg_beta: Random::new_gaussian(-50.0, 50.0),
             font_alpha: Random::new_uniform(0.2, 1.0),
             reverse_prob: 0.5,
+            bg_blend_prob: 0.0,
+            bg_blend_alpha: Random::new_uniform(0.3, 0.7),
+            bg_base_color: Random::new_uniform(255.0, 255.0),
+            bg_base_bg_prob: 0.0,
         };
         let bg_factory = BgFactory::new("synth_text/background", 64, 1000);
 
@@ -434,4 +887,93 @@ mod test {
         );
         a.save("./test-img/tmp1.png").unwrap();
     }
+
+    #[test]
+    fn test_combine_backgrounds() {
+        let first = GrayImage::from_pixel(8, 2, Luma([200]));
+        let second = GrayImage::from_pixel(8, 2, Luma([100]));
+
+        let res = MergeUtil::blend_backgrounds(&first, &second, 0.25);
+        assert!(res.pixels().all(|each| each.0[0] == 125));
+
+        let res = MergeUtil::patch_backgrounds(&first, &second, 4, 0);
+        assert_eq!(res.get_pixel(3, 1).0[0], 200);
+        assert_eq!(res.get_pixel(4, 1).0[0], 100);
+
+        let res = MergeUtil::patch_backgrounds(&first, &second, 4, 2);
+        assert_eq!(res.get_pixel(2, 0).0[0], 200);
+        assert_eq!(res.get_pixel(3, 0).0[0], 175);
+        assert_eq!(res.get_pixel(4, 0).0[0], 125);
+        assert_eq!(res.get_pixel(5, 0).0[0], 100);
+    }
+
+    #[test]
+    fn test_combine_rgb_backgrounds() {
+        let first = RgbImage::from_pixel(8, 1, Rgb([200, 100, 0]));
+        let second = RgbImage::from_pixel(8, 1, Rgb([100, 200, 40]));
+
+        let res = MergeUtil::blend_backgrounds(&first, &second, 0.25);
+        assert!(res.pixels().all(|each| each.0 == [125, 175, 30]));
+
+        let res = MergeUtil::patch_backgrounds(&first, &second, 4, 0);
+        assert_eq!(res.get_pixel(3, 0), &Rgb([200, 100, 0]));
+        assert_eq!(res.get_pixel(4, 0), &Rgb([100, 200, 40]));
+
+        let alpha = GrayImage::from_vec(8, 1, vec![255, 0, 255, 0, 255, 0, 255, 0]).unwrap();
+        let res = MergeUtil::composite_over(&first, &alpha, &second);
+        assert_eq!(res.get_pixel(0, 0), &Rgb([200, 100, 0]));
+        assert_eq!(res.get_pixel(1, 0), &Rgb([100, 200, 40]));
+    }
+
+    #[test]
+    fn test_poisson_editing_rgb() {
+        // a dark bar on white, blended onto a flat color without any change of the background
+        let mut font_img = GrayImage::from_pixel(24, 8, Luma([255]));
+        for x in 6..18 {
+            for y in 3..5 {
+                font_img.put_pixel(x, y, Luma([0]));
+            }
+        }
+        let bg_img = RgbImage::from_pixel(32, 12, Rgb([80, 140, 200]));
+
+        let merge_util = MergeUtil {
+            height_diff: Random::new_uniform(2.0, 2.0),
+            bg_alpha: Random::new_uniform(1.0, 1.0),
+            bg_beta: Random::new_uniform(0.0, 0.0),
+            font_alpha: Random::new_uniform(0.2, 0.2),
+            reverse_prob: 0.0,
+            bg_blend_prob: 0.0,
+            bg_blend_alpha: Random::new_uniform(0.3, 0.7),
+            bg_base_color: Random::new_uniform(255.0, 255.0),
+            bg_base_bg_prob: 0.0,
+        };
+        let res = merge_util.poisson_edit_rgb(&font_img, &bg_img);
+        assert_eq!(res.dimensions(), (32, 12));
+
+        // the same text is solved on each channel, so every pixel is moved from the background
+        // by the same amount in the three channels
+        let offsets: Vec<[i32; 3]> = res
+            .pixels()
+            .map(|pixel| [0, 1, 2].map(|c| pixel.0[c] as i32 - bg_img.get_pixel(0, 0).0[c] as i32))
+            .collect();
+        for [r, g, b] in offsets.iter() {
+            assert!((r - g).abs() <= 1 && (g - b).abs() <= 1, "{:?}", (r, g, b));
+        }
+        assert!(offsets.iter().any(|offset| offset[0].abs() > 10));
+    }
+
+    #[test]
+    fn test_composite_over() {
+        let img = GrayImage::from_pixel(4, 1, Luma([0]));
+        let base = GrayImage::from_pixel(4, 1, Luma([200]));
+        let alpha = GrayImage::from_vec(4, 1, vec![0, 255, 51, 255]).unwrap();
+
+        let res = MergeUtil::composite_over(&img, &alpha, &base);
+        assert_eq!(res.as_raw(), &vec![200, 0, 160, 0]);
+
+        let cut_out = GrayAlphaImage::from_vec(2, 1, vec![10, 0, 20, 255]).unwrap();
+        let (gray, alpha) = split_alpha(&cut_out);
+        assert_eq!(gray.as_raw(), &vec![10, 20]);
+        assert_eq!(alpha.as_raw(), &vec![0, 255]);
+    }
 }
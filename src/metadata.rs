@@ -0,0 +1,215 @@
+//! Generation metadata embedded into saved images, so that an image pulled out of a dataset
+//! still tells its transcript, seed, fonts and effects. PNG files get `iTXt` chunks and JPEG
+//! files an EXIF `ImageDescription`, both holding the metadata as JSON.
+
+use std::{fs, io::Cursor, path::Path};
+
+use image::{DynamicImage, ImageOutputFormat};
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ImageMetadata {
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fonts: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub styles: Vec<String>,
+}
+
+/// The keyword of the `iTXt` chunk with the JSON metadata, the label also gets its own chunk.
+pub const PNG_METADATA_KEYWORD: &str = "TextImageGenerator";
+
+impl ImageMetadata {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("metadata is always serializable")
+    }
+}
+
+/// Encode the image as PNG with the metadata in `iTXt` chunks.
+pub fn encode_png(img: &DynamicImage, metadata: Option<&ImageMetadata>) -> Result<Vec<u8>, String> {
+    let mut png = Cursor::new(vec![]);
+    img.write_to(&mut png, ImageOutputFormat::Png)
+        .map_err(|err| format!("fail to encode the image: {}", err))?;
+    let png = png.into_inner();
+
+    match metadata {
+        Some(metadata) => insert_png_text(
+            png,
+            &[
+                ("Label", &metadata.label),
+                (PNG_METADATA_KEYWORD, &metadata.to_json()),
+            ],
+        ),
+        None => Ok(png),
+    }
+}
+
+/// Encode the image as JPEG with the metadata in the EXIF `ImageDescription`.
+pub fn encode_jpeg(
+    img: &DynamicImage,
+    quality: u8,
+    metadata: Option<&ImageMetadata>,
+) -> Result<Vec<u8>, String> {
+    let mut jpeg = Cursor::new(vec![]);
+    img.write_to(&mut jpeg, ImageOutputFormat::Jpeg(quality))
+        .map_err(|err| format!("fail to encode the image: {}", err))?;
+    let jpeg = jpeg.into_inner();
+
+    match metadata {
+        Some(metadata) => insert_jpeg_exif(jpeg, &metadata.to_json()),
+        None => Ok(jpeg),
+    }
+}
+
+/// Save the image as PNG or JPEG according to the extension of `path`.
+pub fn save_with_metadata<P: AsRef<Path>>(
+    img: &DynamicImage,
+    path: P,
+    metadata: Option<&ImageMetadata>,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let bytes = match extension.as_str() {
+        "png" => encode_png(img, metadata)?,
+        "jpg" | "jpeg" => encode_jpeg(img, 95, metadata)?,
+        _ => {
+            return Err(format!(
+                "`{}` should end with `.png`, `.jpg` or `.jpeg`",
+                path.display()
+            ))
+        }
+    };
+
+    fs::write(path, bytes).map_err(|err| format!("fail to write `{}`: {}", path.display(), err))
+}
+
+/// Insert UTF-8 `iTXt` chunks right after the `IHDR` chunk.
+fn insert_png_text(png: Vec<u8>, entries: &[(&str, &str)]) -> Result<Vec<u8>, String> {
+    // 8 bytes of signature, then IHDR: length, type, 13 bytes of data and the CRC
+    const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < IHDR_END || &png[12..16] != b"IHDR" {
+        return Err("not a PNG file".to_string());
+    }
+
+    let mut res = Vec::with_capacity(png.len() + 256);
+    res.extend_from_slice(&png[..IHDR_END]);
+    for (keyword, text) in entries {
+        // keyword, null, no compression, compression method, empty language and translated
+        // keyword
+        let mut data = keyword.as_bytes().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+        data.extend_from_slice(text.as_bytes());
+
+        let mut chunk = b"iTXt".to_vec();
+        chunk.extend_from_slice(&data);
+        res.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        res.extend_from_slice(&chunk);
+        res.extend_from_slice(&crc32(&chunk).to_be_bytes());
+    }
+    res.extend_from_slice(&png[IHDR_END..]);
+
+    Ok(res)
+}
+
+/// Insert an APP1 EXIF segment with `description` as the `ImageDescription` of IFD0, after the
+/// JFIF APP0 segment if there is one.
+fn insert_jpeg_exif(jpeg: Vec<u8>, description: &str) -> Result<Vec<u8>, String> {
+    if jpeg.len() < 4 || jpeg[..2] != [0xFF, 0xD8] {
+        return Err("not a JPEG file".to_string());
+    }
+    let mut insert_at = 2;
+    if jpeg[2..4] == [0xFF, 0xE0] && jpeg.len() >= 6 {
+        insert_at += 2 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+    }
+
+    let mut text = description.as_bytes().to_vec();
+    text.push(0);
+    // little-endian TIFF header, IFD0 at offset 8 with a single entry, no next IFD, then the
+    // text at offset 8 + 2 + 12 + 4
+    let mut exif = b"Exif\0\0II\x2A\0".to_vec();
+    exif.extend_from_slice(&8u32.to_le_bytes());
+    exif.extend_from_slice(&1u16.to_le_bytes());
+    exif.extend_from_slice(&0x010Eu16.to_le_bytes());
+    exif.extend_from_slice(&2u16.to_le_bytes());
+    exif.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    exif.extend_from_slice(&26u32.to_le_bytes());
+    exif.extend_from_slice(&0u32.to_le_bytes());
+    exif.extend_from_slice(&text);
+
+    let segment_len = exif.len() + 2;
+    if segment_len > u16::MAX as usize {
+        return Err("the metadata is too long for an EXIF segment".to_string());
+    }
+
+    let mut res = Vec::with_capacity(jpeg.len() + segment_len + 2);
+    res.extend_from_slice(&jpeg[..insert_at]);
+    res.extend_from_slice(&[0xFF, 0xE1]);
+    res.extend_from_slice(&(segment_len as u16).to_be_bytes());
+    res.extend_from_slice(&exif);
+    res.extend_from_slice(&jpeg[insert_at..]);
+
+    Ok(res)
+}
+
+/// CRC-32 (ISO-HDLC) as used by PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use image::GrayImage;
+
+    use super::*;
+
+    #[test]
+    fn test_embed_metadata() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let img = DynamicImage::ImageLuma8(GrayImage::from_pixel(8, 4, image::Luma([200])));
+        let metadata = ImageMetadata {
+            label: "測試".to_string(),
+            seed: Some(42),
+            fonts: vec!["SimSun".to_string()],
+            effects: vec![],
+            styles: vec![],
+        };
+        assert_eq!(
+            metadata.to_json(),
+            r#"{"label":"測試","seed":42,"fonts":["SimSun"]}"#
+        );
+
+        let png = encode_png(&img, Some(&metadata)).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded.to_luma8(), img.to_luma8());
+        let needle = "Label\0\0\0\0\0測試".as_bytes();
+        assert!(png.windows(needle.len()).any(|each| each == needle));
+
+        let jpeg = encode_jpeg(&img, 90, Some(&metadata)).unwrap();
+        assert!(image::load_from_memory(&jpeg).is_ok());
+        let needle = metadata.to_json();
+        assert!(jpeg
+            .windows(needle.len())
+            .any(|each| each == needle.as_bytes()));
+    }
+}
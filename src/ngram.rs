@@ -0,0 +1,209 @@
+//! A character n-gram model of a raw Chinese corpus, so that sampled text follows the
+//! transitions of real text instead of being independent draws. The corpus is cut at every
+//! character outside the character dict, each run is counted as bigrams (and trigrams), and
+//! sampling backs off to shorter contexts, then to the character weights.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+};
+
+use indexmap::{IndexMap, IndexSet};
+use rand::Rng;
+use rand_distr::{Distribution, WeightedIndex};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{rng::sample_rng, utils::InternalAttrsOwned};
+
+pub struct NgramModel {
+    order: usize,
+    // characters of the corpus that are in the character dict
+    vocab: IndexSet<String>,
+    // how often each character starts a run
+    starts: Vec<(u32, u32)>,
+    // the context (1 to order - 1 characters) to its followers with their counts
+    transitions: HashMap<Vec<u32>, Vec<(u32, u32)>>,
+}
+
+impl NgramModel {
+    /// Count the n-grams of order 2 or 3 of the corpus, keeping the characters of `ch_dict`
+    /// only.
+    pub fn new<S: AsRef<str>, V>(
+        corpus: &str,
+        ch_dict: &IndexMap<S, V>,
+        order: usize,
+    ) -> Result<Self, String> {
+        if !(2..=3).contains(&order) {
+            return Err("`ngram_order` should be 2 or 3".to_string());
+        }
+        let known: HashSet<&str> = ch_dict.keys().map(|ch| ch.as_ref()).collect();
+
+        let mut vocab = IndexSet::new();
+        let mut starts: HashMap<u32, u32> = HashMap::new();
+        let mut transitions: HashMap<Vec<u32>, HashMap<u32, u32>> = HashMap::new();
+        let mut run: Vec<u32> = vec![];
+        for cluster in corpus.graphemes(true) {
+            if !known.contains(cluster) {
+                run.clear();
+                continue;
+            }
+
+            let (id, _) = vocab.insert_full(cluster.to_string());
+            let id = id as u32;
+            if run.is_empty() {
+                *starts.entry(id).or_default() += 1;
+            }
+            for len in 1..order {
+                if run.len() >= len {
+                    let context = run[run.len() - len..].to_vec();
+                    *transitions
+                        .entry(context)
+                        .or_default()
+                        .entry(id)
+                        .or_default() += 1;
+                }
+            }
+            run.push(id);
+            if run.len() >= order {
+                run.remove(0);
+            }
+        }
+        if vocab.is_empty() {
+            return Err("no character of the n-gram corpus is in the character file".to_string());
+        }
+
+        Ok(Self {
+            order,
+            vocab,
+            starts: starts.into_iter().collect(),
+            transitions: transitions
+                .into_iter()
+                .map(|(context, followers)| (context, followers.into_iter().collect()))
+                .collect(),
+        })
+    }
+
+    /// Sample a sequence of `range` characters. Each character follows the longest context seen
+    /// in the corpus whose followers include an `allowed` character of `ch_dict`, the first one
+    /// is drawn from the starts of the runs; when nothing fits, the character is drawn from
+    /// `weights` as in `get_random_chinese_text_with_font_list`.
+    pub fn sample<'a, W, F>(
+        &self,
+        ch_dict: &'a IndexMap<String, Vec<InternalAttrsOwned>>,
+        weights: &W,
+        allowed: F,
+        range: RangeInclusive<u32>,
+    ) -> Vec<(&'a str, Option<&'a Vec<InternalAttrsOwned>>)>
+    where
+        W: Distribution<usize>,
+        F: Fn(&str) -> bool,
+    {
+        let mut rng = sample_rng();
+
+        let num = rng.gen_range(range);
+        let mut res = Vec::with_capacity(num as usize);
+        let mut history: Vec<u32> = vec![];
+        for _ in 0..num {
+            let mut next = None;
+            for len in (1..self.order.min(history.len() + 1)).rev() {
+                if let Some(followers) = self.transitions.get(&history[history.len() - len..]) {
+                    next = self.choose(followers, ch_dict, &allowed, &mut rng);
+                    if next.is_some() {
+                        break;
+                    }
+                }
+            }
+            if history.is_empty() {
+                next = self.choose(&self.starts, ch_dict, &allowed, &mut rng);
+            }
+
+            let (id, ch, font_list) = match next {
+                Some(next) => next,
+                None => {
+                    let (ch, font_list) = ch_dict.get_index(weights.sample(&mut rng)).unwrap();
+                    let id = self.vocab.get_index_of(ch.as_str()).map(|id| id as u32);
+                    (id, ch.as_str(), font_list)
+                }
+            };
+            res.push((ch, Some(font_list)));
+            match id {
+                Some(id) => {
+                    history.push(id);
+                    if history.len() >= self.order {
+                        history.remove(0);
+                    }
+                }
+                // the corpus never saw this character, start over
+                None => history.clear(),
+            }
+        }
+
+        res
+    }
+
+    fn choose<'a, F, R>(
+        &self,
+        candidates: &[(u32, u32)],
+        ch_dict: &'a IndexMap<String, Vec<InternalAttrsOwned>>,
+        allowed: &F,
+        rng: &mut R,
+    ) -> Option<(Option<u32>, &'a str, &'a Vec<InternalAttrsOwned>)>
+    where
+        F: Fn(&str) -> bool,
+        R: Rng,
+    {
+        let candidates: Vec<_> = candidates
+            .iter()
+            .filter_map(|&(id, count)| {
+                let ch = self.vocab.get_index(id as usize)?;
+                let (ch, font_list) = ch_dict.get_key_value(ch)?;
+                allowed(ch).then_some((id, ch.as_str(), font_list, count))
+            })
+            .collect();
+        let dist = WeightedIndex::new(candidates.iter().map(|each| each.3)).ok()?;
+        let (id, ch, font_list, _) = candidates[dist.sample(rng)];
+
+        Some((Some(id), ch, font_list))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand_distr::WeightedAliasIndex;
+
+    use super::*;
+
+    #[test]
+    fn test_ngram_model() {
+        let ch_dict: IndexMap<String, Vec<InternalAttrsOwned>> = ["天", "地", "玄", "黃", "宇"]
+            .into_iter()
+            .map(|ch| (ch.to_string(), vec![]))
+            .collect();
+        let weights = WeightedAliasIndex::new(vec![1.0; 5]).unwrap();
+        assert!(NgramModel::new("天地", &ch_dict, 4).is_err());
+        assert!(NgramModel::new("abc", &ch_dict, 2).is_err());
+
+        // the corpus only ever goes 天 → 地 → 玄 → 黃, cut at the comma
+        let model = NgramModel::new("天地玄黃，天地玄黃", &ch_dict, 2).unwrap();
+        let text: String = model
+            .sample(&ch_dict, &weights, |_| true, 4..=4)
+            .into_iter()
+            .map(|(ch, _)| ch)
+            .collect();
+        assert_eq!(text, "天地玄黃");
+
+        // without 天 the start backs off to the weights, which exclude it as well
+        let weights = WeightedAliasIndex::new(vec![0.0, 1.0, 1.0, 1.0, 1.0]).unwrap();
+        let text = model.sample(&ch_dict, &weights, |ch| ch != "天", 6..=6);
+        assert_eq!(text.len(), 6);
+        assert!(text.iter().all(|(ch, _)| *ch != "天"));
+
+        let model = NgramModel::new("天地玄黃宇", &ch_dict, 3).unwrap();
+        let text: String = model
+            .sample(&ch_dict, &weights, |_| true, 5..=5)
+            .into_iter()
+            .map(|(ch, _)| ch)
+            .collect();
+        assert_eq!(text, "天地玄黃宇");
+    }
+}
@@ -0,0 +1,104 @@
+//! Normalization of labels, so that they match the vocabulary conventions of the recognizer.
+
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalizationForm {
+    #[default]
+    None,
+    Nfc,
+    Nfkc,
+}
+
+impl NormalizationForm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "" | "none" => Some(Self::None),
+            "nfc" => Some(Self::Nfc),
+            "nfkc" => Some(Self::Nfkc),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LabelNormalizer {
+    pub form: NormalizationForm,
+    // fold full-width ASCII variants (U+FF01–U+FF5E) and the ideographic space to half width
+    pub fold_width: bool,
+    // trim the label and collapse every run of whitespace into one space
+    pub collapse_spaces: bool,
+}
+
+impl LabelNormalizer {
+    pub fn new(form: &str, fold_width: bool) -> Result<Self, String> {
+        let form = NormalizationForm::from_name(form).ok_or_else(|| {
+            format!(
+                "unknown normalization form `{}`, expected none, nfc or nfkc",
+                form
+            )
+        })?;
+
+        Ok(Self {
+            form,
+            fold_width,
+            collapse_spaces: false,
+        })
+    }
+
+    pub fn normalize(&self, label: &str) -> String {
+        let label: String = match self.form {
+            NormalizationForm::None => label.to_string(),
+            NormalizationForm::Nfc => label.nfc().collect(),
+            NormalizationForm::Nfkc => label.nfkc().collect(),
+        };
+
+        let label = if self.fold_width {
+            label.chars().map(fold_width).collect()
+        } else {
+            label
+        };
+
+        if self.collapse_spaces {
+            label.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            label
+        }
+    }
+}
+
+fn fold_width(ch: char) -> char {
+    match ch as u32 {
+        0xFF01..=0xFF5E => char::from_u32(ch as u32 - 0xFEE0).unwrap(),
+        0x3000 => ' ',
+        _ => ch,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_label() {
+        let label = "Ｃａｆe\u{301}　１２３，好";
+
+        let normalizer = LabelNormalizer::new("none", false).unwrap();
+        assert_eq!(normalizer.normalize(label), label);
+
+        let normalizer = LabelNormalizer::new("nfc", false).unwrap();
+        assert_eq!(normalizer.normalize(label), "Ｃａｆé　１２３，好");
+
+        let normalizer = LabelNormalizer::new("nfc", true).unwrap();
+        assert_eq!(normalizer.normalize(label), "Café 123,好");
+
+        let normalizer = LabelNormalizer::new("nfkc", false).unwrap();
+        assert_eq!(normalizer.normalize(label), "Café 123,好");
+
+        let mut normalizer = LabelNormalizer::new("none", true).unwrap();
+        normalizer.collapse_spaces = true;
+        assert_eq!(normalizer.normalize("  天地　 玄黃 "), "天地 玄黃");
+
+        assert!(LabelNormalizer::new("nfd", false).is_err());
+    }
+}
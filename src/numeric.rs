@@ -0,0 +1,243 @@
+//! Generators for realistic numeric strings: amounts, phone numbers, serial/ID numbers and
+//! percentages, formatted in the style of a locale.
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::rng::sample_rng;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericKind {
+    Amount,
+    Phone,
+    Serial,
+    Percent,
+}
+
+impl NumericKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "amount" => Some(Self::Amount),
+            "phone" => Some(Self::Phone),
+            "serial" => Some(Self::Serial),
+            "percent" => Some(Self::Percent),
+            _ => None,
+        }
+    }
+}
+
+/// Number formatting of a locale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericLocale {
+    /// 1,234,567.89
+    En,
+    /// 1,234,567.89, with ¥/￥ and 元
+    Zh,
+    /// 1.234.567,89
+    De,
+    /// 1 234 567,89 (narrow no-break space)
+    Fr,
+    /// 12,34,567.89 (lakh grouping)
+    In,
+}
+
+impl NumericLocale {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "en" => Some(Self::En),
+            "zh" => Some(Self::Zh),
+            "de" => Some(Self::De),
+            "fr" => Some(Self::Fr),
+            "in" => Some(Self::In),
+            _ => None,
+        }
+    }
+
+    fn separators(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::En | Self::Zh | Self::In => (",", "."),
+            Self::De => (".", ","),
+            Self::Fr => ("\u{202F}", ","),
+        }
+    }
+}
+
+/// Group the digits of an integer with the thousands separator of the locale.
+pub fn group_digits(digits: &str, locale: NumericLocale) -> String {
+    let (sep, _) = locale.separators();
+    let len = digits.len();
+    let mut res = String::with_capacity(len * 2);
+    for (idx, ch) in digits.chars().enumerate() {
+        let rest = len - idx;
+        let is_group_start = match locale {
+            // the last group has 3 digits, the others 2
+            NumericLocale::In => rest == 3 || rest > 3 && (rest - 3).is_multiple_of(2),
+            _ => rest.is_multiple_of(3),
+        };
+        if idx > 0 && is_group_start {
+            res.push_str(sep);
+        }
+        res.push(ch);
+    }
+
+    res
+}
+
+fn random_digits<R: Rng>(rng: &mut R, len: usize) -> String {
+    (0..len)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect()
+}
+
+fn random_amount<R: Rng>(rng: &mut R, locale: NumericLocale) -> String {
+    let (_, decimal_sep) = locale.separators();
+    // amounts are spread over several orders of magnitude
+    let int_len = rng.gen_range(1..=9);
+    let mut int_part = random_digits(rng, int_len);
+    if int_len > 1 {
+        int_part.replace_range(0..1, &rng.gen_range(1..10).to_string());
+    }
+    let mut amount = group_digits(&int_part, locale);
+    if rng.gen_bool(0.7) {
+        amount.push_str(decimal_sep);
+        amount.push_str(&random_digits(rng, 2));
+    }
+
+    match locale {
+        NumericLocale::En => ["$", "US$", ""]
+            .choose(rng)
+            .map(|symbol| format!("{}{}", symbol, amount))
+            .unwrap(),
+        NumericLocale::Zh => match rng.gen_range(0..4) {
+            0 => format!("¥{}", amount),
+            1 => format!("￥{}", amount),
+            2 => format!("{}元", amount),
+            _ => amount,
+        },
+        NumericLocale::De | NumericLocale::Fr => ["\u{a0}€", " EUR", ""]
+            .choose(rng)
+            .map(|symbol| format!("{}{}", amount, symbol))
+            .unwrap(),
+        NumericLocale::In => ["₹", "Rs. ", ""]
+            .choose(rng)
+            .map(|symbol| format!("{}{}", symbol, amount))
+            .unwrap(),
+    }
+}
+
+fn random_phone<R: Rng>(rng: &mut R, locale: NumericLocale) -> String {
+    match locale {
+        NumericLocale::Zh => {
+            let prefix = ["13", "15", "17", "18", "19"].choose(rng).unwrap();
+            let digits = format!("{}{}", prefix, random_digits(rng, 9));
+            match rng.gen_range(0..3) {
+                0 => format!("{} {} {}", &digits[..3], &digits[3..7], &digits[7..]),
+                1 => format!("{}-{}-{}", &digits[..3], &digits[3..7], &digits[7..]),
+                _ => digits,
+            }
+        }
+        NumericLocale::En => {
+            let area = format!("{}{}", rng.gen_range(2..10), random_digits(rng, 2));
+            let (exchange, line) = (random_digits(rng, 3), random_digits(rng, 4));
+            match rng.gen_range(0..3) {
+                0 => format!("({}) {}-{}", area, exchange, line),
+                1 => format!("+1 {}-{}-{}", area, exchange, line),
+                _ => format!("{}.{}.{}", area, exchange, line),
+            }
+        }
+        NumericLocale::De => format!(
+            "+49 {}{} {}",
+            rng.gen_range(1..10),
+            random_digits(rng, 2),
+            random_digits(rng, 7)
+        ),
+        NumericLocale::Fr => {
+            let digits = format!("0{}{}", rng.gen_range(1..8), random_digits(rng, 8));
+            digits
+                .as_bytes()
+                .chunks(2)
+                .map(|pair| std::str::from_utf8(pair).unwrap())
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        NumericLocale::In => format!(
+            "+91 {}{} {}",
+            rng.gen_range(6..10),
+            random_digits(rng, 4),
+            random_digits(rng, 5)
+        ),
+    }
+}
+
+fn random_serial<R: Rng>(rng: &mut R, locale: NumericLocale) -> String {
+    const LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+    const ALPHANUMERIC: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ0123456789";
+
+    match rng.gen_range(0..3) {
+        // Chinese resident ID number, the last character may be X
+        0 if locale == NumericLocale::Zh => {
+            let check = *b"0123456789X".choose(rng).unwrap() as char;
+            format!("{}{}", random_digits(rng, 17), check)
+        }
+        // grouped product key
+        0 | 1 => (0..rng.gen_range(3..=5))
+            .map(|_| {
+                (0..4)
+                    .map(|_| *ALPHANUMERIC.choose(rng).unwrap() as char)
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("-"),
+        // letter prefix and a number, like an invoice or a serial number
+        _ => {
+            let prefix: String = (0..rng.gen_range(1..=3))
+                .map(|_| *LETTERS.choose(rng).unwrap() as char)
+                .collect();
+            let sep = ["", "-", " ", "No."].choose(rng).unwrap();
+            let len = rng.gen_range(6..=12);
+            format!("{}{}{}", prefix, sep, random_digits(rng, len))
+        }
+    }
+}
+
+fn random_percent<R: Rng>(rng: &mut R, locale: NumericLocale) -> String {
+    let (_, decimal_sep) = locale.separators();
+    let mut value = rng.gen_range(0..=100).to_string();
+    if value != "100" && rng.gen_bool(0.5) {
+        value.push_str(decimal_sep);
+        let len = rng.gen_range(1..=2);
+        value.push_str(&random_digits(rng, len));
+    }
+    if rng.gen_bool(0.2) {
+        value.insert(0, if rng.gen_bool(0.5) { '+' } else { '-' });
+    }
+
+    match locale {
+        NumericLocale::De | NumericLocale::Fr => format!("{}\u{a0}%", value),
+        _ => format!("{}%", value),
+    }
+}
+
+pub fn random_numeric(kind: NumericKind, locale: NumericLocale) -> String {
+    let mut rng = sample_rng();
+    match kind {
+        NumericKind::Amount => random_amount(&mut rng, locale),
+        NumericKind::Phone => random_phone(&mut rng, locale),
+        NumericKind::Serial => random_serial(&mut rng, locale),
+        NumericKind::Percent => random_percent(&mut rng, locale),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_group_digits() {
+        assert_eq!(group_digits("1234567", NumericLocale::En), "1,234,567");
+        assert_eq!(group_digits("123", NumericLocale::En), "123");
+        assert_eq!(group_digits("1234567", NumericLocale::De), "1.234.567");
+        assert_eq!(group_digits("1234567", NumericLocale::In), "12,34,567");
+        assert_eq!(group_digits("12345", NumericLocale::In), "12,345");
+        assert_eq!(group_digits("123", NumericLocale::In), "123");
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use cosmic_text::{
+    fontdb,
+    rustybuzz::ttf_parser::{
+        gsub::{SingleSubstitution, SubstitutionSubtable},
+        Face, GlyphId, Tag,
+    },
+    FontSystem,
+};
+use rand::Rng;
+
+use crate::rng::sample_rng;
+
+/// Parse an OpenType feature tag such as `vert` or `ss01`.
+pub fn parse_tag(tag: &str) -> Option<Tag> {
+    if tag.is_empty() || tag.len() > 4 || !tag.is_ascii() {
+        return None;
+    }
+
+    // tags shorter than 4 bytes are padded with spaces
+    let mut bytes = [b' '; 4];
+    bytes[..tag.len()].copy_from_slice(tag.as_bytes());
+    Some(Tag::from_bytes(&bytes))
+}
+
+pub fn parse_tags<S: AsRef<str>>(tags: &[S]) -> Result<Vec<Tag>, String> {
+    tags.iter()
+        .map(|tag| {
+            parse_tag(tag.as_ref())
+                .ok_or_else(|| format!("invalid OpenType feature tag: {}", tag.as_ref()))
+        })
+        .collect()
+}
+
+/// OpenType features to apply while rendering: a default set for every font, and extra
+/// features for specific font families.
+///
+/// cosmic-text shapes without any user features, so the single and alternate substitutions of
+/// these features are applied to the shaped glyphs before rasterization.
+#[derive(Clone, Debug, Default)]
+pub struct OpenTypeFeatures {
+    pub default: Vec<Tag>,
+    pub per_font: HashMap<String, Vec<Tag>>,
+}
+
+impl OpenTypeFeatures {
+    pub fn from_names<S: AsRef<str>>(
+        default: &[S],
+        per_font: &HashMap<String, Vec<String>>,
+    ) -> Result<Self, String> {
+        let mut res = Self {
+            default: parse_tags(default)?,
+            per_font: HashMap::with_capacity(per_font.len()),
+        };
+        for (family, tags) in per_font {
+            res.per_font.insert(family.clone(), parse_tags(tags)?);
+        }
+
+        Ok(res)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.default.is_empty() && self.per_font.values().all(|tags| tags.is_empty())
+    }
+
+    /// Replace the default features, keeping the per-font ones.
+    pub fn with_default(&self, default: Vec<Tag>) -> Self {
+        Self {
+            default,
+            per_font: self.per_font.clone(),
+        }
+    }
+
+    fn tags_for(&self, family: Option<&str>) -> Vec<Tag> {
+        let mut tags = self.default.clone();
+        if let Some(extra) = family.and_then(|family| self.per_font.get(family)) {
+            tags.extend(extra.iter().filter(|tag| !self.default.contains(tag)));
+        }
+
+        tags
+    }
+
+    pub fn substitute(
+        &self,
+        font_system: &mut FontSystem,
+        font_id: fontdb::ID,
+        glyph_id: u16,
+    ) -> u16 {
+        let family = font_system
+            .db()
+            .face(font_id)
+            .and_then(|face| face.families.first().map(|(name, _)| name.clone()));
+        let tags = self.tags_for(family.as_deref());
+        if tags.is_empty() {
+            return glyph_id;
+        }
+
+        match font_system.get_font(font_id) {
+            Some(font) => substitute_glyph(font.rustybuzz(), glyph_id, &tags),
+            None => glyph_id,
+        }
+    }
+}
+
+/// Apply the single and alternate substitution lookups of the given features to one glyph.
+/// An alternate substitution picks one of the alternates at random.
+pub fn substitute_glyph(face: &Face, glyph_id: u16, features: &[Tag]) -> u16 {
+    let gsub = match face.tables().gsub {
+        Some(gsub) => gsub,
+        None => return glyph_id,
+    };
+
+    // the same feature is usually listed once per script/language, lookups are applied once
+    // and in lookup list order
+    let mut lookup_indices: Vec<u16> = gsub
+        .features
+        .into_iter()
+        .filter(|feature| features.contains(&feature.tag))
+        .flat_map(|feature| feature.lookup_indices.into_iter())
+        .collect();
+    lookup_indices.sort_unstable();
+    lookup_indices.dedup();
+
+    let mut glyph = GlyphId(glyph_id);
+    for lookup_index in lookup_indices {
+        let lookup = match gsub.lookups.get(lookup_index) {
+            Some(lookup) => lookup,
+            None => continue,
+        };
+        for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+            let substituted = match subtable {
+                SubstitutionSubtable::Single(SingleSubstitution::Format1 { coverage, delta }) => {
+                    coverage
+                        .get(glyph)
+                        .map(|_| GlyphId((glyph.0 as i32 + delta as i32) as u16))
+                }
+                SubstitutionSubtable::Single(SingleSubstitution::Format2 {
+                    coverage,
+                    substitutes,
+                }) => coverage.get(glyph).and_then(|idx| substitutes.get(idx)),
+                SubstitutionSubtable::Alternate(alternate) => alternate
+                    .coverage
+                    .get(glyph)
+                    .and_then(|idx| alternate.alternate_sets.get(idx))
+                    .and_then(|set| {
+                        if set.alternates.is_empty() {
+                            return None;
+                        }
+                        let choice = sample_rng().gen_range(0..set.alternates.len());
+                        set.alternates.get(choice)
+                    }),
+                _ => None,
+            };
+
+            if let Some(substituted) = substituted {
+                glyph = substituted;
+                break;
+            }
+        }
+    }
+
+    glyph.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_tag() {
+        assert_eq!(parse_tag("ss01"), Some(Tag::from_bytes(b"ss01")));
+        assert_eq!(parse_tag("cv1"), Some(Tag::from_bytes(b"cv1 ")));
+        assert_eq!(parse_tag("toolong"), None);
+        assert_eq!(parse_tag(""), None);
+    }
+}
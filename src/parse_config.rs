@@ -1,24 +1,223 @@
-use std::{fs, path::Path};
+use std::{collections::HashMap, fs, path::Path};
 
+#[cfg(feature = "python")]
 use pyo3::pyclass;
 use serde::{Deserialize, Serialize};
 
 use super::effect_helper::math::Random;
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 #[derive(Clone, Debug)]
 pub struct Config {
     // 1. font_util
     pub font_dir: String,
     pub chinese_ch_file_path: String,
+    // raw text to count the frequencies of the characters in, replacing the frequency column
+    // of the character file; empty to disable
+    pub freq_corpus_file_path: String,
+    // added to the count of every character in the corpus, so that the ones missing from it can
+    // still be sampled
+    pub freq_smoothing: f64,
+    // raw text the character n-gram model of get_random_chinese_ngram is counted from, empty
+    // to disable, and the order of the model (2 or 3)
+    pub ngram_corpus_file_path: String,
+    pub ngram_order: usize,
+    // one real sentence per line, get_random_chinese then samples lines of it instead of
+    // characters; empty to disable. With `sentence_substring` a random window of min to max
+    // clusters is taken, otherwise the whole line
+    pub sentence_file_path: String,
+    pub sentence_substring: bool,
+    // OpenCC-style character conversion table (STCharacters.txt), and the script the Chinese
+    // text is emitted in: none, traditional, simplified or mixed
+    pub script_conversion_file_path: String,
+    pub script: String,
+    // file caching which fonts cover which characters between runs, empty to disable
+    pub coverage_cache_path: String,
+    // fail at init if a character of a dict has no font that can render it, instead of
+    // drawing it with a font of the main font list
+    pub strict_coverage: bool,
     pub main_font_list_file_path: String,
     pub latin_corpus_file_path: String,
+    // Arabic, Hebrew or another right-to-left corpus, see get_random_rtl_words
+    pub rtl_corpus_file_path: String,
     pub symbol_file_path: String,
+    // how get_random_chinese inserts symbols: `symbol_count` (min, max) symbols per text, or
+    // each position with probability `symbol_prob` if it is positive; symbols may come before
+    // the first or after the last character only with `symbol_at_start` and `symbol_at_end`
+    pub symbol_count: (u32, u32),
+    pub symbol_prob: f64,
+    pub symbol_at_start: bool,
+    pub symbol_at_end: bool,
+    // jieba-style word dictionary for word segmentation, empty to disable
+    pub word_dict_file_path: String,
+    // code point ranges (e.g. `4E00-9FFF`) and regexes that the characters of the character
+    // file and the words of the Latin corpus are filtered with at init
+    pub include_ranges: Vec<String>,
+    pub exclude_ranges: Vec<String>,
+    pub include_regex: String,
+    pub exclude_regex: String,
+    // characters dropped from the Chinese dict, and the only characters sampled from it; files
+    // of characters such as the label dict of a recognizer, empty to disable
+    pub exclude_chars_file_path: String,
+    pub charset_file_path: String,
+    // groups of confusable characters, one group per line separated by whitespace
+    pub confusable_file_path: String,
+    // probability that get_noisy_label perturbs a label, confusable groups are used as the
+    // confusion table
+    pub label_noise_prob: f64,
+    // the order the dicts are looked up in when wrapping user text
+    pub wrap_dict_order: Vec<String>,
+    // get_random_text: probabilities of Chinese characters, Latin words and symbols
+    pub mixed_pool_ratios: (f64, f64, f64),
+    // get_random_sample: the sources it picks from by weight
+    pub corpus_sources: Vec<CorpusSourceConfig>,
+    // sample Chinese characters by inverse frequency, and the temperature the weights are
+    // scaled with (weight = freq^(1/T), T > 1 flattens, T < 1 sharpens)
+    pub inverse_frequency: bool,
+    // every sampleable Chinese character is drawn at least this many times before drawing by
+    // weight again, 0 to disable
+    pub balanced_min_count: u32,
+    pub sampling_temperature: f64,
     pub font_size: usize,
     pub line_height: usize,
     pub font_img_height: usize,
     pub font_img_width: usize,
+    // lay out every line up to `max_font_img_width` wide and shrink the buffer to the measured
+    // line, instead of clipping at `font_img_width`
+    pub auto_width: bool,
+    pub max_font_img_width: usize,
+    // random line height per sample, the image is then as tall as the line; None keeps
+    // `line_height` and `font_img_height`
+    pub line_height_range: Option<Random>,
+    // random font size per sample, None keeps `font_size`
+    pub font_size_range: Option<Random>,
+    // random empty rows above and below the line
+    pub padding_top: Random,
+    pub padding_bottom: Random,
+    // random empty columns before the first glyph and after the last one
+    pub padding_left: Random,
+    pub padding_right: Random,
+    // crop the image to the ink on every side and put the paddings around the ink, instead of
+    // around the line box
+    pub tight_crop: bool,
+    // vertical mode: columns from right to left, `font_img_width` long and `line_height` wide
+    pub vertical: bool,
+    // vertical mode: max length of Latin/digit runs set horizontally (tate-chū-yoko)
+    pub tcy_max_len: usize,
+    // vertical mode: empty pixels between the columns, and whether the image is rotated 90°
+    // counterclockwise so that it reads from left to right
+    pub column_spacing: usize,
+    pub vertical_rotate: bool,
+    // OpenType feature tags for every font, and extra ones per font family
+    pub opentype_features: Vec<String>,
+    pub font_opentype_features: HashMap<String, Vec<String>>,
+    // sampling weight of each font family when a font is chosen for a character, e.g. common
+    // fonts heavier than decorative ones; the families not listed weigh 1. A JSON file of
+    // weighted font groups as in config.json is read first, `font_weights` overrides it
+    pub font_weights_file_path: String,
+    pub font_weights: HashMap<String, f64>,
+    // normalization of the labels: `none`, `nfc` or `nfkc`, and whether full-width ASCII is
+    // folded to half width
+    pub label_normalization: String,
+    pub fold_full_width: bool,
+    // random runs of spaces between the characters and a leading indentation (insert_spaces),
+    // and whether labels collapse them into single spaces
+    pub space_prob: f64,
+    pub space_run_len: (u32, u32),
+    pub indent_prob: f64,
+    pub indent_len: (u32, u32),
+    pub collapse_label_spaces: bool,
+    // whether standard ligatures may form
+    pub ligatures: bool,
+    // whether kerning is applied, and the max fraction each kerning value is perturbed by
+    pub kerning: bool,
+    pub kerning_jitter: f64,
+    // random size factor of each character in [1 - glyph_size_jitter, 1 + glyph_size_jitter]
+    pub glyph_size_jitter: f64,
+    // noise texture inside the strokes: 0 strength keeps flat ink, scale is in pixels
+    pub ink_texture_strength: f32,
+    pub ink_texture_scale: f32,
+    // synthetic bold and italic for fonts without such faces: the probability of a line being
+    // emboldened and by how much (em), and of it being slanted and the tangent of the slant
+    pub faux_bold_prob: f64,
+    pub faux_bold_width: f32,
+    pub faux_italic_prob: f64,
+    pub faux_italic_slant: f32,
+    // random extra space of every line in em: between every two characters (negative to
+    // tighten), and after every space on top of it
+    pub letter_spacing: Random,
+    pub word_spacing: Random,
+    // random vertical offset of every character in pixels, and rotation in degrees; None for
+    // neither
+    pub baseline_jitter: Option<Random>,
+    pub glyph_rotation: Option<Random>,
+    // curved baselines: the probability of a line on an arc and its height relative to the
+    // length of the line (positive bows upwards), and of a line on a sine wave with its
+    // amplitude and period in em
+    pub arc_text_prob: f64,
+    pub arc_text_bend: Random,
+    pub wave_text_prob: f64,
+    pub wave_text_amplitude: Random,
+    pub wave_text_period: Random,
+    // probability that a line casts a soft shadow, its offset (x and y each) and blur sigma in
+    // em, and its opacity
+    pub drop_shadow_prob: f64,
+    pub drop_shadow_offset: Random,
+    pub drop_shadow_sigma: Random,
+    pub drop_shadow_opacity: Random,
+    // probability that a line is underlined, otherwise struck through, the styles of the line
+    // (`solid`, `dashed`, `wavy`) and its thickness relative to the one given by the font
+    pub underline_prob: f64,
+    pub strikethrough_prob: f64,
+    pub decoration_styles: Vec<String>,
+    pub decoration_thickness: Random,
+    // images drawn instead of the font for some characters, e.g. handwriting samples, and the
+    // probability that a character with images uses one
+    pub image_glyph_dir: String,
+    pub image_glyph_prob: f64,
+    // probability that a line gets a random color for each run of characters, the run length,
+    // and the colors to choose from; an empty palette draws a random hue from char_color_hue
+    pub char_color_prob: f64,
+    pub char_color_run_len: (u32, u32),
+    pub char_color_palette: Vec<(u8, u8, u8)>,
+    pub char_color_hue: Random,
+    // probability that a sample gets a random text and background color instead of black on
+    // white, from the palettes or, if they are empty, the HSV ranges (hue in degrees,
+    // saturation and value in 0..=1), redrawn until their WCAG contrast ratio reaches
+    // min_contrast
+    pub color_prob: f64,
+    pub text_color_palette: Vec<(u8, u8, u8)>,
+    pub text_color_hue: Random,
+    pub text_color_saturation: Random,
+    pub text_color_value: Random,
+    pub background_color_palette: Vec<(u8, u8, u8)>,
+    pub background_color_hue: Random,
+    pub background_color_saturation: Random,
+    pub background_color_value: Random,
+    pub min_contrast: f64,
+    // `cpu` or `gpu`, batches are composited on the GPU when built with the `gpu` feature
+    pub render_backend: String,
     // 2. cv_util
+    // morphology of the strokes
+    pub erode_prob: f64,
+    pub erode_kernel: Random,
+    pub dilate_prob: f64,
+    pub dilate_kernel: Random,
+    // ink bleed around the strokes
+    pub ink_bleed_prob: f64,
+    pub ink_bleed_spread: Random,
+    pub ink_bleed_sigma: Random,
+    pub ink_bleed_noise: Random,
+    // occlusion patches and scratches
+    pub occlusion_prob: f64,
+    pub occlusion_count: Random,
+    pub occlusion_thickness: Random,
+    pub occlusion_intensity: Random,
+    // table lines around the text
+    pub table_line_prob: f64,
+    pub table_line_spacing: Random,
+    pub table_line_thickness: Random,
+    pub table_line_intensity: Random,
     // draw box
     pub box_prob: f64,
     // perspective transform
@@ -33,6 +232,27 @@ pub struct Config {
     pub filter_prob: f64,
     pub emboss_prob: f64,
     pub sharp_prob: f64,
+    // color jitter of RGB images
+    pub color_jitter_prob: f64,
+    pub hue_shift: Random,
+    pub saturation_scale: Random,
+    pub value_scale: Random,
+    // book-spine curvature of the merged image
+    pub curvature_prob: f64,
+    pub curvature_strength: Random,
+    // sine wave warp of the merged image
+    pub wave_prob: f64,
+    pub wave_amplitude: Random,
+    pub wave_wavelength: Random,
+    pub wave_phase: Random,
+    pub wave_rows_prob: f64,
+    // sensor noise of the merged image
+    pub gaussian_noise_prob: f64,
+    pub gaussian_noise_sigma: Random,
+    pub salt_pepper_prob: f64,
+    pub salt_pepper_amount: Random,
+    pub speckle_noise_prob: f64,
+    pub speckle_noise_sigma: Random,
     // 3. merge_util
     pub bg_dir: String,
     pub bg_height: usize,
@@ -42,6 +262,14 @@ pub struct Config {
     pub bg_beta: Random,
     pub font_alpha: Random,
     pub reverse_prob: f64,
+    pub bg_blend_prob: f64,
+    pub bg_blend_alpha: Random,
+    // base of the backgrounds with transparency: a gray level, or a second background with
+    // bg_base_bg_prob
+    pub bg_base_color: Random,
+    pub bg_base_bg_prob: f64,
+    // merge onto color backgrounds, the merged images are RGB
+    pub color_merge: bool,
 }
 
 impl Default for Config {
@@ -49,13 +277,127 @@ impl Default for Config {
         Config {
             font_dir: "./font".to_string(),
             chinese_ch_file_path: "./ch.txt".to_string(),
+            freq_corpus_file_path: "".to_string(),
+            freq_smoothing: 1.0,
+            ngram_corpus_file_path: "".to_string(),
+            ngram_order: default_ngram_order(),
+            sentence_file_path: "".to_string(),
+            sentence_substring: true,
+            script_conversion_file_path: "".to_string(),
+            script: default_script(),
+            coverage_cache_path: "".to_string(),
+            strict_coverage: false,
             main_font_list_file_path: "./symbol.txt".to_string(),
             latin_corpus_file_path: "".to_string(),
+            rtl_corpus_file_path: "".to_string(),
             symbol_file_path: "".to_string(),
+            symbol_count: default_symbol_count(),
+            symbol_prob: 0.0,
+            symbol_at_start: false,
+            symbol_at_end: false,
+            word_dict_file_path: "".to_string(),
+            include_ranges: vec![],
+            exclude_ranges: vec![],
+            include_regex: "".to_string(),
+            exclude_regex: "".to_string(),
+            exclude_chars_file_path: "".to_string(),
+            charset_file_path: "".to_string(),
+            confusable_file_path: "".to_string(),
+            label_noise_prob: 0.0,
+            wrap_dict_order: default_wrap_dict_order(),
+            mixed_pool_ratios: default_mixed_pool_ratios(),
+            corpus_sources: vec![],
+            inverse_frequency: false,
+            balanced_min_count: 0,
+            sampling_temperature: 1.0,
             font_size: 50,
             line_height: 64,
             font_img_width: 2000,
+            auto_width: false,
+            max_font_img_width: 8192,
+            line_height_range: None,
+            font_size_range: None,
+            padding_top: Random::new_uniform(0.0, 0.0),
+            padding_bottom: Random::new_uniform(0.0, 0.0),
+            padding_left: Random::new_uniform(0.0, 0.0),
+            padding_right: Random::new_uniform(0.0, 0.0),
+            tight_crop: false,
             font_img_height: 64,
+            vertical: false,
+            tcy_max_len: 2,
+            column_spacing: 8,
+            vertical_rotate: false,
+            opentype_features: vec![],
+            font_opentype_features: HashMap::new(),
+            font_weights_file_path: "".to_string(),
+            font_weights: HashMap::new(),
+            label_normalization: "none".to_string(),
+            fold_full_width: false,
+            space_prob: 0.1,
+            space_run_len: (1, 4),
+            indent_prob: 0.0,
+            indent_len: (2, 4),
+            collapse_label_spaces: false,
+            ligatures: true,
+            kerning: true,
+            kerning_jitter: 0.0,
+            glyph_size_jitter: 0.0,
+            ink_texture_strength: 0.0,
+            ink_texture_scale: 8.0,
+            faux_bold_prob: 0.0,
+            faux_bold_width: default_faux_bold_width(),
+            faux_italic_prob: 0.0,
+            faux_italic_slant: default_faux_italic_slant(),
+            letter_spacing: Random::new_uniform(0.0, 0.0),
+            word_spacing: Random::new_uniform(0.0, 0.0),
+            baseline_jitter: None,
+            glyph_rotation: None,
+            arc_text_prob: 0.0,
+            arc_text_bend: default_arc_text_bend().to_random(),
+            wave_text_prob: 0.0,
+            wave_text_amplitude: default_wave_text_amplitude().to_random(),
+            wave_text_period: default_wave_text_period().to_random(),
+            drop_shadow_prob: 0.0,
+            drop_shadow_offset: default_drop_shadow_offset().to_random(),
+            drop_shadow_sigma: default_drop_shadow_sigma().to_random(),
+            drop_shadow_opacity: default_drop_shadow_opacity().to_random(),
+            underline_prob: 0.0,
+            strikethrough_prob: 0.0,
+            decoration_styles: default_decoration_styles(),
+            decoration_thickness: default_decoration_thickness().to_random(),
+            image_glyph_dir: "".to_string(),
+            image_glyph_prob: 1.0,
+            char_color_prob: 0.0,
+            char_color_run_len: (1, 3),
+            char_color_palette: vec![],
+            char_color_hue: Random::new_uniform(0.0, 360.0),
+            color_prob: 0.0,
+            text_color_palette: vec![],
+            text_color_hue: Random::new_uniform(0.0, 360.0),
+            text_color_saturation: Random::new_uniform(0.0, 1.0),
+            text_color_value: Random::new_uniform(0.0, 0.35),
+            background_color_palette: vec![],
+            background_color_hue: Random::new_uniform(0.0, 360.0),
+            background_color_saturation: Random::new_uniform(0.0, 0.25),
+            background_color_value: Random::new_uniform(0.75, 1.0),
+            min_contrast: 4.5,
+            render_backend: "cpu".to_string(),
+            erode_prob: 0.0,
+            erode_kernel: default_morph_kernel().to_random(),
+            dilate_prob: 0.0,
+            dilate_kernel: default_morph_kernel().to_random(),
+            ink_bleed_prob: 0.0,
+            ink_bleed_spread: default_ink_bleed_spread().to_random(),
+            ink_bleed_sigma: default_ink_bleed_sigma().to_random(),
+            ink_bleed_noise: default_ink_bleed_noise().to_random(),
+            occlusion_prob: 0.0,
+            occlusion_count: default_occlusion_count().to_random(),
+            occlusion_thickness: default_occlusion_thickness().to_random(),
+            occlusion_intensity: default_occlusion_intensity().to_random(),
+            table_line_prob: 0.0,
+            table_line_spacing: default_table_line_spacing().to_random(),
+            table_line_thickness: default_table_line_thickness().to_random(),
+            table_line_intensity: default_table_line_intensity().to_random(),
             box_prob: 0.1,
             perspective_prob: 0.2,
             perspective_x: Random::new_gaussian(-15.0, 15.0),
@@ -66,6 +408,23 @@ impl Default for Config {
             filter_prob: 0.01,
             emboss_prob: 0.4,
             sharp_prob: 0.6,
+            color_jitter_prob: 0.0,
+            hue_shift: Random::new_uniform(-10.0, 10.0),
+            saturation_scale: Random::new_uniform(0.8, 1.2),
+            value_scale: Random::new_uniform(0.8, 1.2),
+            curvature_prob: 0.0,
+            curvature_strength: Random::new_uniform(0.1, 0.4),
+            wave_prob: 0.0,
+            wave_amplitude: default_wave_amplitude().to_random(),
+            wave_wavelength: default_wave_wavelength().to_random(),
+            wave_phase: default_wave_phase().to_random(),
+            wave_rows_prob: 0.0,
+            gaussian_noise_prob: 0.0,
+            gaussian_noise_sigma: default_gaussian_noise_sigma().to_random(),
+            salt_pepper_prob: 0.0,
+            salt_pepper_amount: default_salt_pepper_amount().to_random(),
+            speckle_noise_prob: 0.0,
+            speckle_noise_sigma: default_speckle_noise_sigma().to_random(),
             bg_dir: "./synth_text/background".to_string(),
             bg_height: 64,
             bg_width: 1000,
@@ -74,6 +433,11 @@ impl Default for Config {
             bg_beta: Random::new_gaussian(-50.0, 50.0),
             font_alpha: Random::new_uniform(0.2, 1.0),
             reverse_prob: 0.5,
+            bg_blend_prob: 0.0,
+            bg_blend_alpha: Random::new_uniform(0.3, 0.7),
+            bg_base_color: Random::new_uniform(255.0, 255.0),
+            bg_base_bg_prob: 0.0,
+            color_merge: false,
         }
     }
 }
@@ -82,15 +446,377 @@ impl Default for Config {
 struct FontYaml {
     font_dir: String,
     chinese_ch_file_path: String,
+    #[serde(default)]
+    freq_corpus_file_path: String,
+    #[serde(default = "default_freq_smoothing")]
+    freq_smoothing: f64,
+    #[serde(default)]
+    ngram_corpus_file_path: String,
+    #[serde(default = "default_ngram_order")]
+    ngram_order: usize,
+    #[serde(default)]
+    sentence_file_path: String,
+    #[serde(default = "default_true")]
+    sentence_substring: bool,
+    #[serde(default)]
+    script_conversion_file_path: String,
+    #[serde(default = "default_script")]
+    script: String,
+    #[serde(default)]
+    coverage_cache_path: String,
+    #[serde(default)]
+    strict_coverage: bool,
     main_font_list_file_path: String,
     #[serde(default)]
     latin_corpus_file_path: String,
     #[serde(default)]
+    rtl_corpus_file_path: String,
+    #[serde(default)]
     symbol_file_path: String,
+    #[serde(default = "default_symbol_count")]
+    symbol_count: (u32, u32),
+    #[serde(default)]
+    symbol_prob: f64,
+    #[serde(default)]
+    symbol_at_start: bool,
+    #[serde(default)]
+    symbol_at_end: bool,
+    #[serde(default)]
+    word_dict_file_path: String,
+    #[serde(default)]
+    include_ranges: Vec<String>,
+    #[serde(default)]
+    exclude_ranges: Vec<String>,
+    #[serde(default)]
+    include_regex: String,
+    #[serde(default)]
+    exclude_regex: String,
+    #[serde(default)]
+    exclude_chars_file_path: String,
+    #[serde(default)]
+    charset_file_path: String,
+    #[serde(default)]
+    confusable_file_path: String,
+    #[serde(default)]
+    label_noise_prob: f64,
+    #[serde(default = "default_wrap_dict_order")]
+    wrap_dict_order: Vec<String>,
+    #[serde(default = "default_mixed_pool_ratios")]
+    mixed_pool_ratios: (f64, f64, f64),
+    #[serde(default)]
+    corpus_sources: Vec<CorpusSourceConfig>,
+    #[serde(default)]
+    inverse_frequency: bool,
+    #[serde(default)]
+    balanced_min_count: u32,
+    #[serde(default = "default_sampling_temperature")]
+    sampling_temperature: f64,
     font_size: usize,
     line_height: usize,
     font_img_height: usize,
     font_img_width: usize,
+    #[serde(default)]
+    auto_width: bool,
+    #[serde(default = "default_max_font_img_width")]
+    max_font_img_width: usize,
+    #[serde(default)]
+    line_height_range: Option<RandomYaml>,
+    #[serde(default)]
+    font_size_range: Option<RandomYaml>,
+    #[serde(default = "default_padding")]
+    padding_top: RandomYaml,
+    #[serde(default = "default_padding")]
+    padding_bottom: RandomYaml,
+    #[serde(default = "default_padding")]
+    padding_left: RandomYaml,
+    #[serde(default = "default_padding")]
+    padding_right: RandomYaml,
+    #[serde(default)]
+    tight_crop: bool,
+    #[serde(default)]
+    vertical: bool,
+    #[serde(default = "default_tcy_max_len")]
+    tcy_max_len: usize,
+    #[serde(default = "default_column_spacing")]
+    column_spacing: usize,
+    #[serde(default)]
+    vertical_rotate: bool,
+    #[serde(default)]
+    opentype_features: Vec<String>,
+    #[serde(default)]
+    font_opentype_features: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    font_weights_file_path: String,
+    #[serde(default)]
+    font_weights: HashMap<String, f64>,
+    #[serde(default = "default_label_normalization")]
+    label_normalization: String,
+    #[serde(default)]
+    fold_full_width: bool,
+    #[serde(default = "default_space_prob")]
+    space_prob: f64,
+    #[serde(default = "default_space_run_len")]
+    space_run_len: (u32, u32),
+    #[serde(default)]
+    indent_prob: f64,
+    #[serde(default = "default_indent_len")]
+    indent_len: (u32, u32),
+    #[serde(default)]
+    collapse_label_spaces: bool,
+    #[serde(default = "default_true")]
+    ligatures: bool,
+    #[serde(default = "default_true")]
+    kerning: bool,
+    #[serde(default)]
+    kerning_jitter: f64,
+    #[serde(default)]
+    glyph_size_jitter: f64,
+    #[serde(default)]
+    ink_texture_strength: f32,
+    #[serde(default = "default_ink_texture_scale")]
+    ink_texture_scale: f32,
+    #[serde(default)]
+    faux_bold_prob: f64,
+    #[serde(default = "default_faux_bold_width")]
+    faux_bold_width: f32,
+    #[serde(default)]
+    faux_italic_prob: f64,
+    #[serde(default = "default_faux_italic_slant")]
+    faux_italic_slant: f32,
+    #[serde(default = "default_spacing")]
+    letter_spacing: RandomYaml,
+    #[serde(default = "default_spacing")]
+    word_spacing: RandomYaml,
+    #[serde(default)]
+    baseline_jitter: Option<RandomYaml>,
+    #[serde(default)]
+    glyph_rotation: Option<RandomYaml>,
+    #[serde(default)]
+    arc_text_prob: f64,
+    #[serde(default = "default_arc_text_bend")]
+    arc_text_bend: RandomYaml,
+    #[serde(default)]
+    wave_text_prob: f64,
+    #[serde(default = "default_wave_text_amplitude")]
+    wave_text_amplitude: RandomYaml,
+    #[serde(default = "default_wave_text_period")]
+    wave_text_period: RandomYaml,
+    #[serde(default)]
+    drop_shadow_prob: f64,
+    #[serde(default = "default_drop_shadow_offset")]
+    drop_shadow_offset: RandomYaml,
+    #[serde(default = "default_drop_shadow_sigma")]
+    drop_shadow_sigma: RandomYaml,
+    #[serde(default = "default_drop_shadow_opacity")]
+    drop_shadow_opacity: RandomYaml,
+    #[serde(default)]
+    underline_prob: f64,
+    #[serde(default)]
+    strikethrough_prob: f64,
+    #[serde(default = "default_decoration_styles")]
+    decoration_styles: Vec<String>,
+    #[serde(default = "default_decoration_thickness")]
+    decoration_thickness: RandomYaml,
+    #[serde(default)]
+    image_glyph_dir: String,
+    #[serde(default = "default_image_glyph_prob")]
+    image_glyph_prob: f64,
+    #[serde(default)]
+    char_color_prob: f64,
+    #[serde(default = "default_char_color_run_len")]
+    char_color_run_len: (u32, u32),
+    #[serde(default)]
+    char_color_palette: Vec<(u8, u8, u8)>,
+    #[serde(default = "default_char_color_hue")]
+    char_color_hue: RandomYaml,
+    #[serde(default)]
+    color_prob: f64,
+    #[serde(default)]
+    text_color_palette: Vec<(u8, u8, u8)>,
+    #[serde(default = "default_char_color_hue")]
+    text_color_hue: RandomYaml,
+    #[serde(default = "default_text_color_saturation")]
+    text_color_saturation: RandomYaml,
+    #[serde(default = "default_text_color_value")]
+    text_color_value: RandomYaml,
+    #[serde(default)]
+    background_color_palette: Vec<(u8, u8, u8)>,
+    #[serde(default = "default_char_color_hue")]
+    background_color_hue: RandomYaml,
+    #[serde(default = "default_background_color_saturation")]
+    background_color_saturation: RandomYaml,
+    #[serde(default = "default_background_color_value")]
+    background_color_value: RandomYaml,
+    #[serde(default = "default_min_contrast")]
+    min_contrast: f64,
+    #[serde(default = "default_render_backend")]
+    render_backend: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_wrap_dict_order() -> Vec<String> {
+    vec![
+        "chinese".to_string(),
+        "latin".to_string(),
+        "symbol".to_string(),
+    ]
+}
+
+fn default_freq_smoothing() -> f64 {
+    1.0
+}
+
+fn default_symbol_count() -> (u32, u32) {
+    (1, 1)
+}
+
+fn default_script() -> String {
+    "none".to_string()
+}
+
+fn default_ngram_order() -> usize {
+    2
+}
+
+fn default_mixed_pool_ratios() -> (f64, f64, f64) {
+    (0.8, 0.15, 0.05)
+}
+
+fn default_label_normalization() -> String {
+    "none".to_string()
+}
+
+fn default_space_prob() -> f64 {
+    0.1
+}
+
+fn default_space_run_len() -> (u32, u32) {
+    (1, 4)
+}
+
+fn default_indent_len() -> (u32, u32) {
+    (2, 4)
+}
+
+fn default_sampling_temperature() -> f64 {
+    1.0
+}
+
+fn default_ink_texture_scale() -> f32 {
+    8.0
+}
+
+fn default_faux_bold_width() -> f32 {
+    0.03
+}
+
+fn default_faux_italic_slant() -> f32 {
+    0.2
+}
+
+fn default_image_glyph_prob() -> f64 {
+    1.0
+}
+
+fn default_char_color_run_len() -> (u32, u32) {
+    (1, 3)
+}
+
+fn default_char_color_hue() -> RandomYaml {
+    RandomYaml(0.0, 360.0, "u".to_string())
+}
+
+fn default_text_color_saturation() -> RandomYaml {
+    RandomYaml(0.0, 1.0, "u".to_string())
+}
+
+fn default_text_color_value() -> RandomYaml {
+    RandomYaml(0.0, 0.35, "u".to_string())
+}
+
+fn default_background_color_saturation() -> RandomYaml {
+    RandomYaml(0.0, 0.25, "u".to_string())
+}
+
+fn default_background_color_value() -> RandomYaml {
+    RandomYaml(0.75, 1.0, "u".to_string())
+}
+
+fn default_min_contrast() -> f64 {
+    4.5
+}
+
+fn default_padding() -> RandomYaml {
+    RandomYaml(0.0, 0.0, "u".to_string())
+}
+
+fn default_spacing() -> RandomYaml {
+    RandomYaml(0.0, 0.0, "u".to_string())
+}
+
+fn default_arc_text_bend() -> RandomYaml {
+    RandomYaml(-0.3, 0.3, "u".to_string())
+}
+
+fn default_wave_text_amplitude() -> RandomYaml {
+    RandomYaml(0.1, 0.3, "u".to_string())
+}
+
+fn default_wave_text_period() -> RandomYaml {
+    RandomYaml(4.0, 8.0, "u".to_string())
+}
+
+fn default_drop_shadow_offset() -> RandomYaml {
+    RandomYaml(0.03, 0.08, "u".to_string())
+}
+
+fn default_drop_shadow_sigma() -> RandomYaml {
+    RandomYaml(0.0, 0.05, "u".to_string())
+}
+
+fn default_drop_shadow_opacity() -> RandomYaml {
+    RandomYaml(0.3, 0.7, "u".to_string())
+}
+
+fn default_decoration_styles() -> Vec<String> {
+    vec![
+        "solid".to_string(),
+        "dashed".to_string(),
+        "wavy".to_string(),
+    ]
+}
+
+fn default_decoration_thickness() -> RandomYaml {
+    RandomYaml(1.0, 1.5, "u".to_string())
+}
+
+fn default_render_backend() -> String {
+    "cpu".to_string()
+}
+
+fn default_max_font_img_width() -> usize {
+    8192
+}
+
+fn default_tcy_max_len() -> usize {
+    2
+}
+
+fn default_column_spacing() -> usize {
+    8
+}
+
+/// A corpus source of `get_random_sample`: `chinese`, `mixed`, `latin` or `sentence` (one
+/// sentence per line in `path`).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CorpusSourceConfig {
+    pub kind: String,
+    #[serde(default)]
+    pub path: String,
+    pub weight: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -110,6 +836,38 @@ impl RandomYaml {
 
 #[derive(Serialize, Deserialize, Debug)]
 struct CvYaml {
+    #[serde(default)]
+    erode_prob: f64,
+    #[serde(default = "default_morph_kernel")]
+    erode_kernel: RandomYaml,
+    #[serde(default)]
+    dilate_prob: f64,
+    #[serde(default = "default_morph_kernel")]
+    dilate_kernel: RandomYaml,
+    #[serde(default)]
+    ink_bleed_prob: f64,
+    #[serde(default = "default_ink_bleed_spread")]
+    ink_bleed_spread: RandomYaml,
+    #[serde(default = "default_ink_bleed_sigma")]
+    ink_bleed_sigma: RandomYaml,
+    #[serde(default = "default_ink_bleed_noise")]
+    ink_bleed_noise: RandomYaml,
+    #[serde(default)]
+    occlusion_prob: f64,
+    #[serde(default = "default_occlusion_count")]
+    occlusion_count: RandomYaml,
+    #[serde(default = "default_occlusion_thickness")]
+    occlusion_thickness: RandomYaml,
+    #[serde(default = "default_occlusion_intensity")]
+    occlusion_intensity: RandomYaml,
+    #[serde(default)]
+    table_line_prob: f64,
+    #[serde(default = "default_table_line_spacing")]
+    table_line_spacing: RandomYaml,
+    #[serde(default = "default_table_line_thickness")]
+    table_line_thickness: RandomYaml,
+    #[serde(default = "default_table_line_intensity")]
+    table_line_intensity: RandomYaml,
     box_prob: f64,
     perspective_prob: f64,
     perspective_x: RandomYaml,
@@ -120,6 +878,116 @@ struct CvYaml {
     filter_prob: f64,
     emboss_prob: f64,
     sharp_prob: f64,
+    #[serde(default)]
+    color_jitter_prob: f64,
+    #[serde(default = "default_hue_shift")]
+    hue_shift: RandomYaml,
+    #[serde(default = "default_hsv_scale")]
+    saturation_scale: RandomYaml,
+    #[serde(default = "default_hsv_scale")]
+    value_scale: RandomYaml,
+    #[serde(default)]
+    curvature_prob: f64,
+    #[serde(default = "default_curvature_strength")]
+    curvature_strength: RandomYaml,
+    #[serde(default)]
+    wave_prob: f64,
+    #[serde(default = "default_wave_amplitude")]
+    wave_amplitude: RandomYaml,
+    #[serde(default = "default_wave_wavelength")]
+    wave_wavelength: RandomYaml,
+    #[serde(default = "default_wave_phase")]
+    wave_phase: RandomYaml,
+    #[serde(default)]
+    wave_rows_prob: f64,
+    #[serde(default)]
+    gaussian_noise_prob: f64,
+    #[serde(default = "default_gaussian_noise_sigma")]
+    gaussian_noise_sigma: RandomYaml,
+    #[serde(default)]
+    salt_pepper_prob: f64,
+    #[serde(default = "default_salt_pepper_amount")]
+    salt_pepper_amount: RandomYaml,
+    #[serde(default)]
+    speckle_noise_prob: f64,
+    #[serde(default = "default_speckle_noise_sigma")]
+    speckle_noise_sigma: RandomYaml,
+}
+
+fn default_morph_kernel() -> RandomYaml {
+    RandomYaml(2.0, 3.0, "u".to_string())
+}
+
+fn default_ink_bleed_spread() -> RandomYaml {
+    RandomYaml(0.0, 1.0, "u".to_string())
+}
+
+fn default_ink_bleed_sigma() -> RandomYaml {
+    RandomYaml(0.5, 1.0, "u".to_string())
+}
+
+fn default_ink_bleed_noise() -> RandomYaml {
+    RandomYaml(10.0, 40.0, "u".to_string())
+}
+
+fn default_occlusion_count() -> RandomYaml {
+    RandomYaml(1.0, 3.0, "u".to_string())
+}
+
+fn default_occlusion_thickness() -> RandomYaml {
+    RandomYaml(1.0, 3.0, "u".to_string())
+}
+
+fn default_occlusion_intensity() -> RandomYaml {
+    RandomYaml(0.0, 255.0, "u".to_string())
+}
+
+fn default_table_line_spacing() -> RandomYaml {
+    RandomYaml(2.0, 8.0, "u".to_string())
+}
+
+fn default_table_line_thickness() -> RandomYaml {
+    RandomYaml(1.0, 2.0, "u".to_string())
+}
+
+fn default_table_line_intensity() -> RandomYaml {
+    RandomYaml(0.0, 150.0, "u".to_string())
+}
+
+fn default_hue_shift() -> RandomYaml {
+    RandomYaml(-10.0, 10.0, "u".to_string())
+}
+
+fn default_hsv_scale() -> RandomYaml {
+    RandomYaml(0.8, 1.2, "u".to_string())
+}
+
+fn default_curvature_strength() -> RandomYaml {
+    RandomYaml(0.1, 0.4, "u".to_string())
+}
+
+fn default_wave_amplitude() -> RandomYaml {
+    RandomYaml(0.02, 0.08, "u".to_string())
+}
+
+fn default_wave_wavelength() -> RandomYaml {
+    RandomYaml(0.5, 2.0, "u".to_string())
+}
+
+fn default_wave_phase() -> RandomYaml {
+    RandomYaml(0.0, 360.0, "u".to_string())
+}
+
+fn default_gaussian_noise_sigma() -> RandomYaml {
+    RandomYaml(2.0, 10.0, "u".to_string())
+}
+
+fn default_salt_pepper_amount() -> RandomYaml {
+    RandomYaml(0.001, 0.01, "u".to_string())
+}
+
+fn default_speckle_noise_sigma() -> RandomYaml {
+    RandomYaml(0.02, 0.1, "u".to_string())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -133,6 +1001,24 @@ struct MergeYaml {
     pub bg_beta: RandomYaml,
     pub font_alpha: RandomYaml,
     pub reverse_prob: f64,
+    #[serde(default)]
+    pub bg_blend_prob: f64,
+    #[serde(default = "default_bg_blend_alpha")]
+    pub bg_blend_alpha: RandomYaml,
+    #[serde(default = "default_bg_base_color")]
+    pub bg_base_color: RandomYaml,
+    #[serde(default)]
+    pub bg_base_bg_prob: f64,
+    #[serde(default)]
+    pub color_merge: bool,
+}
+
+fn default_bg_blend_alpha() -> RandomYaml {
+    RandomYaml(0.3, 0.7, "u".to_string())
+}
+
+fn default_bg_base_color() -> RandomYaml {
+    RandomYaml(255.0, 255.0, "u".to_string())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -143,22 +1029,337 @@ struct GeneratorConfigYaml {
     merge: MergeYaml,
 }
 
+/// A key of the config replaced after the YAML is parsed, e.g. `MERGE.reverse_prob`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigOverride {
+    pub section: String,
+    pub key: String,
+    pub value: serde_yaml::Value,
+}
+
+impl ConfigOverride {
+    /// `path` is `SECTION.key`, the section is case-insensitive.
+    pub fn new(path: &str, value: serde_yaml::Value) -> Result<Self, String> {
+        match path.split_once('.') {
+            Some((section, key)) if !section.is_empty() && !key.is_empty() => Ok(Self {
+                section: section.to_uppercase(),
+                key: key.to_string(),
+                value,
+            }),
+            _ => Err(format!(
+                "config override `{}` should be like `MERGE.reverse_prob`",
+                path
+            )),
+        }
+    }
+
+    /// The value is read as YAML, so `0.2` is a number and `[1, 4]` a list; anything else is
+    /// kept as a string.
+    pub fn parse(path: &str, value: &str) -> Result<Self, String> {
+        let value = serde_yaml::from_str(value)
+            .unwrap_or_else(|_| serde_yaml::Value::String(value.to_string()));
+        Self::new(path, value)
+    }
+
+    /// The overrides given by the environment variables `TIG_<SECTION>__<KEY>`, e.g.
+    /// `TIG_MERGE__REVERSE_PROB=0.2`.
+    pub fn from_env() -> Vec<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    fn from_vars<I: IntoIterator<Item = (String, String)>>(vars: I) -> Vec<Self> {
+        vars.into_iter()
+            .filter_map(|(name, value)| {
+                let (section, key) = name.strip_prefix("TIG_")?.split_once("__")?;
+                let path = format!("{}.{}", section, key.to_lowercase());
+                Self::parse(&path, &value).ok()
+            })
+            .collect()
+    }
+}
+
+/// Built-in effect parameters for `Generator.set_difficulty`, from clean renders to heavily
+/// degraded ones. Each level is given like a profile, as keys of the CV and MERGE sections.
+const DIFFICULTY_PRESETS: [(&str, &str); 3] = [
+    (
+        "easy",
+        r#"
+CV: { box_prob: 0.0, perspective_prob: 0.0, blur_prob: 0.0, filter_prob: 0.0,
+      curvature_prob: 0.0 }
+MERGE: { font_alpha: [0.8, 1.0, "u"], reverse_prob: 0.0, bg_blend_prob: 0.0 }
+"#,
+    ),
+    (
+        "medium",
+        r#"
+CV: { box_prob: 0.1, perspective_prob: 0.2, perspective_x: [-15.0, 15.0, "g"],
+      perspective_y: [-15.0, 15.0, "g"], perspective_z: [-3.0, 3.0, "g"], blur_prob: 0.1,
+      blur_sigma: [0.0, 1.5, "u"], filter_prob: 0.2, curvature_prob: 0.05 }
+MERGE: { font_alpha: [0.4, 1.0, "u"], reverse_prob: 0.3, bg_blend_prob: 0.1 }
+"#,
+    ),
+    (
+        "hard",
+        r#"
+CV: { box_prob: 0.3, perspective_prob: 0.5, perspective_x: [-30.0, 30.0, "g"],
+      perspective_y: [-30.0, 30.0, "g"], perspective_z: [-6.0, 6.0, "g"], blur_prob: 0.4,
+      blur_sigma: [0.5, 2.5, "u"], filter_prob: 0.4, curvature_prob: 0.2 }
+MERGE: { font_alpha: [0.2, 0.8, "u"], reverse_prob: 0.5, bg_blend_prob: 0.5 }
+"#,
+    ),
+];
+
+/// The names of the built-in difficulty levels, from the easiest.
+pub fn difficulty_levels() -> Vec<&'static str> {
+    DIFFICULTY_PRESETS.iter().map(|(level, _)| *level).collect()
+}
+
+/// The overrides of a built-in difficulty level.
+pub fn difficulty_preset(level: &str) -> Result<Vec<ConfigOverride>, String> {
+    let (_, preset) = DIFFICULTY_PRESETS
+        .iter()
+        .find(|(name, _)| *name == level)
+        .ok_or_else(|| {
+            format!(
+                "unknown difficulty `{}`, expected one of {}",
+                level,
+                difficulty_levels().join(", ")
+            )
+        })?;
+    let sections: serde_yaml::Mapping =
+        serde_yaml::from_str(preset).expect("the built-in presets are valid YAML");
+
+    let mut res = vec![];
+    for (section, keys) in sections {
+        let section = section.as_str().unwrap_or_default();
+        for (key, value) in keys.as_mapping().into_iter().flatten() {
+            res.push(ConfigOverride {
+                section: section.to_string(),
+                key: key.as_str().unwrap_or_default().to_string(),
+                value: value.clone(),
+            });
+        }
+    }
+
+    Ok(res)
+}
+
 impl Config {
+    /// Read a config file, with the overrides of the environment variables applied.
     pub fn from_yaml<P: AsRef<Path>>(path: P) -> Config {
-        let yaml_str = fs::read_to_string(path).expect("the config file does not exist");
+        Self::from_yaml_with(path, None, &[]).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn from_yaml_str(yaml_str: &str) -> Config {
         let yaml: GeneratorConfigYaml =
-            serde_yaml::from_str(&yaml_str).expect("fail to parse config file");
+            serde_yaml::from_str(yaml_str).expect("fail to parse config file");
+
+        Self::from_parsed(yaml)
+    }
 
+    /// Read a config file with a profile, the environment variables (see
+    /// `ConfigOverride::from_env`) and then `overrides` applied, see `from_yaml_str_with`.
+    pub fn from_yaml_with<P: AsRef<Path>>(
+        path: P,
+        profile: Option<&str>,
+        overrides: &[ConfigOverride],
+    ) -> Result<Config, String> {
+        let yaml_str = fs::read_to_string(&path).map_err(|err| {
+            format!(
+                "fail to read the config file `{}`: {}",
+                path.as_ref().display(),
+                err
+            )
+        })?;
+        let mut all_overrides = ConfigOverride::from_env();
+        all_overrides.extend_from_slice(overrides);
+        Self::from_yaml_str_with(&yaml_str, profile, &all_overrides)
+    }
+
+    /// A config file may define named profiles under `PROFILES`, each replacing some keys of the
+    /// CV and MERGE sections, while the FONT section is shared by every profile. `None` uses the
+    /// sections as they are. The overrides are applied after the profile, in order; a key that
+    /// the config does not have is an error, so that a typo in a sweep does not go unnoticed.
+    pub fn from_yaml_str_with(
+        yaml_str: &str,
+        profile: Option<&str>,
+        overrides: &[ConfigOverride],
+    ) -> Result<Config, String> {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(yaml_str)
+            .map_err(|err| format!("fail to parse config file: {}", err))?;
+        if let Some(profile) = profile {
+            apply_profile(&mut value, profile)?;
+        }
+        for each in overrides {
+            value
+                .get_mut(&each.section)
+                .and_then(serde_yaml::Value::as_mapping_mut)
+                .ok_or_else(|| format!("the config file has no `{}` section", each.section))?
+                .insert(each.key.clone().into(), each.value.clone());
+        }
+        let yaml: GeneratorConfigYaml = serde_yaml::from_value(value)
+            .map_err(|err| format!("fail to parse config file: {}", err))?;
+
+        if !overrides.is_empty() {
+            // the keys that are not fields of the sections are ignored by serde
+            let known = serde_yaml::to_value(&yaml).map_err(|err| err.to_string())?;
+            for each in overrides {
+                if known
+                    .get(&each.section)
+                    .and_then(|section| section.get(&each.key))
+                    .is_none()
+                {
+                    return Err(format!(
+                        "unknown config key `{}.{}`",
+                        each.section, each.key
+                    ));
+                }
+            }
+        }
+
+        Ok(Self::from_parsed(yaml))
+    }
+
+    /// The names of the profiles defined in a config file.
+    pub fn profile_names(yaml_str: &str) -> Vec<String> {
+        let value: serde_yaml::Value = serde_yaml::from_str(yaml_str).unwrap_or_default();
+        value
+            .get("PROFILES")
+            .and_then(serde_yaml::Value::as_mapping)
+            .map(|profiles| {
+                profiles
+                    .keys()
+                    .filter_map(|key| key.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn from_parsed(yaml: GeneratorConfigYaml) -> Config {
         Config {
             font_dir: yaml.font.font_dir,
             chinese_ch_file_path: yaml.font.chinese_ch_file_path,
+            freq_corpus_file_path: yaml.font.freq_corpus_file_path,
+            freq_smoothing: yaml.font.freq_smoothing,
+            ngram_corpus_file_path: yaml.font.ngram_corpus_file_path,
+            ngram_order: yaml.font.ngram_order,
+            sentence_file_path: yaml.font.sentence_file_path,
+            sentence_substring: yaml.font.sentence_substring,
+            script_conversion_file_path: yaml.font.script_conversion_file_path,
+            script: yaml.font.script,
+            coverage_cache_path: yaml.font.coverage_cache_path,
+            strict_coverage: yaml.font.strict_coverage,
             main_font_list_file_path: yaml.font.main_font_list_file_path,
             latin_corpus_file_path: yaml.font.latin_corpus_file_path,
+            rtl_corpus_file_path: yaml.font.rtl_corpus_file_path,
             symbol_file_path: yaml.font.symbol_file_path,
+            symbol_count: yaml.font.symbol_count,
+            symbol_prob: yaml.font.symbol_prob,
+            symbol_at_start: yaml.font.symbol_at_start,
+            symbol_at_end: yaml.font.symbol_at_end,
+            word_dict_file_path: yaml.font.word_dict_file_path,
+            include_ranges: yaml.font.include_ranges,
+            exclude_ranges: yaml.font.exclude_ranges,
+            include_regex: yaml.font.include_regex,
+            exclude_regex: yaml.font.exclude_regex,
+            exclude_chars_file_path: yaml.font.exclude_chars_file_path,
+            charset_file_path: yaml.font.charset_file_path,
+            confusable_file_path: yaml.font.confusable_file_path,
+            label_noise_prob: yaml.font.label_noise_prob,
+            wrap_dict_order: yaml.font.wrap_dict_order,
+            mixed_pool_ratios: yaml.font.mixed_pool_ratios,
+            corpus_sources: yaml.font.corpus_sources,
+            inverse_frequency: yaml.font.inverse_frequency,
+            balanced_min_count: yaml.font.balanced_min_count,
+            sampling_temperature: yaml.font.sampling_temperature,
             font_size: yaml.font.font_size,
             line_height: yaml.font.line_height,
             font_img_width: yaml.font.font_img_width,
+            auto_width: yaml.font.auto_width,
+            max_font_img_width: yaml.font.max_font_img_width,
+            line_height_range: yaml.font.line_height_range.map(|each| each.to_random()),
+            font_size_range: yaml.font.font_size_range.map(|each| each.to_random()),
+            padding_top: yaml.font.padding_top.to_random(),
+            padding_bottom: yaml.font.padding_bottom.to_random(),
+            padding_left: yaml.font.padding_left.to_random(),
+            padding_right: yaml.font.padding_right.to_random(),
+            tight_crop: yaml.font.tight_crop,
             font_img_height: yaml.font.font_img_height,
+            vertical: yaml.font.vertical,
+            tcy_max_len: yaml.font.tcy_max_len,
+            column_spacing: yaml.font.column_spacing,
+            vertical_rotate: yaml.font.vertical_rotate,
+            opentype_features: yaml.font.opentype_features,
+            font_opentype_features: yaml.font.font_opentype_features,
+            font_weights_file_path: yaml.font.font_weights_file_path,
+            font_weights: yaml.font.font_weights,
+            label_normalization: yaml.font.label_normalization,
+            fold_full_width: yaml.font.fold_full_width,
+            space_prob: yaml.font.space_prob,
+            space_run_len: yaml.font.space_run_len,
+            indent_prob: yaml.font.indent_prob,
+            indent_len: yaml.font.indent_len,
+            collapse_label_spaces: yaml.font.collapse_label_spaces,
+            ligatures: yaml.font.ligatures,
+            kerning: yaml.font.kerning,
+            kerning_jitter: yaml.font.kerning_jitter,
+            glyph_size_jitter: yaml.font.glyph_size_jitter,
+            ink_texture_strength: yaml.font.ink_texture_strength,
+            ink_texture_scale: yaml.font.ink_texture_scale,
+            faux_bold_prob: yaml.font.faux_bold_prob,
+            faux_bold_width: yaml.font.faux_bold_width,
+            faux_italic_prob: yaml.font.faux_italic_prob,
+            faux_italic_slant: yaml.font.faux_italic_slant,
+            letter_spacing: yaml.font.letter_spacing.to_random(),
+            word_spacing: yaml.font.word_spacing.to_random(),
+            baseline_jitter: yaml.font.baseline_jitter.map(|each| each.to_random()),
+            glyph_rotation: yaml.font.glyph_rotation.map(|each| each.to_random()),
+            arc_text_prob: yaml.font.arc_text_prob,
+            arc_text_bend: yaml.font.arc_text_bend.to_random(),
+            wave_text_prob: yaml.font.wave_text_prob,
+            wave_text_amplitude: yaml.font.wave_text_amplitude.to_random(),
+            wave_text_period: yaml.font.wave_text_period.to_random(),
+            drop_shadow_prob: yaml.font.drop_shadow_prob,
+            drop_shadow_offset: yaml.font.drop_shadow_offset.to_random(),
+            drop_shadow_sigma: yaml.font.drop_shadow_sigma.to_random(),
+            drop_shadow_opacity: yaml.font.drop_shadow_opacity.to_random(),
+            underline_prob: yaml.font.underline_prob,
+            strikethrough_prob: yaml.font.strikethrough_prob,
+            decoration_styles: yaml.font.decoration_styles,
+            decoration_thickness: yaml.font.decoration_thickness.to_random(),
+            image_glyph_dir: yaml.font.image_glyph_dir,
+            image_glyph_prob: yaml.font.image_glyph_prob,
+            char_color_prob: yaml.font.char_color_prob,
+            char_color_run_len: yaml.font.char_color_run_len,
+            char_color_palette: yaml.font.char_color_palette,
+            char_color_hue: yaml.font.char_color_hue.to_random(),
+            color_prob: yaml.font.color_prob,
+            text_color_palette: yaml.font.text_color_palette,
+            text_color_hue: yaml.font.text_color_hue.to_random(),
+            text_color_saturation: yaml.font.text_color_saturation.to_random(),
+            text_color_value: yaml.font.text_color_value.to_random(),
+            background_color_palette: yaml.font.background_color_palette,
+            background_color_hue: yaml.font.background_color_hue.to_random(),
+            background_color_saturation: yaml.font.background_color_saturation.to_random(),
+            background_color_value: yaml.font.background_color_value.to_random(),
+            min_contrast: yaml.font.min_contrast,
+            render_backend: yaml.font.render_backend,
+            erode_prob: yaml.cv.erode_prob,
+            erode_kernel: yaml.cv.erode_kernel.to_random(),
+            dilate_prob: yaml.cv.dilate_prob,
+            dilate_kernel: yaml.cv.dilate_kernel.to_random(),
+            ink_bleed_prob: yaml.cv.ink_bleed_prob,
+            ink_bleed_spread: yaml.cv.ink_bleed_spread.to_random(),
+            ink_bleed_sigma: yaml.cv.ink_bleed_sigma.to_random(),
+            ink_bleed_noise: yaml.cv.ink_bleed_noise.to_random(),
+            occlusion_prob: yaml.cv.occlusion_prob,
+            occlusion_count: yaml.cv.occlusion_count.to_random(),
+            occlusion_thickness: yaml.cv.occlusion_thickness.to_random(),
+            occlusion_intensity: yaml.cv.occlusion_intensity.to_random(),
+            table_line_prob: yaml.cv.table_line_prob,
+            table_line_spacing: yaml.cv.table_line_spacing.to_random(),
+            table_line_thickness: yaml.cv.table_line_thickness.to_random(),
+            table_line_intensity: yaml.cv.table_line_intensity.to_random(),
             box_prob: yaml.cv.box_prob,
             perspective_prob: yaml.cv.perspective_prob,
             perspective_x: yaml.cv.perspective_x.to_random(),
@@ -169,6 +1370,23 @@ impl Config {
             filter_prob: yaml.cv.filter_prob,
             emboss_prob: yaml.cv.emboss_prob,
             sharp_prob: yaml.cv.sharp_prob,
+            color_jitter_prob: yaml.cv.color_jitter_prob,
+            hue_shift: yaml.cv.hue_shift.to_random(),
+            saturation_scale: yaml.cv.saturation_scale.to_random(),
+            value_scale: yaml.cv.value_scale.to_random(),
+            curvature_prob: yaml.cv.curvature_prob,
+            curvature_strength: yaml.cv.curvature_strength.to_random(),
+            wave_prob: yaml.cv.wave_prob,
+            wave_amplitude: yaml.cv.wave_amplitude.to_random(),
+            wave_wavelength: yaml.cv.wave_wavelength.to_random(),
+            wave_phase: yaml.cv.wave_phase.to_random(),
+            wave_rows_prob: yaml.cv.wave_rows_prob,
+            gaussian_noise_prob: yaml.cv.gaussian_noise_prob,
+            gaussian_noise_sigma: yaml.cv.gaussian_noise_sigma.to_random(),
+            salt_pepper_prob: yaml.cv.salt_pepper_prob,
+            salt_pepper_amount: yaml.cv.salt_pepper_amount.to_random(),
+            speckle_noise_prob: yaml.cv.speckle_noise_prob,
+            speckle_noise_sigma: yaml.cv.speckle_noise_sigma.to_random(),
             bg_dir: yaml.merge.bg_dir,
             bg_height: yaml.merge.bg_height,
             bg_width: yaml.merge.bg_width,
@@ -177,6 +1395,109 @@ impl Config {
             bg_beta: yaml.merge.bg_beta.to_random(),
             font_alpha: yaml.merge.font_alpha.to_random(),
             reverse_prob: yaml.merge.reverse_prob,
+            bg_blend_prob: yaml.merge.bg_blend_prob,
+            bg_blend_alpha: yaml.merge.bg_blend_alpha.to_random(),
+            bg_base_color: yaml.merge.bg_base_color.to_random(),
+            bg_base_bg_prob: yaml.merge.bg_base_bg_prob,
+            color_merge: yaml.merge.color_merge,
+        }
+    }
+}
+
+/// Replace the keys of the CV and MERGE sections by those of the profile.
+fn apply_profile(value: &mut serde_yaml::Value, profile: &str) -> Result<(), String> {
+    let overrides = value
+        .get("PROFILES")
+        .and_then(|profiles| profiles.get(profile))
+        .and_then(serde_yaml::Value::as_mapping)
+        .cloned()
+        .ok_or_else(|| format!("profile `{}` is not defined in the config file", profile))?;
+
+    for (section, keys) in overrides {
+        let section = section.as_str().unwrap_or_default();
+        if section != "CV" && section != "MERGE" {
+            return Err(format!(
+                "profile `{}` can only override the CV and MERGE sections, found `{}`",
+                profile, section
+            ));
+        }
+        let keys = keys.as_mapping().ok_or_else(|| {
+            format!(
+                "section `{}` of profile `{}` is not a map",
+                section, profile
+            )
+        })?;
+        let target = value
+            .get_mut(section)
+            .and_then(serde_yaml::Value::as_mapping_mut)
+            .ok_or_else(|| format!("the config file has no `{}` section", section))?;
+        for (key, each) in keys {
+            target.insert(key.clone(), each.clone());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        let mut yaml_str = fs::read_to_string("./config.yaml").unwrap();
+        yaml_str.push_str(
+            "\nPROFILES:\n  hard:\n    CV:\n      blur_prob: 0.9\n    MERGE:\n      reverse_prob: 0.0\n",
+        );
+
+        let base = Config::from_yaml_str_with(&yaml_str, None, &[]).unwrap();
+        let hard = Config::from_yaml_str_with(&yaml_str, Some("hard"), &[]).unwrap();
+        assert_eq!(hard.blur_prob, 0.9);
+        assert_eq!(hard.reverse_prob, 0.0);
+        assert_eq!(hard.perspective_prob, base.perspective_prob);
+        assert_eq!(hard.font_size, base.font_size);
+
+        assert_eq!(Config::profile_names(&yaml_str), vec!["hard".to_string()]);
+        assert!(Config::from_yaml_str_with(&yaml_str, Some("clean"), &[]).is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides = ConfigOverride::from_vars([
+            ("TIG_MERGE__REVERSE_PROB".to_string(), "0.2".to_string()),
+            ("TIG_FONT__SPACE_RUN_LEN".to_string(), "[2, 3]".to_string()),
+            ("TIG_UNRELATED".to_string(), "1".to_string()),
+        ]);
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides[0].section, "MERGE");
+        assert_eq!(overrides[0].key, "reverse_prob");
+
+        let yaml_str = fs::read_to_string("./config.yaml").unwrap();
+        let config = Config::from_yaml_str_with(&yaml_str, None, &overrides).unwrap();
+        assert_eq!(config.reverse_prob, 0.2);
+        assert_eq!(config.space_run_len, (2, 3));
+
+        let typo = ConfigOverride::parse("cv.blur_prb", "0.5").unwrap();
+        assert!(Config::from_yaml_str_with(&yaml_str, None, &[typo]).is_err());
+        assert!(ConfigOverride::parse("blur_prob", "0.5").is_err());
+    }
+
+    #[test]
+    fn test_difficulty_presets() {
+        let yaml_str = fs::read_to_string("./config.yaml").unwrap();
+        for level in difficulty_levels() {
+            let overrides = difficulty_preset(level).unwrap();
+            assert!(!overrides.is_empty());
+            assert!(Config::from_yaml_str_with(&yaml_str, None, &overrides).is_ok());
         }
+
+        let easy = difficulty_preset("easy").unwrap();
+        let config = Config::from_yaml_str_with(&yaml_str, None, &easy).unwrap();
+        assert_eq!(config.blur_prob, 0.0);
+        let hard = difficulty_preset("hard").unwrap();
+        let config = Config::from_yaml_str_with(&yaml_str, None, &hard).unwrap();
+        assert_eq!(config.blur_prob, 0.4);
+
+        assert!(difficulty_preset("nightmare").is_err());
     }
 }
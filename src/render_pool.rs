@@ -0,0 +1,182 @@
+//! Render states shared by several threads. Shaping and rasterizing mutate the caches of the
+//! font system, the swash cache and the editor buffer, so a thread takes a state from the pool
+//! for one call and puts it back afterwards. When every state is taken a new one is created from
+//! a clone of the font database, whose face data is reference counted, so only the caches are
+//! duplicated.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Mutex, PoisonError},
+};
+
+use cosmic_text::{fontdb, Buffer, FontSystem, Metrics, SwashCache};
+
+pub struct RenderState {
+    pub font_system: FontSystem,
+    pub editor_buffer: Buffer,
+    pub swash_cache: SwashCache,
+}
+
+impl RenderState {
+    pub fn new(mut font_system: FontSystem, metrics: Metrics, size: (f32, f32)) -> Self {
+        let mut editor_buffer = Buffer::new(&mut font_system, metrics);
+        editor_buffer.set_size(&mut font_system, size.0, size.1);
+
+        Self {
+            font_system,
+            editor_buffer,
+            swash_cache: SwashCache::new(),
+        }
+    }
+}
+
+pub struct RenderPool {
+    db: fontdb::Database,
+    locale: String,
+    metrics: Metrics,
+    size: (f32, f32),
+    idle: Mutex<Vec<RenderState>>,
+}
+
+impl RenderPool {
+    /// The pool starts with one state over `font_system`. The buffer of every state starts with
+    /// `metrics` and `size` (width, height).
+    pub fn new(font_system: FontSystem, metrics: Metrics, size: (f32, f32)) -> Self {
+        let db = font_system.db().clone();
+        let locale = font_system.locale().to_string();
+
+        Self {
+            db,
+            locale,
+            metrics,
+            size,
+            idle: Mutex::new(vec![RenderState::new(font_system, metrics, size)]),
+        }
+    }
+
+    /// Take an idle state, or create one if every state is in use.
+    pub fn get(&self) -> PooledState<'_> {
+        let state = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+            .unwrap_or_else(|| {
+                let font_system =
+                    FontSystem::new_with_locale_and_db(self.locale.clone(), self.db.clone());
+                RenderState::new(font_system, self.metrics, self.size)
+            });
+
+        PooledState {
+            pool: self,
+            state: Some(state),
+        }
+    }
+
+    /// The metrics the buffers start with.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Load the fonts of a directory into the database. The idle states, which hold the old
+    /// database, are dropped and created again on demand.
+    pub fn load_fonts_dir(&mut self, path: &str) {
+        self.db.load_fonts_dir(path);
+        self.idle
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Load a font file from memory into the database, like `load_fonts_dir`.
+    pub fn load_font_data(&mut self, data: Vec<u8>) {
+        self.db.load_font_data(data);
+        self.idle
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+/// A state taken from the pool, put back when dropped.
+pub struct PooledState<'a> {
+    pool: &'a RenderPool,
+    state: Option<RenderState>,
+}
+
+impl Deref for PooledState<'_> {
+    type Target = RenderState;
+
+    fn deref(&self) -> &RenderState {
+        self.state.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledState<'_> {
+    fn deref_mut(&mut self) -> &mut RenderState {
+        self.state.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledState<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_pool() {
+        let font_system =
+            FontSystem::new_with_locale_and_db("en-US".to_string(), fontdb::Database::new());
+        let pool = RenderPool::new(font_system, Metrics::new(50.0, 64.0), (2000.0, 64.0));
+        assert_eq!(pool.idle_count(), 1);
+
+        {
+            let mut first = pool.get();
+            let second = pool.get();
+            assert_eq!(pool.idle_count(), 0);
+            assert_eq!(second.editor_buffer.size(), (2000.0, 64.0));
+
+            let RenderState {
+                font_system,
+                editor_buffer,
+                ..
+            } = &mut *first;
+            editor_buffer.set_size(font_system, 300.0, 64.0);
+        }
+        assert_eq!(pool.idle_count(), 2);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let state = pool.get();
+                    assert_eq!(state.editor_buffer.metrics().font_size, 50.0);
+                });
+            }
+        });
+        assert!(pool.idle_count() >= 2);
+
+        let mut pool = pool;
+        pool.load_fonts_dir("./font");
+        assert_eq!(pool.idle_count(), 0);
+        let state = pool.get();
+        assert_eq!(state.font_system.db().len(), pool.db.len());
+    }
+}
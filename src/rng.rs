@@ -0,0 +1,181 @@
+//! The random number generators of the pipeline. Both are thread-local `StdRng`s that can be
+//! reseeded: the sample RNG draws the text and its layout (characters, fonts, glyph jitter),
+//! the effect RNG the effects and the backgrounds. Keeping them apart lets the same effects and
+//! background be replayed for several images, and seeding both on a thread makes everything
+//! that thread generates reproducible.
+//!
+//! A struct with its own seed keeps it in an `RngState` and does its work in an `enter` scope,
+//! so that it neither draws from nor disturbs the RNGs of the thread or of other structs.
+
+use std::{cell::RefCell, sync::Mutex};
+
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+thread_local! {
+    static SAMPLE_RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+    static EFFECT_RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// A handle to the thread-local sample RNG, use it like `rand::thread_rng()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SampleRng;
+
+pub fn sample_rng() -> SampleRng {
+    SampleRng
+}
+
+/// A handle to the thread-local effect RNG, use it like `rand::thread_rng()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EffectRng;
+
+pub fn effect_rng() -> EffectRng {
+    EffectRng
+}
+
+/// Reseed the sample RNG of the current thread.
+pub fn reseed_sample_rng(seed: u64) {
+    SAMPLE_RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Reseed the effect RNG of the current thread.
+pub fn reseed_effect_rng(seed: u64) {
+    EFFECT_RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Reseed both RNGs of the current thread from one seed.
+pub fn reseed(seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    reseed_sample_rng(rng.gen());
+    reseed_effect_rng(rng.gen());
+}
+
+/// The RNG state of one struct. Every `enter` seeds the two thread-local RNGs from it until the
+/// returned scope is dropped, so the calls on one struct are reproducible in order whatever else
+/// runs on the thread in between.
+#[derive(Debug)]
+pub struct RngState(Mutex<StdRng>);
+
+impl RngState {
+    /// Seeded from `seed`, or from the OS without one.
+    pub fn new(seed: Option<u64>) -> Self {
+        Self(Mutex::new(match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }))
+    }
+
+    pub fn reseed(&self, seed: u64) {
+        *self.0.lock().unwrap() = StdRng::seed_from_u64(seed);
+    }
+
+    /// Seed the RNGs of the current thread from this state, the thread gets its own RNGs back
+    /// when the scope is dropped. The lock is only held to draw the seeds, so calls from several
+    /// threads at once get different seeds.
+    pub fn enter(&self) -> RngScope {
+        let (sample, effect) = {
+            let mut rng = self.0.lock().unwrap();
+            (
+                StdRng::seed_from_u64(rng.gen()),
+                StdRng::seed_from_u64(rng.gen()),
+            )
+        };
+
+        RngScope {
+            sample: SAMPLE_RNG.with(|rng| rng.replace(sample)),
+            effect: EFFECT_RNG.with(|rng| rng.replace(effect)),
+        }
+    }
+}
+
+/// The RNGs the current thread had before `RngState::enter`.
+#[must_use]
+pub struct RngScope {
+    sample: StdRng,
+    effect: StdRng,
+}
+
+impl Drop for RngScope {
+    fn drop(&mut self) {
+        SAMPLE_RNG.with(|rng| std::mem::swap(&mut *rng.borrow_mut(), &mut self.sample));
+        EFFECT_RNG.with(|rng| std::mem::swap(&mut *rng.borrow_mut(), &mut self.effect));
+    }
+}
+
+macro_rules! impl_rng_core {
+    ($handle:ty, $key:ident) => {
+        impl RngCore for $handle {
+            fn next_u32(&mut self) -> u32 {
+                $key.with(|rng| rng.borrow_mut().next_u32())
+            }
+
+            fn next_u64(&mut self) -> u64 {
+                $key.with(|rng| rng.borrow_mut().next_u64())
+            }
+
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                $key.with(|rng| rng.borrow_mut().fill_bytes(dest))
+            }
+
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+                $key.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+            }
+        }
+    };
+}
+
+impl_rng_core!(SampleRng, SAMPLE_RNG);
+impl_rng_core!(EffectRng, EFFECT_RNG);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reseed_effect_rng() {
+        reseed_effect_rng(42);
+        let first: Vec<u32> = (0..4).map(|_| effect_rng().gen()).collect();
+        reseed_effect_rng(42);
+        let second: Vec<u32> = (0..4).map(|_| effect_rng().gen()).collect();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_reseed() {
+        let draw = || -> (Vec<u32>, Vec<u32>) {
+            (
+                (0..4).map(|_| sample_rng().gen()).collect(),
+                (0..4).map(|_| effect_rng().gen()).collect(),
+            )
+        };
+        reseed(7);
+        let first = draw();
+        reseed(7);
+        assert_eq!(draw(), first);
+        assert_ne!(first.0, first.1);
+    }
+
+    #[test]
+    fn test_rng_state() {
+        let draw = |state: &RngState| -> Vec<u32> {
+            let _scope = state.enter();
+            (0..4).map(|_| sample_rng().gen()).collect()
+        };
+        let (first, second) = (RngState::new(Some(3)), RngState::new(Some(3)));
+        let expected = [draw(&first), draw(&first)];
+
+        // the thread keeps its own sequence around the calls, and other states do not change
+        // the sequence of this one
+        reseed(11);
+        let outside: Vec<u32> = (0..2).map(|_| sample_rng().gen()).collect();
+        reseed(11);
+        let before: u32 = sample_rng().gen();
+        assert_eq!(draw(&second), expected[0]);
+        let _ = draw(&RngState::new(Some(3)));
+        assert_eq!(vec![before, sample_rng().gen()], outside);
+        assert_eq!(draw(&second), expected[1]);
+
+        second.reseed(3);
+        assert_eq!(draw(&second), expected[0]);
+    }
+}
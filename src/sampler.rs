@@ -0,0 +1,162 @@
+//! Sampling the index of a Chinese character by weight, optionally balanced so that every
+//! character with a positive weight appears at least `min_count` times: until it does, the
+//! characters still short of `min_count` are dealt from a shuffled deck, one epoch without
+//! replacement after another, and only then are the characters drawn by weight again.
+
+use std::sync::Mutex;
+
+use rand::{seq::SliceRandom, Rng};
+use rand_distr::{Distribution, WeightedAliasIndex, WeightedError};
+
+#[derive(Debug)]
+struct Balance {
+    min_count: u32,
+    // how often each character has been drawn
+    counts: Vec<u32>,
+    // the characters left in this epoch
+    deck: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct CharSampler {
+    weights: WeightedAliasIndex<f64>,
+    len: usize,
+    // the characters with a positive weight
+    eligible: Vec<usize>,
+    // shared by all the threads drawing from the sampler
+    balance: Option<Mutex<Balance>>,
+}
+
+impl CharSampler {
+    pub fn new(weights: Vec<f64>) -> Result<Self, WeightedError> {
+        let eligible = weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0.0)
+            .map(|(idx, _)| idx)
+            .collect();
+
+        Ok(Self {
+            len: weights.len(),
+            weights: WeightedAliasIndex::new(weights)?,
+            eligible,
+            balance: None,
+        })
+    }
+
+    /// Replace the weights, keeping the balance and the counts of the characters (the indices
+    /// are assumed to stay the same, the counts of new characters start at 0).
+    pub fn reweight(&mut self, weights: Vec<f64>) -> Result<(), WeightedError> {
+        let mut sampler = Self::new(weights)?;
+        if let Some(balance) = self.balance.take() {
+            let mut balance = balance.into_inner().unwrap();
+            balance.counts.resize(sampler.len, 0);
+            balance.deck.clear();
+            sampler.balance = Some(Mutex::new(balance));
+        }
+        *self = sampler;
+
+        Ok(())
+    }
+
+    /// Guarantee `min_count` draws of every character with a positive weight, counting from
+    /// now; `None` (or 0) draws by weight only.
+    pub fn set_balance(&mut self, min_count: Option<u32>) {
+        self.balance = min_count.filter(|&count| count > 0).map(|min_count| {
+            Mutex::new(Balance {
+                min_count,
+                counts: vec![0; self.len],
+                deck: vec![],
+            })
+        });
+    }
+
+    /// Forget the count of a character removed from the dict, the later indices shift down. The
+    /// weights should be replaced with `reweight` afterwards.
+    pub fn remove(&mut self, idx: usize) {
+        if let Some(balance) = self.balance.as_mut() {
+            let balance = balance.get_mut().unwrap();
+            if idx < balance.counts.len() {
+                balance.counts.remove(idx);
+            }
+        }
+    }
+
+    /// How often each character has been drawn since `set_balance`, `None` without balance.
+    pub fn counts(&self) -> Option<Vec<u32>> {
+        self.balance
+            .as_ref()
+            .map(|balance| balance.lock().unwrap().counts.clone())
+    }
+}
+
+impl Distribution<usize> for CharSampler {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let balance = match &self.balance {
+            Some(balance) => balance,
+            None => return self.weights.sample(rng),
+        };
+
+        let mut balance = balance.lock().unwrap();
+        if balance.deck.is_empty() {
+            let min_count = balance.min_count;
+            let mut deck: Vec<usize> = self
+                .eligible
+                .iter()
+                .copied()
+                .filter(|&idx| balance.counts[idx] < min_count)
+                .collect();
+            deck.shuffle(rng);
+            balance.deck = deck;
+        }
+        let idx = match balance.deck.pop() {
+            Some(idx) => idx,
+            None => self.weights.sample(rng),
+        };
+        balance.counts[idx] += 1;
+
+        idx
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_balanced_sampling() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut sampler = CharSampler::new(vec![1000.0, 1.0, 1.0, 0.0]).unwrap();
+        assert!(sampler.counts().is_none());
+
+        sampler.set_balance(Some(2));
+        // two epochs over the three characters with a positive weight
+        let mut drawn: Vec<usize> = (0..6).map(|_| sampler.sample(&mut rng)).collect();
+        drawn.sort_unstable();
+        assert_eq!(drawn, vec![0, 0, 1, 1, 2, 2]);
+        assert_eq!(sampler.counts().unwrap(), vec![2, 2, 2, 0]);
+
+        // then by weight again
+        let heavy = (0..100).filter(|_| sampler.sample(&mut rng) == 0).count();
+        assert!(heavy > 90);
+
+        // the counts survive new weights, a new character is dealt until it catches up
+        sampler.reweight(vec![1000.0, 1.0, 1.0, 0.0, 1.0]).unwrap();
+        let drawn: Vec<usize> = (0..2).map(|_| sampler.sample(&mut rng)).collect();
+        assert_eq!(drawn, vec![4, 4]);
+
+        sampler.set_balance(None);
+        assert!(sampler.counts().is_none());
+
+        // the deck is shuffled with the given RNG, the same seed deals the same order
+        let deal = |seed| {
+            let mut sampler = CharSampler::new(vec![1.0; 8]).unwrap();
+            sampler.set_balance(Some(1));
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..8).map(|_| sampler.sample(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(deal(5), deal(5));
+    }
+}
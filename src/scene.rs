@@ -0,0 +1,230 @@
+//! Scene text in the style of SynthText: several independent text instances pasted at random,
+//! non-overlapping places of one large background, each with its own polygon and transcript.
+
+use image::{imageops::FilterType, GrayImage, ImageBuffer, Pixel};
+use rand::Rng;
+
+use crate::rng::effect_rng;
+
+/// The corners of a text instance: top left, top right, bottom right and bottom left.
+pub type Polygon = [(f32, f32); 4];
+
+/// A text instance ready to be pasted.
+pub struct TextInstance {
+    pub text: String,
+    /// the coverage of the ink, 255 is fully covered
+    pub mask: GrayImage,
+    /// the corners of the text in `mask`
+    pub polygon: Polygon,
+}
+
+/// A text instance pasted onto the scene.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneInstance {
+    pub text: String,
+    /// the corners of the text in the scene
+    pub polygon: Polygon,
+}
+
+/// The gap kept between the boxes of two instances.
+const MARGIN: u32 = 2;
+
+/// Crop the mask to its ink, `None` if it has none.
+pub fn crop_to_ink(mask: &GrayImage) -> Option<GrayImage> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in mask.enumerate_pixels() {
+        if pixel.0[0] > 0 {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if min_x > max_x {
+        return None;
+    }
+
+    Some(
+        image::imageops::crop_imm(mask, min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+            .to_image(),
+    )
+}
+
+impl TextInstance {
+    /// Resize the instance so that its mask is `height` pixels high.
+    pub fn resize_to_height(self, height: u32) -> Self {
+        let scale = height as f32 / self.mask.height() as f32;
+        self.scale(scale)
+    }
+
+    fn scale(self, scale: f32) -> Self {
+        let width = ((self.mask.width() as f32 * scale).round() as u32).max(1);
+        let height = ((self.mask.height() as f32 * scale).round() as u32).max(1);
+        let (scale_x, scale_y) = (
+            width as f32 / self.mask.width() as f32,
+            height as f32 / self.mask.height() as f32,
+        );
+
+        Self {
+            text: self.text,
+            mask: image::imageops::resize(&self.mask, width, height, FilterType::Triangle),
+            polygon: self.polygon.map(|(x, y)| (x * scale_x, y * scale_y)),
+        }
+    }
+}
+
+/// Paste the instances in order onto the background, each at the first of `max_tries` random
+/// places where its box does not overlap the ones pasted before; an instance that finds no place
+/// is dropped, and one larger than the background is shrunk to fit. The ink gets a random color,
+/// dark on a light background and light on a dark one.
+pub fn paste_instances<P>(
+    background: &mut ImageBuffer<P, Vec<u8>>,
+    instances: Vec<TextInstance>,
+    max_tries: usize,
+) -> Vec<SceneInstance>
+where
+    P: Pixel<Subpixel = u8>,
+{
+    let (bg_width, bg_height) = background.dimensions();
+    let mut placed: Vec<(u32, u32, u32, u32)> = vec![];
+    let mut res = vec![];
+
+    for instance in instances {
+        let (width, height) = instance.mask.dimensions();
+        let fit = (bg_width as f32 / width as f32).min(bg_height as f32 / height as f32);
+        let instance = if fit < 1.0 {
+            instance.scale(fit)
+        } else {
+            instance
+        };
+        let (width, height) = instance.mask.dimensions();
+        if width > bg_width || height > bg_height {
+            continue;
+        }
+
+        let overlaps = |x: u32, y: u32| {
+            placed.iter().any(|&(x0, y0, x1, y1)| {
+                x < x1 + MARGIN
+                    && x0 < x + width + MARGIN
+                    && y < y1 + MARGIN
+                    && y0 < y + height + MARGIN
+            })
+        };
+        let place = (0..max_tries)
+            .map(|_| {
+                (
+                    effect_rng().gen_range(0..=bg_width - width),
+                    effect_rng().gen_range(0..=bg_height - height),
+                )
+            })
+            .find(|&(x, y)| !overlaps(x, y));
+        let (left, top) = match place {
+            Some(place) => place,
+            None => continue,
+        };
+
+        paste(background, &instance.mask, left, top);
+        placed.push((left, top, left + width, top + height));
+        res.push(SceneInstance {
+            text: instance.text,
+            polygon: instance
+                .polygon
+                .map(|(x, y)| (x + left as f32, y + top as f32)),
+        });
+    }
+
+    res
+}
+
+/// Blend a random ink color into the background through the mask.
+fn paste<P>(background: &mut ImageBuffer<P, Vec<u8>>, mask: &GrayImage, left: u32, top: u32)
+where
+    P: Pixel<Subpixel = u8>,
+{
+    // the brightness of the background under the ink decides the color of the ink
+    let (mut sum, mut count) = (0u64, 0u64);
+    for (x, y, coverage) in mask.enumerate_pixels() {
+        if coverage.0[0] > 0 {
+            sum += background.get_pixel(left + x, top + y).to_luma().0[0] as u64;
+            count += 1;
+        }
+    }
+    let brightness = sum.checked_div(count).unwrap_or(255);
+    let channels: Vec<u8> = (0..P::CHANNEL_COUNT)
+        .map(|_| match brightness > 127 {
+            true => effect_rng().gen_range(0..=80),
+            false => effect_rng().gen_range(175..=255),
+        })
+        .collect();
+    let ink = *P::from_slice(&channels);
+
+    for (x, y, coverage) in mask.enumerate_pixels() {
+        let alpha = coverage.0[0] as f32 / 255.0;
+        if alpha == 0.0 {
+            continue;
+        }
+        let pixel = background.get_pixel_mut(left + x, top + y);
+        *pixel = pixel.map2(&ink, |bg, ink| {
+            (bg as f32 * (1.0 - alpha) + ink as f32 * alpha).round() as u8
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Luma, Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_crop_to_ink() {
+        let mut mask = GrayImage::new(10, 8);
+        assert_eq!(crop_to_ink(&mask), None);
+        mask.put_pixel(2, 3, Luma([255]));
+        mask.put_pixel(5, 4, Luma([100]));
+        assert_eq!(crop_to_ink(&mask).unwrap().dimensions(), (4, 2));
+    }
+
+    #[test]
+    fn test_paste_instances() {
+        let mut background = RgbImage::from_pixel(100, 60, Rgb([230, 230, 230]));
+        let instances: Vec<_> = ["永", "和", "九"]
+            .into_iter()
+            .map(|text| TextInstance {
+                text: text.to_string(),
+                mask: GrayImage::from_pixel(30, 20, Luma([255])),
+                polygon: [(0.0, 0.0), (30.0, 0.0), (30.0, 20.0), (0.0, 20.0)],
+            })
+            .collect();
+
+        let pasted = paste_instances(&mut background, instances, 50);
+        assert!(!pasted.is_empty());
+        for (i, instance) in pasted.iter().enumerate() {
+            let (x0, y0) = instance.polygon[0];
+            let (x1, y1) = instance.polygon[2];
+            assert!(x1 - x0 == 30.0 && y1 - y0 == 20.0);
+            assert!(x1 <= 100.0 && y1 <= 60.0);
+            // dark ink on the light background
+            assert!(background.get_pixel(x0 as u32, y0 as u32).0[0] <= 80);
+            for other in &pasted[i + 1..] {
+                let (ox0, oy0) = other.polygon[0];
+                let (ox1, oy1) = other.polygon[2];
+                assert!(x1 <= ox0 || ox1 <= x0 || y1 <= oy0 || oy1 <= y0);
+            }
+        }
+
+        // an instance larger than the background is shrunk to fit
+        let mut background = GrayImage::new(20, 20);
+        let pasted = paste_instances(
+            &mut background,
+            vec![TextInstance {
+                text: "長".to_string(),
+                mask: GrayImage::from_pixel(40, 10, Luma([255])),
+                polygon: [(0.0, 0.0), (40.0, 0.0), (40.0, 10.0), (0.0, 10.0)],
+            }],
+            1,
+        );
+        assert_eq!(pasted[0].polygon[2].0 - pasted[0].polygon[0].0, 20.0);
+        assert!(background.get_pixel(10, pasted[0].polygon[0].1 as u32).0[0] >= 175);
+    }
+}
@@ -0,0 +1,111 @@
+//! Dictionary-based word segmentation in the style of jieba: every dictionary word found in
+//! the text is an edge of a DAG, and the path with the highest product of word probabilities
+//! is taken.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+pub struct WordDict {
+    freqs: HashMap<String, f64>,
+    log_total: f64,
+    // in grapheme clusters
+    max_word_len: usize,
+}
+
+impl WordDict {
+    /// Each line of the dictionary is `word [freq [tag]]`, separated by spaces or tabs, as in
+    /// jieba's `dict.txt`. Words without a frequency count as 1.
+    pub fn new(data: &str) -> Self {
+        let mut freqs = HashMap::new();
+        for line in data.lines() {
+            let mut split = line.split_whitespace();
+            let word = match split.next() {
+                Some(word) => word,
+                None => continue,
+            };
+            let freq = split
+                .next()
+                .and_then(|freq| freq.parse::<f64>().ok())
+                .filter(|freq| *freq > 0.0)
+                .unwrap_or(1.0);
+            *freqs.entry(word.to_string()).or_insert(0.0) += freq;
+        }
+
+        let total: f64 = freqs.values().sum();
+        let max_word_len = freqs
+            .keys()
+            .map(|word| word.graphemes(true).count())
+            .max()
+            .unwrap_or(1);
+
+        Self {
+            freqs,
+            log_total: total.max(1.0).ln(),
+            max_word_len,
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
+        let data = fs::read_to_string(path).expect("the word dictionary file does not exist");
+        Self::new(&data)
+    }
+
+    /// Split the text into words. Text not covered by the dictionary falls back to single
+    /// grapheme clusters, so joining the result gives back the text.
+    pub fn segment<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let bounds: Vec<usize> = text
+            .grapheme_indices(true)
+            .map(|(idx, _)| idx)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let n = bounds.len() - 1;
+
+        // route[i] = (best log probability of text[i..], end of the first word)
+        let mut route = vec![(0.0, n); n + 1];
+        for start in (0..n).rev() {
+            let mut best = (f64::NEG_INFINITY, start + 1);
+            for end in start + 1..=n.min(start + self.max_word_len) {
+                let word = &text[bounds[start]..bounds[end]];
+                let freq = match self.freqs.get(word) {
+                    Some(freq) => *freq,
+                    // a single cluster is always a word
+                    None if end == start + 1 => 1.0,
+                    None => continue,
+                };
+                let score = freq.ln() - self.log_total + route[end].0;
+                if score > best.0 {
+                    best = (score, end);
+                }
+            }
+            route[start] = best;
+        }
+
+        let mut res = vec![];
+        let mut start = 0;
+        while start < n {
+            let end = route[start].1;
+            res.push(&text[bounds[start]..bounds[end]]);
+            start = end;
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_segment() {
+        let dict = WordDict::new("我們 100\n研究 80\n研究生 20\n生命 60\n起源 50\n的 500\n");
+
+        assert_eq!(
+            dict.segment("我們研究生命的起源"),
+            vec!["我們", "研究", "生命", "的", "起源"]
+        );
+        assert_eq!(dict.segment("今天"), vec!["今", "天"]);
+        assert_eq!(dict.segment(""), Vec::<&str>::new());
+    }
+}
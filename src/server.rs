@@ -0,0 +1,245 @@
+//! HTTP serving mode: the generators live in one long-running process and training jobs
+//! request batches over the network, sharing the warm font and dict caches.
+//!
+//! `POST /batch` takes a JSON body like
+//!
+//! ```json
+//! {"config": "./config.yaml", "count": 32, "min_len": 5, "max_len": 10, "seed": 42,
+//!  "effects": {"blur_prob": 0.5, "curvature_prob": 0.1}}
+//! ```
+//!
+//! where every field (and `"embed_metadata": true`, which writes the label and the seed into
+//! each PNG) is optional, and answers with the labels and the PNG encoded grayscale
+//! images (base64). The config must be one of the paths given to `serve`, whose generators are
+//! all loaded at the start; a client cannot make the server read any other file. The seed
+//! reseeds the sample and the effect RNG, so a batch can be replayed. `GET /health` answers
+//! `{"status": "ok"}`.
+
+use std::{
+    collections::HashMap,
+    ffi::{c_char, CStr},
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    ffi::{TigGenerator, TIG_INVALID_ARGUMENT, TIG_PANIC},
+    metadata::{encode_png, ImageMetadata},
+    rng::reseed,
+};
+
+#[derive(Deserialize, Debug)]
+struct BatchRequest {
+    config: Option<String>,
+    #[serde(default = "default_count")]
+    count: usize,
+    #[serde(default = "default_min_len")]
+    min_len: u32,
+    #[serde(default = "default_max_len")]
+    max_len: u32,
+    seed: Option<u64>,
+    #[serde(default)]
+    effects: EffectOverrides,
+    /// embed the label and the seed into each PNG, see `metadata`
+    #[serde(default)]
+    embed_metadata: bool,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_min_len() -> u32 {
+    5
+}
+
+fn default_max_len() -> u32 {
+    10
+}
+
+/// Probabilities of the config file replaced for one request.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct EffectOverrides {
+    box_prob: Option<f64>,
+    perspective_prob: Option<f64>,
+    blur_prob: Option<f64>,
+    filter_prob: Option<f64>,
+    curvature_prob: Option<f64>,
+    reverse_prob: Option<f64>,
+    bg_blend_prob: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+struct Sample {
+    label: String,
+    raw_label: String,
+    width: u32,
+    height: u32,
+    /// base64 PNG
+    image: String,
+}
+
+#[derive(Serialize, Debug)]
+struct BatchResponse {
+    samples: Vec<Sample>,
+}
+
+const MAX_BATCH: usize = 4096;
+
+/// Serve on `addr` (e.g. `0.0.0.0:8000`) until the process is stopped. `default_config` is
+/// used by the requests without a config path, and the requests may name it or one of
+/// `configs`.
+pub fn serve(addr: &str, default_config: &str, configs: &[String]) -> Result<(), String> {
+    let mut generators: HashMap<String, TigGenerator> = HashMap::new();
+    for config in configs.iter().map(String::as_str).chain([default_config]) {
+        if !generators.contains_key(config) {
+            let generator = catch_unwind(|| TigGenerator::new(config))
+                .unwrap_or_else(|_| Err("panicked".to_string()))
+                .map_err(|err| format!("fail to load the config `{}`: {}", config, err))?;
+            generators.insert(config.to_string(), generator);
+        }
+    }
+    let server =
+        Server::http(addr).map_err(|err| format!("fail to listen on {}: {}", addr, err))?;
+
+    for mut request in server.incoming_requests() {
+        let res = match (request.method(), request.url()) {
+            (Method::Get, "/health") => Ok(r#"{"status": "ok"}"#.to_string()),
+            (Method::Post, "/batch") => handle_batch(&mut request, &mut generators, default_config),
+            _ => Err((404, "not found".to_string())),
+        };
+
+        let response = match res {
+            Ok(body) => Response::from_string(body).with_header(json_header()),
+            Err((status, message)) => Response::from_string(message).with_status_code(status),
+        };
+        // the client may have gone away, which must not stop the server
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+/// Serve on `addr` with `config_path` as the only config, blocking the calling thread.
+/// Returns only on failure.
+///
+/// # Safety
+///
+/// `config_path` and `addr` must be valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn tig_serve(config_path: *const c_char, addr: *const c_char) -> i32 {
+    if config_path.is_null() || addr.is_null() {
+        return TIG_INVALID_ARGUMENT;
+    }
+    let (config_path, addr) = match (
+        CStr::from_ptr(config_path).to_str(),
+        CStr::from_ptr(addr).to_str(),
+    ) {
+        (Ok(config_path), Ok(addr)) => (config_path, addr),
+        _ => return TIG_INVALID_ARGUMENT,
+    };
+
+    match catch_unwind(|| serve(addr, config_path, &[])) {
+        Ok(Ok(())) => 0,
+        Ok(Err(_)) => TIG_INVALID_ARGUMENT,
+        Err(_) => TIG_PANIC,
+    }
+}
+
+fn json_header() -> Header {
+    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap()
+}
+
+fn handle_batch(
+    request: &mut Request,
+    generators: &mut HashMap<String, TigGenerator>,
+    default_config: &str,
+) -> Result<String, (u16, String)> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|err| (400, err.to_string()))?;
+    let batch: BatchRequest = serde_json::from_str(&body).map_err(|err| (400, err.to_string()))?;
+    if batch.min_len == 0 || batch.min_len > batch.max_len {
+        return Err((400, "expected 0 < `min_len` <= `max_len`".to_string()));
+    }
+    if batch.count > MAX_BATCH {
+        return Err((400, format!("`count` should be at most {}", MAX_BATCH)));
+    }
+
+    let config = batch.config.as_deref().unwrap_or(default_config);
+    let generator = generators
+        .get_mut(config)
+        .ok_or_else(|| (400, format!("the config `{}` is not served", config)))?;
+
+    if let Some(seed) = batch.seed {
+        reseed(seed);
+    }
+
+    let (cv_util, merge_util) = (generator.cv_util.clone(), generator.merge_util.clone());
+    batch.effects.apply(generator);
+    let samples = catch_unwind(AssertUnwindSafe(|| {
+        (0..batch.count)
+            .map(|_| {
+                generator.generate(batch.min_len, batch.max_len);
+                encode_sample(generator, batch.embed_metadata.then_some(batch.seed))
+            })
+            .collect::<Vec<_>>()
+    }));
+    generator.cv_util = cv_util;
+    generator.merge_util = merge_util;
+
+    let samples = samples.map_err(|_| (500, "fail to generate the batch".to_string()))?;
+    serde_json::to_string(&BatchResponse { samples }).map_err(|err| (500, err.to_string()))
+}
+
+impl EffectOverrides {
+    fn apply(&self, generator: &mut TigGenerator) {
+        let cv_util = &mut generator.cv_util;
+        let merge_util = &mut generator.merge_util;
+        let overrides = [
+            (self.box_prob, &mut cv_util.box_prob),
+            (self.perspective_prob, &mut cv_util.perspective_prob),
+            (self.blur_prob, &mut cv_util.blur_prob),
+            (self.filter_prob, &mut cv_util.filter_prob),
+            (self.curvature_prob, &mut cv_util.curvature_prob),
+            (self.reverse_prob, &mut merge_util.reverse_prob),
+            (self.bg_blend_prob, &mut merge_util.bg_blend_prob),
+        ];
+        for (value, field) in overrides {
+            if let Some(value) = value {
+                *field = value.clamp(0.0, 1.0);
+            }
+        }
+    }
+}
+
+/// `metadata` is `Some(seed)` to embed the label and the seed of the batch into the PNG.
+fn encode_sample(generator: &TigGenerator, metadata: Option<Option<u64>>) -> Sample {
+    let img = generator
+        .last_image
+        .as_ref()
+        .expect("a sample has been generated");
+    let label = generator.last_label.to_string_lossy().into_owned();
+    let metadata = metadata.map(|seed| ImageMetadata {
+        label: label.clone(),
+        seed,
+        ..Default::default()
+    });
+    let png = encode_png(&DynamicImage::ImageLuma8(img.clone()), metadata.as_ref())
+        .expect("fail to encode the image");
+
+    Sample {
+        label,
+        raw_label: generator.last_raw_label.to_string_lossy().into_owned(),
+        width: img.width(),
+        height: img.height(),
+        image: STANDARD.encode(png),
+    }
+}
@@ -0,0 +1,176 @@
+//! Several corpus sources with sampling weights, so that one generator can mix Chinese
+//! characters, sentences and Latin words in a dataset.
+
+use std::{fs, ops::RangeInclusive};
+
+use rand::{seq::SliceRandom, Rng};
+use rand_distr::{Distribution, WeightedIndex};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{parse_config::CorpusSourceConfig, rng::sample_rng};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+    /// characters sampled from the character file
+    Chinese,
+    /// Chinese characters, Latin words and symbols, see `mixed_pool_ratios`
+    Mixed,
+    /// words of the Latin corpus
+    Latin,
+    /// lines of a sentence file
+    Sentence,
+}
+
+impl SourceKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chinese" => Some(Self::Chinese),
+            "mixed" => Some(Self::Mixed),
+            "latin" => Some(Self::Latin),
+            "sentence" => Some(Self::Sentence),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CorpusSource {
+    pub kind: SourceKind,
+    /// lines of a sentence file, empty for the other kinds
+    pub lines: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CorpusSources {
+    pub sources: Vec<CorpusSource>,
+    weights: Option<WeightedIndex<f64>>,
+}
+
+impl CorpusSources {
+    /// Read the sentence files of the sources. An empty list gives no sources.
+    pub fn new(configs: &[CorpusSourceConfig]) -> Result<Self, String> {
+        if configs.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let mut sources = Vec::with_capacity(configs.len());
+        for config in configs {
+            let kind = SourceKind::from_name(&config.kind).ok_or_else(|| {
+                format!(
+                    "unknown corpus source `{}`, expected chinese, mixed, latin or sentence",
+                    config.kind
+                )
+            })?;
+            let lines = if kind == SourceKind::Sentence {
+                read_sentences(&config.path)?
+            } else {
+                vec![]
+            };
+            sources.push(CorpusSource { kind, lines });
+        }
+
+        let weights = WeightedIndex::new(configs.iter().map(|config| config.weight))
+            .map_err(|err| format!("invalid corpus source weights: {}", err))?;
+
+        Ok(Self {
+            sources,
+            weights: Some(weights),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Pick a source according to the weights.
+    pub fn choose(&self) -> Option<&CorpusSource> {
+        let weights = self.weights.as_ref()?;
+
+        Some(&self.sources[weights.sample(&mut sample_rng())])
+    }
+}
+
+/// The non-empty lines of a sentence file, trimmed. An empty file is an error.
+pub fn read_sentences(path: &str) -> Result<Vec<String>, String> {
+    let data =
+        fs::read_to_string(path).map_err(|err| format!("fail to read `{}`: {}", path, err))?;
+    let lines: Vec<_> = data
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+    if lines.is_empty() {
+        return Err(format!("sentence file `{}` is empty", path));
+    }
+
+    Ok(lines)
+}
+
+/// A random window of a random line, with a number of clusters in `range` (or the whole line
+/// if it is shorter).
+pub fn get_random_sentence<S: AsRef<str>>(lines: &[S], range: RangeInclusive<u32>) -> &str {
+    let mut rng = sample_rng();
+
+    let line = lines.choose(&mut rng).unwrap().as_ref();
+    let num = rng.gen_range(range) as usize;
+    let boundaries: Vec<_> = line
+        .grapheme_indices(true)
+        .map(|(idx, _)| idx)
+        .chain([line.len()])
+        .collect();
+    let cluster_count = boundaries.len() - 1;
+    if num >= cluster_count {
+        return line;
+    }
+
+    let start = rng.gen_range(0..=cluster_count - num);
+    &line[boundaries[start]..boundaries[start + num]]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_random_sentence() {
+        let lines = ["天地玄黃宇宙洪荒", "日月盈昃"];
+        for _ in 0..100 {
+            let sentence = get_random_sentence(&lines, 2..=5);
+            let len = sentence.graphemes(true).count();
+            assert!(lines.iter().any(|line| line.contains(sentence)));
+            assert!((2..=5).contains(&len));
+        }
+
+        assert_eq!(get_random_sentence(&["日月"], 5..=5), "日月");
+    }
+
+    #[test]
+    fn test_corpus_sources() {
+        let configs = vec![
+            CorpusSourceConfig {
+                kind: "chinese".to_string(),
+                path: String::new(),
+                weight: 1.0,
+            },
+            CorpusSourceConfig {
+                kind: "latin".to_string(),
+                path: String::new(),
+                weight: 0.0,
+            },
+        ];
+        let sources = CorpusSources::new(&configs).unwrap();
+        for _ in 0..10 {
+            assert_eq!(sources.choose().unwrap().kind, SourceKind::Chinese);
+        }
+
+        assert!(CorpusSources::new(&[]).unwrap().choose().is_none());
+
+        let unknown = CorpusSourceConfig {
+            kind: "poem".to_string(),
+            path: String::new(),
+            weight: 1.0,
+        };
+        assert!(CorpusSources::new(&[unknown]).is_err());
+    }
+}
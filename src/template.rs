@@ -0,0 +1,245 @@
+//! Structured strings from templates such as `{YYYY}年{MM}月{DD}日` or
+//! `¥{0-9}{3},{0-9}{3}.{0-9}{2}`, for the dates, prices, phone numbers and IDs of receipts and
+//! forms. Outside braces the template is literal text (`{{` and `}}` are literal braces); inside:
+//!
+//! - `YYYY`, `YY`, `MM`, `M`, `DD`, `D`, `hh`, `mm` and `ss`: a field of one random date and time
+//!   shared by the whole template, so that the day always exists in the month
+//! - `0-9`, `A-Z`, `0-9A-F`, `xyz`: one random character of the ranges and characters
+//! - `a|b|c`: one of the alternatives
+//! - `3` or `2,4`: repeat the previous item 3 times, or 2 to 4 times
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::rng::sample_rng;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DateField {
+    Year,
+    ShortYear,
+    Month,
+    ShortMonth,
+    Day,
+    ShortDay,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl DateField {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "YYYY" => Some(Self::Year),
+            "YY" => Some(Self::ShortYear),
+            "MM" => Some(Self::Month),
+            "M" => Some(Self::ShortMonth),
+            "DD" => Some(Self::Day),
+            "D" => Some(Self::ShortDay),
+            "hh" => Some(Self::Hour),
+            "mm" => Some(Self::Minute),
+            "ss" => Some(Self::Second),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    Literal(String),
+    Field(DateField),
+    Class(Vec<char>),
+    Choice(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    // each item with how many times it is repeated
+    items: Vec<(Item, u32, u32)>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut items: Vec<(Item, u32, u32)> = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(format!("unmatched `}}` in template `{}`", template)),
+                '{' => {
+                    let mut content = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => content.push(ch),
+                            None => {
+                                return Err(format!("unclosed `{{` in template `{}`", template))
+                            }
+                        }
+                    }
+
+                    if let Some((min, max)) = parse_repeat(&content) {
+                        // a repeat applies to the last character of a literal only
+                        if !literal.is_empty() {
+                            let last = literal.pop().unwrap();
+                            push_literal(&mut items, &mut literal);
+                            items.push((Item::Literal(last.to_string()), 1, 1));
+                        }
+                        let last = items
+                            .last_mut()
+                            .ok_or_else(|| format!("nothing to repeat before `{{{}}}`", content))?;
+                        if min > max {
+                            return Err(format!("invalid repeat `{{{}}}`", content));
+                        }
+                        (last.1, last.2) = (min, max);
+                        continue;
+                    }
+
+                    push_literal(&mut items, &mut literal);
+                    let item = if let Some(field) = DateField::from_name(&content) {
+                        Item::Field(field)
+                    } else if content.contains('|') {
+                        Item::Choice(content.split('|').map(String::from).collect())
+                    } else {
+                        Item::Class(parse_class(&content)?)
+                    };
+                    items.push((item, 1, 1));
+                }
+                _ => literal.push(ch),
+            }
+        }
+        push_literal(&mut items, &mut literal);
+
+        Ok(Self { items })
+    }
+
+    pub fn render(&self) -> String {
+        let mut rng = sample_rng();
+
+        let year = rng.gen_range(1950..=2035);
+        let month = rng.gen_range(1..=12);
+        let day = rng.gen_range(1..=days_in_month(year, month));
+        let (hour, minute, second) = (
+            rng.gen_range(0..24),
+            rng.gen_range(0..60),
+            rng.gen_range(0..60),
+        );
+
+        let mut res = String::new();
+        for (item, min, max) in self.items.iter() {
+            for _ in 0..rng.gen_range(*min..=*max) {
+                match item {
+                    Item::Literal(text) => res.push_str(text),
+                    Item::Field(field) => res.push_str(&match field {
+                        DateField::Year => format!("{:04}", year),
+                        DateField::ShortYear => format!("{:02}", year % 100),
+                        DateField::Month => format!("{:02}", month),
+                        DateField::ShortMonth => month.to_string(),
+                        DateField::Day => format!("{:02}", day),
+                        DateField::ShortDay => day.to_string(),
+                        DateField::Hour => format!("{:02}", hour),
+                        DateField::Minute => format!("{:02}", minute),
+                        DateField::Second => format!("{:02}", second),
+                    }),
+                    Item::Class(chars) => res.push(*chars.choose(&mut rng).unwrap()),
+                    Item::Choice(choices) => res.push_str(choices.choose(&mut rng).unwrap()),
+                }
+            }
+        }
+
+        res
+    }
+}
+
+fn push_literal(items: &mut Vec<(Item, u32, u32)>, literal: &mut String) {
+    if !literal.is_empty() {
+        items.push((Item::Literal(std::mem::take(literal)), 1, 1));
+    }
+}
+
+/// `3` or `2,4`.
+fn parse_repeat(content: &str) -> Option<(u32, u32)> {
+    let (min, max) = content.split_once(',').unwrap_or((content, content));
+    Some((min.trim().parse().ok()?, max.trim().parse().ok()?))
+}
+
+/// The characters of `0-9A-F`-style ranges and single characters.
+fn parse_class(content: &str) -> Result<Vec<char>, String> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut res = vec![];
+    let mut idx = 0;
+    while idx < chars.len() {
+        if idx + 2 < chars.len() && chars[idx + 1] == '-' {
+            let (start, end) = (chars[idx], chars[idx + 2]);
+            if start > end {
+                return Err(format!("invalid range `{}-{}`", start, end));
+            }
+            res.extend(start..=end);
+            idx += 3;
+        } else {
+            res.push(chars[idx]);
+            idx += 1;
+        }
+    }
+    if res.is_empty() {
+        return Err("empty `{}` in template".to_string());
+    }
+
+    Ok(res)
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    let is_leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        2 if is_leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let template = Template::parse("{YYYY}年{M}月{DD}日").unwrap();
+        for _ in 0..50 {
+            let text = template.render();
+            let (year, rest) = text.split_once('年').unwrap();
+            let (month, rest) = rest.split_once('月').unwrap();
+            let day: u32 = rest.trim_end_matches('日').parse().unwrap();
+            assert_eq!(year.len(), 4);
+            assert!(
+                day >= 1 && day <= days_in_month(year.parse().unwrap(), month.parse().unwrap())
+            );
+            assert_eq!(rest.len(), 2 + '日'.len_utf8());
+        }
+
+        let template = Template::parse("¥{0-9}{3},{0-9}{3}.{0-9}{2}").unwrap();
+        let text = template.render();
+        assert_eq!(text.chars().count(), 11);
+        assert!(text.starts_with('¥') && text.chars().nth(4) == Some(','));
+
+        let template = Template::parse("{{{A|B}}}-{1-3}{2,4}x{2}").unwrap();
+        for _ in 0..20 {
+            let text = template.render();
+            assert!(text.starts_with("{A}-") || text.starts_with("{B}-"));
+            assert!(text.ends_with("xx"));
+            let digits = &text[4..text.len() - 2];
+            assert!((2..=4).contains(&digits.len()));
+            assert!(digits.chars().all(|ch| ('1'..='3').contains(&ch)));
+        }
+
+        for invalid in ["{3}", "{0-9", "a}b", "{}", "{9-0}", "{0-9}{4,2}"] {
+            assert!(Template::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -1,8 +1,22 @@
-use std::{collections::HashMap, str::from_utf8_unchecked};
+#[cfg(feature = "python")]
+use std::collections::hash_map::DefaultHasher;
+use std::{
+    collections::HashSet,
+    hash::{Hash, Hasher},
+};
 
 use cosmic_text::{Attrs, AttrsOwned, Family, Stretch, Style, Weight};
+#[cfg(feature = "python")]
+use image::{DynamicImage, GrayImage, RgbImage};
 use indexmap::IndexMap;
-use pyo3::{IntoPy, PyObject, Python};
+#[cfg(feature = "python")]
+use numpy::{PyArrayDyn, PyReadonlyArrayDyn};
+#[cfg(feature = "python")]
+use pyo3::{
+    exceptions::PyValueError, pyclass, pymethods, types::PyType, FromPyObject, IntoPy, PyObject,
+    PyResult, Python,
+};
+use unicode_segmentation::UnicodeSegmentation;
 
 pub trait StringUsefulUtils {
     fn dedup(&self) -> String;
@@ -10,25 +24,12 @@ pub trait StringUsefulUtils {
 }
 
 impl<S: AsRef<str>> StringUsefulUtils for S {
+    /// Deduplicate the text by grapheme cluster, so that combining marks, ZWJ sequences
+    /// and variation selectors stay attached to their base character.
     fn dedup_to_vec(&self) -> Vec<&str> {
-        let mut reserve: HashMap<&str, i32> = HashMap::new();
-        let bytes = self.as_ref().as_bytes();
-        let total_len = bytes.len();
-        let mut idx = 0;
-        while idx < total_len {
-            let byte = bytes[idx];
-            if !utf8_width::is_width_0(byte) {
-                let ch_len = unsafe { utf8_width::get_width_assume_valid(byte) };
-                let ch = unsafe { from_utf8_unchecked(&bytes[idx..idx + ch_len]) };
-                reserve.entry(ch).or_default();
-
-                idx += ch_len;
-            } else {
-                idx += 1;
-            }
-        }
+        let reserve: HashSet<&str> = self.as_ref().graphemes(true).collect();
 
-        let mut res = reserve.keys().copied().collect::<Vec<_>>();
+        let mut res = reserve.into_iter().collect::<Vec<_>>();
         res.sort();
         res
     }
@@ -173,11 +174,359 @@ impl PartialEq for InternalAttrsOwned {
     }
 }
 
+impl Eq for InternalAttrsOwned {}
+
+impl Hash for InternalAttrsOwned {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.attrs_owned.hash(state);
+    }
+}
+
+#[cfg(feature = "python")]
 impl IntoPy<PyObject> for InternalAttrsOwned {
     fn into_py(self, py: Python<'_>) -> PyObject {
-        let res = self.to_tuple();
-        res.into_py(py)
+        FontAttrs::from(&self).into_py(py)
+    }
+}
+
+/// Font attributes passed across the Python boundary.
+///
+/// The numeric codes are the same as the ones used by `InternalAttrsOwned::from_tuple`.
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontAttrs {
+    pub family: String,
+    pub style: u16,
+    pub weight: u16,
+    pub stretch: u16,
+}
+
+impl FontAttrs {
+    pub fn to_tuple(&self) -> (String, u16, u16, u16) {
+        (self.family.clone(), self.style, self.weight, self.stretch)
+    }
+
+    pub fn to_internal(&self) -> InternalAttrsOwned {
+        InternalAttrsOwned::from_tuple(self.to_tuple())
+    }
+}
+
+impl From<&InternalAttrsOwned> for FontAttrs {
+    fn from(value: &InternalAttrsOwned) -> Self {
+        let (family, style, weight, stretch) = value.to_tuple();
+        Self {
+            family,
+            style,
+            weight,
+            stretch,
+        }
+    }
+}
+
+/// Font style exposed to Python, valued as the numeric code used by `FontAttrs`.
+#[cfg(feature = "python")]
+#[pyclass(name = "Style")]
+#[derive(Clone, Copy, Debug)]
+pub enum PyStyle {
+    Normal = 0,
+    Italic = 1,
+    Oblique = 2,
+}
+
+/// Font weight exposed to Python, valued as the numeric code used by `FontAttrs`.
+#[cfg(feature = "python")]
+#[pyclass(name = "Weight")]
+#[derive(Clone, Copy, Debug)]
+pub enum PyWeight {
+    Thin = 100,
+    ExtraLight = 200,
+    Light = 300,
+    Normal = 400,
+    Medium = 500,
+    SemiBold = 600,
+    Bold = 700,
+    ExtraBold = 800,
+    Black = 900,
+}
+
+/// Font stretch exposed to Python, valued as the numeric code used by `FontAttrs`.
+#[cfg(feature = "python")]
+#[pyclass(name = "Stretch")]
+#[derive(Clone, Copy, Debug)]
+pub enum PyStretch {
+    UltraCondensed = 1,
+    ExtraCondensed = 2,
+    Condensed = 3,
+    SemiCondensed = 4,
+    Normal = 5,
+    SemiExpanded = 6,
+    Expanded = 7,
+    ExtraExpanded = 8,
+    UltraExpanded = 9,
+}
+
+/// A font style given by its numeric code, its name or the Python enum, e.g. `1`, `"italic"`
+/// or `Style.Italic`.
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+pub enum StyleLike {
+    Enum(PyStyle),
+    Code(u16),
+    Name(String),
+}
+
+/// A font weight given by its numeric code, its name or the Python enum, e.g. `700`, `"bold"`
+/// or `Weight.Bold`.
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+pub enum WeightLike {
+    Enum(PyWeight),
+    Code(u16),
+    Name(String),
+}
+
+/// A font stretch given by its numeric code, its name or the Python enum, e.g. `3`,
+/// `"condensed"` or `Stretch.Condensed`.
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+pub enum StretchLike {
+    Enum(PyStretch),
+    Code(u16),
+    Name(String),
+}
+
+#[cfg(feature = "python")]
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|ch| !matches!(ch, '-' | '_' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(feature = "python")]
+fn style_code(src: StyleLike) -> PyResult<u16> {
+    let code = match src {
+        StyleLike::Enum(style) => style as u16,
+        StyleLike::Code(code) => code,
+        StyleLike::Name(name) => match &normalize_name(&name)[..] {
+            "normal" => 0,
+            "italic" => 1,
+            "oblique" => 2,
+            _ => return Err(PyValueError::new_err(format!("unknown font style: {name}"))),
+        },
+    };
+    if code > 2 {
+        return Err(PyValueError::new_err("font style should be 0 to 2"));
     }
+
+    Ok(code)
+}
+
+#[cfg(feature = "python")]
+fn weight_code(src: WeightLike) -> PyResult<u16> {
+    let code = match src {
+        WeightLike::Enum(weight) => weight as u16,
+        WeightLike::Code(code) => code,
+        WeightLike::Name(name) => match &normalize_name(&name)[..] {
+            "thin" => 100,
+            "extralight" | "ultralight" => 200,
+            "light" => 300,
+            "normal" | "regular" => 400,
+            "medium" => 500,
+            "semibold" | "demibold" => 600,
+            "bold" => 700,
+            "extrabold" | "ultrabold" => 800,
+            "black" | "heavy" => 900,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown font weight: {name}"
+                )))
+            }
+        },
+    };
+
+    Ok(code)
+}
+
+#[cfg(feature = "python")]
+fn stretch_code(src: StretchLike) -> PyResult<u16> {
+    let code = match src {
+        StretchLike::Enum(stretch) => stretch as u16,
+        StretchLike::Code(code) => code,
+        StretchLike::Name(name) => match &normalize_name(&name)[..] {
+            "ultracondensed" => 1,
+            "extracondensed" => 2,
+            "condensed" => 3,
+            "semicondensed" => 4,
+            "normal" => 5,
+            "semiexpanded" => 6,
+            "expanded" => 7,
+            "extraexpanded" => 8,
+            "ultraexpanded" => 9,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown font stretch: {name}"
+                )))
+            }
+        },
+    };
+    if !(1..=9).contains(&code) {
+        return Err(PyValueError::new_err("font stretch should be 1 to 9"));
+    }
+
+    Ok(code)
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl FontAttrs {
+    #[new]
+    #[pyo3(signature = (family, style=None, weight=None, stretch=None))]
+    fn py_new(
+        family: String,
+        style: Option<StyleLike>,
+        weight: Option<WeightLike>,
+        stretch: Option<StretchLike>,
+    ) -> PyResult<Self> {
+        Ok(Self {
+            family,
+            style: style.map_or(Ok(0), style_code)?,
+            weight: weight.map_or(Ok(400), weight_code)?,
+            stretch: stretch.map_or(Ok(5), stretch_code)?,
+        })
+    }
+
+    #[classmethod]
+    #[pyo3(name = "from_tuple")]
+    fn from_tuple_py(_cls: &PyType, src: (String, u16, u16, u16)) -> PyResult<Self> {
+        Self::py_new(
+            src.0,
+            Some(StyleLike::Code(src.1)),
+            Some(WeightLike::Code(src.2)),
+            Some(StretchLike::Code(src.3)),
+        )
+    }
+
+    #[pyo3(name = "to_tuple")]
+    fn py_to_tuple(&self) -> (String, u16, u16, u16) {
+        self.to_tuple()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "FontAttrs(family={:?}, style={}, weight={}, stretch={})",
+            self.family, self.style, self.weight, self.stretch
+        )
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Accepts a `FontAttrs` object, the legacy `(family, style, weight, stretch)` tuple, or an index
+/// into the `font_list` of the generator.
+#[cfg(feature = "python")]
+#[derive(FromPyObject)]
+pub enum FontAttrsLike {
+    Attrs(FontAttrs),
+    Tuple((String, u16, u16, u16)),
+    Index(usize),
+}
+
+#[cfg(feature = "python")]
+impl FontAttrsLike {
+    pub fn into_internal(self, font_list: &[InternalAttrsOwned]) -> PyResult<InternalAttrsOwned> {
+        match self {
+            FontAttrsLike::Attrs(attrs) => Ok(attrs.to_internal()),
+            FontAttrsLike::Tuple(tuple) => Ok(InternalAttrsOwned::from_tuple(tuple)),
+            FontAttrsLike::Index(idx) => font_list.get(idx).cloned().ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "font index {} is out of range, the font list has {} fonts",
+                    idx,
+                    font_list.len()
+                ))
+            }),
+        }
+    }
+}
+
+/// Copy the rows of a row-major image `width` pixels wide into the top left corner of `dst`,
+/// whose rows are `dst_width` pixels wide. A pixel is `pixel_len` bytes.
+pub fn copy_rows(src: &[u8], width: usize, dst: &mut [u8], dst_width: usize, pixel_len: usize) {
+    let row_len = width * pixel_len;
+    if row_len == 0 {
+        return;
+    }
+    for (src_row, dst_row) in src
+        .chunks_exact(row_len)
+        .zip(dst.chunks_exact_mut(dst_width * pixel_len))
+    {
+        dst_row[..row_len].copy_from_slice(src_row);
+    }
+}
+
+/// Write an image into the top left corner of a caller-provided array, `(height, width)` for a
+/// grayscale image or `(height, width, 3)` for an RGB one. The rest of the array is left as is.
+/// Returns the height and width that were written.
+#[cfg(feature = "python")]
+pub fn write_into_array(
+    out: &PyArrayDyn<u8>,
+    raw: &[u8],
+    height: usize,
+    width: usize,
+    channels: usize,
+) -> PyResult<(usize, usize)> {
+    let shape = out.shape().to_vec();
+    let expected_ndim = if channels == 1 { 2 } else { 3 };
+    if shape.len() != expected_ndim || (channels > 1 && shape[2] != channels) {
+        return Err(PyValueError::new_err(format!(
+            "`out` should have the shape (height, width{})",
+            if channels == 1 { "" } else { ", 3" }
+        )));
+    }
+    if shape[0] < height || shape[1] < width {
+        return Err(PyValueError::new_err(format!(
+            "`out` is too small: the image is {}x{}, the array is {}x{}",
+            height, width, shape[0], shape[1]
+        )));
+    }
+
+    let mut out = out
+        .try_readwrite()
+        .map_err(|err| PyValueError::new_err(format!("`out` is not writable: {}", err)))?;
+    let dst = out
+        .as_slice_mut()
+        .map_err(|_| PyValueError::new_err("`out` should be C-contiguous"))?;
+    copy_rows(raw, width, dst, shape[1], channels);
+
+    Ok((height, width))
+}
+
+/// A `(height, width)` numpy array as a grayscale image, or a `(height, width, 3)` one as an RGB
+/// image.
+#[cfg(feature = "python")]
+pub fn array_to_image(image: &PyReadonlyArrayDyn<'_, u8>) -> PyResult<DynamicImage> {
+    let shape = image.shape().to_vec();
+    let raw: Vec<u8> = image.as_array().iter().copied().collect();
+    match shape.as_slice() {
+        [height, width] if *height > 0 && *width > 0 => {
+            GrayImage::from_vec(*width as u32, *height as u32, raw).map(DynamicImage::ImageLuma8)
+        }
+        [height, width, 3] if *height > 0 && *width > 0 => {
+            RgbImage::from_vec(*width as u32, *height as u32, raw).map(DynamicImage::ImageRgb8)
+        }
+        _ => None,
+    }
+    .ok_or_else(|| {
+        PyValueError::new_err("`image` should have the shape (height, width) or (height, width, 3)")
+    })
 }
 
 #[cfg(test)]
@@ -186,6 +535,19 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_copy_rows() {
+        let src = [1, 2, 3, 4, 5, 6];
+        let mut dst = [0; 12];
+        copy_rows(&src, 3, &mut dst, 4, 1);
+        assert_eq!(dst, [1, 2, 3, 0, 4, 5, 6, 0, 0, 0, 0, 0]);
+
+        let src = [1, 1, 1, 2, 2, 2];
+        let mut dst = [0; 12];
+        copy_rows(&src, 1, &mut dst, 2, 3);
+        assert_eq!(dst, [1, 1, 1, 0, 0, 0, 2, 2, 2, 0, 0, 0]);
+    }
+
     #[test]
     fn test_string_dedup() {
         let data = fs::read_to_string("./latin_corpus.txt").unwrap();
@@ -193,4 +555,49 @@ mod test {
 
         println!("{:#?}", result);
     }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_font_attrs_names() {
+        assert_eq!(
+            style_code(StyleLike::Name("Italic".to_string())).unwrap(),
+            1
+        );
+        assert_eq!(
+            weight_code(WeightLike::Name("semi-bold".to_string())).unwrap(),
+            600
+        );
+        assert_eq!(
+            stretch_code(StretchLike::Name("ultra_condensed".to_string())).unwrap(),
+            1
+        );
+        assert_eq!(style_code(StyleLike::Enum(PyStyle::Oblique)).unwrap(), 2);
+        assert_eq!(weight_code(WeightLike::Enum(PyWeight::Bold)).unwrap(), 700);
+        assert_eq!(
+            stretch_code(StretchLike::Enum(PyStretch::Condensed)).unwrap(),
+            3
+        );
+
+        let attrs = FontAttrs {
+            family: "SimSun".to_string(),
+            style: 1,
+            weight: 700,
+            stretch: 5,
+        };
+        assert_eq!(FontAttrs::from(&attrs.to_internal()), attrs);
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_font_index() {
+        let font_list = vec![
+            InternalAttrsOwned::from_tuple(("SimSun".to_string(), 0, 400, 5)),
+            InternalAttrsOwned::from_tuple(("SimHei".to_string(), 0, 700, 5)),
+        ];
+        assert_eq!(
+            FontAttrsLike::Index(1).into_internal(&font_list).unwrap(),
+            font_list[1]
+        );
+        assert!(FontAttrsLike::Index(2).into_internal(&font_list).is_err());
+    }
 }
@@ -0,0 +1,154 @@
+//! wasm-bindgen interface for previewing a config file in the browser: text in, PNG bytes out.
+//!
+//! There is no file system in the browser, so the font, corpus and background paths of the
+//! config file are ignored. Fonts are added from bytes with `add_font`, and fallback between
+//! them is left to cosmic-text.
+
+use std::io::Cursor;
+
+use cosmic_text::{fontdb, Attrs, Buffer, BufferLine, Color, FontSystem, Metrics, SwashCache};
+use image::ImageOutputFormat;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    cv_util::CvUtil,
+    image_process::{
+        build_line, generate_image, CharColors, FauxStyle, GlyphJitter, InkTexture, Kerning,
+        Padding, RenderOptions, Spacing, VerticalLayout,
+    },
+    opentype::OpenTypeFeatures,
+    parse_config::Config,
+};
+
+#[wasm_bindgen]
+pub struct PreviewGenerator {
+    font_system: FontSystem,
+    swash_cache: SwashCache,
+    editor_buffer: Buffer,
+    options: RenderOptions,
+    ligatures: bool,
+    cv_util: CvUtil,
+}
+
+#[wasm_bindgen]
+impl PreviewGenerator {
+    /// `config` is the content of a config file.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: &str) -> Result<PreviewGenerator, JsError> {
+        let config = Config::from_yaml_str(config);
+
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".to_string(), fontdb::Database::new());
+        let mut buffer = Buffer::new(
+            &mut font_system,
+            Metrics::new(config.font_size as f32, config.line_height as f32),
+        );
+        buffer.set_size(
+            &mut font_system,
+            config.font_img_width as f32,
+            config.font_img_height as f32,
+        );
+
+        let features =
+            OpenTypeFeatures::from_names(&config.opentype_features, &config.font_opentype_features)
+                .map_err(|err| JsError::new(&err))?;
+
+        Ok(Self {
+            font_system,
+            swash_cache: SwashCache::new(),
+            editor_buffer: buffer,
+            options: RenderOptions {
+                features,
+                kerning: Kerning {
+                    enabled: config.kerning,
+                    jitter: config.kerning_jitter,
+                },
+                ink_texture: InkTexture {
+                    strength: config.ink_texture_strength,
+                    scale: config.ink_texture_scale,
+                },
+                padding: Padding {
+                    tight: config.tight_crop,
+                    ..Default::default()
+                },
+                glyph_size_jitter: config.glyph_size_jitter,
+                image_glyphs: None,
+                char_colors: CharColors::from_config(&config),
+                vertical: VerticalLayout::from_config(&config),
+                faux_style: FauxStyle::default(),
+                spacing: Spacing::default(),
+                glyph_jitter: GlyphJitter::from_config(&config),
+                text_path: None,
+                drop_shadow: None,
+                decoration: None,
+            },
+            ligatures: config.ligatures,
+            cv_util: CvUtil::from_config(&config),
+        })
+    }
+
+    /// Add a font file (TTF/OTF/TTC).
+    pub fn add_font(&mut self, data: Vec<u8>) {
+        self.font_system.db_mut().load_font_data(data);
+    }
+
+    /// Render one line of text into PNG bytes. With `apply_effect`, the cv effects of the config
+    /// file are applied and the result is a grayscale image.
+    pub fn render_png(
+        &mut self,
+        text: &str,
+        text_color: &[u8],
+        background_color: &[u8],
+        apply_effect: bool,
+    ) -> Result<Vec<u8>, JsError> {
+        let (text_color, background_color) = match (text_color, background_color) {
+            ([r, g, b], [bg_r, bg_g, bg_b]) => {
+                (Color::rgb(*r, *g, *b), image::Rgb([*bg_r, *bg_g, *bg_b]))
+            }
+            _ => return Err(JsError::new("colors should be given as [r, g, b]")),
+        };
+
+        let attrs = Attrs::new();
+        if let Some(vertical) = &self.options.vertical {
+            vertical.set_lines(
+                &mut self.editor_buffer,
+                &mut self.font_system,
+                vec![(text, attrs)],
+                attrs,
+                self.ligatures,
+            );
+        } else {
+            let (line_text, attrs_list) = build_line(vec![(text, attrs)], attrs, self.ligatures);
+            self.editor_buffer.lines.clear();
+            self.editor_buffer.lines.push(BufferLine::new(
+                &line_text,
+                attrs_list,
+                cosmic_text::Shaping::Advanced,
+            ));
+            self.editor_buffer
+                .shape_until_scroll(&mut self.font_system, false);
+        }
+
+        let (img, _) = generate_image(
+            &mut self.editor_buffer,
+            &mut self.font_system,
+            &mut self.swash_cache,
+            text_color,
+            background_color,
+            &self.options,
+        );
+
+        let mut png = Cursor::new(vec![]);
+        let res = if apply_effect {
+            let gray = image::imageops::grayscale(&img);
+            self.cv_util
+                .apply_effect(gray)
+                .write_to(&mut png, ImageOutputFormat::Png)
+        } else {
+            img.write_to(&mut png, ImageOutputFormat::Png)
+        };
+        res.map_err(|err| JsError::new(&err.to_string()))?;
+
+        Ok(png.into_inner())
+    }
+}
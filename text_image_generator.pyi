@@ -1,6 +1,70 @@
-from typing import Tuple
+from typing import Any, Optional, Tuple, Union
 import numpy.typing as npt
 
+class Style:
+    Normal: Style
+    Italic: Style
+    Oblique: Style
+
+    def __int__(self) -> int: ...
+
+class Weight:
+    Thin: Weight
+    ExtraLight: Weight
+    Light: Weight
+    Normal: Weight
+    Medium: Weight
+    SemiBold: Weight
+    Bold: Weight
+    ExtraBold: Weight
+    Black: Weight
+
+    def __int__(self) -> int: ...
+
+class Stretch:
+    UltraCondensed: Stretch
+    ExtraCondensed: Stretch
+    Condensed: Stretch
+    SemiCondensed: Stretch
+    Normal: Stretch
+    SemiExpanded: Stretch
+    Expanded: Stretch
+    ExtraExpanded: Stretch
+    UltraExpanded: Stretch
+
+    def __int__(self) -> int: ...
+
+class FontAttrs:
+    family: str
+    style: int
+    weight: int
+    stretch: int
+
+    def __init__(
+        self,
+        family: str,
+        style: Optional[Union[Style, int, str]] = None,
+        weight: Optional[Union[Weight, int, str]] = None,
+        stretch: Optional[Union[Stretch, int, str]] = None,
+    ) -> None:
+        """
+        Create font attributes. Style, weight and stretch can be given by enum, numeric code or name.
+
+        :param family: font family name
+        :param style: `Style.Italic`, `1` or `"italic"`, defaults to normal
+        :param weight: `Weight.Bold`, `700` or `"bold"`, defaults to 400
+        :param stretch: `Stretch.Condensed`, `3` or `"condensed"`, defaults to 5 (normal)
+        """
+    @classmethod
+    def from_tuple(cls, src: Tuple[str, int, int, int]) -> FontAttrs:
+        """
+        Create font attributes from the legacy `(family, style, weight, stretch)` tuple.
+        """
+    def to_tuple(self) -> Tuple[str, int, int, int]:
+        """
+        Convert to the legacy `(family, style, weight, stretch)` tuple.
+        """
+
 class CvUtil:
     def apply_effect(
         self,
@@ -12,6 +76,83 @@ class CvUtil:
         :param img: grayscale image
         :return: the resulting image
         """
+    def apply_color_jitter(self, img: npt.NDArray) -> npt.NDArray:
+        """
+        Randomly jitter the hue, saturation and value according to `color_jitter_prob`, `hue_shift`, `saturation_scale` and `value_scale` in the config file.
+
+        :param img: RGB image
+        :return: the resulting image
+        """
+    @classmethod
+    def hsv_jitter(
+        cls,
+        img: npt.NDArray,
+        hue_shift: float,
+        saturation_scale: float,
+        value_scale: float,
+    ) -> npt.NDArray:
+        """
+        Rotate the hue and scale the saturation and value.
+
+        :param img: RGB image
+        :param hue_shift: hue rotation in degrees
+        :param saturation_scale: saturation scale factor
+        :param value_scale: value scale factor
+        :return: the resulting image
+        """
+    def apply_curvature(self, img: npt.NDArray) -> npt.NDArray:
+        """
+        Randomly curve the image like a book page near the spine, according to `curvature_prob`
+        and `curvature_strength` in the config file.
+
+        :param img: grayscale image
+        :return: the resulting image
+        """
+    def apply_wave(self, img: npt.NDArray) -> npt.NDArray:
+        """
+        Randomly bend the image along a sine wave, according to `wave_prob`, `wave_amplitude`, `wave_wavelength`, `wave_phase` and `wave_rows_prob` in the config file.
+
+        :param img: grayscale image
+        :return: the resulting image
+        """
+    @classmethod
+    def wave_warp(
+        cls,
+        img: npt.NDArray,
+        amplitude: float,
+        wavelength: float,
+        phase: float = 0.0,
+        shift_rows: bool = False,
+    ) -> npt.NDArray:
+        """
+        Shift every column up or down by `amplitude * sin(2 * pi * x / wavelength + phase)` pixels, or every row left or right with `shift_rows`; the pixels shifted in from outside repeat the border.
+
+        :param img: grayscale image
+        :param amplitude: the largest shift in pixels
+        :param wavelength: the period of the wave in pixels
+        :param phase: the phase in degrees
+        :param shift_rows: whether to shift the rows instead of the columns
+        :return: the resulting image
+        """
+    def apply_noise(self, img: npt.NDArray) -> npt.NDArray:
+        """
+        Randomly add speckle, gaussian and salt-and-pepper noise, according to `speckle_noise_prob`, `gaussian_noise_prob` and `salt_pepper_prob` and their strengths in the config file.
+
+        :param img: grayscale image
+        :return: the resulting image
+        """
+    @classmethod
+    def cylinder_warp(
+        cls, img: npt.NDArray, strength: float, spine_on_left: bool = True
+    ) -> npt.NDArray:
+        """
+        Project the image onto a cylinder, compressing and shading it towards the spine edge.
+
+        :param img: grayscale image
+        :param strength: fraction of the width (0 to 1) that is curved
+        :param spine_on_left: whether the spine is at the left edge
+        :return: the resulting image
+        """
     @classmethod
     def warp_perspective_transform(
         cls, img: npt.NDArray, rotate_angle: Tuple[int, int, int]
@@ -24,6 +165,26 @@ class CvUtil:
         :return: the resulting image
         """
     @classmethod
+    def erode(cls, img: npt.NDArray, width: int, height: int) -> npt.NDArray:
+        """
+        Thin the dark strokes of a text image: every pixel takes the brightest value of the rectangle around it.
+
+        :param img: grayscale image, dark text on a light background
+        :param width: the width of the rectangle
+        :param height: the height of the rectangle
+        :return: the resulting image
+        """
+    @classmethod
+    def dilate(cls, img: npt.NDArray, width: int, height: int) -> npt.NDArray:
+        """
+        Spread the dark strokes of a text image: every pixel takes the darkest value of the rectangle around it.
+
+        :param img: grayscale image, dark text on a light background
+        :param width: the width of the rectangle
+        :param height: the height of the rectangle
+        :return: the resulting image
+        """
+    @classmethod
     def apply_emboss(cls, img: npt.NDArray) -> npt.NDArray:
         """
         Apply emboss filter.
@@ -78,6 +239,20 @@ class MergeUtil:
         :param bg_width: width of the background image
         :return: the resulting image
         """
+    def random_background(self, bg_factory: BgFactory) -> npt.NDArray:
+        """
+        Get a random background image from the factory. With probability `bg_blend_prob` in the config file, two backgrounds are alpha blended or patched side by side instead.
+
+        :param bg_factory: the background images to choose from
+        :return: the resulting background image
+        """
+    def random_background_rgb(self, bg_factory: BgFactory) -> npt.NDArray:
+        """
+        Same as `random_background`, but in color. The factory should be created with `color=True`.
+
+        :param bg_factory: the background images to choose from
+        :return: the resulting (height, width, 3) background image
+        """
     def random_change_bgcolor(self, bg_img: npt.NDArray) -> npt.NDArray:
         """
         Randomly change background color.
@@ -93,31 +268,127 @@ class MergeUtil:
         :param bg_img: grayscale background image
         :return: the resulting merge image
         """
+    def poisson_edit_rgb(self, font_img: npt.NDArray, bg_img: npt.NDArray) -> npt.NDArray:
+        """
+        Use poisson editing to merge the text image onto a color background, each channel is solved with the same text.
+
+        :param font_img: grayscale text image
+        :param bg_img: (height, width, 3) RGB background image
+        :return: the resulting (height, width, 3) merge image
+        """
 
 class BgFactory:
     height: int
     width: int
+    color: bool
 
-    def __init__(self, dir: str, height: int, width: int) -> None: ...
+    def __init__(self, dir: str, height: int, width: int, color: bool = False) -> None:
+        """
+        :param dir: the directory of the background images
+        :param height: height of the backgrounds
+        :param width: width of the backgrounds
+        :param color: also keep the RGB version of each background, for `MergeUtil.random_background_rgb`
+        """
     def random(self) -> npt.NDArray:
         """
-        Get a random background image.
+        Get a random background image. The alpha channel of a background with transparency is ignored here; `MergeUtil` composites it over `bg_base_color` or a second background when merging.
 
         :return: the resulting background image
         """
+    def rgb(self, index: int) -> npt.NDArray | None:
+        """
+        Get the RGB version of a background image.
+
+        :param index: index of the background image
+        :return: the (height, width, 3) background image, None if the factory is not created with `color=True`
+        """
+
+class FontUtil:
+    def __init__(self, font_dir: str) -> None:
+        """
+        :param font_dir: the directory to load fonts from, recursively
+        """
+    def get_full_font_list(self) -> list[FontAttrs]:
+        """
+        Get all the loaded font faces.
+        """
+    def fonts_containing(self, ch: str) -> list[FontAttrs]:
+        """
+        Get the font faces that can render a character (or a grapheme cluster).
+
+        :param ch: the character to check
+        :return: a list of font faces
+        """
+    def font_name_to_attrs(self, name: str) -> FontAttrs:
+        """
+        Get the attributes of a font family, a random face is chosen if the family has several. Raises `ValueError` if the family is not loaded.
+
+        :param name: font family name
+        """
 
 class Generator:
+    """
+    `gen_image_from_text_with_font_list` and `gen_images_from_text_with_font_list` release the GIL
+    while rendering, and one generator can be shared by several threads: each thread renders with
+    its own font caches and buffer, while the fonts and dicts are loaded once. The other methods
+    hold the GIL.
+    """
+
     cv_util: CvUtil
     merge_util: MergeUtil
     bg_factory: BgFactory
-    font_list: Tuple[str, int, int, int]
-    chinese_ch_dict: dict[str, list[Tuple[str, int, int, int]]]
+    font_list: list[FontAttrs]
+    chinese_ch_dict: dict[str, list[FontAttrs]]
     latin_corpus: str
-    latin_ch_dict: dict[str, list[Tuple[str, int, int, int]]]
-    symbol_dict: dict[str, list[Tuple[str, int, int, int]]]
+    latin_ch_dict: dict[str, list[FontAttrs]]
+    symbol_dict: dict[str, list[FontAttrs]]
+    rtl_corpus: Optional[str]
+    rtl_ch_dict: Optional[dict[str, list[FontAttrs]]]
     main_font_list: str
+    font_util: FontUtil
+    """a `FontUtil` over the fonts loaded by this generator"""
+    profile: Optional[str]
+    """the profile in use, `None` for the CV and MERGE sections of the config file as they are"""
+    profiles: list[str]
+    """the profiles defined under `PROFILES` in the config file"""
+    difficulty: Optional[str]
+    """the difficulty set by `set_difficulty`, `None` for the parameters of the config file"""
+    difficulty_levels: list[str]
+    """the built-in difficulty levels, from the easiest"""
+    seed: Optional[int]
+    """the seed given to the constructor or the last `reseed`"""
+
+    def __init__(
+        self,
+        config_path: str = "./config.yaml",
+        profile: Optional[str] = None,
+        overrides: Optional[dict[str, Any]] = None,
+        seed: Optional[int] = None,
+    ) -> None:
+        """
+        :param config_path: the config file
+        :param profile: a profile defined under `PROFILES` in the config file, replacing some keys of its CV and MERGE sections
+        :param overrides: config keys replaced after the profile and the `TIG_<SECTION>__<KEY>` environment variables, e.g. `{"MERGE.reverse_prob": 0.2}`; they are kept by `set_profile`, and an unknown key raises a `ValueError`
+        :param seed: seed of the random number generators, see `reseed`
+        """
+    def reseed(self, seed: int) -> None:
+        """
+        Reseed the random number generator of this generator. The text, fonts, layout, effects and backgrounds generated afterwards are the same as after another `reseed` with the same seed, or in a new generator with that seed, so a dataset can be regenerated exactly. Every `Generator` and `ImageEffect` has its own state and does not change the sequence of another. Calls from several threads at once each draw their own seed, so the result depends on the order of the calls, while `gen_batch` seeds each sample and does not depend on the thread scheduling.
+
+        :param seed: the seed
+        """
+    def set_profile(self, profile: Optional[str] = None) -> None:
+        """
+        Switch to another profile of the config file, rebuilding `cv_util` and `merge_util`; the backgrounds are reloaded only if their directory or size changes.
+
+        :param profile: a profile defined under `PROFILES`, or `None` for the CV and MERGE sections as they are
+        """
+    def set_difficulty(self, level: Optional[str] = None) -> None:
+        """
+        Switch to a built-in bundle of effect parameters, replacing `cv_util` and `merge_util` together, e.g. for a curriculum that starts easy. The preset is applied after the profile and before the overrides given to the constructor, and is kept by `set_profile`.
 
-    def __init__(self, config_path: str) -> None: ...
+        :param level: "easy", "medium" or "hard" (see `difficulty_levels`), or `None` for the parameters of the config file
+        """
     def set_bg_size(
         self,
         height: int,
@@ -128,39 +399,784 @@ class Generator:
         :param height: specify the height of the background image
         :param width: specify the width of the background image
         """
+    def set_line_metrics(
+        self,
+        font_size: Optional[Tuple[float, float]] = None,
+        line_height: Optional[Tuple[float, float]] = None,
+    ) -> None:
+        """
+        Draw the font size and the line height of every sample uniformly from a range, overriding `font_size_range` and `line_height_range` in the config file.
+
+        :param font_size: (min, max) in pixels, or `None` for `font_size`; keep it below the line height
+        :param line_height: (min, max) in pixels, or `None` for `line_height`; with a range the image is as tall as the line
+        """
+    def add_characters(
+        self, chars: list[str], dict: str = "chinese", freq: float = 1.0
+    ) -> None:
+        """
+        Add characters to a dictionary, their font lists are found from the loaded fonts. The frequency of a character already in `chinese_ch_dict` is updated.
+
+        :param chars: characters (or grapheme clusters) to add
+        :param dict: `chinese`, `latin`, `symbol` or `rtl`
+        :param freq: sampling frequency of the characters, only used by `chinese_ch_dict`
+        """
+    def remove_characters(self, chars: list[str], dict: str = "chinese") -> None:
+        """
+        Remove characters from a dictionary.
+
+        :param chars: characters to remove
+        :param dict: `chinese`, `latin`, `symbol` or `rtl`
+        """
+    def remove_chars_from_file(self, path: str, dict: str = "chinese") -> None:
+        """
+        Remove the characters of a file from a dictionary, e.g. the ones missing from your label set. Whitespace in the file is ignored, so it can list one character per line. The sampling weights are renormalized over the remaining characters.
+
+        :param path: the file of characters
+        :param dict: `chinese`, `latin`, `symbol` or `rtl`
+        """
+    def add_font_dir(self, path: str) -> list[FontAttrs]:
+        """
+        Load the fonts of a directory without constructing the generator again: only the new fonts are analysed, and each is added to the font lists of the characters it covers in every dictionary. A font that is already loaded is not added to the font lists twice.
+
+        :param path: the directory of fonts
+        :return: the new fonts
+        """
+    def add_font_data(self, data: bytes) -> list[FontAttrs]:
+        """
+        Load a font file (TTF/OTF/TTC) from bytes, e.g. a downloaded or embedded font, where no writable font directory is available. The new fonts are analysed and added to the font lists of the characters they cover, as in `add_font_dir`.
+
+        :param data: the content of the font file
+        :return: the new fonts
+        """
+    def set_font_weights(self, weights: dict[str, float]) -> None:
+        """
+        Choose the font of each character from its font list by the weights of the font families, so that common fonts dominate while decorative ones appear occasionally. Overrides `font_weights` in the config file.
+
+        :param weights: the weight of each font family, the families not listed weigh 1; an empty dict chooses uniformly
+        """
+    def set_char_fonts(
+        self,
+        ch: str,
+        font_list: list[Union[FontAttrs, Tuple[str, int, int, int], int]],
+        dict: str = "chinese",
+    ) -> None:
+        """
+        Override the font list of a character, e.g. to exclude a font whose glyph is wrong. The character is added if it is not in the dictionary.
+
+        :param ch: the character
+        :param font_list: the fonts to render the character with
+        :param dict: `chinese`, `latin`, `symbol` or `rtl`
+        """
+    def set_sampling_weights(
+        self, inverse: bool = False, temperature: float = 1.0
+    ) -> None:
+        """
+        Recompute the sampling weights of `chinese_ch_dict` from the frequency column of the character file, overriding `inverse_frequency` and `sampling_temperature` in the config file. Characters with a zero frequency are never sampled.
+
+        :param inverse: sample by `1 / freq`, so that rare characters are over-represented
+        :param temperature: the weights are raised to `1 / temperature`, values above 1 flatten the distribution and values below 1 sharpen it
+        """
+    def restrict_charset(self, chars: list[str]) -> None:
+        """
+        Only sample Chinese characters from the given subset of `chinese_ch_dict` until `reset_charset` is called, e.g. for curriculum stages. Characters not in the dictionary are ignored. Raises `ValueError` if no character of the subset has a positive frequency.
+
+        :param chars: the characters to sample from
+        """
+    def set_script(self, script: str) -> None:
+        """
+        Emit the generated Chinese text in another script, converting character by character with the OpenCC-style table of `script_conversion_file_path`. Each converted character gets the font list of its own, a character whose converted form no font covers is left as it is.
+
+        :param script: "none", "traditional", "simplified" or "mixed" (each character randomly in either form)
+        """
+    def set_balanced_sampling(self, min_count: Optional[int] = None) -> None:
+        """
+        Guarantee that every Chinese character that can be sampled is generated at least `min_count` times, counting from now: until it is, the characters still short of it are dealt from a shuffled deck, epoch after epoch without replacement, and only then are characters drawn by weight again. The counts are shared by all threads. Defaults to `balanced_min_count` in the config file.
+
+        :param min_count: the guaranteed number of occurrences, `None` or 0 to turn balancing off
+        """
+    def get_char_counts(self) -> dict[str, int]:
+        """
+        How often each character of `chinese_ch_dict` has been generated since `set_balanced_sampling`. Raises `ValueError` if balancing is off.
+        """
+    def restrict_charset_from_file(self, path: str) -> None:
+        """
+        `restrict_charset` to the characters of a file, e.g. the label dict of a recognizer with one character per line. Whitespace in the file is ignored.
+
+        :param path: the file of characters
+        """
+    def reset_charset(self) -> None:
+        """
+        Sample from the whole `chinese_ch_dict` again.
+        """
+    def add_image_glyph(self, ch: str, image: npt.NDArray) -> None:
+        """
+        Register an image glyph, e.g. a cropped handwriting sample, drawn instead of the font for a character. The image is scaled into the cell of the character (its advance wide and one em tall), so it should be cropped to the character cell rather than tightly to the ink. Several images of one character are chosen at random, and a character uses an image with `image_glyph_prob` of the config file. Images can also be loaded from `image_glyph_dir`.
+
+        :param ch: the character
+        :param image: a grayscale image of shape (height, width), dark ink on a light background
+        """
+    def remove_image_glyph(self, ch: str) -> None:
+        """
+        Remove every image glyph of a character, which is drawn with its font again.
+
+        :param ch: the character
+        """
+    def font_coverage_report(self, common: int = 500) -> list[Tuple[str, int, list[str]]]:
+        """
+        For each font family, count how many characters of `chinese_ch_dict` it covers (only the charset if it is restricted), and list the most frequent characters it misses, e.g. to curate the font folder or to find the cause of frequent tofu.
+
+        :param common: how many of the most frequent characters are checked for the missing list
+        :return: a list of (font family, number of covered characters, missing common characters), the families covering the most characters first
+        """
+    def get_font_usage_stats(self, reset: bool = False) -> list[Tuple[FontAttrs, str, int]]:
+        """
+        Count how many characters each loaded font has actually rendered, after the random choice of the fonts and the fallback to `main_font_list`, e.g. to check that every licensed font contributes and none dominates.
+
+        :param reset: whether to start counting over afterwards, e.g. once per batch
+        :return: a list of (font, font file, count), most used first, the unused fonts included
+        """
+    def get_uncovered_chars(self) -> dict[str, list[str]]:
+        """
+        Get the characters of each dictionary that no loaded font can render. They are drawn with a font of `main_font_list` and usually come out as tofu, so the labels would be wrong; set `strict_coverage` in the config file to refuse to start instead.
+
+        :return: the uncovered characters by dictionary (`chinese`, `latin`, `symbol` or `rtl`), dictionaries without any are left out
+        """
+    def get_uncovered_supplementary_chars(self) -> list[str]:
+        """
+        Get the characters above U+FFFF (e.g. CJK Ext B–G) in the character file that no font can render.
+
+        :return: a list of uncovered characters
+        """
     def get_random_chinese(
-        self, min: int, max: int, add_extra_symbol: bool = False
-    ) -> list[Tuple[str, list[Tuple[str, int, int, int]]]]:
+        self,
+        min: int,
+        max: int,
+        add_extra_symbol: bool = False,
+        font_indices: bool = False,
+    ) -> list[Tuple[str, Union[list[FontAttrs], list[int]]]]:
         """
-        Generate random text with chinese characters.
+        Generate random text with chinese characters. When `sentence_file_path` is set in the config file, a real sentence is sampled instead: a random window of `min` to `max` clusters of a line with `sentence_substring`, otherwise the whole line, and `add_extra_symbol` is ignored.
 
         :param min: specify the minimum word count for generated text
         :param max: specify the maximum word count for generated text
-        :param add_extra_symbol: whether to add punctuation to the generated text
+        :param add_extra_symbol: whether to add punctuation to the generated text, how many and where is set by `symbol_count`, `symbol_prob`, `symbol_at_start` and `symbol_at_end` in the config file
+        :param font_indices: return the fonts as indices into `font_list`, which are stable for the lifetime of the generator and cheaper to pass back to the gen methods
+        :return: a list of tuples that contains text and font infos
+        """
+    def get_random_chinese_ngram(
+        self, min: int = 5, max: int = 10, font_indices: bool = False
+    ) -> list[Tuple[str, Union[list[FontAttrs], list[int]]]]:
+        """
+        Generate random chinese text from the character n-gram model counted from `ngram_corpus_file_path`, so that each character follows the previous one or two as in the corpus. Where the corpus has no continuation the character is drawn as in `get_random_chinese`. Requires `ngram_corpus_file_path` in the config file.
+
+        :param min: specify the minimum word count for generated text
+        :param max: specify the maximum word count for generated text
+        :param font_indices: see `get_random_chinese`
+        :return: a list of tuples that contains text and font infos
+        """
+    def get_random_chinese_by_width(
+        self, min_width: Optional[float] = None, max_width: Optional[float] = None
+    ) -> list[Tuple[str, list[FontAttrs]]]:
+        """
+        Generate random text with chinese characters until the estimated rendered width reaches a random target in `[min_width, max_width]`. The width of each character is estimated from the advance widths of its fonts at `font_size`, kerning and ligatures are ignored.
+
+        :param min_width: minimum target width in pixels, defaults to 80% of `max_width`
+        :param max_width: maximum target width in pixels, defaults to `font_img_width`
         :return: a list of tuples that contains text and font infos
         """
+    def get_random_text(
+        self,
+        min: int = 5,
+        max: int = 10,
+        ratios: Optional[Tuple[float, float, float]] = None,
+    ) -> list[Tuple[str, list[FontAttrs]]]:
+        """
+        Get a random mixed-script text. Each position is a Chinese character, a Latin word from the Latin corpus or a symbol from the symbol file; adjacent Latin words are separated by a space.
+
+        :param min: minimum number of positions
+        :param max: maximum number of positions
+        :param ratios: probabilities of Chinese characters, Latin words and symbols, defaults to `mixed_pool_ratios` in the config file
+        :return: a list of tuples that contains text and font infos
+        """
+    def get_random_mixed(
+        self,
+        min: int = 5,
+        max: int = 10,
+        latin_ratio: float = 0.2,
+        digit_ratio: float = 0.1,
+        font_indices: bool = False,
+    ) -> list[Tuple[str, Union[list[FontAttrs], list[int]]]]:
+        """
+        Get a random line interleaving Chinese characters, Latin words from the Latin corpus and groups of 1 to 6 digits. Adjacent words and digit groups are separated by a space.
+
+        :param min: minimum number of positions, each is a Chinese character, a Latin word or a digit group
+        :param max: maximum number of positions
+        :param latin_ratio: probability of a Latin word at each position, requires `latin_corpus_file_path` in the config file if positive
+        :param digit_ratio: probability of a digit group at each position, the rest are Chinese characters
+        :param font_indices: see `get_random_chinese`
+        :return: a list of tuples that contains text and font infos
+        """
+    def get_random_latin_words(
+        self,
+        min: int = 3,
+        max: int = 8,
+        by_frequency: bool = True,
+        font_indices: bool = False,
+    ) -> list[Tuple[str, list[Union[FontAttrs, int]]]]:
+        """
+        Generate a line of whole words from `latin_corpus_file_path`, separated by spaces, so that Latin samples look like real language instead of random letters. The punctuation around each word in the corpus is stripped.
+
+        :param min: the min number of words
+        :param max: the max number of words
+        :param by_frequency: whether to draw the words by how often they appear in the corpus, otherwise every distinct word is equally likely
+        :param font_indices: return fonts as indices into `font_list`, see `get_random_chinese`
+        :return: a list of (cluster, fonts), the same as `get_random_chinese`
+        """
+    def get_random_rtl_words(
+        self,
+        min: int = 3,
+        max: int = 8,
+        by_frequency: bool = True,
+        font_indices: bool = False,
+    ) -> list[Tuple[str, list[Union[FontAttrs, int]]]]:
+        """
+        Generate a line of whole words from `rtl_corpus_file_path` (Arabic, Hebrew, ...), separated by spaces. Each word is a single unit whose fonts can render all of it, so that its letters are shaped together into their joined forms; the text is in logical order and laid out right to left when rendered.
+
+        :param min: the min number of words
+        :param max: the max number of words
+        :param by_frequency: whether to draw the words by how often they appear in the corpus, otherwise every distinct word is equally likely
+        :param font_indices: return fonts as indices into `font_list`, see `get_random_chinese`
+        :return: a list of (word or space, fonts)
+        """
+    def get_random_template(
+        self, template: str, font_indices: bool = False
+    ) -> list[Tuple[str, Union[list[FontAttrs], list[int]]]]:
+        """
+        Generate a structured string from a template, e.g. `"{YYYY}年{MM}月{DD}日"` or `"¥{0-9}{3},{0-9}{3}.{0-9}{2}"`. Outside braces the template is literal text, `{{` and `}}` are literal braces. Inside braces:
+
+        - `YYYY`, `YY`, `MM`, `M`, `DD`, `D`, `hh`, `mm`, `ss`: a field of one random date and time shared by the whole template
+        - `0-9`, `A-Z`, `0-9A-F`: one random character of the ranges
+        - `a|b|c`: one of the alternatives
+        - `3` or `2,4`: repeat the previous item (or the last literal character) 3 times, or 2 to 4 times
+
+        :param template: the template
+        :param font_indices: see `get_random_chinese`
+        :return: a list of tuples that contains text and font infos
+        """
+    def get_random_numeric(
+        self,
+        kind: str = "amount",
+        locale: str = "en",
+    ) -> list[Tuple[str, list[FontAttrs]]]:
+        """
+        Get a random numeric string, e.g. `$1,234.56`, `138 1234 5678`, `AB-20240315` or `12,5 %`.
+
+        :param kind: amount with thousands separators and currency, phone number, ID/serial number or percentage
+        :param locale: number style, `en` (1,234.56), `zh` (1,234.56 with ¥ or 元), `de` (1.234,56), `fr` (1 234,56) or `in` (12,34,567.89)
+        :return: a list of tuples that contains text and font infos
+        """
+    def get_random_sample(
+        self, min: int = 5, max: int = 10
+    ) -> list[Tuple[str, list[FontAttrs]]]:
+        """
+        Get a random text from one of the `corpus_sources` in the config file, picked by weight for every sample.
+
+        :param min: the lower limit of the length, in characters for sentences, otherwise as the `get_random_*` method of the source
+        :param max: the upper limit of the length
+        :return: a list of tuples that contains text and font infos
+        """
+    def insert_spaces(
+        self,
+        text_with_font_list: list[
+            Tuple[str, list[Union[FontAttrs, Tuple[str, int, int, int], int]]]
+        ],
+    ) -> list[Tuple[str, list[FontAttrs]]]:
+        """
+        Insert random runs of spaces between the characters and possibly a leading indentation, according to `space_prob`, `space_run_len`, `indent_prob` and `indent_len` in the config file.
+
+        :param text_with_font_list: a list of tuples that contains text and font infos
+        :return: a list of tuples that contains text and font infos
+        """
+    def get_noisy_label(
+        self,
+        text_with_font_list: list[
+            Tuple[str, list[Union[FontAttrs, Tuple[str, int, int, int], int]]]
+        ],
+    ) -> Tuple[str, bool]:
+        """
+        Get the normalized label, perturbed with probability `label_noise_prob` in the config file: one character is substituted (by a member of its confusable group if it has one), deleted or inserted. The image should still be rendered from the original text.
+
+        :param text_with_font_list: a list of tuples that contains text and font infos
+        :return: the label and whether it is perturbed
+        """
+    def normalize_label(self, text: str) -> str:
+        """
+        Normalize a label according to `label_normalization` (none, NFC or NFKC), `fold_full_width` and `collapse_label_spaces` in the config file.
+
+        :param text: the raw label
+        :return: the normalized label
+        """
+    def get_label(
+        self, text_with_font_list: list[Tuple[str, list[FontAttrs]]]
+    ) -> Tuple[str, str]:
+        """
+        Get the label of a text with font list, e.g. one returned by `get_random_chinese`.
+
+        :param text_with_font_list: a list of tuples that contains text and font infos
+        :return: the raw label and the normalized label
+        """
     def wrap_text_with_font_list(
-        self, text: str
-    ) -> list[Tuple[str, list[Tuple[str, int, int, int]]]]:
+        self, text: str, segment: bool = False, font_indices: bool = False
+    ) -> list[Tuple[str, Union[list[FontAttrs], list[int]]]]:
         """
         Gets the available font information for each character in the specified text.
 
         :param text: a simple sentence of text
-        :return: a list of tuples that contains text and font infos
+        :param segment: split the text into words with the dictionary at `word_dict_file_path` in the config file, each word then gets the fonts that can render all of its characters so that it is drawn in one font
+        :param font_indices: return the fonts as indices into `font_list`, see `get_random_chinese`
+        :return: a list of tuples that contains text (a character or a word) and font infos
         """
     def gen_image_from_text_with_font_list(
         self,
-        text_with_font_list: list[Tuple[str, list[Tuple[str, int, int, int]]]],
-        text_color: Tuple[int, int, int],
-        background_color: Tuple[int, int, int],
+        text_with_font_list: list[
+            Tuple[str, list[Union[FontAttrs, Tuple[str, int, int, int], int]]]
+        ],
+        text_color: Optional[Tuple[int, int, int]] = None,
+        background_color: Optional[Tuple[int, int, int]] = None,
         apply_effect: bool = False,
-    ) -> npt.NDArray:
+        return_clusters: bool = False,
+        features: Optional[list[str]] = None,
+        ligatures: Optional[bool] = None,
+        heights: Optional[list[int]] = None,
+        return_fonts: bool = False,
+        width: Optional[int] = None,
+        height: Optional[int] = None,
+        return_metadata: bool = False,
+        mask: Optional[str] = None,
+        return_x_ranges: bool = False,
+        output: str = "numpy",
+    ) -> Any:
         """
         Generate an image based on a given list of characters and font information.
 
+        :param text_with_font_list: a list of tuples that contains text and font infos, font infos can also be given as legacy `(family, style, weight, stretch)` tuples or as indices into `font_list`
+        :param text_color: text color in RGB form; if not given, drawn from the color scheme of the config file (`color_prob`) with enough contrast against the background, otherwise black
+        :param background_color: background color in RGB form; if not given, drawn from the color scheme of the config file, otherwise white
+        :param apply_effect: whether to perform image enhancement, if true, the resulting image is a grayscale image (RGB with `color_merge` in the config file); color glyphs (CBDT/COLR emoji) are drawn in their own colors when false and merged like the rest of the text when true
+        :param return_clusters: whether to add `clusters` to the result: the shaping clusters in visual order, each with its pixel box `(x0, y0, x1, y1)`; boxes are in the coordinates of the returned image after the right-border crop, clusters without ink (spaces) get their advance box; with `apply_effect`, each box bounds the ink of the cluster after the same transforms as the image (padding, perspective, curvature, ...), and is `None` for a cluster that is cut off
+        :param features: OpenType feature tags for this sample (e.g. `["trad", "ss01"]`), replacing the `opentype_features` in the config file; the per-font features in the config file still apply
+        :param ligatures: whether standard ligatures may form between Latin characters, defaults to `ligatures` in the config file
+        :param heights: output heights (e.g. `[32, 48, 64]`), the image is rendered once and resized to each height with the width scaled proportionally; `image` and `clusters` then become lists with one entry per height, the cluster boxes scaled accordingly
+        :param return_fonts: whether to add `fonts` to the result: the face that actually rendered each cluster after the fallback to `main_font_list`, as `(cluster, font, file)` tuples in visual order; the file is empty for fonts loaded from memory
+        :param width: the width of the canvas for this call only, replacing `font_img_width` (and `auto_width`) in the config file; the image is still cropped to the text
+        :param height: the height of the canvas for this call only, replacing `font_img_height` (or the sampled line height)
+        :param return_metadata: whether to add `metadata` to the result, a dict for auditing the sample: `text`, `fonts` (as with `return_fonts`), `effects` (the cv effects that fired in order, each a dict of `name`, sampled `params` and a `description` such as `"blur(sigma=1.2)"`), `styles` (the synthetic styles of the line: `"faux_bold"`, `"faux_italic"`, `"arc_text"`, `"wave_text"`, `"drop_shadow"`, or a decoration such as `"underline"`, `"dashed_underline"` or `"wavy_strikethrough"`), `backgrounds` (the background files merged onto, two when they are blended), `seed`, `profile` and `difficulty`
+        :param mask: `"binary"` to add `mask` to the result, a uint8 mask of the glyph pixels (255 on ink, 0 elsewhere), `"instance"` to number the pixels of each cluster with its index plus one instead (clusters after the 255th share 255); the mask goes through the same perspective, padding and curvature as the image, so it stays aligned with the merged image; not available with `heights`
+        :param return_x_ranges: whether to add `x_ranges` to the result: the columns `[x0, x1)` each cluster occupies in the final image, as `(cluster, (x0, x1))` tuples in visual order, tracked through the same crop, perspective, resizing, padding and curvature as the image, for alignment-supervised training and character-level evaluation; the range is `None` for a cluster that is cut off, and a space covers its advance box; not available with `heights`
+        :param output: the form of the image: `"numpy"` for an array, `"png"` or `"jpg"` (`"jpeg"`) for the bytes of an encoded file, `"pil"` for a `PIL.Image.Image` (Pillow must be installed); masks stay numpy arrays
+        :return: the resulting image (a list of images, one per height, when `heights` is given); if any of `return_clusters`, `mask`, `return_x_ranges`, `return_fonts` or `return_metadata` is requested, a dict with the image under `image` and only the requested ones of `clusters`, `mask`, `x_ranges`, `fonts` and `metadata`
+        """
+    def gen_image_into(
+        self,
+        text_with_font_list: list[
+            Tuple[str, list[Union[FontAttrs, Tuple[str, int, int, int], int]]]
+        ],
+        out: npt.NDArray,
+        text_color: Optional[Tuple[int, int, int]] = None,
+        background_color: Optional[Tuple[int, int, int]] = None,
+        apply_effect: bool = False,
+        features: Optional[list[str]] = None,
+        ligatures: Optional[bool] = None,
+    ) -> Tuple[int, int]:
+        """
+        Same as `gen_image_from_text_with_font_list`, but write the image into the top left corner of a preallocated array instead of allocating a new one; the rest of the array is left as is.
+
         :param text_with_font_list: a list of tuples that contains text and font infos
-        :param text_color: text color in RGB form
-        :param background_color: background color in RGB form
-        :param apply_effect: whether to perform image enhancement, if true, the resulting image is a grayscale image
-        :return: the resulting image
+        :param out: a writable, C-contiguous uint8 array of shape `(height, width)` when `apply_effect` is true, `(height, width, 3)` otherwise; a `ValueError` is raised if the image does not fit
+        :param text_color: text color in RGB form; if not given, drawn from the color scheme of the config file (`color_prob`) with enough contrast against the background, otherwise black
+        :param background_color: background color in RGB form; if not given, drawn from the color scheme of the config file, otherwise white
+        :param apply_effect: whether to perform image enhancement, the image is then grayscale (RGB with `color_merge` in the config file)
+        :param features: OpenType feature tags for this sample, see `gen_image_from_text_with_font_list`
+        :param ligatures: whether standard ligatures may form between Latin characters
+        :return: the height and width of the image written into `out`
+        """
+    def gen_confusable_images(
+        self,
+        group: Optional[list[str]] = None,
+        apply_effect: bool = True,
+        context: Tuple[int, int] = (0, 0),
+    ) -> list[Tuple[str, npt.NDArray]]:
+        """
+        Render every member of a group of confusable characters (e.g. `["己", "已", "巳"]` or `["O", "0"]`) as hard negatives: all samples share the font, the context, the cv effects and the background, only the member differs.
+
+        :param group: the confusable characters, defaults to a random group from `confusable_file_path` in the config file
+        :param apply_effect: whether to apply the cv effects and merge with a background, see `gen_image_from_text_with_font_list`
+        :param context: range of the number of Chinese characters around the member
+        :return: a list of tuples that contains the label and the image of each member
         """
+    def gen_images_from_text_with_font_list(
+        self,
+        batch: list[
+            list[Tuple[str, list[Union[FontAttrs, Tuple[str, int, int, int], int]]]]
+        ],
+        text_color: Optional[Tuple[int, int, int]] = None,
+        background_color: Optional[Tuple[int, int, int]] = None,
+        features: Optional[list[str]] = None,
+        ligatures: Optional[bool] = None,
+        output: str = "numpy",
+    ) -> list[Any]:
+        """
+        Generate one image per line of a batch without image enhancement. With `render_backend: gpu` in the config file, the whole batch is composited on the GPU at once.
+
+        :param batch: a list of lines, each a list of tuples that contains text and font infos
+        :param text_color: text color in RGB form; if not given, drawn from the color scheme of the config file (`color_prob`) with enough contrast against the background, otherwise black
+        :param background_color: background color in RGB form; if not given, drawn from the color scheme of the config file, otherwise white
+        :param features: OpenType feature tags for this batch, see `gen_image_from_text_with_font_list`
+        :param ligatures: whether standard ligatures may form between Latin characters, defaults to `ligatures` in the config file
+        :param output: the form of the images, see `gen_image_from_text_with_font_list`
+        :return: the resulting images
+        """
+    def gen_images_from_long_text(
+        self,
+        text: str,
+        segment: bool = False,
+        missing: str = "fallback",
+        apply_effect: bool = False,
+        text_color: Optional[Tuple[int, int, int]] = None,
+        background_color: Optional[Tuple[int, int, int]] = None,
+        features: Optional[list[str]] = None,
+        ligatures: Optional[bool] = None,
+        output: str = "numpy",
+    ) -> list[Tuple[Any, str]]:
+        """
+        Split text of any length into lines that fit `font_img_width` and render each of them, e.g. to turn a whole document into recognition crops. Every newline starts a new line; a line breaks at the last space that fits, otherwise between characters (or words with `segment`).
+
+        :param text: the text to split
+        :param segment: whether to split the text into words with the word dict first, so that a word is neither split nor drawn in mixed fonts
+        :param missing: what to do with characters that are in none of the dicts: "fallback" draws them with `main_font_list`, "skip" drops them from the image and the label, "error" raises ValueError
+        :param apply_effect: whether to apply image enhancement and merge onto a background
+        :param text_color: text color in RGB form; if not given, drawn from the color scheme of the config file (`color_prob`) with enough contrast against the background, otherwise black
+        :param background_color: background color in RGB form; if not given, drawn from the color scheme of the config file, otherwise white
+        :param features: OpenType feature tags, see `gen_image_from_text_with_font_list`
+        :param ligatures: whether standard ligatures may form between Latin characters, defaults to `ligatures` in the config file
+        :param output: the form of the images, see `gen_image_from_text_with_font_list`
+        :return: a list of (image, normalized label)
+        """
+
+    def gen_batch(
+        self,
+        n: int,
+        min: int = 5,
+        max: int = 10,
+        add_extra_symbol: bool = False,
+        apply_effect: bool = True,
+        text_color: Optional[Tuple[int, int, int]] = None,
+        background_color: Optional[Tuple[int, int, int]] = None,
+        features: Optional[list[str]] = None,
+        ligatures: Optional[bool] = None,
+        num_threads: Optional[int] = None,
+        writer: Optional[Union[DatasetWriter, LmdbWriter]] = None,
+        output: str = "numpy",
+    ) -> list[Tuple[Any, str]]:
+        """
+        Generate `n` random Chinese samples on parallel Rust threads, with the GIL released, instead of calling `get_random_chinese` and `gen_image_from_text_with_font_list` in a Python loop.
+
+        :param n: the number of samples
+        :param min: the min number of characters of a sample
+        :param max: the max number of characters of a sample
+        :param add_extra_symbol: whether to add extra symbols, see `get_random_chinese`
+        :param apply_effect: whether to apply image enhancement and merge onto a background
+        :param text_color: text color in RGB form; if not given, drawn from the color scheme of the config file (`color_prob`) with enough contrast against the background, otherwise black
+        :param background_color: background color in RGB form; if not given, drawn from the color scheme of the config file, otherwise white
+        :param features: OpenType feature tags, see `gen_image_from_text_with_font_list`
+        :param ligatures: whether standard ligatures may form between Latin characters, defaults to `ligatures` in the config file
+        :param num_threads: the number of threads, defaults to the global rayon pool (one thread per CPU core)
+        :param writer: a dataset to write the samples into; the images are encoded on the worker threads and never handed back to Python
+        :param output: the form of the images, see `gen_image_from_text_with_font_list`; `"png"` and `"jpg"` are encoded on the worker threads as well; ignored with `writer`
+        :return: a list of (image, normalized label); the image is grayscale with `apply_effect`, otherwise RGB. With `writer`, a list of (where the image is stored, normalized label) instead: the path relative to the dataset directory for `DatasetWriter`, the key for `LmdbWriter`
+        """
+
+    def gen_paragraph_image(
+        self,
+        text: str,
+        max_width: Optional[int] = None,
+        line_spacing: float = 1.0,
+        missing: str = "fallback",
+        text_color: Optional[Tuple[int, int, int]] = None,
+        background_color: Optional[Tuple[int, int, int]] = None,
+        features: Optional[list[str]] = None,
+        ligatures: Optional[bool] = None,
+        output: str = "numpy",
+    ) -> Tuple[Any, list[Tuple[str, Tuple[int, int, int, int]]]]:
+        """
+        Render text as one block of wrapped lines, e.g. for document OCR. Every newline starts a new paragraph, other lines wrap between words or characters. The block is always laid out horizontally and no effect is applied, so that the line boxes stay valid.
+
+        :param text: the text to render
+        :param max_width: the width lines wrap at, defaults to `font_img_width` in the config file
+        :param line_spacing: the line height as a multiple of `line_height` in the config file
+        :param missing: what to do with characters that are in none of the dicts, see `gen_images_from_long_text`
+        :param text_color: text color in RGB form; if not given, drawn from the color scheme of the config file (`color_prob`) with enough contrast against the background, otherwise black
+        :param background_color: background color in RGB form; if not given, drawn from the color scheme of the config file, otherwise white
+        :param features: OpenType feature tags, see `gen_image_from_text_with_font_list`
+        :param ligatures: whether standard ligatures may form between Latin characters, defaults to `ligatures` in the config file
+        :param output: the form of the image, see `gen_image_from_text_with_font_list`
+        :return: (RGB image of shape (height, width, 3), a list of (normalized label, (x0, y0, x1, y1)) for each line, top to bottom, with the right and bottom edges exclusive)
+        """
+
+    def gen_scene_image(
+        self,
+        bg_factory: BgFactory,
+        texts: Optional[list[str]] = None,
+        num_instances: Tuple[int, int] = (2, 5),
+        min: int = 2,
+        max: int = 8,
+        instance_height: Tuple[int, int] = (24, 64),
+        max_tries: int = 20,
+        features: Optional[list[str]] = None,
+        ligatures: Optional[bool] = None,
+    ) -> Tuple[npt.NDArray, list[Tuple[str, list[Tuple[float, float]]]]]:
+        """
+        Generate a scene text image in the style of SynthText: several independent text instances, each with a random perspective transform (the `perspective_x/y/z` settings of the config file), pasted at random non-overlapping places of one full-size background, in a random ink color contrasting with the background.
+
+        :param bg_factory: the backgrounds, whose size is the size of the scene, e.g. `BgFactory("./background", 512, 512)`; with `color`, the scene is RGB
+        :param texts: the text of each instance, by default `num_instances` random Chinese texts of `min` to `max` characters
+        :param num_instances: the range of the number of random instances
+        :param min: the min number of characters of a random instance
+        :param max: the max number of characters of a random instance
+        :param instance_height: the range of the height of an instance after the perspective transform
+        :param max_tries: the number of random places tried for each instance before it is dropped
+        :param features: OpenType feature tags, see `gen_image_from_text_with_font_list`
+        :param ligatures: whether standard ligatures may form between Latin characters, defaults to `ligatures` in the config file
+        :return: (the scene image, a list of (normalized label, polygon) for each pasted instance); the polygon is the corners [(x, y)] top left, top right, bottom right, bottom left
+        """
+
+class ImageEffect:
+    """
+    The effects and background merging of `Generator` (the CV and MERGE sections of the config file) applied to images of your own, e.g. scans of real text lines, without rendering any text.
+    """
+
+    seed: Optional[int]
+    """the seed given to the constructor or the last `reseed`"""
+
+    def __init__(
+        self,
+        config_path: str = "./config.yaml",
+        profile: Optional[str] = None,
+        overrides: Optional[dict[str, Any]] = None,
+        seed: Optional[int] = None,
+    ) -> None:
+        """
+        :param config_path: the config file, only its CV and MERGE sections are used
+        :param profile: a profile defined under `PROFILES` in the config file, see `Generator`
+        :param overrides: config keys to replace, see `Generator`
+        :param seed: seed of the random number generators, see `Generator.reseed`
+        """
+    def reseed(self, seed: int) -> None:
+        """
+        Reseed the random number generator of this instance, see `Generator.reseed`.
+
+        :param seed: the seed
+        """
+    def apply(self, image: npt.NDArray, merge: bool = True) -> npt.NDArray:
+        """
+        Apply the cv effects to an image of dark text on a light background and merge it onto a random background, the same as `apply_effect` of `Generator`.
+
+        :param image: a grayscale image of shape (height, width) or an RGB image of shape (height, width, 3), converted to grayscale
+        :param merge: whether to merge onto a background; if false, only the cv effects are applied and the result is grayscale
+        :return: the resulting image, (height, width, 3) with `color_merge` in the config file, otherwise (height, width)
+        """
+    def apply_batch(
+        self,
+        images: list[npt.NDArray],
+        merge: bool = True,
+        num_threads: Optional[int] = None,
+    ) -> list[npt.NDArray]:
+        """
+        `apply` on several images in parallel, releasing the GIL. Each image is seeded on its own, so the results do not depend on the thread scheduling.
+
+        :param images: the images, see `apply`
+        :param merge: see `apply`
+        :param num_threads: the number of threads, by default the global rayon pool (one thread per CPU core)
+        :return: the resulting images, in the order of `images`
+        """
+    def set_bg_size(self, height: int, width: int) -> None:
+        """
+        Set the background image's height and width.
+
+        :param height: specify the height of the background image
+        :param width: specify the width of the background image
+        """
+
+class DatasetWriter:
+    """
+    A text recognition dataset in the label format of PaddleOCR and of the ICDAR recognition tasks: the images are saved in `<dir>/images`, and each sample appends a line `images/<name>\t<label>` to `<dir>/train.txt`, or to `<dir>/val.txt` for the validation split. An existing dataset is extended rather than overwritten.
+    """
+
+    def __init__(
+        self,
+        dir: str,
+        extension: str = "png",
+        val_ratio: float = 0.0,
+        embed_metadata: bool = False,
+    ) -> None:
+        """
+        :param dir: the dataset directory, created if needed
+        :param extension: `png` or `jpg`
+        :param val_ratio: the fraction of the samples that go to `val.txt`, spread evenly over the samples
+        :param embed_metadata: whether to embed the generation metadata in the images, see `save_image`
+        """
+    def __len__(self) -> int:
+        """
+        The number of samples in the dataset, including the ones written by earlier runs.
+        """
+    def write(self, image: npt.NDArray, label: str, seed: Optional[int] = None) -> str:
+        """
+        Save one image and append its label line. To write the samples of `Generator.gen_batch`, pass the writer as its `writer` argument instead, which avoids the round trip through Python.
+
+        :param image: a grayscale image of shape (height, width) or an RGB image of shape (height, width, 3)
+        :param label: the transcript; tabs and line breaks are written as spaces
+        :param seed: the seed the sample was generated with, if any, for the embedded metadata
+        :return: the image path relative to the dataset directory, as written in the label file
+        """
+    def flush(self) -> None:
+        """
+        Flush the label files to disk.
+        """
+
+class LmdbWriter:
+    """
+    A text recognition dataset in an LMDB database, in the layout of the crnn datasets: sample `i` (counting from 1) is stored as `image-%09d` with the encoded image and `label-%09d` with the UTF-8 label, and `num-samples` holds the number of samples. An existing database is extended rather than overwritten. Only with the `lmdb` feature.
+    """
+
+    def __init__(
+        self,
+        path: str,
+        extension: str = "png",
+        embed_metadata: bool = False,
+        map_size: int = 1 << 40,
+    ) -> None:
+        """
+        :param path: the database directory, created if needed
+        :param extension: `png` or `jpg`
+        :param embed_metadata: whether to embed the generation metadata in the images, see `save_image`
+        :param map_size: the maximum size of the database in bytes
+        """
+    def __len__(self) -> int:
+        """
+        The number of samples in the database, including the ones written by earlier runs.
+        """
+    def write(self, image: npt.NDArray, label: str, seed: Optional[int] = None) -> str:
+        """
+        Store one image with its label. The samples are committed every 1000 samples, by `flush` and when the writer is garbage collected. To write the samples of `Generator.gen_batch`, pass the writer as its `writer` argument instead.
+
+        :param image: a grayscale image of shape (height, width) or an RGB image of shape (height, width, 3)
+        :param label: the transcript
+        :param seed: the seed the sample was generated with, if any, for the embedded metadata
+        :return: the key of the image
+        """
+    def flush(self) -> None:
+        """
+        Commit the samples not committed yet.
+        """
+
+class CocoExporter:
+    """
+    Accumulates annotations of detection-style samples, e.g. the clusters of `return_clusters` or the lines of `gen_paragraph_image`, and writes them as COCO JSON. Each box becomes an annotation with `bbox` (x, y, width, height), a rectangular `segmentation` polygon and the transcript in `text`; the ids count from 1.
+    """
+
+    def __init__(self, categories: Optional[list[str]] = None) -> None:
+        """
+        :param categories: the category names, e.g. ["char", "line"], defaults to ["text"]
+        """
+    def __len__(self) -> int:
+        """
+        The number of images.
+        """
+    def add_image(self, file_name: str, width: int, height: int) -> int:
+        """
+        :param file_name: the path of the saved image, as the training framework should find it
+        :param width: the width of the image
+        :param height: the height of the image
+        :return: the id of the image
+        """
+    def add_annotation(
+        self,
+        image_id: int,
+        bbox: Tuple[int, int, int, int],
+        text: str = "",
+        category: Optional[str] = None,
+    ) -> int:
+        """
+        :param image_id: the id from `add_image`
+        :param bbox: (x0, y0, x1, y1) with the right and bottom edges exclusive, as the boxes returned by the generator
+        :param text: the transcript
+        :param category: the category name, defaults to the first category
+        :return: the id of the annotation
+        """
+    def add_sample(
+        self,
+        file_name: str,
+        width: int,
+        height: int,
+        boxes: list[Tuple[str, Tuple[int, int, int, int]]],
+        category: Optional[str] = None,
+    ) -> int:
+        """
+        `add_image` and `add_annotation` for each box; nothing is added if a box is invalid.
+
+        :param file_name: see `add_image`
+        :param width: see `add_image`
+        :param height: see `add_image`
+        :param boxes: a list of (text, (x0, y0, x1, y1))
+        :param category: see `add_annotation`
+        :return: the id of the image
+        """
+    def to_json(self) -> str:
+        """
+        The COCO JSON with `images`, `annotations` and `categories`.
+        """
+    def save(self, path: str) -> None:
+        """
+        Write the COCO JSON to `path`.
+        """
+
+def save_image(
+    path: str,
+    image: npt.NDArray,
+    label: str,
+    seed: Optional[int] = None,
+    fonts: Optional[list[str]] = None,
+    effects: Optional[list[str]] = None,
+    embed_metadata: bool = True,
+    styles: Optional[list[str]] = None,
+) -> None:
+    """
+    Save an image as PNG or JPEG according to the extension of `path`, with the generation metadata embedded as JSON (PNG `iTXt` chunks, the label also in a `Label` chunk; JPEG EXIF `ImageDescription`), so that an image pulled out of a dataset is still self-describing.
+
+    :param path: a path ending with `.png`, `.jpg` or `.jpeg`
+    :param image: a grayscale image of shape (height, width) or an RGB image of shape (height, width, 3)
+    :param label: the transcript
+    :param seed: the seed the sample was generated with, if any
+    :param fonts: the fonts used, e.g. from `return_fonts` of `gen_image_from_text_with_font_list`
+    :param effects: a record of the applied effects
+    :param embed_metadata: whether to embed the metadata at all
+    :param styles: the synthetic styles of the line, e.g. `"faux_bold"` or `"arc_text"`
+    """
+    ...
+
+def serve(
+    addr: str = "127.0.0.1:8000",
+    config_path: str = "./config.yaml",
+    configs: Optional[list[str]] = None,
+) -> None:
+    """
+    Serve batches over HTTP until the process is stopped (only with the `server` feature).
+    `POST /batch` takes a JSON body with the optional fields `config`, `count`, `min_len`,
+    `max_len`, `seed` and `effects`, and answers with the labels and base64 PNG images.
+    All the configs are loaded at the start, and a request naming any other config is rejected.
+
+    :param addr: the address to listen on, e.g. "0.0.0.0:8000"
+    :param config_path: the config of the requests without a `config` field
+    :param configs: the other configs that a request may name in its `config` field
+    """
+    ...